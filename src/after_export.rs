@@ -0,0 +1,131 @@
+//! Server-side actions applied to messages once they are exported.
+//!
+//! Configured with `after_export:` (per account or per folder, see
+//! `folder_rules::FolderOverrides`). Actions run once per folder, after the
+//! last message, in this order: flags (`keyword`, `mark_seen`), then `move_to`.
+//! An empty block (`after_export: {}`) does nothing and cancels inherited
+//! actions. Exported messages are fetched with `BODY.PEEK[]`, so `\Seen` is
+//! only set when `mark_seen` asks for it.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AfterExport {
+    /// Archive mailbox (decoded UTF-8 name) exported messages are moved to:
+    /// `MOVE` (RFC 6851) when the server supports it, `COPY` + `\Deleted` otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub move_to: Option<String>,
+    /// Keyword added to exported messages, e.g. `$Exported`. With `skip_existing`,
+    /// messages carrying it are skipped on later runs without being fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    /// Set `\Seen` on exported messages.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub mark_seen: bool,
+}
+
+impl AfterExport {
+    pub fn is_noop(&self) -> bool {
+        self.move_to.is_none() && self.keyword.is_none() && !self.mark_seen
+    }
+
+    /// Flag list for `STORE +FLAGS`, e.g. `\Seen $Exported`.
+    pub fn flags(&self) -> Option<String> {
+        let flags: Vec<&str> = self
+            .mark_seen
+            .then_some("\\Seen")
+            .into_iter()
+            .chain(self.keyword.as_deref())
+            .collect();
+        (!flags.is_empty()).then(|| flags.join(" "))
+    }
+
+    /// `SEARCH` criteria listing the messages still to export: those without
+    /// the keyword when `skip_existing` is on, all of them otherwise.
+    pub fn search_criteria(&self, skip_existing: bool) -> String {
+        match (&self.keyword, skip_existing) {
+            (Some(keyword), true) => format!("NOT KEYWORD {}", keyword),
+            _ => "ALL".to_string(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(keyword) = &self.keyword {
+            if !is_valid_keyword(keyword) {
+                return Err(format!(
+                    "after_export: invalid keyword {:?} (IMAP atom without spaces, parentheses or a leading backslash)",
+                    keyword
+                ));
+            }
+        }
+        if self.move_to.as_deref().is_some_and(|m| m.trim().is_empty()) {
+            return Err("after_export: empty move_to mailbox".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Whether `keyword` is a valid IMAP flag keyword (RFC 3501 `atom`, not a
+/// `\`-prefixed system flag).
+pub fn is_valid_keyword(keyword: &str) -> bool {
+    !keyword.is_empty()
+        && keyword
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !matches!(c, '(' | ')' | '{' | '%' | '*' | '"' | '\\' | ']'))
+}
+
+/// Compact IMAP sequence set, e.g. `[1, 2, 3, 7]` → `1:3,7`.
+pub fn sequence_set(ids: &[u32]) -> String {
+    let mut sorted = ids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let start = sorted[i];
+        let mut end = start;
+        while i + 1 < sorted.len() && sorted[i + 1] == end + 1 {
+            i += 1;
+            end = sorted[i];
+        }
+        parts.push(if start == end { start.to_string() } else { format!("{}:{}", start, end) });
+        i += 1;
+    }
+    parts.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_and_search_criteria() {
+        let actions = AfterExport { keyword: Some("$Exported".into()), mark_seen: true, ..Default::default() };
+        assert_eq!(actions.flags().as_deref(), Some("\\Seen $Exported"));
+        assert_eq!(actions.search_criteria(true), "NOT KEYWORD $Exported");
+        // Exclusive: no keyword skip without skip_existing, no flags for a move only
+        assert_eq!(actions.search_criteria(false), "ALL");
+        let move_only = AfterExport { move_to: Some("Archives".into()), ..Default::default() };
+        assert_eq!(move_only.flags(), None);
+        assert!(AfterExport::default().is_noop());
+    }
+
+    #[test]
+    fn test_validate_keyword() {
+        assert!(is_valid_keyword("$Exported"));
+        assert!(!is_valid_keyword("\\Seen"));
+        assert!(!is_valid_keyword("two words"));
+        let bad = AfterExport { keyword: Some("(x)".into()), ..Default::default() };
+        assert!(bad.validate().is_err());
+        let empty_move = AfterExport { move_to: Some(" ".into()), ..Default::default() };
+        assert!(empty_move.validate().is_err());
+    }
+
+    #[test]
+    fn test_sequence_set() {
+        assert_eq!(sequence_set(&[7, 1, 2, 3, 3]), "1:3,7");
+        assert_eq!(sequence_set(&[5]), "5");
+        assert_eq!(sequence_set(&[]), "");
+    }
+}
@@ -0,0 +1,312 @@
+//! Body selection: which text of a MIME tree becomes the note body.
+//!
+//! [`extract_body`] walks the whole tree:
+//!
+//! - `multipart/alternative` — one alternative, chosen by `body_preference`
+//! - `multipart/related`     — its root part (`start` parameter, else the first)
+//! - `multipart/mixed` (and other multiparts) — every inline body, in order
+//! - `message/rfc822`        — under the embedded message's headers, a link to
+//!   its child note (see `embedded`), else its body
+//!
+//! Parts with an `attachment` disposition are left to attachment extraction
+//! (embedded messages exported as child notes excepted).
+//! HTML bodies are converted to Markdown.
+
+use mailparse::{MailHeaderMap, ParsedMail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::embedded::EmbeddedNotes;
+
+/// Which alternative of a `multipart/alternative` becomes the body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyPreference {
+    /// The plain-text part; HTML only when there is none (historical behaviour).
+    #[default]
+    Plain,
+    /// The HTML part, converted to Markdown; plain text only when there is none.
+    Html,
+    /// Whichever scores higher (see [`score`]): a plain-text stub or a plain
+    /// part much shorter than the HTML one loses to the HTML.
+    Best,
+}
+
+/// "View this email in your browser" and similar plain-text stubs.
+static STUB_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(view|read|open|display) (this|the|it) (e-?mail|message|newsletter)? ?(online|in (your|a) (web )?browser)|(e-?mail|message) (requires|needs) (an )?html|(client|reader|programme?) (does not|doesn't|cannot|can't) (support|display) html|(afficher|voir|lire|consulter) (ce|cet|le) (e-?mail|courriel|message) (en ligne|dans (votre|un) navigateur)|version (en ligne|web)|ne (supporte|prend en charge|permet) pas (le format |l'affichage )?html",
+    )
+    .expect("static regex")
+});
+
+static URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:https?://|www\.)\S+").expect("static regex"));
+
+/// Markdown link targets `](...)`, not visible text.
+static MD_LINK_TARGET_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\]\([^)]*\)").expect("static regex"));
+
+/// Plain text shorter than this, carrying a stub phrase, is a stub.
+const STUB_MAX_CHARS: usize = 500;
+
+/// Convert HTML to Markdown using htmd. Returns empty string on failure.
+pub(crate) fn html_to_markdown(html: &str) -> String {
+    htmd::convert(html).unwrap_or_default()
+}
+
+/// How useful a body text is: its visible characters (URLs and Markdown link
+/// targets excluded), weighted down by link density; 0 for a stub.
+pub fn score(text: &str) -> f64 {
+    let visible = MD_LINK_TARGET_RE.replace_all(text, "]");
+    let total = visible.chars().filter(|c| !c.is_whitespace()).count();
+    if total == 0 {
+        return 0.0;
+    }
+    let in_links: usize = URL_RE
+        .find_iter(&visible)
+        .map(|m| m.as_str().chars().count())
+        .sum();
+    let words = total.saturating_sub(in_links);
+    if total < STUB_MAX_CHARS && STUB_RE.is_match(&visible) {
+        return 0.0;
+    }
+    let link_density = in_links as f64 / total as f64;
+    words as f64 * (1.0 - link_density)
+}
+
+/// A body candidate and whether it came from HTML.
+struct Body {
+    text: String,
+    html: bool,
+}
+
+fn content_type(part: &ParsedMail) -> String {
+    part.ctype.mimetype.to_lowercase()
+}
+
+fn is_attachment(part: &ParsedMail) -> bool {
+    part.headers
+        .get_first_value("Content-Disposition")
+        .is_some_and(|d| d.trim_start().to_lowercase().starts_with("attachment"))
+}
+
+/// Text of a `text/*` part, decoded again with the best-scoring charset when
+/// its declared one is wrong (see `cleaner::redecode`).
+fn part_text(part: &ParsedMail) -> String {
+    let text = part.get_body().unwrap_or_default();
+    if !text.contains(|c: char| c == '\u{FFFD}' || ('\u{0080}'..='\u{009F}').contains(&c)) {
+        return text;
+    }
+    part.get_body_raw()
+        .ok()
+        .and_then(|raw| crate::cleaner::redecode(&raw, &part.ctype.charset))
+        .unwrap_or(text)
+}
+
+/// True when a text part of `mail` had to be decoded with another charset
+/// than the declared one (`charset_repaired: true` in the note).
+pub(crate) fn has_mislabeled_charset(mail: &ParsedMail) -> bool {
+    let ctype = content_type(mail);
+    if ctype == "text/plain" || ctype == "text/html" {
+        return part_text(mail) != mail.get_body().unwrap_or_default();
+    }
+    mail.subparts.iter().any(has_mislabeled_charset)
+}
+
+/// The body of `part`, or `None` when it has none (attachment, image…).
+fn select(part: &ParsedMail, preference: BodyPreference, embedded: &EmbeddedNotes) -> Option<Body> {
+    let ctype = content_type(part);
+    if ctype == "text/plain" || ctype == "text/html" {
+        if is_attachment(part) {
+            return None;
+        }
+        let text = part_text(part);
+        if text.trim().is_empty() {
+            return None;
+        }
+        return Some(match ctype.as_str() {
+            "text/html" => Body { text: html_to_markdown(&text), html: true },
+            _ => Body { text, html: false },
+        });
+    }
+    if ctype == "message/rfc822" {
+        // Forwarded as attachment: linked when it has a child note
+        if is_attachment(part) && embedded.file(part).is_none() {
+            return None;
+        }
+        return embedded_message(part, preference, embedded);
+    }
+    if !ctype.starts_with("multipart/") || part.subparts.is_empty() {
+        return None;
+    }
+
+    match ctype.as_str() {
+        "multipart/alternative" => {
+            let candidates: Vec<Body> =
+                part.subparts.iter().filter_map(|p| select(p, preference, embedded)).collect();
+            choose(candidates, preference)
+        }
+        "multipart/related" => {
+            let start = part.ctype.params.get("start").map(|s| s.trim_matches(['<', '>']).to_string());
+            let root = start
+                .and_then(|id| {
+                    part.subparts.iter().find(|p| {
+                        p.headers
+                            .get_first_value("Content-ID")
+                            .is_some_and(|cid| cid.trim().trim_matches(['<', '>']) == id)
+                    })
+                })
+                .or_else(|| part.subparts.first());
+            root.and_then(|r| select(r, preference, embedded))
+        }
+        _ => {
+            let bodies: Vec<Body> = part.subparts.iter().filter_map(|p| select(p, preference, embedded)).collect();
+            if bodies.is_empty() {
+                return None;
+            }
+            let html = bodies.iter().any(|b| b.html);
+            let text = bodies
+                .into_iter()
+                .map(|b| b.text.trim_end().to_string())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            Some(Body { text, html })
+        }
+    }
+}
+
+/// One alternative. Alternatives are ordered from plainest to richest
+/// (RFC 2046), so the last of each kind is kept.
+fn choose(candidates: Vec<Body>, preference: BodyPreference) -> Option<Body> {
+    let (mut plain, mut html) = (None, None);
+    for body in candidates {
+        if body.html {
+            html = Some(body);
+        } else {
+            plain = Some(body);
+        }
+    }
+    match (plain, html) {
+        (Some(p), Some(h)) => Some(match preference {
+            BodyPreference::Plain => p,
+            BodyPreference::Html => h,
+            BodyPreference::Best if score(&h.text) > 1.5 * score(&p.text) => h,
+            BodyPreference::Best => p,
+        }),
+        (p, h) => p.or(h),
+    }
+}
+
+/// An embedded message under a short header block: a link to its child
+/// note when it has one, else its body.
+fn embedded_message(part: &ParsedMail, preference: BodyPreference, embedded: &EmbeddedNotes) -> Option<Body> {
+    let raw = part.get_body_raw().ok()?;
+    let inner = mailparse::parse_mail(&raw).ok()?;
+    let mut header = String::from("---------- Forwarded message ----------\n");
+    for name in ["From", "Date", "Subject", "To"] {
+        if let Some(value) = inner.headers.get_first_value(name) {
+            header.push_str(&format!("{}: {}\n", name, value));
+        }
+    }
+    if let Some(file) = embedded.file(part) {
+        let subject = inner.headers.get_first_value("Subject").unwrap_or_default();
+        let title = if subject.trim().is_empty() { "Message" } else { subject.trim() };
+        let target = if file.contains([' ', '(', ')']) { format!("<{}>", file) } else { file.to_string() };
+        return Some(Body { text: format!("{}\n[{}]({})", header, title, target), html: false });
+    }
+    let body = select(&inner, preference, embedded)?;
+    Some(Body { text: format!("{}\n{}", header, body.text), html: body.html })
+}
+
+/// Extract the note body from a parsed email (see the module docs).
+/// `embedded` holds the child notes of its embedded messages.
+pub(crate) fn extract_body(mail: &ParsedMail, preference: BodyPreference, embedded: &EmbeddedNotes) -> String {
+    let ctype = content_type(mail);
+    if mail.subparts.is_empty() && !ctype.starts_with("message/") {
+        // Single part: kept as is (a lone HTML body is fixed by `fix-html`);
+        // a lone calendar is rendered as an agenda block (see `calendar`), a
+        // lone TNEF stream falls back to its RTF body (see `tnef`)
+        if crate::calendar::is_calendar_part(mail) || crate::tnef::is_tnef_part(mail) {
+            return String::new();
+        }
+        return part_text(mail);
+    }
+    select(mail, preference, embedded).map(|b| b.text).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alternative(plain: &str, html: &str) -> Vec<u8> {
+        format!(
+            "From: a@example.com\r\nSubject: S\r\nContent-Type: multipart/alternative; boundary=\"ALT\"\r\n\r\n\
+             --ALT\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{plain}\r\n\
+             --ALT\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{html}\r\n--ALT--\r\n"
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_preference_plain_html_best() {
+        let html = "<h1>Offre</h1><p>Le détail complet de notre offre de printemps, avec les prix, \
+                    les conditions et les dates de validité pour chaque produit de la gamme.</p>";
+        let raw = alternative("View this email in your browser: https://example.com/v/1", html);
+        let mail = mailparse::parse_mail(&raw).unwrap();
+
+        assert!(extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default()).starts_with("View this email"));
+        assert!(extract_body(&mail, BodyPreference::Html, &EmbeddedNotes::default()).contains("# Offre"));
+        // Best: the stub loses to the HTML
+        assert!(extract_body(&mail, BodyPreference::Best, &EmbeddedNotes::default()).contains("offre de printemps"));
+
+        // Exclusive: a real plain part wins under `best`
+        let raw = alternative("Bonjour Bob,\n\nVoici le devis demandé, valable un mois.", "<p>Bonjour Bob,</p><p>Voici le devis demandé, valable un mois.</p>");
+        let mail = mailparse::parse_mail(&raw).unwrap();
+        assert!(!extract_body(&mail, BodyPreference::Best, &EmbeddedNotes::default()).contains("<p>"));
+        assert!(extract_body(&mail, BodyPreference::Best, &EmbeddedNotes::default()).starts_with("Bonjour Bob,\n"));
+    }
+
+    #[test]
+    fn test_related_root_and_mixed_bodies() {
+        // mixed( related(start=<root>: image, html root), text footer, attachment )
+        let raw = b"Content-Type: multipart/mixed; boundary=MIX\r\n\r\n\
+            --MIX\r\nContent-Type: multipart/related; boundary=REL; start=\"<root@x>\"\r\n\r\n\
+            --REL\r\nContent-Type: image/png\r\nContent-ID: <img@x>\r\n\r\nPNG\r\n\
+            --REL\r\nContent-Type: text/html\r\nContent-ID: <root@x>\r\n\r\n<p>Corps HTML</p>\r\n--REL--\r\n\
+            --MIX\r\nContent-Type: text/plain\r\n\r\nPied de liste\r\n\
+            --MIX\r\nContent-Type: text/plain\r\nContent-Disposition: attachment; filename=notes.txt\r\n\r\nPiece jointe\r\n\
+            --MIX--\r\n";
+        let mail = mailparse::parse_mail(raw).unwrap();
+        let body = extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default());
+        assert_eq!(body, "Corps HTML\n\nPied de liste");
+    }
+
+    #[test]
+    fn test_embedded_message_body() {
+        let raw = b"Content-Type: multipart/mixed; boundary=MIX\r\n\r\n\
+            --MIX\r\nContent-Type: text/plain\r\n\r\nVoir ci-dessous.\r\n\
+            --MIX\r\nContent-Type: message/rfc822\r\n\r\n\
+            From: Carol <carol@example.com>\r\nSubject: Origine\r\nContent-Type: text/plain\r\n\r\nTexte transfere\r\n\
+            --MIX--\r\n";
+        let mail = mailparse::parse_mail(raw).unwrap();
+        let body = extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default());
+        assert!(body.starts_with("Voir ci-dessous.\n\n---------- Forwarded message ----------\n"));
+        assert!(body.contains("From: Carol <carol@example.com>\nSubject: Origine\n"));
+        assert!(body.ends_with("Texte transfere"));
+
+        // With a child note: a link in place of the body
+        let mut notes = EmbeddedNotes::default();
+        notes.insert(&mail.subparts[1], "2024-01-01_Origine.md");
+        let body = extract_body(&mail, BodyPreference::Plain, &notes);
+        assert!(body.ends_with("Subject: Origine\n\n[Origine](2024-01-01_Origine.md)"), "{:?}", body);
+    }
+
+    #[test]
+    fn test_score_penalizes_links_and_stubs() {
+        assert_eq!(score("Trouble viewing? View this email in your browser"), 0.0);
+        assert!(score("https://example.com/a https://example.com/b x") < score("Un vrai paragraphe de texte"));
+        assert!(score("Voir [le site](https://example.com/tres/long/lien)") > 10.0);
+    }
+}
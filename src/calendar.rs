@@ -0,0 +1,506 @@
+//! Calendar invitations: the `VEVENT` of a `text/calendar` part.
+//!
+//! Invitations (`METHOD:REQUEST`), replies (`REPLY`) and cancellations
+//! (`CANCEL`) carry an iCalendar object (RFC 5545) as a `text/calendar` part,
+//! often doubled by an `invite.ics` attachment. [`find_event`] parses the
+//! first event of the message: its fields go to the note header
+//! (`event_start`, `event_end`, `location`, `organizer`, `attendees`,
+//! `status`), [`agenda`] renders them at the top of the body, and the
+//! calendar itself is saved once as a clean `.ics` next to the note.
+//!
+//! Times with a `TZID` are converted with the calendar's `VTIMEZONE` (yearly
+//! `RRULE` observances); without one they are kept as local times.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use mailparse::ParsedMail;
+use serde::{Deserialize, Serialize};
+
+/// Event fields of an invitation note.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalendarEvent {
+    /// `METHOD` of the calendar: `request`, `reply`, `cancel`, `publish`…
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// RFC 3339 date-time, `YYYY-MM-DD` for all-day events, or a local
+    /// `YYYY-MM-DDTHH:MM:SS` when the time zone is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// `TZID` of a local `start` (time zone without `VTIMEZONE`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tzid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// `Name <address>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organizer: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attendees: Vec<String>,
+    /// `cancelled` for a cancellation, the attendee's answer for a reply
+    /// (`accepted`, `declined`, `tentative`), else the event's `STATUS`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+/// A content line: name, parameters, value (RFC 5545 §3.1).
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Unfold the content lines of `text` (a line starting with a space or a tab
+/// continues the previous one).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if !line.is_empty() => lines.push(line.to_string()),
+            _ => {}
+        }
+    }
+    lines
+}
+
+fn parse_line(line: &str) -> Option<Property> {
+    // The value starts at the first `:` outside a quoted parameter value
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let mut head = line[..colon].split(';');
+    let name = head.next()?.trim().to_uppercase();
+    let params = head
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.trim().to_uppercase(), v.trim().trim_matches('"').to_string()))
+        .collect();
+    Some(Property { name, params, value: line[colon + 1..].to_string() })
+}
+
+/// TEXT value with its escapes (`\n`, `\,`, `\;`, `\\`) resolved.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// `Name <address>` of an `ORGANIZER` or `ATTENDEE` property.
+fn person(prop: &Property) -> String {
+    let address = prop.value.trim();
+    let address = address
+        .get(..7)
+        .filter(|p| p.eq_ignore_ascii_case("mailto:"))
+        .map_or(address, |_| &address[7..]);
+    match prop.param("CN").map(str::trim).filter(|cn| !cn.is_empty() && *cn != address) {
+        Some(cn) => format!("{} <{}>", cn, address),
+        None => address.to_string(),
+    }
+}
+
+/// One `STANDARD` or `DAYLIGHT` observance of a `VTIMEZONE`.
+#[derive(Debug, Default)]
+struct Observance {
+    start: Option<NaiveDateTime>,
+    offset_to: Option<i32>,
+    /// `(month, nth, weekday)` of a yearly `RRULE` (`nth` < 0 counts from the end).
+    rule: Option<(u32, i32, Weekday)>,
+}
+
+impl Observance {
+    /// Onset of this observance in `year`.
+    fn onset(&self, year: i32) -> Option<NaiveDateTime> {
+        let start = self.start?;
+        let Some((month, nth, weekday)) = self.rule else {
+            return Some(start);
+        };
+        let day = nth_weekday(year, month, nth, weekday)?;
+        Some(day.and_time(start.time()))
+    }
+}
+
+fn nth_weekday(year: i32, month: u32, nth: i32, weekday: Weekday) -> Option<NaiveDate> {
+    if nth > 0 {
+        NaiveDate::from_weekday_of_month_opt(year, month, weekday, nth as u8)
+    } else {
+        let next_month = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        let mut day = next_month - Duration::days(1);
+        while day.weekday() != weekday {
+            day -= Duration::days(1);
+        }
+        day.checked_sub_signed(Duration::weeks(i64::from(nth.checked_neg()? - 1)))
+    }
+}
+
+/// `+0100` / `-0530` → seconds east of UTC.
+fn parse_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let (sign, digits) = match value.as_bytes().first()? {
+        b'-' => (-1, &value[1..]),
+        b'+' => (1, &value[1..]),
+        _ => (1, value),
+    };
+    let hours: i32 = digits.get(..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// `FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU` → `(3, -1, Sun)`.
+fn parse_yearly_rule(value: &str) -> Option<(u32, i32, Weekday)> {
+    let parts: Vec<(&str, &str)> = value.split(';').filter_map(|p| p.split_once('=')).collect();
+    let get = |key: &str| parts.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| *v);
+    if !get("FREQ")?.eq_ignore_ascii_case("YEARLY") {
+        return None;
+    }
+    let month: u32 = get("BYMONTH")?.parse().ok()?;
+    if !(1..=12).contains(&month) {
+        return None;
+    }
+    let byday = get("BYDAY")?;
+    let split = byday.len().checked_sub(2)?;
+    let weekday = match byday.get(split..)? {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let nth: i32 = match byday.get(..split)? {
+        "" => get("BYSETPOS").and_then(|p| p.parse().ok()).unwrap_or(1),
+        n => n.trim_start_matches('+').parse().ok()?,
+    };
+    // A month has at most five of each weekday
+    if !(1..=5).contains(&nth.unsigned_abs()) {
+        return None;
+    }
+    Some((month, nth, weekday))
+}
+
+/// `20260310T140000` (the `Z` already stripped) or `20260310`.
+fn parse_local(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(|d| d.and_time(NaiveTime::MIN)))
+}
+
+/// Offset in force at local time `at` in a time zone made of `observances`.
+fn offset_at(observances: &[Observance], at: NaiveDateTime) -> Option<i32> {
+    observances
+        .iter()
+        .flat_map(|o| [o.onset(at.year() - 1), o.onset(at.year())].into_iter().flatten().map(move |t| (t, o)))
+        .filter(|(onset, _)| *onset <= at)
+        .max_by_key(|(onset, _)| *onset)
+        .and_then(|(_, o)| o.offset_to)
+}
+
+/// Frontmatter form of a `DTSTART` / `DTEND`, and its `TZID` when the time
+/// could not be placed on UTC.
+fn event_time(prop: &Property, zones: &[(String, Vec<Observance>)]) -> Option<(String, Option<String>)> {
+    let value = prop.value.trim();
+    if prop.param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some((date.format("%Y-%m-%d").to_string(), None));
+    }
+    if let Some(utc) = value.strip_suffix(['Z', 'z']) {
+        let local = parse_local(utc)?;
+        return Some((local.and_utc().fixed_offset().to_rfc3339(), None));
+    }
+    let local = parse_local(value)?;
+    let tzid = prop.param("TZID").map(str::to_string);
+    let offset = tzid
+        .as_ref()
+        .and_then(|id| zones.iter().find(|(zone, _)| zone == id))
+        .and_then(|(_, observances)| offset_at(observances, local))
+        .and_then(chrono::FixedOffset::east_opt);
+    match offset.and_then(|o| local.and_local_timezone(o).single()) {
+        Some(time) => Some((time.to_rfc3339(), None)),
+        None => Some((local.format("%Y-%m-%dT%H:%M:%S").to_string(), tzid)),
+    }
+}
+
+/// Parse the first `VEVENT` of an iCalendar object.
+pub fn parse(text: &str) -> Option<CalendarEvent> {
+    let props: Vec<Property> = unfold(text).iter().filter_map(|l| parse_line(l)).collect();
+
+    // Time zones first: DTSTART may come before its VTIMEZONE is complete
+    let mut zones: Vec<(String, Vec<Observance>)> = Vec::new();
+    let mut in_zone = false;
+    let mut observance: Option<Observance> = None;
+    for prop in &props {
+        match (prop.name.as_str(), prop.value.trim().to_uppercase().as_str()) {
+            ("BEGIN", "VTIMEZONE") => {
+                in_zone = true;
+                zones.push((String::new(), Vec::new()));
+            }
+            ("END", "VTIMEZONE") => in_zone = false,
+            ("BEGIN", "STANDARD" | "DAYLIGHT") if in_zone => observance = Some(Observance::default()),
+            ("END", "STANDARD" | "DAYLIGHT") => {
+                if let (Some(o), Some((_, list))) = (observance.take(), zones.last_mut()) {
+                    list.push(o);
+                }
+            }
+            ("TZID", _) if in_zone && observance.is_none() => {
+                if let Some((id, _)) = zones.last_mut() {
+                    *id = prop.value.trim().to_string();
+                }
+            }
+            ("DTSTART", _) => {
+                if let Some(o) = observance.as_mut() {
+                    o.start = parse_local(prop.value.trim());
+                }
+            }
+            ("TZOFFSETTO", _) => {
+                if let Some(o) = observance.as_mut() {
+                    o.offset_to = parse_offset(&prop.value);
+                }
+            }
+            ("RRULE", _) => {
+                if let Some(o) = observance.as_mut() {
+                    o.rule = parse_yearly_rule(&prop.value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let method = props
+        .iter()
+        .find(|p| p.name == "METHOD")
+        .map(|p| p.value.trim().to_lowercase());
+    let mut event = CalendarEvent { method, ..Default::default() };
+    let mut partstat = None;
+    let mut event_status = None;
+    let mut depth = 0usize;
+    let mut found = false;
+    for prop in &props {
+        let value = prop.value.trim();
+        match prop.name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VEVENT") && !found => {
+                found = true;
+                depth = 1;
+                continue;
+            }
+            _ if depth == 0 => continue,
+            "BEGIN" => depth += 1,
+            "END" => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            // Nested components (VALARM) have their own properties
+            _ if depth > 1 => {}
+            "SUMMARY" => event.summary = Some(unescape(value)).filter(|s| !s.is_empty()),
+            "LOCATION" => event.location = Some(unescape(value)).filter(|s| !s.is_empty()),
+            "DTSTART" => {
+                if let Some((start, tzid)) = event_time(prop, &zones) {
+                    event.start = Some(start);
+                    event.tzid = tzid;
+                }
+            }
+            "DTEND" => event.end = event_time(prop, &zones).map(|(end, _)| end),
+            "ORGANIZER" => event.organizer = Some(person(prop)),
+            "ATTENDEE" => {
+                event.attendees.push(person(prop));
+                if partstat.is_none() {
+                    partstat = prop.param("PARTSTAT").map(str::to_lowercase);
+                }
+            }
+            "STATUS" => event_status = Some(value.to_lowercase()),
+            _ => {}
+        }
+    }
+    if !found {
+        return None;
+    }
+    event.status = match event.method.as_deref() {
+        Some("cancel") => Some("cancelled".to_string()),
+        Some("reply") => partstat.or(event_status),
+        _ => event_status,
+    };
+    Some(event)
+}
+
+/// Whether `part` holds an iCalendar object.
+pub fn is_calendar_part(part: &ParsedMail) -> bool {
+    let ctype = part.ctype.mimetype.to_lowercase();
+    ctype == "text/calendar" || ctype == "application/ics"
+}
+
+/// The first event of the message's calendar parts, with its calendar text
+/// (transfer encoding removed, CRLF line endings).
+pub fn find_event(mail: &ParsedMail) -> Option<(CalendarEvent, String)> {
+    if is_calendar_part(mail) {
+        let text = mail.get_body().ok()?;
+        if let Some(event) = parse(&text) {
+            let lines: Vec<&str> = text.lines().map(|l| l.trim_end_matches('\r')).collect();
+            return Some((event, format!("{}\r\n", lines.join("\r\n").trim_end())));
+        }
+    }
+    mail.subparts.iter().find_map(find_event)
+}
+
+/// `2026-03-10 14:00 (+01:00)` for a frontmatter time.
+fn display_time(value: &str, tzid: Option<&str>) -> String {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return time.format("%Y-%m-%d %H:%M (%:z)").to_string();
+    }
+    match NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        Ok(local) => match tzid {
+            Some(tz) => format!("{} ({})", local.format("%Y-%m-%d %H:%M"), tz),
+            None => local.format("%Y-%m-%d %H:%M").to_string(),
+        },
+        Err(_) => value.to_string(),
+    }
+}
+
+/// Readable agenda block for the top of the note body.
+pub fn agenda(event: &CalendarEvent) -> String {
+    let title = match event.method.as_deref() {
+        Some("cancel") => "Invitation annulée",
+        Some("reply") => "Réponse à une invitation",
+        Some("request") => "Invitation",
+        _ => "Événement",
+    };
+    let mut out = match &event.summary {
+        Some(summary) => format!("### {} : {}\n\n", title, summary),
+        None => format!("### {}\n\n", title),
+    };
+    let mut line = |label: &str, value: Option<String>| {
+        if let Some(value) = value {
+            out.push_str(&format!("- **{}** : {}\n", label, value));
+        }
+    };
+    line("Début", event.start.as_deref().map(|s| display_time(s, event.tzid.as_deref())));
+    line("Fin", event.end.as_deref().map(|e| display_time(e, event.tzid.as_deref())));
+    line("Lieu", event.location.clone());
+    line("Organisateur", event.organizer.clone());
+    line(
+        "Participants",
+        Some(event.attendees.join(", ")).filter(|a| !a.is_empty()),
+    );
+    line("Statut", event.status.clone());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTLOOK_REQUEST: &str = "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nPRODID:Microsoft Exchange Server 2010\r\n\
+        BEGIN:VTIMEZONE\r\nTZID:Romance Standard Time\r\n\
+        BEGIN:STANDARD\r\nDTSTART:16010101T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\n\
+        RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=10\r\nEND:STANDARD\r\n\
+        BEGIN:DAYLIGHT\r\nDTSTART:16010101T020000\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\n\
+        RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=3\r\nEND:DAYLIGHT\r\nEND:VTIMEZONE\r\n\
+        BEGIN:VEVENT\r\nORGANIZER;CN=Alice Martin:mailto:alice@example.com\r\n\
+        ATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;CN=\"Durand, Bob\":mailto:bob@example.com\r\n\
+        ATTENDEE;CN=carol@example.com:mailto:carol@example.com\r\n\
+        SUMMARY;LANGUAGE=fr-FR:Revue\\, sprint 12\r\n\
+        DTSTART;TZID=Romance Standard Time:20260310T140000\r\n\
+        DTEND;TZID=Romance Standard Time:20260410T150000\r\n\
+        LOCATION:Salle 3 /\r\n  Teams\r\nSTATUS:CONFIRMED\r\n\
+        BEGIN:VALARM\r\nDESCRIPTION:REMINDER\r\nEND:VALARM\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parse_outlook_request_with_vtimezone() {
+        let event = parse(OUTLOOK_REQUEST).unwrap();
+        assert_eq!(event.method.as_deref(), Some("request"));
+        assert_eq!(event.summary.as_deref(), Some("Revue, sprint 12"));
+        // Winter time in March, summer time in April (last Sunday of March)
+        assert_eq!(event.start.as_deref(), Some("2026-03-10T14:00:00+01:00"));
+        assert_eq!(event.end.as_deref(), Some("2026-04-10T15:00:00+02:00"));
+        assert_eq!(event.location.as_deref(), Some("Salle 3 / Teams"));
+        assert_eq!(event.organizer.as_deref(), Some("Alice Martin <alice@example.com>"));
+        assert_eq!(event.attendees, vec!["Durand, Bob <bob@example.com>", "carol@example.com"]);
+        assert_eq!(event.status.as_deref(), Some("confirmed"));
+    }
+
+    #[test]
+    fn test_parse_reply_cancel_and_floating_times() {
+        let reply = "BEGIN:VCALENDAR\nMETHOD:REPLY\nBEGIN:VEVENT\n\
+            ATTENDEE;PARTSTAT=DECLINED:mailto:bob@example.com\nDTSTART:20260310T130000Z\nEND:VEVENT\nEND:VCALENDAR\n";
+        let event = parse(reply).unwrap();
+        assert_eq!(event.status.as_deref(), Some("declined"));
+        assert_eq!(event.start.as_deref(), Some("2026-03-10T13:00:00+00:00"));
+
+        let cancel = "BEGIN:VCALENDAR\nMETHOD:CANCEL\nBEGIN:VEVENT\nSTATUS:CONFIRMED\n\
+            DTSTART;TZID=Europe/Paris:20260310T140000\nDTEND;VALUE=DATE:20260311\nEND:VEVENT\nEND:VCALENDAR\n";
+        let event = parse(cancel).unwrap();
+        assert_eq!(event.status.as_deref(), Some("cancelled"));
+        // No VTIMEZONE: kept as local time with its zone name
+        assert_eq!(event.start.as_deref(), Some("2026-03-10T14:00:00"));
+        assert_eq!(event.tzid.as_deref(), Some("Europe/Paris"));
+        assert_eq!(event.end.as_deref(), Some("2026-03-11"));
+        assert!(agenda(&event).contains("- **Début** : 2026-03-10 14:00 (Europe/Paris)\n"));
+
+        // Exclusive: no VEVENT
+        assert!(parse("BEGIN:VCALENDAR\nBEGIN:VTODO\nEND:VTODO\nEND:VCALENDAR\n").is_none());
+    }
+
+    #[test]
+    fn test_agenda_block() {
+        let event = parse(OUTLOOK_REQUEST).unwrap();
+        let block = agenda(&event);
+        assert!(block.starts_with("### Invitation : Revue, sprint 12\n\n"));
+        assert!(block.contains("- **Début** : 2026-03-10 14:00 (+01:00)\n"));
+        assert!(block.contains("- **Participants** : Durand, Bob <bob@example.com>, carol@example.com\n"));
+    }
+
+    #[test]
+    fn test_malformed_vtimezone_rules_are_rejected() {
+        for rule in [
+            "FREQ=YEARLY;BYDAY=-1SU;BYMONTH=4294967295",
+            "FREQ=YEARLY;BYDAY=-1SU;BYMONTH=0",
+            "FREQ=YEARLY;BYDAY=-2000000000SU;BYMONTH=3",
+            "FREQ=YEARLY;BYDAY=-2147483648SU;BYMONTH=3",
+            "FREQ=YEARLY;BYDAY=0SU;BYMONTH=3",
+            "FREQ=YEARLY;BYDAY=a€;BYMONTH=3",
+        ] {
+            assert_eq!(parse_yearly_rule(rule), None, "{rule}");
+        }
+        assert_eq!(nth_weekday(2026, 3, i32::MIN, Weekday::Sun), None);
+
+        // The event still parses, without a resolvable offset
+        let ics = OUTLOOK_REQUEST.replace("BYDAY=-1SU;BYMONTH=3", "BYDAY=a€;BYMONTH=4294967295");
+        let event = parse(&ics).unwrap();
+        assert_eq!(event.summary.as_deref(), Some("Revue, sprint 12"));
+    }
+}
@@ -0,0 +1,234 @@
+//! Inline images: `cid:` references resolved to the saved attachment files.
+//!
+//! HTML bodies embed their images as `<img src="cid:...">`, pointing at the
+//! `Content-ID` of a sibling part (RFC 2392); Outlook's plain-text parts keep
+//! `[cid:...]` placeholders. `extract_attachments` records the file each
+//! `Content-ID` is saved to ([`InlineImages`]), then the references of the
+//! note body are rewritten into relative `![alt](file)` embeds (`src`
+//! attributes for the standalone HTML format). References to images that were
+//! not saved (signature images, `skip_attachments`) are removed.
+//!
+//! Images referenced from the body are content (a screenshot in a bug
+//! report): `skip_signature_images` keeps them unless they are named like a
+//! signature image or smaller than [`CONTENT_IMAGE_MIN_BYTES`].
+
+use mailparse::{MailHeaderMap, ParsedMail};
+use regex::{Captures, Regex};
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+/// Referenced images smaller than this are treated as signature logos.
+pub const CONTENT_IMAGE_MIN_BYTES: usize = 15 * 1024;
+
+/// Names that mark a signature image whatever its size (see `is_signature_image`).
+const SIGNATURE_NAMES: &[&str] = &["signature", "logo", "banner", "footer", "brand"];
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "svg", "webp", "tif", "tiff"];
+
+/// `cid:` URLs anywhere in a text part.
+static CID_URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\bcid:([^\s"'<>()\[\]]+)"#).expect("static regex"));
+
+/// Markdown image embeds of a `cid:` URL, with an optional title.
+static MD_CID_IMAGE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)!\[([^\]]*)\]\(<?cid:([^\s)>]+)>?(?:\s+"[^"]*")?\)"#).expect("static regex")
+});
+
+/// Outlook's plain-text placeholders: `[cid:image001.png@01DA...]`.
+static PLAIN_CID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\[cid:([^\]\s]+)\]").expect("static regex"));
+
+/// `src` attributes of a `cid:` URL in HTML.
+static HTML_CID_SRC_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\bsrc\s*=\s*(["']?)cid:([^"'\s>]+)(["']?)"#).expect("static regex")
+});
+
+/// Content-IDs referenced by the text parts of a message and the files their
+/// parts were saved to.
+#[derive(Debug, Default)]
+pub struct InlineImages {
+    /// Normalized Content-IDs referenced by a `text/html` or `text/plain` part.
+    pub referenced: HashSet<String>,
+    /// Normalized Content-ID → bare file name of the saved part.
+    pub files: HashMap<String, String>,
+}
+
+impl InlineImages {
+    /// Collect the `cid:` references of every text part of `mail`.
+    pub fn scan(mail: &ParsedMail) -> Self {
+        let mut images = InlineImages::default();
+        collect_references(mail, &mut images.referenced);
+        images
+    }
+
+    pub fn is_referenced(&self, content_id: &str) -> bool {
+        self.referenced.contains(&normalize(content_id))
+    }
+
+    /// Record that the part with `content_id` was saved as `file`.
+    pub fn insert(&mut self, content_id: &str, file: &str) {
+        self.files.insert(normalize(content_id), file.to_string());
+    }
+
+    fn file(&self, cid: &str) -> Option<&str> {
+        self.files.get(&normalize(cid)).map(String::as_str)
+    }
+}
+
+fn collect_references(part: &ParsedMail, referenced: &mut HashSet<String>) {
+    let ctype = part.ctype.mimetype.to_lowercase();
+    if ctype == "text/html" || ctype == "text/plain" {
+        if let Ok(text) = part.get_body() {
+            referenced.extend(CID_URL_RE.captures_iter(&text).map(|c| normalize(&c[1])));
+        }
+    }
+    for sub in &part.subparts {
+        collect_references(sub, referenced);
+    }
+}
+
+/// Comparable form of a `Content-ID` header value or a `cid:` URL: angle
+/// brackets stripped, `%XX` escapes decoded, lowercased.
+pub fn normalize(cid: &str) -> String {
+    let cid = cid.trim().trim_start_matches("cid:").trim_matches(['<', '>']);
+    let bytes = cid.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| cid.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_lowercase()
+}
+
+/// `Content-ID` of a part, if any.
+pub fn content_id(part: &ParsedMail) -> Option<String> {
+    part.headers
+        .get_first_value("Content-ID")
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// File name for an inline part that has no name: the local part of its
+/// `Content-ID`, with an extension from its image type when missing.
+pub fn fallback_filename(content_id: &str, mimetype: &str) -> String {
+    let cid = normalize(content_id);
+    let local = cid.split('@').next().unwrap_or_default();
+    let local = if local.is_empty() { "inline" } else { local };
+    let mimetype = mimetype.to_lowercase();
+    let ext = match mimetype.as_str() {
+        "image/jpeg" | "image/pjpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        other => other.strip_prefix("image/").unwrap_or("bin"),
+    };
+    let has_image_ext = local
+        .rsplit_once('.')
+        .is_some_and(|(_, e)| IMAGE_EXTENSIONS.contains(&e));
+    if has_image_ext {
+        local.to_string()
+    } else {
+        format!("{}.{}", local, ext)
+    }
+}
+
+/// A referenced image is body content, not a signature logo, when it is not
+/// named like one and is at least [`CONTENT_IMAGE_MIN_BYTES`].
+pub fn is_content_image(filename: &str, payload_size: usize) -> bool {
+    let lower = filename.to_lowercase();
+    payload_size >= CONTENT_IMAGE_MIN_BYTES && !SIGNATURE_NAMES.iter().any(|n| lower.contains(n))
+}
+
+/// Markdown link target for a bare file name (angle brackets when it has
+/// spaces or parentheses).
+fn link_target(file: &str) -> String {
+    if file.contains([' ', '(', ')']) {
+        format!("<{}>", file)
+    } else {
+        file.to_string()
+    }
+}
+
+/// Rewrite the `cid:` image embeds and `[cid:...]` placeholders of a Markdown
+/// or plain-text body into `![alt](file)`; unresolved ones are removed.
+pub fn resolve_markdown(body: &str, images: &InlineImages) -> String {
+    let embed = |alt: &str, cid: &str| match images.file(cid) {
+        Some(file) => format!("![{}]({})", alt, link_target(file)),
+        None => String::new(),
+    };
+    let body = MD_CID_IMAGE_RE.replace_all(body, |c: &Captures| embed(&c[1], &c[2]));
+    PLAIN_CID_RE
+        .replace_all(&body, |c: &Captures| embed("", &c[1]))
+        .into_owned()
+}
+
+/// Rewrite the `src="cid:..."` attributes of an HTML body to the saved files.
+/// Unresolved ones are left for the sanitizer, which drops `cid:` URLs.
+pub fn resolve_html(html: &str, images: &InlineImages) -> String {
+    HTML_CID_SRC_RE
+        .replace_all(html, |c: &Captures| match images.file(&c[2]) {
+            Some(file) => format!("src={}{}{}", &c[1], file, &c[3]),
+            None => c[0].to_string(),
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_markdown_embeds_and_placeholders() {
+        let mut images = InlineImages::default();
+        images.insert("<image001.png@01DA2B3C.4D5E6F70>", "2026-03-02_image001.png");
+        images.insert("<capture%20ecran@x>", "2026-03-02_capture ecran.png");
+
+        let body = "Voir ![Erreur](cid:image001.png@01DA2B3C.4D5E6F70) et ![](cid:capture%20ecran@x \"t\")\n\
+                    [cid:IMAGE001.png@01DA2B3C.4D5E6F70]\n![logo](cid:logo@sig)";
+        assert_eq!(
+            resolve_markdown(body, &images),
+            "Voir ![Erreur](2026-03-02_image001.png) et ![](<2026-03-02_capture ecran.png>)\n\
+             ![](2026-03-02_image001.png)\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_html_and_scan_references() {
+        let raw = b"Content-Type: multipart/related; boundary=R\r\n\r\n\
+            --R\r\nContent-Type: text/html\r\n\r\n<p><img src=\"cid:shot@x\" alt=\"A\"><img src='cid:gone@x'></p>\r\n\
+            --R\r\nContent-Type: image/png\r\nContent-ID: <shot@x>\r\n\r\nPNG\r\n--R--\r\n";
+        let mail = mailparse::parse_mail(raw).unwrap();
+        let mut images = InlineImages::scan(&mail);
+        assert!(images.is_referenced("<shot@x>") && images.is_referenced("gone@x"));
+        assert!(!images.is_referenced("<other@x>"));
+
+        images.insert("<shot@x>", "2026-03-02_shot.png");
+        let html = "<img src=\"cid:shot@x\" alt=\"A\"><img src='cid:gone@x'>";
+        assert_eq!(
+            resolve_html(html, &images),
+            "<img src=\"2026-03-02_shot.png\" alt=\"A\"><img src='cid:gone@x'>"
+        );
+    }
+
+    #[test]
+    fn test_content_image_and_fallback_name() {
+        assert!(is_content_image("image001.png", 180 * 1024));
+        assert!(!is_content_image("image001.png", 4 * 1024));
+        assert!(!is_content_image("logo_societe.png", 180 * 1024));
+        assert_eq!(fallback_filename("<image001.png@01DA2B3C>", "image/png"), "image001.png");
+        assert_eq!(fallback_filename("<part1.06090408@example.com>", "image/jpeg"), "part1.06090408.jpg");
+        assert_eq!(fallback_filename("<shot@x>", "image/jpeg"), "shot.jpg");
+        assert_eq!(fallback_filename("<@x>", "image/png"), "inline.png");
+    }
+}
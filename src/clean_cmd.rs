@@ -0,0 +1,156 @@
+//! `clean` CLI subcommand: run the body cleaner on one message.
+//!
+//! The body of an `.eml` file (any other file is read as text) goes through
+//! the cleaner pipeline with the `cleaner` settings of `--account`, its rules
+//! scoped by the `From` address (or `--from`); the account's `signatures` and
+//! `quoted_history` settings apply as in an export. `--explain` shows, stage by
+//! stage, the lines each one removed (`-`) and added (`+`).
+
+use anyhow::{Context, Result};
+use clap::Args;
+use mailparse::MailHeaderMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::body::{extract_body, BodyPreference};
+use crate::cleaner::{self, CleanerSettings, MessageStages, QuotedHistory, RuleScope, StageChange};
+use crate::config::{self, Config};
+use crate::embedded::EmbeddedNotes;
+use crate::signatures::{SignatureHistory, SignatureSettings};
+use crate::utils::{extract_emails, normalize_line_breaks};
+
+#[derive(Args)]
+pub struct CleanArgs {
+    /// Message (.eml) or text file to clean
+    pub file: PathBuf,
+
+    /// Show what each stage of the pipeline changed
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Use the cleaner settings of this account (default: built-in stages only)
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Sender address for domain-scoped rules (default: the From header)
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Path to config file (default: system config dir)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+}
+
+/// Line diff of one stage: removed lines prefixed with `- `, added lines with
+/// `+ `, unchanged lines left out.
+pub fn line_diff(before: &str, after: &str) -> Vec<String> {
+    let (a, b): (Vec<&str>, Vec<&str>) = (before.lines().collect(), after.lines().collect());
+    // Longest common subsequence table, from the end
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j, mut out) = (0, 0, Vec::new());
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// `--explain` report: one section per stage that changed the body.
+pub fn explain_report(changes: &[StageChange]) -> String {
+    if changes.is_empty() {
+        return "No stage changed the body.\n".to_string();
+    }
+    let mut out = String::new();
+    for change in changes {
+        out.push_str(&format!("== {} ==\n", change.stage));
+        for line in line_diff(&change.before, &change.after) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Entry point dispatched from `main`.
+pub fn run(args: CleanArgs) -> Result<()> {
+    let (account_name, settings, preference, signatures, quoted_history) = match &args.account {
+        Some(name) => {
+            let config_path = args.config.clone().unwrap_or_else(config::accounts_yaml_path);
+            let config = Config::load(&config_path).context("Failed to load configuration")?;
+            let account = config
+                .accounts
+                .into_iter()
+                .find(|a| a.name.eq_ignore_ascii_case(name))
+                .with_context(|| format!("No account '{}' in {}", name, config_path.display()))?;
+            (account.name, account.cleaner, account.body_preference, account.signatures, account.quoted_history)
+        }
+        None => (
+            String::new(),
+            CleanerSettings::default(),
+            BodyPreference::default(),
+            SignatureSettings::default(),
+            QuotedHistory::default(),
+        ),
+    };
+
+    let raw = fs::read(&args.file).with_context(|| format!("failed to read {}", args.file.display()))?;
+    let is_eml = args.file.extension().is_some_and(|e| e.eq_ignore_ascii_case("eml"));
+    let (body, from) = if is_eml {
+        let mail = mailparse::parse_mail(&raw).with_context(|| format!("failed to parse {}", args.file.display()))?;
+        let from = mail.headers.get_first_value("From");
+        (extract_body(&mail, preference, &EmbeddedNotes::default()), from)
+    } else {
+        (String::from_utf8_lossy(&raw).into_owned(), None)
+    };
+    let sender = args
+        .from
+        .or_else(|| extract_emails(from.as_deref()).into_iter().next())
+        .unwrap_or_default();
+
+    let body = normalize_line_breaks(&body);
+    let scope = RuleScope { account: &account_name, sender: &sender };
+    // Read-only: the tails recorded here are not saved
+    let mut history = signatures.strip.then(|| SignatureHistory::load(&SignatureHistory::path()));
+    let message = MessageStages {
+        quoted_history,
+        earlier_note: None,
+        signatures,
+        signature_history: history.as_mut(),
+    };
+    if args.explain {
+        let (_, changes) = cleaner::explain(&body, &settings, &scope, message);
+        print!("{}", explain_report(&changes));
+    } else {
+        println!("{}", cleaner::clean_message(&body, &settings, &scope, message).body);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff_and_report() {
+        assert_eq!(line_diff("a\nb\nc", "a\nB\nc\nd"), vec!["- b", "+ B", "+ d"]);
+        assert!(line_diff("same", "same").is_empty());
+
+        let changes = [StageChange { stage: "boilerplate", before: "Hi\nFooter".into(), after: "Hi".into() }];
+        assert_eq!(explain_report(&changes), "== boilerplate ==\n- Footer\n\n");
+        assert_eq!(explain_report(&[]), "No stage changed the body.\n");
+    }
+}
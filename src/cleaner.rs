@@ -1,44 +1,303 @@
 use std::collections::BTreeMap;
 use std::sync::LazyLock;
 
+use encoding_rs::{Encoding, ISO_8859_15, UTF_8, WINDOWS_1252};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::signatures::{self, SignatureHistory, SignatureSettings};
+
 /// Result of running the cleaner pipeline on an email body.
 ///
 /// `body` is the cleaned text. `social_links` holds any social-network
 /// links extracted from a trailing footer block, to be merged into the
-/// caller's frontmatter. Phase 0 always returns `None`.
+/// caller's frontmatter. Phase 0 always returns `None`. `charset_repaired`
+/// is set when mojibake was repaired (`charset_repaired: true` in the note).
+/// `signature_block` is the signature removed by the `signatures` stage, cleaned.
 pub struct CleanResult {
     pub body: String,
     pub social_links: Option<BTreeMap<String, String>>,
+    pub charset_repaired: bool,
+    pub signature_block: Option<String>,
+}
+
+/// Built-in stages of the cleaner pipeline, all on by default (`cleaner.stages`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanerStages {
+    pub decode_qp: bool,
+    pub html_entities: bool,
+    pub invisible_chars: bool,
+    pub mojibake: bool,
+    pub social_footer: bool,
+    pub reattach_urls: bool,
+    pub unwrap_lines: bool,
+    pub extract_links: bool,
+    pub trackers: bool,
+    pub whitespace: bool,
+    /// Signature block removal, when `signatures.strip` is set.
+    pub signatures: bool,
+    /// Quoted history folding or removal, as set by `quoted_history`.
+    pub quoted_history: bool,
+}
+
+impl Default for CleanerStages {
+    fn default() -> Self {
+        Self {
+            decode_qp: true,
+            html_entities: true,
+            invisible_chars: true,
+            mojibake: true,
+            social_footer: true,
+            reattach_urls: true,
+            unwrap_lines: true,
+            extract_links: true,
+            trackers: true,
+            whitespace: true,
+            signatures: true,
+            quoted_history: true,
+        }
+    }
+}
+
+/// User regex rule: matches of `pattern` are replaced by `replace` (removed
+/// by default), for the listed sender domains and accounts (all when empty).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CleanerRule {
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub replace: String,
+    /// Sender domains, subdomains included.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+}
+
+/// `cleaner:` settings: built-in stages, user rules and boilerplate blocks.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanerSettings {
+    pub stages: CleanerStages,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<CleanerRule>,
+    /// Multi-line blocks removed wherever they appear, whatever their line
+    /// wrapping and case.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub boilerplate: Vec<String>,
+    /// Redirect wrappers unwrapped in the `trackers` stage, before the built-in ones.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub url_wrappers: Vec<UrlWrapper>,
+}
+
+impl CleanerSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in &self.rules {
+            Regex::new(&rule.pattern).map_err(|e| format!("cleaner: invalid rule pattern '{}': {}", rule.pattern, e))?;
+        }
+        if self.boilerplate.iter().any(|block| block.trim().is_empty()) {
+            return Err("cleaner: empty boilerplate block".to_string());
+        }
+        self.url_wrappers.iter().try_for_each(UrlWrapper::validate)
+    }
+}
+
+/// Message the user rules are applied to: its account and sender address.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RuleScope<'a> {
+    pub account: &'a str,
+    pub sender: &'a str,
+}
+
+impl CleanerRule {
+    fn applies(&self, scope: &RuleScope) -> bool {
+        let domain = scope.sender.rsplit_once('@').map_or("", |(_, d)| d).to_lowercase();
+        let domain_ok = self.domains.is_empty()
+            || self.domains.iter().any(|d| {
+                let d = d.trim().trim_start_matches('@').to_lowercase();
+                domain == d || domain.ends_with(&format!(".{}", d))
+            });
+        let account_ok = self.accounts.is_empty() || self.accounts.iter().any(|a| a == scope.account);
+        domain_ok && account_ok
+    }
+}
+
+/// Apply the user rules of `settings` that match `scope`.
+pub fn apply_rules(s: &str, settings: &CleanerSettings, scope: &RuleScope) -> String {
+    let mut out = s.to_string();
+    for rule in settings.rules.iter().filter(|r| r.applies(scope)) {
+        if let Ok(re) = Regex::new(&rule.pattern) {
+            out = re.replace_all(&out, rule.replace.as_str()).into_owned();
+        }
+    }
+    out
+}
+
+/// Remove the boilerplate blocks of `settings`: words separated by any
+/// whitespace (line breaks included), case-insensitive.
+pub fn strip_boilerplate(s: &str, settings: &CleanerSettings) -> String {
+    let mut out = s.to_string();
+    for block in &settings.boilerplate {
+        let words: Vec<String> = block.split_whitespace().map(regex::escape).collect();
+        if let Ok(re) = Regex::new(&format!(r"(?i){}[ \t]*", words.join(r"\s+"))) {
+            out = re.replace_all(&out, "").into_owned();
+        }
+    }
+    out
+}
+
+/// Reply and signature handling of one message (`quoted_history`,
+/// `signatures:`), for the stages that need more than the body. The default
+/// leaves both the history and the signature in place.
+#[derive(Default)]
+pub struct MessageStages<'a> {
+    pub quoted_history: QuotedHistory,
+    /// Note of the quoted message, linked in place of the history.
+    pub earlier_note: Option<&'a str>,
+    pub signatures: SignatureSettings,
+    /// Tails of the sender's previous messages; the current one is recorded.
+    pub signature_history: Option<&'a mut SignatureHistory>,
+}
+
+/// Output of one stage that changed the body (`clean --explain`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageChange {
+    pub stage: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+/// Run the cleaner pipeline on an email body with the default settings.
+pub fn clean(body: &str) -> CleanResult {
+    clean_with(body, &CleanerSettings::default(), &RuleScope::default())
+}
+
+/// Run the cleaner pipeline on an email body (see [`explain`] for the stages).
+pub fn clean_with(body: &str, settings: &CleanerSettings, scope: &RuleScope) -> CleanResult {
+    clean_message(body, settings, scope, MessageStages::default())
+}
+
+/// Run the cleaner pipeline on a message body, its quoted history and
+/// signature handled as set in `message`.
+pub fn clean_message(body: &str, settings: &CleanerSettings, scope: &RuleScope, message: MessageStages) -> CleanResult {
+    run(body, settings, scope, message, &mut |_| {})
+}
+
+/// Run the cleaner pipeline and report every stage that changed the body.
+pub fn explain(
+    body: &str,
+    settings: &CleanerSettings,
+    scope: &RuleScope,
+    message: MessageStages,
+) -> (CleanResult, Vec<StageChange>) {
+    let mut changes = Vec::new();
+    let result = run(body, settings, scope, message, &mut |change| changes.push(change));
+    (result, changes)
 }
 
-/// Run the cleaner pipeline on an email body.
+/// Own text and quoted history of a reply back together, in their order.
+fn join_reply(split: &ReplySplit, own: &str, history: Option<String>) -> String {
+    match history {
+        Some(history) if split.history_first => format!("{}\n\n{}", history, own),
+        Some(history) => format!("{}\n\n{}\n", own.trim_end(), history),
+        None => own.to_string(),
+    }
+}
+
+/// The cleaner pipeline around the body stages ([`run_body`]): the quoted
+/// history of a reply is split off and cleaned apart, the signature block
+/// removed from the own text, then the history rendered back (`quoted_history`
+/// stage). Stages turned off in `settings.stages` are skipped.
+fn run(
+    body: &str,
+    settings: &CleanerSettings,
+    scope: &RuleScope,
+    message: MessageStages,
+    trace: &mut dyn FnMut(StageChange),
+) -> CleanResult {
+    let stages = &settings.stages;
+    let reply = match message.quoted_history {
+        QuotedHistory::Keep => None,
+        _ if !stages.quoted_history => None,
+        _ => split_reply(body),
+    };
+    let own = reply.as_ref().map_or(body, |reply| reply.own.as_str());
+
+    let mut block = None;
+    let own = if stages.signatures && message.signatures.strip {
+        let stripped = signatures::strip(own, scope.sender, &message.signatures, message.signature_history);
+        if stripped.body != own {
+            trace(StageChange { stage: "signatures", before: own.to_string(), after: stripped.body.clone() });
+        }
+        block = stripped.block.map(|b| run_body(&b, settings, scope, &mut |_| {}));
+        stripped.body
+    } else {
+        own.to_string()
+    };
+
+    let mut result = run_body(&own, settings, scope, trace);
+    if let Some(reply) = &reply {
+        let history = run_body(&reply.history, settings, scope, &mut |_| {}).body;
+        let kept = join_reply(reply, &result.body, Some(history.clone()));
+        let rendered = render_history(reply, &history, message.quoted_history, message.earlier_note);
+        let body = join_reply(reply, &result.body, rendered);
+        if body != kept {
+            trace(StageChange { stage: "quoted_history", before: kept, after: body.clone() });
+        }
+        result.body = body;
+    }
+    if let Some(block) = block {
+        // Social links of a removed signature are still extracted
+        result.social_links = result.social_links.or(block.social_links);
+        result.signature_block = Some(block.body.trim().to_string());
+    }
+    result
+}
+
+/// The body stages of the cleaner pipeline.
 ///
 /// Pipeline order (final architecture):
 /// 1. Decode fidelity: residual quoted-printable, HTML entities, strip
-///    invisible chars, mojibake warning.
-/// 2. URL reattachment — must run before any line-structure pass so that
-///    URLs broken across two physical lines are seen as single tokens by
-///    later stages.
+///    invisible chars, mojibake repair.
+/// 2. User rules and boilerplate blocks (`cleaner.rules`, `cleaner.boilerplate`),
+///    on the decoded text and before any line is unwrapped.
 /// 3. Social footer extraction — must run before `unwrap_lines` collapses
 ///    the vertical layout that the footer detector relies on.
-/// 4. Unwrap 80-char wrapping into flowing paragraphs.
-/// 5. Inline link extraction → numbered references, then tracker
+/// 4. URL reattachment — must run before any line-structure pass so that
+///    URLs broken across two physical lines are seen as single tokens by
+///    later stages.
+/// 5. Unwrap 80-char wrapping into flowing paragraphs.
+/// 6. Inline link extraction → numbered references, then tracker
 ///    decontamination of the generated reference URLs.
-/// 6. Whitespace hygiene: collapse runs of horizontal whitespace and
+/// 7. Whitespace hygiene: collapse runs of horizontal whitespace and
 ///    strip trailing whitespace per line.
-pub fn clean(body: &str) -> CleanResult {
-    // 1. Decode fidelity
-    let body = decode_residual_qp(body);
-    let body = decode_html_entities(&body);
-    let body = strip_invisible_chars(&body);
-    if detect_mojibake(&body) {
-        eprintln!("warning: possible mojibake detected in email body");
-    }
+fn run_body(body: &str, settings: &CleanerSettings, scope: &RuleScope, trace: &mut dyn FnMut(StageChange)) -> CleanResult {
+    let stages = &settings.stages;
+    let mut step = |stage: &'static str, enabled: bool, body: String, f: &dyn Fn(&str) -> String| {
+        if !enabled {
+            return body;
+        }
+        let after = f(&body);
+        if after != body {
+            trace(StageChange { stage, before: body, after: after.clone() });
+        }
+        after
+    };
 
-    // 2. Social footer extraction.
+    // 1. Decode fidelity
+    let body = step("decode_qp", stages.decode_qp, body.to_string(), &decode_residual_qp);
+    let body = step("html_entities", stages.html_entities, body, &decode_html_entities);
+    let body = step("invisible_chars", stages.invisible_chars, body, &strip_invisible_chars);
+    let before = body.clone();
+    let body = step("mojibake", stages.mojibake, body, &|s| repair_mojibake(s).unwrap_or_else(|| s.to_string()));
+    let charset_repaired = body != before;
+
+    // 2. User rules, then boilerplate blocks
+    let body = step("rules", true, body, &|s| apply_rules(s, settings, scope));
+    let body = step("boilerplate", true, body, &|s| strip_boilerplate(s, settings));
+
+    // 3. Social footer extraction.
     //
     //    NOTE: the original phase plan put `reattach_urls` first, but that
     //    interacts badly with stacked single-link social lines whose URL-safe
@@ -46,29 +305,34 @@ pub fn clean(body: &str) -> CleanResult {
     //    footer detector first (which keys on lines matching
     //    `^\[.+\]\(.+\)$`) keeps the verbatim line layout intact so the four
     //    social links are recognised as a block, and any wrapped URLs *inside*
-    //    the surviving body are still repaired in step 3.
-    let (body, social_links) = extract_social_footer(&body);
+    //    the surviving body are still repaired in step 4.
+    let social_links = std::cell::RefCell::new(None);
+    let body = step("social_footer", stages.social_footer, body, &|s| {
+        let (rest, links) = extract_social_footer(s);
+        *social_links.borrow_mut() = links;
+        rest
+    });
+    let social_links = social_links.into_inner();
 
-    // 3. URL reattachment. The detector is dangling-URL-aware: it only joins
+    // 4. URL reattachment. The detector is dangling-URL-aware: it only joins
     //    two lines when the previous line's tail actually looks like a URL
     //    (`https?://…` or `www.…`). Plain prose boundaries are left alone, so
     //    wrapped paragraphs survive intact for `unwrap_lines` to handle next.
-    let body = reattach_urls(&body);
+    let body = step("reattach_urls", stages.reattach_urls, body, &reattach_urls);
 
-    // 4. Unwrap 80-char wrapping
-    let body = unwrap_lines(&body);
+    // 5. Unwrap 80-char wrapping
+    let body = step("unwrap_lines", stages.unwrap_lines, body, &unwrap_lines);
 
-    // 5. Inline link extraction → numbered refs, then tracker decontamination
+    // 6. Inline link extraction → numbered refs, then tracker decontamination
     //    of the reference URLs. We post-process the generated reference lines
     //    rather than threading a transform closure through `extract_links`.
-    let body = extract_links(&body);
-    let body = decontaminate_ref_urls(&body);
+    let body = step("extract_links", stages.extract_links, body, &extract_links);
+    let body = step("trackers", stages.trackers, body, &|s| decontaminate_ref_urls(s, &settings.url_wrappers));
 
-    // 6. Whitespace hygiene
-    let body = collapse_whitespace(&body);
-    let body = trim_trailing(&body);
+    // 7. Whitespace hygiene
+    let body = step("whitespace", stages.whitespace, body, &|s| trim_trailing(&collapse_whitespace(s)));
 
-    CleanResult { body, social_links }
+    CleanResult { body, social_links, charset_repaired, signature_block: None }
 }
 
 /// Collapse runs of horizontal whitespace (ASCII space, tab, U+00A0) into a
@@ -122,10 +386,17 @@ pub fn trim_trailing(s: &str) -> String {
     joined
 }
 
+/// URL in running text, bare or autolinked (`<…>`), trailing punctuation
+/// excluded.
+static TEXT_URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"https?://[^\s<>"()\[\]]*[^\s<>"()\[\].,;:!?'*_]"#).expect("static regex"));
+
 /// Walk lines and rewrite any numbered-reference line `[N]: <url>` by
-/// passing its URL through `decontaminate_trackers`. Non-matching lines
-/// are left untouched. A trailing newline in the input is preserved.
-fn decontaminate_ref_urls(s: &str) -> String {
+/// passing its URL through `decontaminate_with`. On the other lines, bare,
+/// autolinked and inline-link URLs that are redirect wrappers are replaced
+/// by their cleaned destination; the others are left untouched, whether
+/// `extract_links` ran or not. A trailing newline in the input is preserved.
+fn decontaminate_ref_urls(s: &str, wrappers: &[UrlWrapper]) -> String {
     if s.is_empty() {
         return String::new();
     }
@@ -144,7 +415,7 @@ fn decontaminate_ref_urls(s: &str) -> String {
         if let Some(caps) = re.captures(line) {
             if let Some(url_match) = caps.get(1) {
                 let url = url_match.as_str();
-                let cleaned = decontaminate_trackers(url);
+                let cleaned = decontaminate_with(url, wrappers);
                 // Preserve the `[N]: ` prefix length by reconstructing.
                 let prefix_end = url_match.start();
                 let prefix = &line[..prefix_end];
@@ -152,7 +423,15 @@ fn decontaminate_ref_urls(s: &str) -> String {
                 continue;
             }
         }
-        out.push((*line).to_string());
+        let line = TEXT_URL_RE.replace_all(line, |caps: &regex::Captures| {
+            let url = &caps[0];
+            if unwrap_redirects(url, wrappers) == url {
+                url.to_string()
+            } else {
+                decontaminate_with(url, wrappers)
+            }
+        });
+        out.push(line.into_owned());
     }
 
     let mut joined = out.join("\n");
@@ -249,6 +528,167 @@ pub fn detect_mojibake(s: &str) -> bool {
     PATTERNS.iter().any(|p| s.contains(p))
 }
 
+/// Encodings a UTF-8 text is mistakenly decoded with, tried in this order.
+const MOJIBAKE_CANDIDATES: &[&Encoding] = &[WINDOWS_1252, ISO_8859_15];
+
+/// Rounds of repair, for text encoded twice or three times (`ÃƒÂ©`).
+const MOJIBAKE_MAX_ROUNDS: usize = 3;
+
+/// Characters a repaired sequence may decode to: Latin-1 and Latin
+/// Extended-A letters, typographic punctuation, `€`, `™`, symbols and emoji.
+/// Anything else (`CAFÉ»` would give `ɻ`) is a legitimate pair of characters.
+fn is_plausible_repair(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00A0}'..='\u{017F}'
+            | '\u{2010}'..='\u{2044}'
+            | '\u{20AC}'
+            | '\u{2122}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{1F000}'..='\u{1FAFF}'
+    )
+}
+
+/// Badness of a decoded text: replacement characters, C1 controls and
+/// mojibake digraphs (`Ã©`, `â€™`…). Lower is better.
+pub fn decoding_score(s: &str) -> usize {
+    let chars: Vec<char> = s.chars().collect();
+    let mut score = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '\u{FFFD}' {
+            score += 4;
+        } else if ('\u{0080}'..='\u{009F}').contains(&c) {
+            score += 2;
+        } else if ('\u{00C2}'..='\u{00F4}').contains(&c) {
+            let next_is_continuation = chars
+                .get(i + 1)
+                .and_then(|&n| latin_byte(n, WINDOWS_1252))
+                .is_some_and(|b| (0x80..=0xBF).contains(&b));
+            if next_is_continuation {
+                score += 3;
+            }
+        }
+    }
+    score
+}
+
+/// Byte `c` was decoded from with `encoding` (C1 controls pass through, as
+/// when the text was decoded as ISO-8859-1).
+fn latin_byte(c: char, encoding: &'static Encoding) -> Option<u8> {
+    if (c as u32) < 0x80 {
+        return Some(c as u8);
+    }
+    if ('\u{0080}'..='\u{009F}').contains(&c) {
+        return Some(c as u32 as u8);
+    }
+    let mut buf = [0u8; 4];
+    let (bytes, _, unmappable) = encoding.encode(c.encode_utf8(&mut buf));
+    (!unmappable && bytes.len() == 1).then(|| bytes[0])
+}
+
+/// One round of repair: in each run of non-ASCII characters, the sequences
+/// whose bytes (in `encoding`) form valid UTF-8 are decoded again.
+fn repair_round(s: &str, encoding: &'static Encoding) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let lead = latin_byte(chars[i], encoding).filter(|b| (0xC2..=0xF4).contains(b));
+        let len = match lead {
+            Some(0xC2..=0xDF) => 2,
+            Some(0xE0..=0xEF) => 3,
+            Some(_) => 4,
+            None => 0,
+        };
+        let bytes: Option<Vec<u8>> = (len > 0 && i + len <= chars.len())
+            .then(|| chars[i..i + len].iter().map(|&c| latin_byte(c, encoding)).collect())
+            .flatten();
+        let decoded = bytes
+            .as_deref()
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .and_then(|d| d.chars().next())
+            .filter(|&d| is_plausible_repair(d));
+        match decoded {
+            Some(d) => {
+                out.push(d);
+                i += len;
+            }
+            None => {
+                out.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Repair UTF-8 text that was decoded as Windows-1252 / ISO-8859-x (`Ã©tÃ©`
+/// for `été`), once or several times over. Each candidate encoding is tried
+/// and the best-scoring result kept (see [`decoding_score`]); correctly
+/// decoded characters next to the garbled ones are left untouched. Returns
+/// `None` when nothing needed (or could be) repaired.
+pub fn repair_mojibake(s: &str) -> Option<String> {
+    let original = decoding_score(s);
+    if original == 0 {
+        return None;
+    }
+    let mut best: Option<(usize, String)> = None;
+    for &encoding in MOJIBAKE_CANDIDATES {
+        let mut text = s.to_string();
+        for _ in 0..MOJIBAKE_MAX_ROUNDS {
+            let next = repair_round(&text, encoding);
+            if next == text {
+                break;
+            }
+            text = next;
+        }
+        if text != s {
+            text = fix_lost_nbsp(s, &text);
+        }
+        let score = decoding_score(&text);
+        if score < original && best.as_ref().is_none_or(|(b, _)| score < *b) {
+            best = Some((score, text));
+        }
+    }
+    best.map(|(_, text)| text)
+}
+
+/// `à ` whose NBSP second byte became a plain space (`Ã  ` → `à `), on the
+/// lines of `repaired` a round actually changed: elsewhere in `original`, a
+/// `Ã ` is a legitimate capital (`IRMÃ Maria`).
+fn fix_lost_nbsp(original: &str, repaired: &str) -> String {
+    original
+        .split_inclusive('\n')
+        .zip(repaired.split_inclusive('\n'))
+        .map(|(before, after)| {
+            if before == after {
+                after.to_string()
+            } else {
+                after.replace("Ã  ", "à ").replace("Ã ", "à ")
+            }
+        })
+        .collect()
+}
+
+/// Decode `raw` text declared as `charset` with the best-scoring candidate
+/// (declared charset, UTF-8, Windows-1252), for parts whose declared charset
+/// is wrong. Returns `None` when the declared charset is the best.
+pub fn redecode(raw: &[u8], charset: &str) -> Option<String> {
+    let declared = Encoding::for_label(charset.as_bytes()).unwrap_or(UTF_8);
+    let score_of = |encoding: &'static Encoding| {
+        let text = encoding.decode_without_bom_handling(raw).0.into_owned();
+        (decoding_score(&text), text)
+    };
+    let (declared_score, _) = score_of(declared);
+    [UTF_8, WINDOWS_1252]
+        .into_iter()
+        .filter(|&e| e != declared)
+        .map(score_of)
+        .filter(|(score, _)| *score < declared_score)
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, text)| text)
+}
+
 /// True if `c` is a character that can legitimately appear inside a URL.
 ///
 /// Mirrors the regex class `[A-Za-z0-9._~:/?#\[\]@!$&'()*+,;=%-]` from RFC 3986
@@ -498,51 +938,202 @@ pub fn extract_links(s: &str) -> String {
     out
 }
 
-/// Strip tracker noise from a URL string and unwrap known click-tracker
-/// redirects. Returns the cleaned URL string. If parsing fails at any step,
+/// How a redirect wrapper carries its destination URL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapperFormat {
+    /// Percent-encoded URL in one of the `params`.
+    #[default]
+    Query,
+    /// Base64 (standard or URL-safe) URL in one of the `params`.
+    Base64,
+    /// Proofpoint URL Defense v2: `u=https-3A__example.com_path`.
+    UrldefenseV2,
+    /// Proofpoint URL Defense v3: `/v3/__<url>__;<replaced chars>!!…`.
+    UrldefenseV3,
+}
+
+/// Redirect wrapper whose destination is carried by the URL itself, so it
+/// is recovered without following the redirect (`cleaner.url_wrappers`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UrlWrapper {
+    /// Host: exact, `*.example.com` (domain and subdomains) or `click.*` (prefix).
+    pub host: String,
+    /// Path prefix (any path when empty).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+    /// Query parameters that may hold the destination, first valid one wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    #[serde(default)]
+    pub format: WrapperFormat,
+}
+
+/// Query parameters of the generic click trackers.
+const TRACKER_PARAMS: &[&str] = &["url", "u", "r"];
+
+/// Built-in wrappers, tried after the `cleaner.url_wrappers` of the account.
+static BUILTIN_WRAPPERS: LazyLock<Vec<UrlWrapper>> = LazyLock::new(|| {
+    let wrapper = |host: &str, path: &str, params: &[&str], format| UrlWrapper {
+        host: host.to_string(),
+        path: path.to_string(),
+        params: params.iter().map(|p| p.to_string()).collect(),
+        format,
+    };
+    vec![
+        wrapper("*.safelinks.protection.outlook.com", "", &["url"], WrapperFormat::Query),
+        wrapper("*.google.com", "/url", &["q", "url"], WrapperFormat::Query),
+        wrapper("urldefense.com", "/v3/", &[], WrapperFormat::UrldefenseV3),
+        wrapper("urldefense.proofpoint.com", "/v3/", &[], WrapperFormat::UrldefenseV3),
+        wrapper("urldefense.proofpoint.com", "/v2/url", &["u"], WrapperFormat::UrldefenseV2),
+        wrapper("*.list-manage.com", "", TRACKER_PARAMS, WrapperFormat::Query),
+        wrapper("*.sendgrid.net", "", TRACKER_PARAMS, WrapperFormat::Query),
+        wrapper("click.*", "", TRACKER_PARAMS, WrapperFormat::Query),
+        wrapper("email.*", "", TRACKER_PARAMS, WrapperFormat::Query),
+    ]
+});
+
+static URLDEFENSE_V3_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"/v3/__(?P<url>.+?)__;(?P<chars>[^!]*)!").expect("static regex"));
+
+/// Maximum number of nested wrappers removed from one URL.
+const MAX_UNWRAP_DEPTH: usize = 8;
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        host == domain || host.ends_with(&format!(".{}", domain))
+    } else if let Some(prefix) = pattern.strip_suffix(".*") {
+        host.starts_with(&format!("{}.", prefix))
+    } else {
+        host == pattern
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    percent_encoding::percent_decode_str(s).decode_utf8_lossy().into_owned()
+}
+
+fn decode_base64(s: &str) -> Option<String> {
+    use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+    use base64::Engine;
+    let s = s.trim().trim_end_matches('=');
+    let bytes = URL_SAFE_NO_PAD.decode(s).or_else(|_| STANDARD_NO_PAD.decode(s)).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// URL Defense v2 `u` value: `-` stands for `%` and `_` for `/`.
+fn decode_urldefense_v2(s: &str) -> String {
+    html_escape::decode_html_entities(&percent_decode(&s.replace('-', "%").replace('_', "/"))).into_owned()
+}
+
+/// URL Defense v3: every `*` of the URL is the next character of the base64
+/// block after `__;`, and `**X` a run of them (`A` = 2, `B` = 3, …).
+fn decode_urldefense_v3(raw: &str) -> Option<String> {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    const RUN_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    let caps = URLDEFENSE_V3_RE.captures(raw)?;
+    let url = percent_decode(&caps["url"]);
+    let bytes = URL_SAFE_NO_PAD.decode(caps["chars"].trim_end_matches('=')).ok()?;
+    let mut replaced = String::from_utf8(bytes).ok()?.chars().collect::<Vec<_>>().into_iter();
+
+    let mut out = String::new();
+    let mut chars = url.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '*' {
+            out.push(c);
+        } else if chars.peek() == Some(&'*') {
+            chars.next();
+            let run = RUN_CHARS.find(chars.next()?)? + 2;
+            for _ in 0..run {
+                out.push(replaced.next()?);
+            }
+        } else {
+            out.push(replaced.next()?);
+        }
+    }
+    Some(out)
+}
+
+impl UrlWrapper {
+    /// Destination of `url` (`raw` before parsing) if `self` wraps it and it
+    /// decodes to an HTTP(S) URL.
+    fn destination(&self, url: &Url, raw: &str) -> Option<String> {
+        if !url.host_str().is_some_and(|host| host_matches(&self.host, host)) || !url.path().starts_with(&self.path) {
+            return None;
+        }
+        let is_web = |dest: &String| Url::parse(dest).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+        if self.format == WrapperFormat::UrldefenseV3 {
+            return decode_urldefense_v3(raw).filter(is_web);
+        }
+        // query_pairs already URL-decodes the values
+        url.query_pairs()
+            .filter(|(key, _)| self.params.iter().any(|p| p == key))
+            .find_map(|(_, value)| {
+                match self.format {
+                    WrapperFormat::Base64 => decode_base64(&value),
+                    WrapperFormat::UrldefenseV2 => Some(decode_urldefense_v2(&value)),
+                    _ => Some(value.into_owned()),
+                }
+                .filter(is_web)
+            })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() {
+            return Err("cleaner: url wrapper without host".to_string());
+        }
+        if self.params.is_empty() && self.format != WrapperFormat::UrldefenseV3 {
+            return Err(format!("cleaner: url wrapper '{}' has no params", self.host));
+        }
+        Ok(())
+    }
+}
+
+/// Recover the destination of a redirect-wrapped URL, offline: Outlook Safe
+/// Links, `google.com/url`, URL Defense v2/v3, Mailchimp, SendGrid and
+/// generic `click.*` / `email.*` trackers, after the `wrappers` of the
+/// settings. Nested wrappers are removed in turn. Wrappers that only carry
+/// an identifier (Mailchimp `track/click?u=…&id=…`) are left as they are.
+pub fn unwrap_redirects(url_str: &str, wrappers: &[UrlWrapper]) -> String {
+    let mut current = url_str.to_string();
+    for _ in 0..MAX_UNWRAP_DEPTH {
+        let Ok(url) = Url::parse(&current) else {
+            break;
+        };
+        match wrappers.iter().chain(BUILTIN_WRAPPERS.iter()).find_map(|w| w.destination(&url, &current)) {
+            Some(destination) => current = destination,
+            None => break,
+        }
+    }
+    current
+}
+
+/// Strip tracker noise from a URL string and unwrap known redirect
+/// wrappers. Returns the cleaned URL string. If parsing fails at any step,
 /// returns the input unchanged.
 ///
 /// Cleaning rules, in order:
-/// 1. Parse the URL via `url::Url::parse`. On failure: return input unchanged.
-/// 2. If the host is a known tracker pattern (mailchimp `*.list-manage.com`,
-///    sendgrid `*.sendgrid.net`, generic `click.*`, generic `email.*`), look
-///    for a query param named `url`, `u`, or `r` whose value parses as an
-///    HTTPS URL. If found, recursively clean and return that URL.
-/// 3. Otherwise, strip every query param whose key starts with `utm_` and
-///    return the rebuilt URL. If the resulting query is empty, the trailing
-///    `?` is removed too.
+/// 1. Recover the destination of wrapped URLs with `unwrap_redirects`,
+///    built-in wrappers only (see `decontaminate_with` for settings).
+/// 2. Parse the URL via `url::Url::parse`. On failure: return input unchanged.
+/// 3. Strip every query param whose key starts with `utm_` and return the
+///    rebuilt URL. If the resulting query is empty, the trailing `?` is
+///    removed too.
 /// 4. The URL fragment is preserved across all steps.
 pub fn decontaminate_trackers(url_str: &str) -> String {
-    let Ok(url) = Url::parse(url_str) else {
-        return url_str.to_string();
-    };
+    decontaminate_with(url_str, &[])
+}
 
-    // Step 1: try to unwrap a known tracker redirect
-    if let Some(host) = url.host_str() {
-        let is_tracker = host.ends_with(".list-manage.com")
-            || host == "list-manage.com"
-            || host.ends_with(".sendgrid.net")
-            || host == "sendgrid.net"
-            || host.starts_with("click.")
-            || host.starts_with("email.");
-
-        if is_tracker {
-            for (key, value) in url.query_pairs() {
-                if key == "url" || key == "u" || key == "r" {
-                    // query_pairs already URL-decodes the value
-                    if let Ok(inner) = Url::parse(&value) {
-                        if inner.scheme() == "https" || inner.scheme() == "http" {
-                            // Recursively clean the unwrapped URL (handles nested utm)
-                            return decontaminate_trackers(inner.as_str());
-                        }
-                    }
-                }
-            }
-        }
+/// `decontaminate_trackers` with the `cleaner.url_wrappers` of the settings.
+pub fn decontaminate_with(url_str: &str, wrappers: &[UrlWrapper]) -> String {
+    match Url::parse(&unwrap_redirects(url_str, wrappers)) {
+        Ok(url) => strip_utm_params(&url),
+        Err(_) => url_str.to_string(),
     }
-
-    // Step 2: strip utm_* params
-    strip_utm_params(&url)
 }
 
 /// Rebuild `url` with every `utm_*` query parameter removed. If the remaining
@@ -746,6 +1337,177 @@ pub fn unwrap_lines(s: &str) -> String {
     joined
 }
 
+/// Attribution line of a reply, on one or two lines (Gmail wraps it):
+/// « Le lun. 3 juin 2026 à 10:00, X a écrit : », "On Mon, Jun 3, 2026 at
+/// 10:00 AM X wrote:", "Am 03.06.2026 um 10:00 schrieb X:", "El lun, 3 jun
+/// 2026 a las 10:00, X escribió:".
+static ATTRIBUTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:le|on|am|el)\s.*\d.*\b(?:a\s+écrit|wrote|schrieb|escribió)(?:\s.*)?:$").expect("static regex")
+});
+
+/// Outlook separator: `-----Original Message-----` and its translations.
+static ORIGINAL_MESSAGE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^-{3,}\s*(?:original message|message d['’]origine|ursprüngliche nachricht|mensaje original)\s*-{3,}$")
+        .expect("static regex")
+});
+
+/// Separator of a forwarded message: its content is not quoted history.
+static FORWARDED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^-{3,}\s*(?:forwarded message|message transféré|weitergeleitete nachricht|mensaje reenviado)\s*-{3,}$")
+        .expect("static regex")
+});
+
+/// Field of an Outlook header block (`De : X`, `Envoyé : …`, `Betreff: …`).
+static HEADER_FIELD_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(from|de|von|sent|envoyé|gesendet|enviado|date|datum|fecha|to|à|an|para|cc|subject|objet|betreff|asunto)\s*:\s*\S",
+    )
+    .expect("static regex")
+});
+
+/// How the quoted history of a reply ends up in the note (`quoted_history`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotedHistory {
+    /// Left inline (only `quote_depth` applies).
+    #[default]
+    Keep,
+    /// Folded into a `<details>` block under its attribution line.
+    Fold,
+    /// Removed.
+    Cut,
+}
+
+/// A header line without Markdown emphasis (`**De :** X` from HTML bodies).
+fn header_text(line: &str) -> String {
+    line.replace('*', "").trim().to_string()
+}
+
+/// Lines of the Outlook header block starting at `i` (`De :` then at least a
+/// date field), 0 when there is none.
+fn outlook_block_len(lines: &[&str], i: usize) -> usize {
+    let field = |j: usize| HEADER_FIELD_RE.captures(&header_text(lines[j])).map(|c| c[1].to_lowercase());
+    if !matches!(field(i).as_deref(), Some("from" | "de" | "von")) {
+        return 0;
+    }
+    let len = (i..lines.len()).take_while(|&j| field(j).is_some()).count();
+    let dated = (i..i + len).any(|j| {
+        matches!(
+            field(j).as_deref(),
+            Some("sent" | "envoyé" | "gesendet" | "enviado" | "date" | "datum" | "fecha")
+        )
+    });
+    if dated { len } else { 0 }
+}
+
+/// Lines of the reply header starting at line `i` — attribution line(s), or
+/// Outlook separator and header block — if one starts there.
+pub fn reply_header_at(lines: &[&str], i: usize) -> Option<usize> {
+    let line = header_text(lines[i]);
+    let separator = ORIGINAL_MESSAGE_RE.is_match(&line);
+    if separator || (line.len() >= 10 && line.chars().all(|c| c == '_')) {
+        // Separator, then (Outlook) the header block under it
+        let next = (i + 1..lines.len()).find(|&j| !lines[j].trim().is_empty());
+        let block = next.map_or(0, |j| match outlook_block_len(lines, j) {
+            0 => 0,
+            len => j + len - i - 1,
+        });
+        return (separator || block > 0).then_some(1 + block);
+    }
+    match outlook_block_len(lines, i) {
+        0 => {}
+        len => return Some(len),
+    }
+    if ATTRIBUTION_RE.is_match(&line) {
+        return Some(1);
+    }
+    let wrapped = i + 1 < lines.len()
+        && !lines[i + 1].trim().is_empty()
+        && ATTRIBUTION_RE.is_match(&format!("{} {}", line, header_text(lines[i + 1])));
+    wrapped.then_some(2)
+}
+
+/// True for the separator line of a forwarded message.
+pub fn is_forwarded_separator(line: &str) -> bool {
+    FORWARDED_RE.is_match(&header_text(line))
+}
+
+/// A reply split into the sender's own text and the quoted history.
+#[derive(Debug, PartialEq)]
+pub struct ReplySplit {
+    /// Own text (before the history, and after it for a bottom-posted reply).
+    pub own: String,
+    /// Attribution of the history on one line (`From`/`Sent` fields for Outlook).
+    pub header: String,
+    /// Quoted history, reply header included.
+    pub history: String,
+    /// The history came before the own text (bottom posting).
+    pub history_first: bool,
+}
+
+/// Find the first reply header of `body` (French, English, German, Spanish
+/// attribution lines and Outlook header blocks) and split the body around
+/// the quoted history it introduces: the rest of the message, or only the
+/// `>` lines under it when own text follows them. Forwarded messages are
+/// content, not history.
+pub fn split_reply(body: &str) -> Option<ReplySplit> {
+    let lines: Vec<&str> = body.lines().collect();
+    let (start, header_len) = (0..lines.len())
+        .take_while(|&i| !is_forwarded_separator(lines[i]))
+        .find_map(|i| reply_header_at(&lines, i).map(|len| (i, len)))?;
+    let first_quoted = (start + header_len..lines.len()).find(|&j| !lines[j].trim().is_empty());
+    let end = match first_quoted {
+        Some(j) if lines[j].trim_start().starts_with('>') => (j..lines.len())
+            .find(|&k| !lines[k].trim().is_empty() && !lines[k].trim_start().starts_with('>'))
+            .unwrap_or(lines.len()),
+        _ => lines.len(),
+    };
+    if (start + header_len..end).all(|j| lines[j].trim().is_empty()) {
+        return None;
+    }
+
+    let header_lines: Vec<String> = lines[start..start + header_len].iter().map(|l| header_text(l)).collect();
+    let fields: Vec<&str> = header_lines
+        .iter()
+        .filter(|l| HEADER_FIELD_RE.is_match(l))
+        .map(String::as_str)
+        .take(2)
+        .collect();
+    let header = if fields.is_empty() { header_lines.join(" ") } else { fields.join(", ") };
+    let before = lines[..start].join("\n");
+    let after = lines[end..].join("\n");
+    let own = [before.trim_end(), after.trim()]
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Some(ReplySplit {
+        own,
+        header,
+        history: lines[start..end].join("\n").trim_end().to_string(),
+        history_first: before.trim().is_empty(),
+    })
+}
+
+/// Render the quoted history of `split` for `mode`: a link to the earlier
+/// note (`earlier_note`, file stem) when it is known, a `<details>` block
+/// (`fold`) or nothing (`cut`). `history` is the cleaned history text.
+pub fn render_history(split: &ReplySplit, history: &str, mode: QuotedHistory, earlier_note: Option<&str>) -> Option<String> {
+    if let Some(note) = earlier_note {
+        let label = split.header.replace(['[', ']', '|'], "");
+        return Some(format!("> [[{}|{}]]", note, label));
+    }
+    match mode {
+        QuotedHistory::Keep => Some(history.to_string()),
+        QuotedHistory::Fold => Some(format!(
+            "<details>\n<summary>{}</summary>\n\n{}\n\n</details>",
+            html_escape::encode_text(&split.header),
+            history.trim()
+        )),
+        QuotedHistory::Cut => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -831,7 +1593,7 @@ mod tests {
     fn test_decontaminate_ref_urls_strips_utm() {
         let input = "[1]: https://example.com/?utm_source=x";
         assert_eq!(
-            decontaminate_ref_urls(input),
+            decontaminate_ref_urls(input, &[]),
             "[1]: https://example.com/"
         );
     }
@@ -839,14 +1601,30 @@ mod tests {
     #[test]
     fn test_decontaminate_ref_urls_non_matching_left_alone() {
         let input = "Just some prose with [a link][1] in it.";
-        assert_eq!(decontaminate_ref_urls(input), input);
+        assert_eq!(decontaminate_ref_urls(input, &[]), input);
     }
 
     #[test]
     fn test_decontaminate_ref_urls_mixed_body() {
         let input = "Body text [a][1] here.\n\n[1]: https://example.com/?utm_source=x&id=42\n";
         let expected = "Body text [a][1] here.\n\n[1]: https://example.com/?id=42\n";
-        assert_eq!(decontaminate_ref_urls(input), expected);
+        assert_eq!(decontaminate_ref_urls(input, &[]), expected);
+    }
+
+    #[test]
+    fn test_decontaminate_bare_and_autolinked_urls() {
+        let safelinks = "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2Fpage%3Futm_source%3Dnl%26id%3D3&data=05%7C01&reserved=0";
+        let input = format!("Voir {}.\nOu <{}>\nSite : https://example.com/?utm_source=x\n", safelinks, safelinks);
+        assert_eq!(
+            decontaminate_ref_urls(&input, &[]),
+            "Voir https://example.com/page?id=3.\nOu <https://example.com/page?id=3>\nSite : https://example.com/?utm_source=x\n"
+        );
+
+        // Independent of `extract_links`: inline links are unwrapped too
+        let mut settings = CleanerSettings::default();
+        settings.stages.extract_links = false;
+        let body = format!("[la page]({})\n", safelinks);
+        assert_eq!(clean_with(&body, &settings, &RuleScope::default()).body, "[la page](https://example.com/page?id=3)\n");
     }
 
     // ---------- decode_residual_qp ----------
@@ -1034,6 +1812,178 @@ mod tests {
         assert_eq!(strip_invisible_chars(input), "a\u{00A0}b");
     }
 
+    // ---------- repair_mojibake / redecode ----------
+
+    #[test]
+    fn test_repair_mojibake_double_encoded_french() {
+        assert_eq!(
+            repair_mojibake("Ã©tÃ© Ã  Paris, 20Â°C â€” lâ€™Ã©quipe").as_deref(),
+            Some("été à Paris, 20°C — l’équipe")
+        );
+        // Encoded twice
+        assert_eq!(repair_mojibake("cafÃƒÂ©").as_deref(), Some("café"));
+        // Mixed: correct characters next to garbled ones are kept
+        assert_eq!(repair_mojibake("Déjà reçu : rÃ©ponse").as_deref(), Some("Déjà reçu : réponse"));
+        // A lost NBSP is restored on repaired lines only
+        assert_eq!(
+            repair_mojibake("Bisous de IRMÃ Maria\n> Ã©tÃ© Ã  Paris").as_deref(),
+            Some("Bisous de IRMÃ Maria\n> été à Paris")
+        );
+    }
+
+    #[test]
+    fn test_repair_mojibake_leaves_clean_text() {
+        assert_eq!(repair_mojibake("Café et thé à Paris"), None);
+        assert_eq!(repair_mojibake("São Paulo"), None);
+        // Exclusive: uppercase accents before punctuation are not mojibake
+        assert_eq!(repair_mojibake("«L'ÉTÉ» À BIENTÔT…"), None);
+    }
+
+    #[test]
+    fn test_redecode_mislabeled_charset() {
+        // Declared UTF-8, actually Windows-1252
+        assert_eq!(redecode(b"r\xe9sum\xe9 \x80", "utf-8").as_deref(), Some("résumé €"));
+        // Declared ISO-8859-1, actually UTF-8
+        assert_eq!(redecode("résumé".as_bytes(), "iso-8859-1").as_deref(), Some("résumé"));
+        assert_eq!(redecode("résumé".as_bytes(), "utf-8"), None);
+    }
+
+    #[test]
+    fn test_clean_reports_charset_repair() {
+        let result = clean("Bonjour,\n\nVotre rÃ©servation est confirmÃ©e.\n");
+        assert!(result.charset_repaired);
+        assert!(result.body.contains("réservation est confirmée."));
+        assert!(!clean("Bonjour\n").charset_repaired);
+    }
+
+    // ---------- cleaner settings / explain ----------
+
+    #[test]
+    fn test_clean_with_stage_turned_off() {
+        let body = "First line of a paragraph that was wrapped by the mail client\nat eighty columns.";
+        let mut settings = CleanerSettings::default();
+        assert!(!clean_with(body, &settings, &RuleScope::default()).body.contains('\n'));
+        settings.stages.unwrap_lines = false;
+        assert_eq!(clean_with(body, &settings, &RuleScope::default()).body, body);
+    }
+
+    #[test]
+    fn test_clean_with_scoped_rules_and_boilerplate() {
+        let settings: CleanerSettings = serde_yaml::from_str(
+            "rules:\n  - pattern: '(?m)^Ref\\. interne : \\d+\\n'\n    domains: [example.com]\n\
+             boilerplate:\n  - |\n    This message is confidential and intended\n    solely for its addressee.\n",
+        )
+        .unwrap();
+        assert!(settings.validate().is_ok());
+        let body = "Bonjour,\nRef. interne : 42\nMerci.\n\nThis message is confidential\nand intended solely for its ADDRESSEE.";
+        let scope = RuleScope { account: "Work", sender: "alice@mail.example.com" };
+        assert_eq!(clean_with(body, &settings, &scope).body.trim_end(), "Bonjour,\nMerci.");
+
+        // Exclusive: another domain keeps the line (boilerplate still removed)
+        let other = RuleScope { account: "Work", sender: "bob@example.org" };
+        assert!(clean_with(body, &settings, &other).body.contains("Ref. interne : 42"));
+        let rule = CleanerRule { pattern: "(".into(), replace: String::new(), domains: vec![], accounts: vec![] };
+        assert!(CleanerSettings { rules: vec![rule], ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_explain_reports_changing_stages() {
+        let settings = CleanerSettings { boilerplate: vec!["Sent from my phone".into()], ..Default::default() };
+        let (result, changes) =
+            explain("Caf&eacute; ?\n\nSent from my phone", &settings, &RuleScope::default(), MessageStages::default());
+        assert_eq!(result.body.trim_end(), "Café ?");
+        let stages: Vec<&str> = changes.iter().map(|c| c.stage).collect();
+        assert_eq!(stages, vec!["html_entities", "boilerplate"]);
+        assert_eq!(changes[1].after, "Café ?\n\n");
+    }
+
+    #[test]
+    fn test_explain_signature_and_quoted_history_stages() {
+        let body = "Le devis est en pièce jointe.\n\nCordialement,\nJean Dupont\nTél : 01 23 45 67 89\n\n\
+                    On Mon, Jun 3, 2026 at 10:00 AM Alice <alice@example.com> wrote:\n> Bonjour\n";
+        let message = || MessageStages {
+            quoted_history: QuotedHistory::Cut,
+            signatures: SignatureSettings { strip: true, ..Default::default() },
+            ..Default::default()
+        };
+        let (result, changes) = explain(body, &CleanerSettings::default(), &RuleScope::default(), message());
+        assert_eq!(result.body, "Le devis est en pièce jointe.\n\nCordialement,\nJean Dupont");
+        assert_eq!(result.signature_block.as_deref(), Some("Tél : 01 23 45 67 89"));
+        let stages: Vec<&str> = changes.iter().map(|c| c.stage).collect();
+        assert_eq!(stages, vec!["signatures", "quoted_history"]);
+
+        // Exclusive: both stages can be turned off in `cleaner.stages`
+        let mut settings = CleanerSettings::default();
+        settings.stages.signatures = false;
+        settings.stages.quoted_history = false;
+        let result = clean_message(body, &settings, &RuleScope::default(), message());
+        assert!(result.body.contains("Tél : 01 23 45 67 89") && result.body.contains("> Bonjour"));
+        assert_eq!(result.signature_block, None);
+    }
+
+    // ---------- split_reply / render_history ----------
+
+    #[test]
+    fn test_split_reply_attribution_lines() {
+        let headers = [
+            "Le lun. 3 juin 2026 à 10:00, Alice Martin <alice@example.com> a écrit :",
+            "On Mon, Jun 3, 2026 at 10:00 AM Alice Martin <alice@example.com> wrote:",
+            "Am 03.06.2026 um 10:00 schrieb Alice Martin <alice@example.com>:",
+            "El lun, 3 jun 2026 a las 10:00, Alice Martin (<alice@example.com>) escribió:",
+            "Le lun. 3 juin 2026 à 10:00, Alice Martin <alice@example.com> a\nécrit :",
+        ];
+        for header in headers {
+            let body = format!("Bien reçu.\n\n{}\n> Voici le devis.\n> Alice\n", header);
+            let split = split_reply(&body).unwrap_or_else(|| panic!("not found: {}", header));
+            assert_eq!(split.own, "Bien reçu.");
+            assert_eq!(split.header, header.replace('\n', " "));
+            assert!(split.history.ends_with("> Alice") && !split.history_first);
+        }
+    }
+
+    #[test]
+    fn test_split_reply_outlook_block() {
+        let body = "Merci, c'est noté.\n\n-----Message d'origine-----\n**De :** Alice Martin\n\
+                    **Envoyé :** lundi 3 juin 2026 10:00\n**À :** Bob\n**Objet :** Devis\n\nVoici le devis.\n";
+        let split = split_reply(body).unwrap();
+        assert_eq!(split.own, "Merci, c'est noté.");
+        assert_eq!(split.header, "De : Alice Martin, Envoyé : lundi 3 juin 2026 10:00");
+        assert!(split.history.starts_with("-----Message d'origine-----"));
+        assert!(split.history.ends_with("Voici le devis."));
+
+        let outlook = "Thanks!\n\n________________________________\nFrom: Alice\n\
+                       Sent: Monday, June 3, 2026 10:00 AM\nSubject: Quote\n\nHere it is.";
+        assert_eq!(split_reply(outlook).unwrap().header, "From: Alice, Sent: Monday, June 3, 2026 10:00 AM");
+    }
+
+    #[test]
+    fn test_split_reply_bottom_posting_and_exclusions() {
+        let body = "On Mon, Jun 3, 2026 at 10:00 AM Alice wrote:\n> Can you send the quote?\n\nHere it is.\n";
+        let split = split_reply(body).unwrap();
+        assert_eq!(split.own, "Here it is.");
+        assert!(split.history_first);
+
+        // Exclusive: forwarded content, prose ending with "wrote:" without a date, no history
+        assert_eq!(split_reply("---------- Forwarded message ---------\nFrom: Alice\nDate: Mon, Jun 3, 2026\n\nHi"), None);
+        assert_eq!(split_reply("On the form he wrote:\n> yes"), None);
+        assert_eq!(split_reply("Hi\n\nOn Mon, Jun 3, 2026 at 10:00 AM Alice wrote:\n"), None);
+    }
+
+    #[test]
+    fn test_render_history_modes() {
+        let split = split_reply("OK\n\nOn Mon, Jun 3, 2026 at 10:00 AM A <a@x> wrote:\n> Hi").unwrap();
+        assert_eq!(
+            render_history(&split, &split.history, QuotedHistory::Fold, None).unwrap(),
+            "<details>\n<summary>On Mon, Jun 3, 2026 at 10:00 AM A &lt;a@x&gt; wrote:</summary>\n\n\
+             On Mon, Jun 3, 2026 at 10:00 AM A <a@x> wrote:\n> Hi\n\n</details>"
+        );
+        assert_eq!(render_history(&split, &split.history, QuotedHistory::Cut, None), None);
+        assert_eq!(
+            render_history(&split, &split.history, QuotedHistory::Cut, Some("2026-06-03_Devis")).unwrap(),
+            "> [[2026-06-03_Devis|On Mon, Jun 3, 2026 at 10:00 AM A <a@x> wrote:]]"
+        );
+    }
+
     // ---------- detect_mojibake ----------
 
     #[test]
@@ -1590,6 +2540,49 @@ More text after.";
         assert_eq!(out, "https://real.example.com/path?foo=bar");
     }
 
+    #[test]
+    fn test_unwrap_safelinks_google_and_nested() {
+        let safelinks = "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2Fpage%3Futm_source%3Dnl%26id%3D3&data=05%7C01&reserved=0";
+        assert_eq!(decontaminate_trackers(safelinks), "https://example.com/page?id=3");
+        let nested = "https://www.google.com/url?q=https://eur01.safelinks.protection.outlook.com/?url%3Dhttps%253A%252F%252Fexample.com%252F%26data%3D1&sa=D";
+        assert_eq!(decontaminate_trackers(nested), "https://example.com/");
+
+        // Exclusive: a Google search is not a redirect, a Mailchimp id-only click cannot be recovered
+        let search = "https://www.google.com/search?q=https://example.com/";
+        assert_eq!(unwrap_redirects(search, &[]), search);
+        let mailchimp = "https://abc.us1.list-manage.com/track/click?u=1a2b&id=3c4d&e=5e6f";
+        assert_eq!(decontaminate_trackers(mailchimp), mailchimp);
+    }
+
+    #[test]
+    fn test_unwrap_urldefense_v2_and_v3() {
+        let v3 = "https://urldefense.com/v3/__https://google.com:443/search?q=a*test&gs=ps__;Kw!!-612Flbf0JvQ3kNJkRi5Jg$";
+        assert_eq!(unwrap_redirects(v3, &[]), "https://google.com:443/search?q=a+test&gs=ps");
+        let run = "https://urldefense.com/v3/__https://example.com/a**Ab__;ISE!!abc$";
+        assert_eq!(unwrap_redirects(run, &[]), "https://example.com/a!!b");
+        let v2 = "https://urldefense.proofpoint.com/v2/url?u=https-3A__example.com_path-3Fa-3D1&d=DwM&c=x";
+        assert_eq!(decontaminate_trackers(v2), "https://example.com/path?a=1");
+    }
+
+    #[test]
+    fn test_unwrap_with_settings_wrapper() {
+        let settings: CleanerSettings = serde_yaml::from_str(
+            "url_wrappers:\n  - host: '*.links.example.net'\n    path: /r\n    params: [target]\n    format: base64\n",
+        )
+        .unwrap();
+        assert!(settings.validate().is_ok());
+        let url = "https://go.links.example.net/r?target=aHR0cHM6Ly9leGFtcGxlLm9yZy94P3V0bV9tZWRpdW09ZW1haWw=";
+        assert_eq!(decontaminate_with(url, &settings.url_wrappers), "https://example.org/x");
+        assert_eq!(unwrap_redirects(url, &[]), url);
+
+        let body = "Voir [le rapport](https://go.links.example.net/r?target=aHR0cHM6Ly9leGFtcGxlLm9yZy94P3V0bV9tZWRpdW09ZW1haWw=).";
+        let cleaned = clean_with(body, &settings, &RuleScope::default());
+        assert!(cleaned.body.trim_end().ends_with("[1]: https://example.org/x"), "{}", cleaned.body);
+
+        let no_params = UrlWrapper { host: "r.example.net".into(), path: String::new(), params: vec![], format: WrapperFormat::Query };
+        assert!(no_params.validate().is_err());
+    }
+
     #[test]
     fn test_decontaminate_preserves_fragment() {
         let input = "https://example.com/page?utm_source=x#top";
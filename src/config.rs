@@ -1,5 +1,16 @@
+use crate::after_export::AfterExport;
+use crate::filename::{validate_template, FilenamePolicy, DEFAULT_FILENAME_TEMPLATE};
+use crate::folder_rules::FolderOverrides;
+use crate::frontmatter::{FieldKeys, FrontmatterSchema, FrontmatterSettings};
+use crate::mirror::MirrorSettings;
+use crate::namespace::NamespaceSettings;
+use crate::cleaner::{CleanerSettings, QuotedHistory};
+use crate::crypto::CryptoSettings;
+use crate::signatures::SignatureSettings;
+use crate::body::BodyPreference;
+use crate::output::OutputFormat;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -67,6 +78,67 @@ pub struct AccountBehavior {
     pub delete_after_export: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cleanup_empty_dirs: Option<bool>,
+    /// Do not save attachments (the note is still written).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_attachments: Option<bool>,
+    /// Keep the original message next to each note (`<note>.eml`), used by `restore`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_raw: Option<bool>,
+    /// Only export folders matching one of these patterns (glob, or `re:` regex).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_folders: Option<Vec<String>>,
+    /// Never export folders matching one of these patterns (glob, or `re:` regex).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_folders: Option<Vec<String>>,
+    /// Per-folder overrides keyed by folder pattern, e.g. `Sent/**: {quote_depth: 0}`.
+    /// Per-account entries are merged pattern by pattern over `defaults`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folders: Option<BTreeMap<String, FolderOverrides>>,
+    /// Server folder → local path mapping, e.g. `"[Gmail]/Sent Mail": Sent` or
+    /// `INBOX.Clients.*: Clients/*`. Merged pattern by pattern over `defaults`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder_map: Option<BTreeMap<String, String>>,
+    /// Only export folders the user is subscribed to (`LSUB`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscribed_only: Option<bool>,
+    /// Server actions on exported messages: `move_to`, `keyword`, `mark_seen`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_export: Option<AfterExport>,
+    /// Move routed messages into IMAP folders mirroring their destinations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror_routing: Option<MirrorSettings>,
+    /// Opt-in export of shared and other-users namespaces, with their tag prefixes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespaces: Option<NamespaceSettings>,
+    /// Note filename template, e.g. `{date:%Y%m%d}_{from_domain}_{subject_slug:40}`.
+    /// See `filename::PLACEHOLDERS` for the accepted placeholders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+    /// Portable-name policy (Unicode form, transliteration, max path length).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_policy: Option<FilenamePolicy>,
+    /// Frontmatter mapping: dialect, fields, key renames, date format, list style,
+    /// static fields. Per-account values are merged field by field over `defaults`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontmatter: Option<FrontmatterSettings>,
+    /// Note format: `markdown` (default), `org`, `json` or `html`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<OutputFormat>,
+    /// Body of `multipart/alternative` emails: `plain` (default), `html` or `best`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_preference: Option<BodyPreference>,
+    /// Keys and programs for encrypted and signed messages (PGP/MIME, S/MIME).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crypto: Option<CryptoSettings>,
+    /// Signature block detection and removal (off by default).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signatures: Option<SignatureSettings>,
+    /// Quoted history of replies: `keep` (default), `fold` or `cut`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quoted_history: Option<QuotedHistory>,
+    /// Cleaner pipeline: built-in stages, user regex rules, boilerplate blocks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleaner: Option<CleanerSettings>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -200,6 +272,74 @@ fn merge_account(raw: &RawAccount, settings: &Settings) -> Account {
         skip_signature_images: per.and_then(|a| a.skip_signature_images).or(def.skip_signature_images).unwrap_or(false),
         delete_after_export: per.and_then(|a| a.delete_after_export).or(def.delete_after_export).unwrap_or(false),
         cleanup_empty_dirs: per.and_then(|a| a.cleanup_empty_dirs).or(def.cleanup_empty_dirs).unwrap_or(true),
+        skip_attachments: per.and_then(|a| a.skip_attachments).or(def.skip_attachments).unwrap_or(false),
+        keep_raw: per.and_then(|a| a.keep_raw).or(def.keep_raw).unwrap_or(false),
+        include_folders: per
+            .and_then(|a| a.include_folders.clone())
+            .or_else(|| def.include_folders.clone())
+            .unwrap_or_default(),
+        exclude_folders: per
+            .and_then(|a| a.exclude_folders.clone())
+            .or_else(|| def.exclude_folders.clone())
+            .unwrap_or_default(),
+        folder_overrides: def
+            .folders
+            .iter()
+            .chain(per.and_then(|a| a.folders.as_ref()))
+            .flatten()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        folder_map: def
+            .folder_map
+            .iter()
+            .chain(per.and_then(|a| a.folder_map.as_ref()))
+            .flatten()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        subscribed_only: per.and_then(|a| a.subscribed_only).or(def.subscribed_only).unwrap_or(false),
+        after_export: per
+            .and_then(|a| a.after_export.clone())
+            .or_else(|| def.after_export.clone())
+            .unwrap_or_default(),
+        mirror_routing: per
+            .and_then(|a| a.mirror_routing.clone())
+            .or_else(|| def.mirror_routing.clone())
+            .unwrap_or_default(),
+        namespaces: per
+            .and_then(|a| a.namespaces.clone())
+            .or_else(|| def.namespaces.clone())
+            .unwrap_or_default(),
+        filename_template: per
+            .and_then(|a| a.filename_template.clone())
+            .or_else(|| def.filename_template.clone())
+            .unwrap_or_else(default_filename_template),
+        filename_policy: per
+            .and_then(|a| a.filename_policy.clone())
+            .or_else(|| def.filename_policy.clone())
+            .unwrap_or_default(),
+        frontmatter: per
+            .and_then(|a| a.frontmatter.as_ref())
+            .cloned()
+            .unwrap_or_default()
+            .merged(def.frontmatter.as_ref().unwrap_or(&FrontmatterSettings::default()))
+            .resolve(),
+        field_keys: FieldKeys::from_settings(settings),
+        notes_dir: settings.notes_dir.as_deref().map(PathBuf::from),
+        output_format: per.and_then(|a| a.output_format).or(def.output_format).unwrap_or_default(),
+        body_preference: per.and_then(|a| a.body_preference).or(def.body_preference).unwrap_or_default(),
+        crypto: per
+            .and_then(|a| a.crypto.clone())
+            .or_else(|| def.crypto.clone())
+            .unwrap_or_default(),
+        signatures: per
+            .and_then(|a| a.signatures.clone())
+            .or_else(|| def.signatures.clone())
+            .unwrap_or_default(),
+        quoted_history: per.and_then(|a| a.quoted_history).or(def.quoted_history).unwrap_or_default(),
+        cleaner: per
+            .and_then(|a| a.cleaner.clone())
+            .or_else(|| def.cleaner.clone())
+            .unwrap_or_default(),
     }
 }
 
@@ -237,6 +377,52 @@ pub struct Account {
     pub skip_signature_images: bool,
     pub delete_after_export: bool,
     pub cleanup_empty_dirs: bool,
+    #[serde(default)]
+    pub skip_attachments: bool,
+    #[serde(default)]
+    pub keep_raw: bool,
+    #[serde(default)]
+    pub include_folders: Vec<String>,
+    #[serde(default)]
+    pub exclude_folders: Vec<String>,
+    /// Per-folder overrides (pattern → behaviour), see `folder_rules`.
+    #[serde(default)]
+    pub folder_overrides: BTreeMap<String, FolderOverrides>,
+    /// Server folder → local path mapping, see `folder_rules::local_folder_path`.
+    #[serde(default)]
+    pub folder_map: BTreeMap<String, String>,
+    #[serde(default)]
+    pub subscribed_only: bool,
+    #[serde(default)]
+    pub namespaces: NamespaceSettings,
+    #[serde(default)]
+    pub after_export: AfterExport,
+    #[serde(default)]
+    pub mirror_routing: MirrorSettings,
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String,
+    #[serde(default)]
+    pub filename_policy: FilenamePolicy,
+    #[serde(default)]
+    pub frontmatter: FrontmatterSchema,
+    /// Keys readers accept for each canonical field (renames of every account).
+    #[serde(skip, default = "FieldKeys::builtin")]
+    pub field_keys: FieldKeys,
+    /// Routing root (`settings.notes_dir`), where exported notes end up.
+    #[serde(skip)]
+    pub notes_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    #[serde(default)]
+    pub body_preference: BodyPreference,
+    #[serde(default)]
+    pub crypto: CryptoSettings,
+    #[serde(default)]
+    pub signatures: SignatureSettings,
+    #[serde(default)]
+    pub quoted_history: QuotedHistory,
+    #[serde(default)]
+    pub cleaner: CleanerSettings,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -319,6 +505,40 @@ impl Config {
                     account.name
                 )));
             }
+
+            if let Err(e) = validate_template(&account.filename_template) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid filename_template for account '{}': {}",
+                    account.name, e
+                )));
+            }
+
+            if let Err(e) = crate::folder_rules::validate_account(account) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid folder settings for account '{}': {}",
+                    account.name, e
+                )));
+            }
+
+            if let Err(e) = account.frontmatter.validate() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid frontmatter settings for account '{}': {}",
+                    account.name, e
+                )));
+            }
+
+            if let Err(e) = account.crypto.validate() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid crypto settings for account '{}': {}",
+                    account.name, e
+                )));
+            }
+            if let Err(e) = account.cleaner.validate() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid cleaner settings for account '{}': {}",
+                    account.name, e
+                )));
+            }
         }
 
         Ok(())
@@ -341,6 +561,10 @@ fn default_ai_confidence_threshold() -> f32 {
     0.7
 }
 
+fn default_filename_template() -> String {
+    DEFAULT_FILENAME_TEMPLATE.to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -434,4 +658,167 @@ mod tests {
         assert!(config.accounts[0].cleanup_empty_dirs);
     }
 
+    #[test]
+    fn test_filename_template_per_account_override() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: test@example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  filename_policy:\n    transliterate: true\naccounts:\n  Test:\n    filename_template: \"{date:%Y%m%d}_{subject_slug:40}\"\n",
+        )
+        .unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[0].filename_template, "{date:%Y%m%d}_{subject_slug:40}");
+        assert!(config.accounts[0].filename_policy.transliterate);
+        // Unset policy fields keep their defaults
+        assert_eq!(config.accounts[0].filename_policy.max_path_len, FilenamePolicy::default().max_path_len);
+    }
+
+    #[test]
+    fn test_filename_template_unknown_placeholder_rejected() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: test@example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  filename_template: \"{date}_{sender}\"\n",
+        )
+        .unwrap();
+
+        let err = Config::load_with_settings(&accounts_path, &settings_path).unwrap_err();
+        assert!(err.to_string().contains("sender"), "got {}", err);
+    }
+
+    #[test]
+    fn test_frontmatter_per_account_merges_over_defaults() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: test@example.com\n  - name: Other\n    server: imap.example.com\n    port: 993\n    username: o@example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  frontmatter:\n    dialect: obsidian\naccounts:\n  Test:\n    frontmatter:\n      static:\n        team: sales\n",
+        )
+        .unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let test = config.get_account("Test").unwrap();
+        assert_eq!(test.frontmatter.dialect, crate::frontmatter::Dialect::Obsidian);
+        assert!(test.frontmatter.static_fields.contains_key("team"));
+        // Exclusive: the static field stays on its account
+        assert!(!config.get_account("Other").unwrap().frontmatter.static_fields.contains_key("team"));
+    }
+    #[test]
+    fn test_folder_overrides_merge_and_bad_pattern_rejected() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: test@example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  exclude_folders: [Spam]\n  folders:\n    Sent/**: {quote_depth: 0}\n    Newsletters: {skip_attachments: true}\naccounts:\n  Test:\n    folders:\n      Newsletters: {delete_after_export: true}\n",
+        )
+        .unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let account = &config.accounts[0];
+        assert_eq!(account.exclude_folders, vec!["Spam"]);
+        assert_eq!(account.folder_overrides["Sent/**"].quote_depth, Some(0));
+        // Per-account entry replaces the defaults entry for the same pattern
+        assert_eq!(account.folder_overrides["Newsletters"].delete_after_export, Some(true));
+        assert_eq!(account.folder_overrides["Newsletters"].skip_attachments, None);
+
+        std::fs::write(&settings_path, "export_base_dir: /tmp/exports\ndefaults:\n  include_folders: [\"re:(\"]\n").unwrap();
+        let err = Config::load_with_settings(&accounts_path, &settings_path).unwrap_err();
+        assert!(err.to_string().contains("folder regex"), "got {}", err);
+    }
+
+    #[test]
+    fn test_folder_map_merge_and_bad_target_rejected() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: test@example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  folder_map:\n    \"[Gmail]/Sent Mail\": Sent\naccounts:\n  Test:\n    folder_map:\n      INBOX.Clients.*: Clients/*\n",
+        )
+        .unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let account = &config.accounts[0];
+        assert_eq!(account.folder_map["[Gmail]/Sent Mail"], "Sent");
+        assert_eq!(account.folder_map["INBOX.Clients.*"], "Clients/*");
+
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  folder_map:\n    Sent: \"Sent/*\"\n",
+        )
+        .unwrap();
+        let err = Config::load_with_settings(&accounts_path, &settings_path).unwrap_err();
+        assert!(err.to_string().contains("folder_map"), "got {}", err);
+    }
+
+    #[test]
+    fn test_after_export_folder_override_and_conflict_rejected() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: test@example.com\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  after_export: {move_to: Archives, keyword: $Exported}\n  folders:\n    Sent: {after_export: {}}\n",
+        )
+        .unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let account = &config.accounts[0];
+        assert_eq!(account.after_export.move_to.as_deref(), Some("Archives"));
+        let sent = crate::folder_rules::account_for_folder(
+            account,
+            &crate::email_export::FolderName {
+                raw: "Sent".into(),
+                display: "Sent".into(),
+                delimiter: Some("/".into()),
+                namespace: None,
+            },
+        );
+        assert!(sent.after_export.is_noop());
+
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/exports\ndefaults:\n  folders:\n    INBOX: {delete_after_export: true, after_export: {move_to: Archives}}\n",
+        )
+        .unwrap();
+        let err = Config::load_with_settings(&accounts_path, &settings_path).unwrap_err();
+        assert!(err.to_string().contains("mutually exclusive"), "got {}", err);
+    }
 }
@@ -0,0 +1,595 @@
+//! Encrypted and signed messages: PGP/MIME (RFC 3156) and S/MIME (RFC 8551).
+//!
+//! [`open`] replaces the MIME tree of an encrypted message with its decrypted
+//! content, under the outer headers, so that the body and attachments go
+//! through the normal pipeline; signed messages get a [`SignatureStatus`]
+//! (`signature:` field) and their signed content replaces the tree. The work
+//! is delegated to the local `gpg` and `openssl` programs with the keys of the
+//! `crypto:` settings — key servers are never contacted. A message that cannot
+//! be decrypted is exported as before (encrypted payload as attachment).
+
+use anyhow::{bail, Context, Result};
+use mailparse::{MailHeaderMap, ParsedMail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use crate::utils::extract_emails;
+
+/// Layers opened at most (signed then encrypted, or the reverse).
+const MAX_LAYERS: usize = 3;
+
+/// Outcome of a signature check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// Good signature by a known key whose identity is the sender's address.
+    Valid,
+    /// Altered content, revoked or expired key, or a signer other than the sender.
+    Invalid,
+    /// Signer not in the local keyring (or certificate not trusted).
+    UnknownKey,
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureStatus::Valid => write!(f, "valid"),
+            SignatureStatus::Invalid => write!(f, "invalid"),
+            SignatureStatus::UnknownKey => write!(f, "unknown_key"),
+        }
+    }
+}
+
+/// Keys and programs used for encrypted and signed messages (`crypto:`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CryptoSettings {
+    /// Check the signature of signed messages.
+    pub verify_signatures: bool,
+    /// GnuPG home: public keyring for signatures, secret keys (or agent) for
+    /// decryption. GnuPG's own default when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gnupg_home: Option<String>,
+    /// OpenPGP secret key file, imported into a temporary keyring to decrypt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pgp_key_file: Option<String>,
+    /// S/MIME private key (PEM) used to decrypt `application/pkcs7-mime`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smime_key_file: Option<String>,
+    /// Certificate (PEM) of `smime_key_file`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smime_cert_file: Option<String>,
+    /// Trusted certificates (PEM) for S/MIME signers; OpenSSL's default store when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smime_ca_file: Option<String>,
+    /// Environment variable holding the passphrase of the keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passphrase_env: Option<String>,
+    pub gpg_program: String,
+    pub openssl_program: String,
+}
+
+impl Default for CryptoSettings {
+    fn default() -> Self {
+        Self {
+            verify_signatures: true,
+            gnupg_home: None,
+            pgp_key_file: None,
+            smime_key_file: None,
+            smime_cert_file: None,
+            smime_ca_file: None,
+            passphrase_env: None,
+            gpg_program: "gpg".to_string(),
+            openssl_program: "openssl".to_string(),
+        }
+    }
+}
+
+impl CryptoSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.smime_key_file.is_some() != self.smime_cert_file.is_some() {
+            return Err("crypto: smime_key_file and smime_cert_file go together".to_string());
+        }
+        if self.gpg_program.trim().is_empty() || self.openssl_program.trim().is_empty() {
+            return Err("crypto: empty program name".to_string());
+        }
+        Ok(())
+    }
+
+    fn passphrase(&self) -> Option<String> {
+        self.passphrase_env.as_deref().and_then(|var| std::env::var(var).ok())
+    }
+}
+
+/// An encrypted or signed message, opened.
+#[derive(Debug, Default)]
+pub struct Opened {
+    /// Outer headers followed by the decrypted or signed content; `None` when
+    /// the MIME tree is unchanged.
+    pub bytes: Option<Vec<u8>>,
+    /// Status of the outermost signature.
+    pub signature: Option<SignatureStatus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layer {
+    PgpEncrypted,
+    PgpSigned,
+    SmimeEnveloped,
+    SmimeSigned,
+    /// `application/pkcs7-mime; smime-type=signed-data`: content inside the signature.
+    SmimeOpaqueSigned,
+}
+
+fn layer(part: &ParsedMail) -> Option<Layer> {
+    let mimetype = part.ctype.mimetype.to_lowercase();
+    let param = |name: &str| part.ctype.params.get(name).map(|v| v.to_lowercase()).unwrap_or_default();
+    match mimetype.as_str() {
+        "multipart/encrypted" if part.subparts.len() >= 2 && param("protocol") == "application/pgp-encrypted" => {
+            Some(Layer::PgpEncrypted)
+        }
+        "multipart/signed" if part.subparts.len() >= 2 => match param("protocol").as_str() {
+            "application/pgp-signature" => Some(Layer::PgpSigned),
+            "application/pkcs7-signature" | "application/x-pkcs7-signature" => Some(Layer::SmimeSigned),
+            _ => None,
+        },
+        "application/pkcs7-mime" | "application/x-pkcs7-mime" => match param("smime-type").as_str() {
+            "signed-data" => Some(Layer::SmimeOpaqueSigned),
+            "enveloped-data" | "authenveloped-data" | "" => Some(Layer::SmimeEnveloped),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Decrypt and check the signatures of `mail`, layer by layer.
+pub fn open(mail: &ParsedMail, settings: &CryptoSettings, debug_mode: bool) -> Opened {
+    let mut opened = Opened::default();
+    if layer(mail).is_none() {
+        return opened;
+    }
+    let sender = extract_emails(mail.headers.get_first_value("From").as_deref())
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+
+    let mut content: Option<Vec<u8>> = None;
+    for _ in 0..MAX_LAYERS {
+        let inner = {
+            let parsed;
+            let part = match &content {
+                Some(bytes) => match mailparse::parse_mail(bytes) {
+                    Ok(p) => {
+                        parsed = p;
+                        &parsed
+                    }
+                    Err(_) => break,
+                },
+                None => mail,
+            };
+            let Some(layer) = layer(part) else { break };
+            match open_layer(part, layer, &sender, settings, &mut opened.signature) {
+                Ok(inner) => inner,
+                Err(e) => {
+                    if debug_mode {
+                        println!("    Encrypted or signed part left as is: {:#}", e);
+                    }
+                    None
+                }
+            }
+        };
+        match inner {
+            Some(bytes) => content = Some(bytes),
+            None => break,
+        }
+    }
+
+    opened.bytes = content.map(|content| with_outer_headers(mail, &content));
+    opened
+}
+
+/// Content of one layer; `None` when it is left as is (signature not checked).
+fn open_layer(
+    part: &ParsedMail,
+    layer: Layer,
+    sender: &str,
+    settings: &CryptoSettings,
+    signature: &mut Option<SignatureStatus>,
+) -> Result<Option<Vec<u8>>> {
+    if matches!(layer, Layer::PgpSigned | Layer::SmimeSigned | Layer::SmimeOpaqueSigned) && !settings.verify_signatures {
+        return Ok(None);
+    }
+    let (content, status) = match layer {
+        Layer::PgpEncrypted => (decrypt_pgp(&part.subparts[1].get_body_raw()?, settings)?, None),
+        Layer::SmimeEnveloped => (decrypt_smime(&part.get_body_raw()?, settings)?, None),
+        Layer::PgpSigned | Layer::SmimeSigned => {
+            let content = signed_content(&part.subparts[0]);
+            let sig = part.subparts[1].get_body_raw()?;
+            let status = if layer == Layer::PgpSigned {
+                verify_pgp(&canonical(content), &sig, sender, settings)?
+            } else {
+                verify_smime(Some(&canonical(content)), &sig, sender, settings)?.0
+            };
+            (content.to_vec(), Some(status))
+        }
+        Layer::SmimeOpaqueSigned => {
+            let (status, content) = verify_smime(None, &part.get_body_raw()?, sender, settings)?;
+            let Some(content) = content else { bail!("no signed content") };
+            (content, Some(status))
+        }
+    };
+    if let Some(status) = status {
+        signature.get_or_insert(status);
+    }
+    Ok(Some(content))
+}
+
+/// Signed entity of a `multipart/signed`: the first part, headers included,
+/// without the line break that belongs to the next boundary.
+fn signed_content<'a>(part: &ParsedMail<'a>) -> &'a [u8] {
+    let raw = part.raw_bytes;
+    let raw = raw.strip_suffix(b"\n").unwrap_or(raw);
+    raw.strip_suffix(b"\r").unwrap_or(raw)
+}
+
+/// CRLF line endings, as the signature was computed on.
+fn canonical(content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + content.len() / 32);
+    for (i, &b) in content.iter().enumerate() {
+        if b == b'\n' && (i == 0 || content[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// The message headers (MIME headers excluded) above the opened content.
+fn with_outer_headers(mail: &ParsedMail, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 1024);
+    for header in &mail.headers {
+        let key = header.get_key_ref();
+        let lower = key.to_lowercase();
+        if lower.starts_with("content-") || lower == "mime-version" {
+            continue;
+        }
+        out.extend_from_slice(key.as_bytes());
+        out.extend_from_slice(b": ");
+        out.extend_from_slice(header.get_value_raw());
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b"MIME-Version: 1.0\r\n");
+    out.extend_from_slice(content);
+    out
+}
+
+/// Run `program` with `stdin` as input.
+fn run(program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<Output> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot run {}", program))?;
+    if let Some(mut input) = child.stdin.take() {
+        input.write_all(stdin.unwrap_or_default())?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+/// `--homedir` arguments of the keyring in use.
+fn homedir_args(home: Option<&str>) -> Vec<&str> {
+    home.map(|h| vec!["--homedir", h]).unwrap_or_default()
+}
+
+fn decrypt_pgp(encrypted: &[u8], settings: &CryptoSettings) -> Result<Vec<u8>> {
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("message.asc");
+    fs::write(&input, encrypted)?;
+
+    // A key file is imported into a keyring of its own, the agent is not used
+    let keyring = dir.path().join("gnupg");
+    let home = match &settings.pgp_key_file {
+        Some(key_file) => {
+            fs::create_dir(&keyring)?;
+            let home = keyring.to_string_lossy().into_owned();
+            let mut args = homedir_args(Some(&home));
+            args.extend(["--batch", "--import", key_file]);
+            let output = run(&settings.gpg_program, &args, None)?;
+            if !output.status.success() {
+                bail!("cannot import {}: {}", key_file, String::from_utf8_lossy(&output.stderr).trim());
+            }
+            Some(home)
+        }
+        None => settings.gnupg_home.clone(),
+    };
+
+    let passphrase = settings.passphrase();
+    let input = input.to_string_lossy();
+    let mut args = homedir_args(home.as_deref());
+    args.extend(["--batch", "--no-auto-key-retrieve", "--status-fd", "2"]);
+    if passphrase.is_some() {
+        args.extend(["--pinentry-mode", "loopback", "--passphrase-fd", "0"]);
+    }
+    args.extend(["--decrypt", &input]);
+    let output = run(&settings.gpg_program, &args, passphrase.as_deref().map(str::as_bytes))?;
+    // A failed inner signature check fails the command, not the decryption
+    let status = String::from_utf8_lossy(&output.stderr);
+    if !status.contains("[GNUPG:] DECRYPTION_OKAY") || output.stdout.is_empty() {
+        bail!("gpg could not decrypt the message: {}", gpg_error(&status));
+    }
+    Ok(output.stdout)
+}
+
+/// Last human-readable gpg message, for debug output.
+fn gpg_error(stderr: &str) -> &str {
+    stderr
+        .lines()
+        .rfind(|l| !l.starts_with("[GNUPG:]"))
+        .unwrap_or("unknown error")
+}
+
+fn verify_pgp(content: &[u8], signature: &[u8], sender: &str, settings: &CryptoSettings) -> Result<SignatureStatus> {
+    let dir = tempfile::tempdir()?;
+    let (data, sig) = (dir.path().join("content"), dir.path().join("content.asc"));
+    fs::write(&data, content)?;
+    fs::write(&sig, signature)?;
+
+    let home = settings.gnupg_home.as_deref();
+    let (data, sig) = (data.to_string_lossy(), sig.to_string_lossy());
+    let mut args = homedir_args(home);
+    args.extend(["--batch", "--no-auto-key-retrieve", "--status-fd", "1", "--verify", &sig, &data]);
+    let output = run(&settings.gpg_program, &args, None)?;
+    let status = String::from_utf8_lossy(&output.stdout);
+
+    let mut fingerprint = None;
+    for line in status.lines().filter_map(|l| l.strip_prefix("[GNUPG:] ")) {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("BADSIG" | "EXPSIG" | "EXPKEYSIG" | "REVKEYSIG") => return Ok(SignatureStatus::Invalid),
+            Some("NO_PUBKEY") => return Ok(SignatureStatus::UnknownKey),
+            Some("VALIDSIG") => fingerprint = words.last().map(str::to_string),
+            _ => {}
+        }
+    }
+    let Some(fingerprint) = fingerprint else {
+        bail!("gpg could not check the signature: {}", gpg_error(&String::from_utf8_lossy(&output.stderr)));
+    };
+
+    // The signing key must carry the sender's address
+    let mut args = homedir_args(home);
+    args.extend(["--batch", "--with-colons", "--list-keys", &fingerprint]);
+    let listing = run(&settings.gpg_program, &args, None)?;
+    let identities: Vec<String> = String::from_utf8_lossy(&listing.stdout)
+        .lines()
+        .filter(|l| l.starts_with("uid:"))
+        .flat_map(|l| extract_emails(l.split(':').nth(9)))
+        .collect();
+    Ok(identity_status(&identities, sender))
+}
+
+fn identity_status(identities: &[String], sender: &str) -> SignatureStatus {
+    if !sender.is_empty() && identities.iter().any(|id| id.eq_ignore_ascii_case(sender)) {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    }
+}
+
+fn decrypt_smime(encrypted: &[u8], settings: &CryptoSettings) -> Result<Vec<u8>> {
+    let (Some(key), Some(cert)) = (&settings.smime_key_file, &settings.smime_cert_file) else {
+        bail!("no S/MIME key configured (crypto.smime_key_file)");
+    };
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("smime.p7m");
+    fs::write(&input, encrypted)?;
+
+    let input = input.to_string_lossy();
+    let mut args = vec!["cms", "-decrypt", "-binary", "-inform", "DER", "-in", &input, "-inkey", key, "-recip", cert];
+    let passin = settings.passphrase_env.as_ref().map(|var| format!("env:{}", var));
+    if let Some(passin) = &passin {
+        args.extend(["-passin", passin]);
+    }
+    let output = run(&settings.openssl_program, &args, None)?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!("openssl could not decrypt the message: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+/// Check an S/MIME signature, detached (`content`) or enveloping the content,
+/// which is then returned.
+fn verify_smime(
+    content: Option<&[u8]>,
+    signature: &[u8],
+    sender: &str,
+    settings: &CryptoSettings,
+) -> Result<(SignatureStatus, Option<Vec<u8>>)> {
+    let dir = tempfile::tempdir()?;
+    let sig = dir.path().join("smime.p7s");
+    let signer = dir.path().join("signer.pem");
+    fs::write(&sig, signature)?;
+    let data = dir.path().join("content");
+    if let Some(content) = content {
+        fs::write(&data, content)?;
+    }
+
+    let (sig_arg, signer_arg, data_arg) = (sig.to_string_lossy(), signer.to_string_lossy(), data.to_string_lossy());
+    let mut args = vec!["cms", "-verify", "-binary", "-inform", "DER", "-in", &sig_arg, "-signer", &signer_arg];
+    if content.is_some() {
+        args.extend(["-content", &data_arg]);
+    }
+    if let Some(ca) = &settings.smime_ca_file {
+        args.extend(["-CAfile", ca]);
+    }
+    let output = run(&settings.openssl_program, &args, None)?;
+    let enveloped = content.is_none().then(|| output.stdout.clone());
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let status = if stderr.contains("certificate verify error") {
+            SignatureStatus::UnknownKey
+        } else if stderr.contains("content verify error") || stderr.contains("verification failure") {
+            SignatureStatus::Invalid
+        } else {
+            bail!("openssl could not check the signature: {}", stderr.trim());
+        };
+        // The enveloped content is not output on failure: read it unchecked
+        let enveloped = match content {
+            Some(_) => None,
+            None => Some(smime_content(&sig_arg, settings)?),
+        };
+        return Ok((status, enveloped));
+    }
+
+    let signer_arg = signer_arg.to_string();
+    let output = run(&settings.openssl_program, &["x509", "-in", &signer_arg, "-noout", "-email"], None)?;
+    let identities = extract_emails(Some(&String::from_utf8_lossy(&output.stdout)));
+    Ok((identity_status(&identities, sender), enveloped))
+}
+
+/// Content of an opaque-signed message, without checking the signature.
+fn smime_content(sig: &str, settings: &CryptoSettings) -> Result<Vec<u8>> {
+    let args = ["cms", "-verify", "-noverify", "-nosigs", "-binary", "-inform", "DER", "-in", sig];
+    let output = run(&settings.openssl_program, &args, None)?;
+    if !output.status.success() {
+        bail!("openssl could not read the signed content");
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Whether `program` can be run (tests skip when GnuPG or OpenSSL is missing).
+    fn available(program: &str) -> bool {
+        Command::new(program).arg("version").output().is_ok_and(|o| o.status.success())
+            || Command::new(program).arg("--version").output().is_ok_and(|o| o.status.success())
+    }
+
+    /// Throwaway GnuPG home with a key for `uid` (sign + encrypt).
+    fn gpg_home(dir: &Path, uid: &str) -> String {
+        let home = dir.join("gnupg");
+        fs::create_dir(&home).unwrap();
+        let home = home.to_string_lossy().into_owned();
+        let args = ["--homedir", &home, "--batch", "--passphrase", "", "--quick-gen-key", uid, "default", "default", "never"];
+        assert!(run("gpg", &args, None).unwrap().status.success());
+        home
+    }
+
+    fn gpg(home: &str, args: &[&str], input: &[u8]) -> Vec<u8> {
+        let mut all = vec!["--homedir", home, "--batch", "--yes", "--armor", "--trust-model", "always"];
+        all.extend(args);
+        let output = run("gpg", &all, Some(input)).unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        output.stdout
+    }
+
+    fn signed_message(from: &str, entity: &[u8], signature: &[u8]) -> Vec<u8> {
+        let mut raw = format!(
+            "From: {from}\r\nSubject: Signed\r\nMIME-Version: 1.0\r\n\
+             Content-Type: multipart/signed; micalg=pgp-sha256; protocol=\"application/pgp-signature\"; boundary=S\r\n\r\n\
+             --S\r\n"
+        )
+        .into_bytes();
+        raw.extend(entity);
+        raw.extend(b"\r\n--S\r\nContent-Type: application/pgp-signature; name=signature.asc\r\n\r\n");
+        raw.extend(signature);
+        raw.extend(b"\r\n--S--\r\n");
+        raw
+    }
+
+    #[test]
+    fn test_pgp_signature_status_and_decryption() {
+        if !available("gpg") {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let home = gpg_home(dir.path(), "Alice <alice@example.com>");
+        let settings = CryptoSettings { gnupg_home: Some(home.clone()), ..Default::default() };
+
+        let entity = b"Content-Type: text/plain\r\n\r\nSigned body";
+        let signature = gpg(&home, &["--detach-sign"], entity);
+        let raw = signed_message("Alice <alice@example.com>", entity, &signature);
+        let opened = open(&mailparse::parse_mail(&raw).unwrap(), &settings, false);
+        assert_eq!(opened.signature, Some(SignatureStatus::Valid));
+        let inner = opened.bytes.unwrap();
+        let inner = mailparse::parse_mail(&inner).unwrap();
+        assert_eq!(inner.headers.get_first_value("Subject").as_deref(), Some("Signed"));
+        assert_eq!(inner.get_body().unwrap().trim(), "Signed body");
+
+        // Exclusive: other sender, altered content, unknown key
+        let raw = signed_message("mallory@example.com", entity, &signature);
+        let opened = open(&mailparse::parse_mail(&raw).unwrap(), &settings, false);
+        assert_eq!(opened.signature, Some(SignatureStatus::Invalid));
+        let raw = signed_message("alice@example.com", b"Content-Type: text/plain\r\n\r\nAltered", &signature);
+        let opened = open(&mailparse::parse_mail(&raw).unwrap(), &settings, false);
+        assert_eq!(opened.signature, Some(SignatureStatus::Invalid));
+        let empty = dir.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        let stranger = CryptoSettings { gnupg_home: Some(empty.to_string_lossy().into_owned()), ..Default::default() };
+        let raw = signed_message("alice@example.com", entity, &signature);
+        let opened = open(&mailparse::parse_mail(&raw).unwrap(), &stranger, false);
+        assert_eq!(opened.signature, Some(SignatureStatus::UnknownKey));
+
+        // Encrypted: decrypted with the keyring, or not at all without the key
+        let encrypted = gpg(&home, &["--encrypt", "-r", "alice@example.com"], b"Content-Type: text/plain\r\n\r\nSecret");
+        let mut raw = b"From: alice@example.com\r\nSubject: Enc\r\n\
+            Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=E\r\n\r\n\
+            --E\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n\
+            --E\r\nContent-Type: application/octet-stream; name=encrypted.asc\r\n\r\n"
+            .to_vec();
+        raw.extend(&encrypted);
+        raw.extend(b"\r\n--E--\r\n");
+        let mail = mailparse::parse_mail(&raw).unwrap();
+        let inner = open(&mail, &settings, false).bytes.unwrap();
+        assert_eq!(mailparse::parse_mail(&inner).unwrap().get_body().unwrap().trim(), "Secret");
+        assert!(open(&mail, &stranger, false).bytes.is_none());
+    }
+
+    #[test]
+    fn test_smime_signature_and_decryption() {
+        if !available("openssl") {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        let (key, cert) = (path("key.pem"), path("cert.pem"));
+        let args = [
+            "req", "-x509", "-newkey", "rsa:2048", "-nodes", "-keyout", &key, "-out", &cert, "-days", "30", "-subj",
+            "/CN=Alice", "-addext", "subjectAltName=email:alice@example.com",
+        ];
+        assert!(run("openssl", &args, None).unwrap().status.success());
+        let settings = CryptoSettings {
+            smime_key_file: Some(key.clone()),
+            smime_cert_file: Some(cert.clone()),
+            smime_ca_file: Some(cert.clone()),
+            ..Default::default()
+        };
+
+        let entity = b"Content-Type: text/plain\r\n\r\nSigned body";
+        fs::write(path("entity"), entity).unwrap();
+        let (entity_file, sig_file) = (path("entity"), path("sig.p7s"));
+        let args = ["cms", "-sign", "-binary", "-in", &entity_file, "-signer", &cert, "-inkey", &key, "-outform", "DER", "-out", &sig_file];
+        assert!(run("openssl", &args, None).unwrap().status.success());
+        let signature = fs::read(&sig_file).unwrap();
+        let status = verify_smime(Some(entity), &signature, "alice@example.com", &settings).unwrap().0;
+        assert_eq!(status, SignatureStatus::Valid);
+        let status = verify_smime(Some(b"altered"), &signature, "alice@example.com", &settings).unwrap().0;
+        assert_eq!(status, SignatureStatus::Invalid);
+        let untrusted = CryptoSettings { smime_ca_file: None, ..settings.clone() };
+        let status = verify_smime(Some(entity), &signature, "alice@example.com", &untrusted).unwrap().0;
+        assert_eq!(status, SignatureStatus::UnknownKey);
+
+        let enc_file = path("enc.p7m");
+        let args = ["cms", "-encrypt", "-binary", "-in", &entity_file, "-outform", "DER", "-out", &enc_file, "-aes256", &cert];
+        assert!(run("openssl", &args, None).unwrap().status.success());
+        let decrypted = decrypt_smime(&fs::read(&enc_file).unwrap(), &settings).unwrap();
+        assert_eq!(decrypted, entity);
+    }
+}
@@ -0,0 +1,193 @@
+//! `deleted` CLI subcommand: read the deletion journal written by
+//! `delete_after_export` (see `deletion`).
+//!
+//! - `list`         — journal entries, newest first (or pending deletions).
+//! - `restore-info` — everything needed to find a deleted message again.
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use crate::deletion::{self, DeletionRecord, Expunge};
+use crate::utils::decode_imap_utf7;
+
+#[derive(Args)]
+pub struct DeletedArgs {
+    #[command(subcommand)]
+    pub command: DeletedCommand,
+}
+
+#[derive(Subcommand)]
+pub enum DeletedCommand {
+    /// List messages deleted from the server, newest first
+    List {
+        /// Only this account
+        #[arg(long)]
+        account: Option<String>,
+
+        /// Show at most this many entries
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+
+        /// Show deletions still waiting for their note to be verified instead
+        #[arg(long)]
+        pending: bool,
+    },
+
+    /// Show the journal details of a deleted message
+    RestoreInfo {
+        /// Message-ID, note path or UID (substring match on Message-ID and path)
+        query: String,
+    },
+}
+
+/// Entry point dispatched from `main`.
+pub fn run(args: DeletedArgs) -> Result<()> {
+    match args.command {
+        DeletedCommand::List { account, limit, pending } => {
+            if pending {
+                list_pending(account.as_deref())
+            } else {
+                list(account.as_deref(), limit)
+            }
+        }
+        DeletedCommand::RestoreInfo { query } => restore_info(&query),
+    }
+}
+
+fn list(account: Option<&str>, limit: usize) -> Result<()> {
+    let journal = deletion::journal_path();
+    let records: Vec<DeletionRecord> = deletion::read_journal(&journal)?
+        .into_iter()
+        .rev()
+        .filter(|r| account.is_none_or(|a| r.account == a))
+        .take(limit)
+        .collect();
+    if records.is_empty() {
+        println!("No deletions recorded ({}).", journal.display());
+        return Ok(());
+    }
+    for r in &records {
+        let missing = if r.note_path.exists() { "" } else { "  (note missing)" };
+        let flagged = if r.expunge == Expunge::FlaggedOnly { "  (flagged, not expunged)" } else { "" };
+        println!(
+            "{}  {}/{}  uid {}  {}{}",
+            r.deleted_at.format("%Y-%m-%d %H:%M"),
+            r.account,
+            r.folder,
+            r.uid,
+            r.message_id.as_deref().unwrap_or("-"),
+            flagged
+        );
+        println!("    {}{}", r.note_path.display(), missing);
+    }
+    Ok(())
+}
+
+fn list_pending(account: Option<&str>) -> Result<()> {
+    let pending: Vec<_> = deletion::load_pending(&deletion::pending_path())?
+        .into_iter()
+        .filter(|p| account.is_none_or(|a| p.account == a))
+        .collect();
+    if pending.is_empty() {
+        println!("No pending deletions.");
+        return Ok(());
+    }
+    for p in &pending {
+        println!(
+            "{}/{}  uid {}  {}",
+            p.account,
+            decode_imap_utf7(&p.folder),
+            p.uid,
+            p.message_id.as_deref().unwrap_or("-")
+        );
+        println!("    {}", p.note_path.display());
+    }
+    Ok(())
+}
+
+/// Journal entries matching `query`: exact UID, or substring of the
+/// Message-ID or note path.
+pub fn find_records<'a>(records: &'a [DeletionRecord], query: &str) -> Vec<&'a DeletionRecord> {
+    let query = query.trim().trim_start_matches('<').trim_end_matches('>');
+    records
+        .iter()
+        .filter(|r| {
+            query.parse::<u32>().is_ok_and(|uid| uid == r.uid)
+                || r.message_id.as_deref().is_some_and(|id| id.contains(query))
+                || r.note_path.to_string_lossy().contains(query)
+        })
+        .collect()
+}
+
+fn restore_info(query: &str) -> Result<()> {
+    let records = deletion::read_journal(&deletion::journal_path())?;
+    let matches = find_records(&records, query);
+    if matches.is_empty() {
+        println!("No deleted message matches {:?}.", query);
+        return Ok(());
+    }
+    for r in matches {
+        println!("Account:    {}", r.account);
+        println!("Folder:     {}", r.folder);
+        println!("UID:        {}", r.uid);
+        println!("Message-ID: {}", r.message_id.as_deref().map(|id| format!("<{}>", id)).unwrap_or_else(|| "-".into()));
+        println!("Deleted at: {}", r.deleted_at.to_rfc3339());
+        println!(
+            "Expunge:    {}",
+            match r.expunge {
+                Expunge::Uid => "UID EXPUNGE of this message only",
+                Expunge::FlaggedOnly => "none (no UIDPLUS) — still in the folder, flagged \\Deleted, until a client expunges it",
+                Expunge::Gmail => "Gmail All Mail expunge",
+                Expunge::Mailbox => "folder-wide EXPUNGE",
+            }
+        );
+        println!(
+            "Note:       {}{}",
+            r.note_path.display(),
+            if r.note_path.exists() { "" } else { " (missing)" }
+        );
+        if r.note_path.exists() {
+            println!(
+                "Put it back with: email-to-markdown restore \"{}\" --account {}",
+                r.note_path.display(),
+                r.account
+            );
+        }
+        match &r.message_id {
+            Some(id) => println!(
+                "Look for it in the server's trash or backups with: SEARCH HEADER Message-ID \"<{}>\"",
+                id
+            ),
+            None => println!("No Message-ID: identify it in the server's trash by folder and date."),
+        }
+        println!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_find_records() {
+        let record = |uid: u32, id: &str, path: &str| DeletionRecord {
+            account: "Test".into(),
+            folder: "INBOX".into(),
+            uid,
+            message_id: Some(id.into()),
+            note_path: PathBuf::from(path),
+            deleted_at: Utc::now(),
+            expunge: Expunge::Uid,
+        };
+        let records = vec![record(12, "abc@example.com", "/notes/Pro/a.md"), record(13, "def@example.com", "/notes/Perso/b.md")];
+
+        assert_eq!(find_records(&records, "<abc@example.com>").len(), 1);
+        assert_eq!(find_records(&records, "13")[0].uid, 13);
+        assert_eq!(find_records(&records, "Perso")[0].uid, 13);
+        // Exclusive: no match
+        assert!(find_records(&records, "zzz").is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+//! Two-phase safe deletion for `delete_after_export`, and its audit journal.
+//!
+//! Phase 1 (`export_folder`) never touches the server: every message that
+//! would be deleted is recorded as a [`PendingDeletion`] in
+//! `<app_config_dir>/pending_deletions.json`, with the note it was exported
+//! to and a hash of that note's body. Routing then relocates the record to
+//! the note's final path (`relocate_pending`).
+//!
+//! Phase 2 (`ImapExporter::delete_verified`) flags a message `\Deleted` only
+//! when its note exists at the recorded final path and the body hash still
+//! matches, then expunges exactly those UIDs (`UID EXPUNGE`, UIDPLUS). A
+//! server without UIDPLUS only gets the flag: a plain `EXPUNGE` would also
+//! remove messages other clients flagged. Each deletion is appended to
+//! `<app_config_dir>/deletions.jsonl` (read by the `deleted` CLI command)
+//! with the [`Expunge`] mode that applied.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter::{note_body, FieldKeys, NoteHeader};
+
+/// A message to delete once its note is verified at its final path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingDeletion {
+    pub account: String,
+    /// Raw (modified UTF-7) mailbox name.
+    pub folder: String,
+    pub uid: u32,
+    /// `UIDVALIDITY` of the folder when the UID was read; a mismatch means
+    /// the UID may now name another message, so nothing is deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid_validity: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub note_path: PathBuf,
+    /// `md5` of the note body (header excluded) when it was recorded.
+    pub body_hash: String,
+}
+
+/// How a deleted message left its folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expunge {
+    /// `UID EXPUNGE` of the verified UIDs only (server with UIDPLUS).
+    Uid,
+    /// Flagged `\Deleted` but not expunged (server without UIDPLUS): the
+    /// message goes with the next expunge of a mail client.
+    FlaggedOnly,
+    /// Gmail: flagged in the folder, removed by the All Mail expunge.
+    Gmail,
+    /// Mailbox-wide `EXPUNGE` (journal lines written before this field).
+    #[default]
+    Mailbox,
+}
+
+/// One line of the deletion journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletionRecord {
+    pub account: String,
+    /// Decoded folder name.
+    pub folder: String,
+    pub uid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub note_path: PathBuf,
+    pub deleted_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expunge: Expunge,
+}
+
+/// Outcome of checking a pending deletion before phase 2.
+#[derive(Debug, PartialEq)]
+pub enum Verification {
+    /// Note present at its final path with an unchanged body.
+    Ready,
+    /// Note still in staging, waiting for routing.
+    AwaitingRouting,
+    /// Note missing or modified: the message is kept on the server.
+    Rejected(String),
+}
+
+pub fn pending_path() -> PathBuf {
+    crate::config::app_config_dir().join("pending_deletions.json")
+}
+
+pub fn journal_path() -> PathBuf {
+    crate::config::app_config_dir().join("deletions.jsonl")
+}
+
+/// `md5` of a note's body, ignoring its header so that fields added after
+/// routing (destination `frontmatter:` fields) do not invalidate it. A JSON
+/// Lines note keeps its body in the `body` field of the record (a key the
+/// frontmatter schema cannot rename).
+pub fn note_hash(path: &Path) -> Result<String> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let body = match note_body(&content) {
+        "" => NoteHeader::read(&content)
+            .and_then(|h| h.get_str("body", &FieldKeys::builtin()))
+            .unwrap_or_default(),
+        body => body.to_string(),
+    };
+    Ok(format!("{:x}", md5::compute(body.as_bytes())))
+}
+
+/// Check a pending deletion. With `notes_dir` set, notes outside it are still
+/// in staging and wait for routing; without it, staging is the final place.
+pub fn verify(pending: &PendingDeletion, notes_dir: Option<&Path>) -> Verification {
+    if notes_dir.is_some_and(|dir| !pending.note_path.starts_with(dir)) {
+        return if pending.note_path.exists() {
+            Verification::AwaitingRouting
+        } else {
+            Verification::Rejected(format!("note not found: {}", pending.note_path.display()))
+        };
+    }
+    match fs::symlink_metadata(&pending.note_path) {
+        Ok(meta) if meta.file_type().is_file() => {}
+        Ok(_) => return Verification::Rejected(format!("not a regular file: {}", pending.note_path.display())),
+        Err(_) => return Verification::Rejected(format!("note not found: {}", pending.note_path.display())),
+    }
+    match note_hash(&pending.note_path) {
+        Ok(hash) if hash == pending.body_hash => Verification::Ready,
+        Ok(_) => Verification::Rejected(format!("note modified since export: {}", pending.note_path.display())),
+        Err(e) => Verification::Rejected(format!("{:#}", e)),
+    }
+}
+
+pub fn load_pending(path: &Path) -> Result<Vec<PendingDeletion>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn save_pending(path: &Path, pending: &[PendingDeletion]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let json = serde_json::to_string_pretty(pending).context("failed to serialize pending deletions")?;
+    fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Add records, replacing any earlier record for the same message
+/// (account, folder, `UIDVALIDITY`, UID).
+pub fn add_pending(path: &Path, records: Vec<PendingDeletion>) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let mut pending = load_pending(path)?;
+    pending.retain(|p| {
+        !records.iter().any(|r| {
+            r.account == p.account && r.folder == p.folder && r.uid_validity == p.uid_validity && r.uid == p.uid
+        })
+    });
+    pending.extend(records);
+    save_pending(path, &pending)
+}
+
+/// Point records at the notes' final paths after routing (`(staging, final)` pairs).
+pub fn relocate_pending(path: &Path, moves: &[(PathBuf, PathBuf)]) -> Result<()> {
+    if moves.is_empty() || !path.exists() {
+        return Ok(());
+    }
+    let mut pending = load_pending(path)?;
+    let mut changed = false;
+    for p in &mut pending {
+        if let Some((_, final_path)) = moves.iter().find(|(staging, _)| *staging == p.note_path) {
+            p.note_path = final_path.clone();
+            changed = true;
+        }
+    }
+    if changed {
+        save_pending(path, &pending)?;
+    }
+    Ok(())
+}
+
+pub fn append_journal(path: &Path, records: &[DeletionRecord]) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    for record in records {
+        let line = serde_json::to_string(record).context("failed to serialize deletion record")?;
+        writeln!(file, "{}", line).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Read the journal, oldest first. Unparseable lines are skipped with a warning.
+pub fn read_journal(path: &Path) -> Result<Vec<DeletionRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(n, line)| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                eprintln!("warning: {}:{}: skipping unreadable entry: {}", path.display(), n + 1, e);
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_for(note: &Path) -> PendingDeletion {
+        PendingDeletion {
+            account: "Test".into(),
+            folder: "INBOX".into(),
+            uid: 42,
+            uid_validity: Some(7),
+            message_id: Some("abc@example.com".into()),
+            note_path: note.to_path_buf(),
+            body_hash: note_hash(note).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_verify_two_phases() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let staging = temp.path().join("staging/note.md");
+        let notes_dir = temp.path().join("notes");
+        fs::create_dir_all(staging.parent().unwrap()).unwrap();
+        fs::create_dir_all(&notes_dir).unwrap();
+        fs::write(&staging, "---\nfrom: a\n---\n\nBody\n").unwrap();
+        let mut pending = pending_for(&staging);
+
+        // Still in staging while a notes_dir is configured
+        assert_eq!(verify(&pending, Some(&notes_dir)), Verification::AwaitingRouting);
+        // Without notes_dir, staging is the final place
+        assert_eq!(verify(&pending, None), Verification::Ready);
+
+        // Routed, with a field appended to the header: still verified
+        let routed = notes_dir.join("note.md");
+        fs::write(&routed, "---\nfrom: a\nteam: x\n---\n\nBody\n").unwrap();
+        pending.note_path = routed.clone();
+        assert_eq!(verify(&pending, Some(&notes_dir)), Verification::Ready);
+
+        // Exclusive: a modified body or a missing note blocks the deletion
+        fs::write(&routed, "---\nfrom: a\n---\n\nEdited\n").unwrap();
+        assert!(matches!(verify(&pending, Some(&notes_dir)), Verification::Rejected(_)));
+        fs::remove_file(&routed).unwrap();
+        assert!(matches!(verify(&pending, Some(&notes_dir)), Verification::Rejected(_)));
+    }
+
+    #[test]
+    fn test_pending_add_and_relocate() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let note = temp.path().join("note.md");
+        fs::write(&note, "---\nfrom: a\n---\n\nBody\n").unwrap();
+        let path = temp.path().join("pending.json");
+
+        add_pending(&path, vec![pending_for(&note)]).unwrap();
+        // Same message recorded again: replaced, not duplicated
+        add_pending(&path, vec![pending_for(&note)]).unwrap();
+        assert_eq!(load_pending(&path).unwrap().len(), 1);
+
+        let final_path = temp.path().join("notes/note.md");
+        relocate_pending(&path, &[(note.clone(), final_path.clone())]).unwrap();
+        assert_eq!(load_pending(&path).unwrap()[0].note_path, final_path);
+    }
+
+    #[test]
+    fn test_journal_append_and_read() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("deletions.jsonl");
+        let record = DeletionRecord {
+            account: "Test".into(),
+            folder: "INBOX".into(),
+            uid: 42,
+            message_id: Some("abc@example.com".into()),
+            note_path: temp.path().join("note.md"),
+            deleted_at: Utc::now(),
+            expunge: Expunge::Uid,
+        };
+        append_journal(&path, std::slice::from_ref(&record)).unwrap();
+        append_journal(&path, std::slice::from_ref(&record)).unwrap();
+        fs::write(&path, format!("{}not json\n", fs::read_to_string(&path).unwrap())).unwrap();
+
+        // Line written before the `expunge` field
+        let legacy = r#"{"account":"Test","folder":"INBOX","uid":7,"note_path":"/n.md","deleted_at":"2024-01-15T10:00:00Z"}"#;
+        fs::write(&path, format!("{}{}\n", fs::read_to_string(&path).unwrap(), legacy)).unwrap();
+
+        let records = read_journal(&path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], record);
+        assert_eq!(records[2].expunge, Expunge::Mailbox);
+    }
+}
@@ -13,6 +13,7 @@ use std::path::{Path, PathBuf};
 
 use crate::config;
 use crate::destinations::{self, DestinationEntry, DestinationRule, DestinationsConfig};
+use crate::frontmatter::{FieldKeys, NoteHeader};
 use crate::route;
 
 #[derive(Args)]
@@ -48,6 +49,10 @@ pub enum DestCommand {
         #[arg(long)]
         account: Option<String>,
 
+        /// Route emails of this `email_type` here (e.g. invitation)
+        #[arg(long = "type")]
+        email_type: Option<String>,
+
         /// Human note describing this destination
         #[arg(long)]
         note: Option<String>,
@@ -95,6 +100,7 @@ pub fn run(args: DestArgs) -> Result<()> {
             from,
             subject,
             account,
+            email_type,
             note,
             default,
         } => {
@@ -113,6 +119,9 @@ pub fn run(args: DestArgs) -> Result<()> {
             if let Some(a) = account {
                 rules.push(DestinationRule::Account(a));
             }
+            if let Some(t) = email_type {
+                rules.push(DestinationRule::Type(t.to_lowercase()));
+            }
 
             add_entry(&dest_file, &path, &rules, note.as_deref(), default)?;
 
@@ -192,6 +201,7 @@ fn prompt_rule(kinds: &str) -> Result<Option<DestinationRule>> {
         "from" => DestinationRule::From(value),
         "subject" => DestinationRule::Subject(value),
         "account" => DestinationRule::Account(value),
+        "type" => DestinationRule::Type(value.to_lowercase()),
         _ => {
             eprintln!("  unknown rule type: {kind}");
             return Ok(None);
@@ -323,7 +333,7 @@ fn action_add(cfg: &mut DestinationsConfig) -> Result<bool> {
         return Ok(false);
     }
 
-    let rules: Vec<DestinationRule> = match prompt_rule("domain/from/subject/account/none")? {
+    let rules: Vec<DestinationRule> = match prompt_rule("domain/from/subject/account/type/none")? {
         Some(rule) => vec![rule],
         None => vec![],
     };
@@ -387,6 +397,7 @@ fn rule_label(rule: &DestinationRule) -> String {
         DestinationRule::From(a) => format!("from:{a}"),
         DestinationRule::Subject(k) => format!("subject:{k}"),
         DestinationRule::Account(n) => format!("account:{n}"),
+        DestinationRule::Type(t) => format!("type:{t}"),
     }
 }
 
@@ -402,7 +413,7 @@ fn suggest(dest_file: &Path) -> Result<()> {
     let scan_root = resolve_scan_root(&settings, &cfg)?;
     println!("Scanning {} ...", scan_root.display());
 
-    let groups = scan_domains(&scan_root)?;
+    let groups = scan_domains(&scan_root, &FieldKeys::from_settings(&settings))?;
     let candidates = uncovered_domains(groups, &cfg);
     if candidates.is_empty() {
         println!("Nothing to suggest — no uncovered sender domains found.");
@@ -488,12 +499,12 @@ pub struct ScanResult {
     pub folders: Vec<String>,
 }
 
-/// Walk `root` once, collecting sender domains from `.md` files and all
+/// Walk `root` once, collecting sender domains from notes (any output format) and all
 /// subdirectory paths up to `max_depth` levels deep.
 ///
 /// Excludes entries starting with `.` or `_`, never follows symlinks
-/// (rule 02-rust-filesystem-safety).
-pub fn scan_notes(root: &Path) -> Result<ScanResult> {
+/// (rule 02-rust-filesystem-safety). Senders are read under `keys`.
+pub fn scan_notes(root: &Path, keys: &FieldKeys) -> Result<ScanResult> {
     let mut files: Vec<PathBuf> = Vec::new();
     let mut folders: Vec<String> = Vec::new();
     if root.exists() {
@@ -506,7 +517,10 @@ pub fn scan_notes(root: &Path) -> Result<ScanResult> {
         let Ok(content) = std::fs::read_to_string(&file) else {
             continue;
         };
-        if let Some(from) = parse_from(&content) {
+        if !crate::output::is_note_content(&file, &content) {
+            continue;
+        }
+        if let Some(from) = parse_from(&content, keys) {
             if let Some(domain) = extract_domain(&from) {
                 *domains.entry(domain).or_insert(0) += 1;
             }
@@ -516,11 +530,11 @@ pub fn scan_notes(root: &Path) -> Result<ScanResult> {
 }
 
 /// Thin wrapper kept for callers that only need domain counts.
-pub fn scan_domains(root: &Path) -> Result<HashMap<String, usize>> {
-    Ok(scan_notes(root)?.domains)
+pub fn scan_domains(root: &Path, keys: &FieldKeys) -> Result<HashMap<String, usize>> {
+    Ok(scan_notes(root, keys)?.domains)
 }
 
-/// Recursively collect `.md` files and subdirectory paths, honoring exclusion
+/// Recursively collect note files and subdirectory paths, honoring exclusion
 /// and symlink rules.
 fn walk_notes(
     base: &Path,
@@ -555,7 +569,7 @@ fn walk_notes(
             }
             walk_notes(base, &path, depth + 1, max_depth, files, folders)?;
         } else if file_type.is_file()
-            && path.extension().and_then(|e| e.to_str()) == Some("md")
+            && crate::output::is_note_file(&path)
         {
             files.push(path);
         }
@@ -563,19 +577,11 @@ fn walk_notes(
     Ok(())
 }
 
-/// Extract the `from:` value from a `.md`'s YAML frontmatter, if present.
-fn parse_from(content: &str) -> Option<String> {
-    #[derive(serde::Deserialize)]
-    struct FromHead {
-        #[serde(default)]
-        from: String,
-    }
-    let rest = content.strip_prefix("---\n")?;
-    let end = rest.find("\n---")?;
-    let frontmatter = &rest[..end];
-    serde_yaml::from_str::<FromHead>(frontmatter)
-        .ok()
-        .map(|h| h.from)
+/// Extract the sender address from a `.md`'s frontmatter, if present
+/// (any configured dialect or renamed key).
+fn parse_from(content: &str, keys: &FieldKeys) -> Option<String> {
+    NoteHeader::read(content)
+        .map(|h| h.sender_address(keys))
         .filter(|s| !s.is_empty())
 }
 
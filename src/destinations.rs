@@ -14,12 +14,18 @@
 //!     rules:
 //!       - domain: ubs.ch
 //!       - subject: facture
+//!   - path: Pro/Clients/Acme
+//!     rules:
+//!       - domain: acme.com
+//!     frontmatter:           # static fields stamped on notes routed here
+//!       client: Acme
 //!   - path: Perso/Messy/Emails
 //!     default: true
 //! ```
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -33,6 +39,8 @@ pub enum DestinationRule {
     From(String),
     Subject(String),
     Account(String),
+    /// `email_type` of the note (`invitation`, `newsletter`…).
+    Type(String),
 }
 
 /// A single routing destination: a relative path under `notes_dir`, optional
@@ -58,6 +66,9 @@ pub struct DestinationEntry {
     /// `true` if this is the fallback destination.
     #[serde(default, skip_serializing_if = "is_false")]
     pub default: bool,
+    /// Static frontmatter fields added to notes routed here (existing keys win).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub frontmatter: BTreeMap<String, serde_yaml::Value>,
 }
 
 fn is_false(b: &bool) -> bool {
@@ -83,6 +94,7 @@ impl From<crate::route::MatchRule> for DestinationRule {
             MatchRule::From(a) => DestinationRule::From(a),
             MatchRule::Subject(k) => DestinationRule::Subject(k),
             MatchRule::Account(n) => DestinationRule::Account(n),
+            MatchRule::Type(t) => DestinationRule::Type(t.to_lowercase()),
         }
     }
 }
@@ -95,6 +107,7 @@ impl From<&DestinationRule> for crate::route::MatchRule {
             DestinationRule::From(a) => MatchRule::From(a.clone()),
             DestinationRule::Subject(k) => MatchRule::Subject(k.clone()),
             DestinationRule::Account(n) => MatchRule::Account(n.clone()),
+            DestinationRule::Type(t) => MatchRule::Type(t.clone()),
         }
     }
 }
@@ -169,10 +182,33 @@ pub fn upsert_entry(config: &mut DestinationsConfig, path: &str, rules: &[Destin
             note: None,
             rules: rules.to_vec(),
             default: false,
+            frontmatter: BTreeMap::new(),
         });
     }
 }
 
+/// Static frontmatter fields of the destination a note was routed to.
+///
+/// `rel_path` is the applied routing path; a trailing `<Year>/<Month>` is
+/// ignored when matching entry paths (case-insensitive).
+pub fn static_fields_for<'a>(
+    config: &'a DestinationsConfig,
+    rel_path: &str,
+) -> Option<&'a BTreeMap<String, serde_yaml::Value>> {
+    let trimmed = rel_path.trim_end_matches('/');
+    let base = if crate::route::ends_with_year_month(trimmed) {
+        trimmed.rsplitn(3, '/').nth(2).unwrap_or("")
+    } else {
+        trimmed
+    };
+    config
+        .destinations
+        .iter()
+        .find(|e| e.path.trim_end_matches('/').eq_ignore_ascii_case(base))
+        .map(|e| &e.frontmatter)
+        .filter(|f| !f.is_empty())
+}
+
 // ── Mutators (pure, for the interactive editor) ───────────────────────────────
 
 /// Remove the entry whose `path` matches (case-insensitive). Returns `true` if one was removed.
@@ -287,6 +323,7 @@ pub fn migrate_from_txt(txt_path: &Path, yaml_path: &Path) -> Result<()> {
                 note: None,
                 rules: d.rules.into_iter().map(DestinationRule::from).collect(),
                 default: d.is_default,
+                frontmatter: BTreeMap::new(),
             })
             .collect(),
     };
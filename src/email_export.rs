@@ -1,12 +1,6 @@
-use crate::config::Account;
-use crate::network::{NetworkConfig, ProgressIndicator, with_retry};  // [3][4]
-use crate::route::{route_email, Destination, EmailMeta, RouteDecision};
-use crate::utils::{
-    decode_imap_utf7, decode_mime_filename, extract_emails, get_short_name, hash_md5_prefix,
-    is_signature_image, limit_quote_depth, normalize_line_breaks, sanitize_filename, subject_extract,
-};
 use anyhow::{Context, Result};
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Utc};
+use imap::types::Flag;
 use imap::{ImapConnection, Session};
 use imap_proto::NameAttribute;
 use mailparse::{self, MailHeaderMap, ParsedMail};
@@ -19,12 +13,44 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::LazyLock;
 use walkdir::WalkDir;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::after_export::sequence_set;
+use crate::body::{extract_body, has_mislabeled_charset, html_to_markdown};
+use crate::calendar::{self, CalendarEvent};
+use crate::cid::{self, InlineImages};
+use crate::cleaner::{MessageStages, QuotedHistory, RuleScope};
+use crate::config::Account;
+use crate::crypto::{self, SignatureStatus};
+use crate::deletion::{self, DeletionRecord, Expunge, PendingDeletion, Verification};
+use crate::embedded::{embedded_messages, is_embedded_message, EmbeddedNotes};
+use crate::filename::{normalize_message_id, render_template, unique_note_path, NoteNameFields};
+use crate::folder_rules::{
+    account_for_folder, folder_selected, folder_tag, local_folder_path, validate_server_actions,
+};
+use crate::frontmatter::{note_body, FieldKeys, NoteHeader};
+use crate::mirror::{group_moves, mailbox_ancestors, mailbox_name, mirror_levels, quote_imap, PlannedMove, ServerCopy};
+use crate::namespace::{namespace_of, parse_namespace_response, Namespace, NamespaceKind};
+use crate::network::{NetworkConfig, ProgressIndicator, with_retry};  // [3][4]
+use crate::output::{is_note_content, is_note_file, render_note, NoteParts, OutputFormat};
+use crate::plan::{PlanAction, PlanEntry};
+use crate::quarantine::{self, FailureStage, QuarantineRecord};
+use crate::rebuild::{raw_message_path, RebuiltMessage};
+use crate::route::{route_email, unique_name_in, Destination, EmailMeta, RouteDecision};
+use crate::signatures::{self, SignatureHistory, SignatureKeep};
+use crate::tnef;
+use crate::utils::{
+    decode_imap_utf7, decode_mime_filename, encode_imap_utf7, extract_emails, hash_md5_prefix, is_signature_image,
+    limit_quote_depth, normalize_line_breaks, sanitize_filename,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EmailFrontmatter {
     pub from: String,
     pub to: String,
     pub date: String,
     pub subject: String,
+    /// `Message-ID` header without angle brackets — the primary duplicate key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
     pub subject_hash: String,
     pub tags: Vec<String>,
     pub attachments: Vec<String>,
@@ -32,6 +58,27 @@ pub struct EmailFrontmatter {
     pub email_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub social_links: Option<BTreeMap<String, String>>,
+    /// IMAP flags of the message when exported (`\Seen`, keywords), restored
+    /// by `restore`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+    /// Exported from best-effort headers and the undecoded body: the message
+    /// could not be parsed, even after repair (see `lenient`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub parse_degraded: bool,
+    /// Event of a calendar invitation (`event_start`, `organizer`… fields).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<CalendarEvent>,
+    /// Signature check of a signed message (`valid`, `invalid`, `unknown_key`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureStatus>,
+    /// Mojibake or a wrong declared charset was repaired in the headers or
+    /// the body (see `cleaner::repair_mojibake`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub charset_repaired: bool,
+    /// Signature block removed from the body (`signatures.keep: frontmatter`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_block: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -50,6 +97,8 @@ pub enum EmailType {
     Group,
     Newsletter,
     MailingList,
+    /// Calendar invitation, reply or cancellation (`text/calendar` part).
+    Invitation,
     Unknown,
 }
 
@@ -60,6 +109,7 @@ impl std::fmt::Display for EmailType {
             EmailType::Group => write!(f, "group"),
             EmailType::Newsletter => write!(f, "newsletter"),
             EmailType::MailingList => write!(f, "mailing_list"),
+            EmailType::Invitation => write!(f, "invitation"),
             EmailType::Unknown => write!(f, "unknown"),
         }
     }
@@ -70,6 +120,7 @@ pub struct ContactsCollector {
     pub group: HashSet<String>,
     pub newsletter: HashSet<String>,
     pub mailing_list: HashSet<String>,
+    pub invitation: HashSet<String>,
     pub unknown: HashSet<String>,
 }
 
@@ -80,6 +131,7 @@ impl ContactsCollector {
             group: HashSet::new(),
             newsletter: HashSet::new(),
             mailing_list: HashSet::new(),
+            invitation: HashSet::new(),
             unknown: HashSet::new(),
         }
     }
@@ -90,6 +142,7 @@ impl ContactsCollector {
             EmailType::Group => self.group.insert(contact),
             EmailType::Newsletter => self.newsletter.insert(contact),
             EmailType::MailingList => self.mailing_list.insert(contact),
+            EmailType::Invitation => self.invitation.insert(contact),
             EmailType::Unknown => self.unknown.insert(contact),
         };
     }
@@ -110,6 +163,7 @@ impl ContactsCollector {
             (&self.group, "Group"),
             (&self.newsletter, "Newsletter"),
             (&self.mailing_list, "Mailing List"),
+            (&self.invitation, "Invitation"),
             (&self.unknown, "Unknown"),
         ];
 
@@ -168,7 +222,9 @@ pub fn analyze_email_type(mail: &ParsedMail) -> EmailAnalysis {
     let cc_emails = extract_emails(Some(&cc_field));
 
     // Determine email type
-    let email_type = if to_emails.len() > 1 || cc_emails.len() > 1 {
+    let email_type = if crate::calendar::find_event(mail).is_some() {
+        EmailType::Invitation
+    } else if to_emails.len() > 1 || cc_emails.len() > 1 {
         EmailType::Group
     } else if subject.to_lowercase().contains("newsletter")
         || subject.to_lowercase().contains("bulletin")
@@ -203,77 +259,246 @@ pub fn analyze_email_type(mail: &ParsedMail) -> EmailAnalysis {
     }
 }
 
-/// Check if an email has already been exported.
-pub fn email_already_exported(
-    date_str: &str,
-    sender_short: &str,
-    recipient_short: &str,
-    subject_hash: &str,
-    export_directory: &Path,
-) -> bool {
-    if !export_directory.exists() {
-        return false;
+/// Identity of a message for duplicate detection.
+///
+/// `message_id` is authoritative when both the message and the note carry one;
+/// `fallback` (`<YYYY-MM-DD>|<sender address>|<subject_hash>`) covers messages
+/// without a `Message-ID` and notes written before the field existed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailKey {
+    pub message_id: Option<String>,
+    pub fallback: String,
+}
+
+impl EmailKey {
+    pub fn new(message_id: Option<&str>, date: Option<DateTime<FixedOffset>>, from: &str, subject_hash: &str) -> Self {
+        let message_id = message_id
+            .map(normalize_message_id)
+            .filter(|id| !id.is_empty());
+        let date_str = date
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "unknown-date".to_string());
+        let sender = extract_emails(Some(from))
+            .into_iter()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        EmailKey {
+            message_id,
+            fallback: format!("{}|{}|{}", date_str, sender, subject_hash),
+        }
+    }
+
+    /// Build the key from the headers of a parsed message.
+    pub fn from_mail(mail: &ParsedMail) -> Self {
+        let from_field = mail.headers.get_first_value("From").unwrap_or_default();
+        let date_field = mail.headers.get_first_value("Date").unwrap_or_default();
+        let subject = mail.headers.get_first_value("Subject").unwrap_or_default();
+        let message_id = mail.headers.get_first_value("Message-ID");
+        EmailKey::new(
+            message_id.as_deref(),
+            parse_email_date(&date_field),
+            &from_field,
+            &subject_hash_of(&subject),
+        )
     }
+}
 
-    let search_pattern = format!("email_{}_{}*to_{}*.md", date_str, sender_short, recipient_short);
+/// Keys of every note already present under an export directory.
+///
+/// Built once per folder from the notes' frontmatter, so duplicate detection
+/// does not depend on the filename template in use.
+#[derive(Debug, Default)]
+pub struct ExportIndex {
+    message_ids: HashSet<String>,
+    /// Fallback keys of every note.
+    fallback_all: HashSet<String>,
+    /// Fallback keys of notes without a `message_id` (legacy notes).
+    fallback_legacy: HashSet<String>,
+    /// Note found on disk for each `message_id` (see `note_path`).
+    paths: HashMap<String, PathBuf>,
+}
 
-    for entry in WalkDir::new(export_directory)
-        .follow_links(false)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().is_some_and(|ext| ext == "md"))
-    {
-        let filename = entry.file_name().to_string_lossy();
-        if glob::Pattern::new(&search_pattern)
-            .map(|p| p.matches(filename.as_ref()))
-            .unwrap_or(false)
+impl ExportIndex {
+    /// Scan notes (any output format) under `dir` (symlinks are not followed),
+    /// reading their header fields under `keys`.
+    pub fn scan(dir: &Path, keys: &FieldKeys) -> Self {
+        let mut index = ExportIndex::default();
+        if !dir.exists() {
+            return index;
+        }
+        for entry in WalkDir::new(dir)
+            .follow_links(false)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| is_note_file(e.path()))
         {
-            if let Ok(content) = fs::read_to_string(entry.path()) {
-                if content.contains(subject_hash) {
-                    return true;
-                }
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            if !is_note_content(entry.path(), &content) {
+                continue;
+            }
+            let Some(header) = NoteHeader::read(&content) else {
+                continue;
+            };
+            let key = EmailKey::new(
+                header.get_str("message_id", keys).as_deref(),
+                header.date(keys),
+                &header.sender_address(keys),
+                &header.get_str("subject_hash", keys).unwrap_or_default(),
+            );
+            index.insert_note(key, entry.path());
+        }
+        index
+    }
+
+    pub fn contains(&self, key: &EmailKey) -> bool {
+        match &key.message_id {
+            Some(id) => {
+                self.message_ids.contains(id) || self.fallback_legacy.contains(&key.fallback)
+            }
+            None => self.fallback_all.contains(&key.fallback),
+        }
+    }
+
+    /// Note already exported for a (normalized) `message_id`.
+    pub fn note_path(&self, message_id: &str) -> Option<&Path> {
+        self.paths.get(message_id).map(PathBuf::as_path)
+    }
+
+    /// Insert the key of a note just written to `path`.
+    pub fn insert_note(&mut self, key: EmailKey, path: &Path) {
+        if let Some(id) = &key.message_id {
+            self.paths.insert(id.clone(), path.to_path_buf());
+        }
+        self.insert(key);
+    }
+
+    pub fn insert(&mut self, key: EmailKey) {
+        match key.message_id {
+            Some(id) => {
+                self.message_ids.insert(id);
+            }
+            None => {
+                self.fallback_legacy.insert(key.fallback.clone());
             }
         }
+        self.fallback_all.insert(key.fallback);
+    }
+}
+
+fn subject_hash_of(subject: &str) -> String {
+    if !subject.is_empty() {
+        hash_md5_prefix(subject, 6)
+    } else {
+        "no-subject".to_string()
     }
+}
 
-    false
+/// Check if an email has already been exported under `export_directory`.
+///
+/// Matches on the notes' frontmatter, not their filenames. Scans the directory
+/// on every call — use an [`ExportIndex`] when checking many messages.
+pub fn email_already_exported(key: &EmailKey, export_directory: &Path, keys: &FieldKeys) -> bool {
+    ExportIndex::scan(export_directory, keys).contains(key)
+}
+
+/// Phase-1 record of a message to delete once the note at `note_path` is
+/// verified. `None` (message kept) when the note cannot be read.
+fn pending_deletion(
+    account: &Account,
+    folder: &FolderName,
+    uid: u32,
+    uid_validity: Option<u32>,
+    message_id: Option<String>,
+    note_path: &Path,
+) -> Option<PendingDeletion> {
+    let body_hash = deletion::note_hash(note_path).ok()?;
+    Some(PendingDeletion {
+        account: account.name.clone(),
+        folder: folder.raw.clone(),
+        uid,
+        uid_validity,
+        message_id,
+        note_path: note_path.to_path_buf(),
+        body_hash,
+    })
 }
 
-/// Check if an email should be skipped based on its raw headers alone.
-/// Also returns the email analysis so callers can collect contacts without re-parsing.
+/// Check if an email should be skipped based on its raw headers alone: returns
+/// its key when it is already exported. Also returns the email analysis so
+/// callers can collect contacts without re-parsing.
 fn should_skip_from_headers(
     raw_headers: &[u8],
-    export_dir: &Path,
-) -> (bool, Option<EmailAnalysis>) {
+    index: &ExportIndex,
+) -> (Option<EmailKey>, Option<EmailAnalysis>) {
     if raw_headers.is_empty() {
-        return (false, None);
+        return (None, None);
     }
     let mail = match mailparse::parse_mail(raw_headers) {
         Ok(m) => m,
-        Err(_) => return (false, None),
-    };
-    let from_field = mail.headers.get_first_value("From").unwrap_or_default();
-    let to_field = mail.headers.get_first_value("To").unwrap_or_default();
-    let date_field = mail.headers.get_first_value("Date").unwrap_or_default();
-    let subject = mail.headers.get_first_value("Subject").unwrap_or_default();
-
-    let date_obj = parse_email_date(&date_field);
-    let date_str = date_obj
-        .map(|d| d.format("%Y-%m-%d").to_string())
-        .unwrap_or_else(|| "unknown-date".to_string());
-    let sender_short = get_short_name(Some(&from_field));
-    let recipient_short = get_short_name(Some(&to_field));
-    let subject_hash = if !subject.is_empty() {
-        hash_md5_prefix(&subject, 6)
-    } else {
-        "no-subject".to_string()
+        Err(_) => return (None, None),
     };
 
-    let skip = email_already_exported(&date_str, &sender_short, &recipient_short, &subject_hash, export_dir);
+    let key = EmailKey::from_mail(&mail);
+    let skip = index.contains(&key).then_some(key);
     let analysis = analyze_email_type(&mail);
     (skip, Some(analysis))
 }
 
+/// Dry-run decision for one message, from its raw headers (see `plan`).
+/// `searched` is false for messages the export search leaves out (they
+/// carry `after_export.keyword`).
+fn plan_message(
+    raw_headers: &[u8],
+    searched: bool,
+    index: &ExportIndex,
+    account: &Account,
+    folder: &str,
+    dests: &[Destination],
+) -> PlanEntry {
+    let mail = mailparse::parse_mail(raw_headers).ok();
+    let header = |name: &str| {
+        mail.as_ref()
+            .and_then(|m| m.headers.get_first_value(name))
+            .unwrap_or_default()
+    };
+    let (from_field, subject) = (header("From"), header("Subject"));
+    let date_obj = parse_email_date(&header("Date"));
+    let key = mail.as_ref().map(EmailKey::from_mail);
+
+    let mut entry = PlanEntry {
+        account: account.name.clone(),
+        folder: folder.to_string(),
+        uid: None,
+        action: PlanAction::Export,
+        date: date_obj.map(|d| d.to_rfc3339()),
+        from: from_field.clone(),
+        subject: subject.clone(),
+        route: None,
+        rule: None,
+        delete: false,
+    };
+    if !searched {
+        entry.action = PlanAction::KeywordSkipped;
+    } else if let Some(key) = key.filter(|k| account.skip_existing && index.contains(k)) {
+        entry.action = PlanAction::AlreadyExported;
+        // Deleted only when the existing note can be found again
+        entry.delete = account.delete_after_export
+            && key.message_id.as_deref().and_then(|id| index.note_path(id)).is_some();
+    } else {
+        // From the headers only: invitations are not recognized here
+        let email_type = mail.as_ref().map(|m| analyze_email_type(m).email_type.to_string()).unwrap_or_default();
+        let meta = email_meta(&from_field, &subject, &account.name, date_obj, &email_type);
+        let decision = route_email(&meta, dests);
+        entry.route = Some(decision.rel_path);
+        entry.rule = decision.matched_rule;
+        entry.delete = account.delete_after_export;
+    }
+    entry
+}
+
 /// Parse email date string to DateTime.
 fn parse_email_date(date_str: &str) -> Option<DateTime<FixedOffset>> {
     mailparse::dateparse(date_str)
@@ -297,12 +522,23 @@ pub struct ExportContext<'a> {
     pub debug_mode: bool,
     /// Routing destinations parsed from `destinations.txt`.
     pub dests: &'a [Destination],
+    /// Decoded IMAP folder name (`{folder}` in the filename template).
+    pub folder: &'a str,
+    /// Notes already exported, shared across a folder's messages. When `None`,
+    /// duplicate detection scans `export_directory` for every message.
+    pub index: Option<&'a mut ExportIndex>,
+    /// IMAP flags of the message (`flags` field); empty outside an IMAP export.
+    pub flags: &'a [String],
+    /// Tails of the senders' previous messages, for the repeated signature
+    /// block detection; `None` leaves it out.
+    pub signatures: Option<&'a mut SignatureHistory>,
 }
 
-/// Export a single email to Markdown with frontmatter.
+/// Export a single email as a note in the account's `output_format`
+/// (Markdown with frontmatter by default, see `output`).
 ///
 /// Returns `Ok(Some((filepath, decision)))` when the email was written, where
-/// `decision` is the routing proposal (not yet applied — the `.md` stays in staging).
+/// `decision` is the routing proposal (not yet applied — the note stays in staging).
 /// Returns `Ok(None)` when the email was skipped (already exported or filtered).
 pub fn export_to_markdown(
     raw_email: &[u8],
@@ -314,13 +550,22 @@ pub fn export_to_markdown(
     let account = ctx.account;
     let debug_mode = ctx.debug_mode;
     let dests = ctx.dests;
-    let mail = mailparse::parse_mail(raw_email)
+    // Malformed messages are exported from a repaired or degraded copy
+    let recovered = crate::lenient::recover(raw_email);
+    if debug_mode {
+        if let Some(r) = &recovered {
+            println!("  Malformed message {}", if r.degraded { "exported as raw text" } else { "repaired" });
+        }
+    }
+    let parse_degraded = recovered.as_ref().is_some_and(|r| r.degraded);
+    let mail = mailparse::parse_mail(recovered.as_ref().map_or(raw_email, |r| &r.bytes))
         .context("Failed to parse email")?;
 
     let from_field = mail.headers.get_first_value("From").unwrap_or_default();
     let to_field = mail.headers.get_first_value("To").unwrap_or_default();
     let date_field = mail.headers.get_first_value("Date").unwrap_or_default();
     let subject = mail.headers.get_first_value("Subject").unwrap_or_default();
+    let message_id = mail.headers.get_first_value("Message-ID");
 
     // Parse date
     let date_obj = parse_email_date(&date_field);
@@ -328,23 +573,42 @@ pub fn export_to_markdown(
         .map(|d| d.format("%Y-%m-%d").to_string())
         .unwrap_or_else(|| "unknown-date".to_string());
 
-    let sender_short = get_short_name(Some(&from_field));
-    let recipient_short = get_short_name(Some(&to_field));
-
     // Generate subject hash for uniqueness
-    let subject_hash = if !subject.is_empty() {
-        hash_md5_prefix(&subject, 6)
-    } else {
-        "no-subject".to_string()
-    };
+    let subject_hash = subject_hash_of(&subject);
 
     // Check if email already exported
-    if account.skip_existing
-        && email_already_exported(&date_str, &sender_short, &recipient_short, &subject_hash, export_directory)
-    {
-        return Ok(None); // skipped — no (PathBuf, RouteDecision) to return
+    let key = EmailKey::new(message_id.as_deref(), date_obj, &from_field, &subject_hash);
+    if account.skip_existing {
+        let exists = match ctx.index.as_deref() {
+            Some(index) => index.contains(&key),
+            None => email_already_exported(&key, export_directory, &account.field_keys),
+        };
+        if exists {
+            return Ok(None); // skipped — no (PathBuf, RouteDecision) to return
+        }
     }
 
+    // Encrypted and signed messages are exported from their opened content
+    let opened = crypto::open(&mail, &account.crypto, debug_mode);
+    let mail = match &opened.bytes {
+        Some(bytes) => mailparse::parse_mail(bytes).context("Failed to parse decrypted email")?,
+        None => mail,
+    };
+
+    // Headers decoded with the wrong charset are repaired for display; the
+    // key above keeps hashing the subject as received
+    let mut charset_repaired = has_mislabeled_charset(&mail);
+    let mut repair = |value: String| match crate::cleaner::repair_mojibake(&value) {
+        Some(repaired) => {
+            charset_repaired = true;
+            repaired
+        }
+        None => value,
+    };
+    let from_field = repair(from_field);
+    let to_field = repair(to_field);
+    let subject = repair(subject);
+
     // Analyze email type and collect contacts if enabled
     let analysis = analyze_email_type(&mail);
     let email_type_str = analysis.email_type.to_string();
@@ -357,22 +621,67 @@ pub fn export_to_markdown(
     // Create export directory if needed
     fs::create_dir_all(export_directory)?;
 
-    // Generate unique filename
-    let extract = subject_extract(&subject);
-    let base_filename = if extract.is_empty() {
-        format!("email_{}_{}*to_{}", date_str, sender_short, recipient_short)
-    } else {
-        format!("email_{}_{}_{}*to_{}", date_str, sender_short, extract, recipient_short)
-    };
-    let mut counter = 1;
-    let mut filename = format!("{}.md", base_filename.replace('*', "_"));
-    while export_directory.join(&filename).exists() {
-        counter += 1;
-        filename = format!("{}_{}.md", base_filename.replace('*', "_"), counter);
+    // Embedded messages become child notes, written before the parent so that
+    // its file name is picked among theirs; the parent lists and links them.
+    let mut attachments = Vec::new();
+    let mut embedded_notes = EmbeddedNotes::default();
+    let mut embedded_decisions = Vec::new();
+    for part in embedded_messages(&mail) {
+        match export_embedded(part, &tags, ctx) {
+            Ok((child, decision)) => {
+                let file = child.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                embedded_notes.insert(part, &file);
+                attachments.push(file);
+                embedded_decisions.push((child, decision));
+            }
+            Err(e) => {
+                if debug_mode {
+                    println!("    Embedded message kept as .eml: {:#}", e);
+                }
+                if !account.skip_attachments {
+                    attachments.push(save_embedded_eml(part, export_directory, &date_str)?);
+                }
+            }
+        }
     }
 
-    // Extract body
-    let body = extract_body(&mail);
+    // Generate unique, portable filename from the account's template
+    let stem = render_template(
+        &account.filename_template,
+        &NoteNameFields {
+            date: date_obj,
+            from: &from_field,
+            to: &to_field,
+            subject: &subject,
+            message_id: message_id.as_deref(),
+            account: &account.name,
+            folder: ctx.folder,
+        },
+    );
+    // Route the email on its sender, subject, account and date; the name must
+    // fit in the destination the note is moved to
+    let meta = email_meta(&from_field, &subject, &account.name, date_obj, &email_type_str);
+    let mut decision = route_email(&meta, dests);
+    let final_dir = match &account.notes_dir {
+        Some(notes_dir) => notes_dir.join(&decision.rel_path),
+        None => export_directory.to_path_buf(),
+    };
+    let filepath = unique_note_path(
+        export_directory,
+        &final_dir,
+        &stem,
+        &date_str,
+        account.output_format.extension(),
+        &account.filename_policy,
+    );
+
+    // Extract body; Outlook rich-text messages only have the RTF body of their `winmail.dat`
+    let body = extract_body(&mail, account.body_preference, &embedded_notes);
+    let body = if body.trim().is_empty() {
+        tnef::find_body(&mail).unwrap_or(body)
+    } else {
+        body
+    };
 
     // Apply quote depth limiting
     let body = if account.quote_depth > 0 {
@@ -383,22 +692,61 @@ pub fn export_to_markdown(
 
     // Handle attachments — written into the same directory as the .md file,
     // named `<date>_<original-name>` for readability.
-    let mut attachments = Vec::new();
+    let mut inline_images = InlineImages::scan(&mail);
+
+    if !account.skip_attachments {
+        extract_attachments(
+            &mail,
+            export_directory,
+            &date_str,
+            account.skip_signature_images,
+            debug_mode,
+            &mut attachments,
+            &mut inline_images,
+        )?;
+    }
 
-    extract_attachments(
-        &mail,
-        export_directory,
-        &date_str,
-        account.skip_signature_images,
-        debug_mode,
-        &mut attachments,
-    )?;
+    // Inline images: `cid:` references → saved files
+    let body = cid::resolve_markdown(&body, &inline_images);
+
+    // Calendar invitation: agenda block on top, clean `.ics` next to the note
+    let event = calendar::find_event(&mail);
+    if let Some((_, ics)) = &event {
+        if !account.skip_attachments {
+            let file_name = unique_name_in(export_directory, &format!("{}_invitation.ics", date_str));
+            let path = export_directory.join(&file_name);
+            fs::write(&path, ics).with_context(|| format!("failed to write {}", path.display()))?;
+            attachments.push(file_name);
+        }
+    }
+    let event = event.map(|(event, _)| event);
 
-    // Normalize body and add attachments list
+    // Normalize body, then clean it with its quoted history and signature
     let body = normalize_line_breaks(&body);
-    let cleaned = crate::cleaner::clean(&body);
-    let mut normalized_body = cleaned.body;
+    let sender = extract_emails(Some(&from_field)).into_iter().next().unwrap_or_default();
+    let scope = RuleScope { account: &account.name, sender: &sender };
+    let earlier = match account.quoted_history {
+        QuotedHistory::Keep => None,
+        _ => earlier_note(&mail, account, ctx.index.as_deref()),
+    };
+    let message = MessageStages {
+        quoted_history: account.quoted_history,
+        earlier_note: earlier.as_deref(),
+        signatures: account.signatures.clone(),
+        signature_history: ctx.signatures.as_deref_mut(),
+    };
+    let cleaned = crate::cleaner::clean_message(&body, &account.cleaner, &scope, message);
+    let normalized_body = match &event {
+        Some(event) => format!("{}\n{}", calendar::agenda(event), cleaned.body),
+        None => cleaned.body,
+    };
     let social_links = cleaned.social_links;
+    let charset_repaired = charset_repaired || cleaned.charset_repaired;
+    let signature_block = cleaned.signature_block;
+    if let (SignatureKeep::Contact, Some(block), false) = (account.signatures.keep, &signature_block, sender.is_empty()) {
+        let dir = local_dir(&account.export_directory).join("signatures");
+        signatures::write_contact_note(&dir, &sender, &date_str, block)?;
+    }
 
     // Create frontmatter
     let frontmatter = EmailFrontmatter {
@@ -408,64 +756,135 @@ pub fn export_to_markdown(
             .map(|d| d.to_rfc3339())
             .unwrap_or_else(|| date_field.clone()),
         subject: subject.clone(),
+        message_id: key.message_id.clone(),
         subject_hash,
         tags,
         attachments: attachments.clone(),
         email_type: Some(email_type_str),
         social_links,
+        flags: ctx.flags.to_vec(),
+        parse_degraded,
+        event,
+        signature: opened.signature,
+        charset_repaired,
+        signature_block: signature_block.filter(|_| account.signatures.keep == SignatureKeep::Frontmatter),
     };
 
-    if !attachments.is_empty() {
-        normalized_body.push_str("\n\n### Pieces jointes :\n");
-        for attachment in &attachments {
-            let filename_only = Path::new(attachment)
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy();
-            normalized_body.push_str(&format!("- [{}]({})\n", filename_only, attachment));
-        }
-    }
+    // The standalone HTML format keeps the original HTML part (sanitized)
+    let html = match account.output_format {
+        OutputFormat::Html => extract_html(&mail).map(|h| cid::resolve_html(&h, &inline_images)),
+        _ => None,
+    };
 
-    // Write file
-    let filepath = export_directory.join(&filename);
+    // Write file (header, body and attachments list in the output format)
+    let note = NoteParts {
+        frontmatter: &frontmatter,
+        date: date_obj,
+        body: &normalized_body,
+        html: html.as_deref(),
+    };
     let mut file = File::create(&filepath)?;
+    write!(file, "{}", render_note(account.output_format, &note, &account.frontmatter))?;
+    if account.keep_raw {
+        let raw_path = raw_message_path(&filepath);
+        fs::write(&raw_path, raw_email)
+            .with_context(|| format!("failed to write {}", raw_path.display()))?;
+    }
+
+    if let Some(index) = ctx.index.as_deref_mut() {
+        index.insert_note(key, &filepath);
+    }
+
+    decision.embedded = embedded_decisions;
+
+    Ok(Some((filepath, decision)))
+}
 
-    writeln!(file, "---")?;
-    let yaml = serde_yaml::to_string(&frontmatter)?;
-    write!(file, "{}", yaml)?;
-    writeln!(file, "---\n")?;
-    write!(file, "{}", normalized_body)?;
+/// Export an embedded message as a child note next to its parent, with the
+/// parent's tags. Never skipped as a duplicate: the parent links to it.
+fn export_embedded(
+    part: &ParsedMail,
+    tags: &[String],
+    ctx: &ExportContext<'_>,
+) -> Result<(PathBuf, RouteDecision)> {
+    let raw = part.get_body_raw().context("Failed to decode embedded message")?;
+    let account = Account { skip_existing: false, ..ctx.account.clone() };
+    let mut child_ctx = ExportContext {
+        export_directory: ctx.export_directory,
+        base_export_directory: ctx.base_export_directory,
+        account: &account,
+        debug_mode: ctx.debug_mode,
+        dests: ctx.dests,
+        folder: ctx.folder,
+        index: None,
+        flags: &[],
+        signatures: None,
+    };
+    export_to_markdown(&raw, tags.to_vec(), None, &mut child_ctx)?
+        .context("embedded message was not exported")
+}
+
+/// File stem of the note of the message `mail` replies to (`In-Reply-To`),
+/// when `index` knows it. Linked as `[[stem]]`, hence Markdown notes only.
+fn earlier_note(mail: &ParsedMail, account: &Account, index: Option<&ExportIndex>) -> Option<String> {
+    if account.output_format != OutputFormat::Markdown {
+        return None;
+    }
+    let in_reply_to = mail.headers.get_first_value("In-Reply-To")?;
+    let id = normalize_message_id(in_reply_to.split_whitespace().next()?);
+    let path = index?.note_path(&id)?;
+    Some(path.file_stem()?.to_string_lossy().into_owned())
+}
+
+/// `_local/` directory next to an account's export directory (contacts files,
+/// per-contact signature notes).
+fn local_dir(export_directory: &str) -> PathBuf {
+    let export_dir = PathBuf::from(export_directory);
+    export_dir.parent().unwrap_or(&export_dir).join("_local")
+}
+
+/// Save an embedded message that could not be exported as an `.eml`
+/// attachment. Returns its bare file name.
+fn save_embedded_eml(part: &ParsedMail, attachments_dir: &Path, name_prefix: &str) -> Result<String> {
+    let name = extract_attachment_filename(part)
+        .map(|f| sanitize_filename(&decode_mime_filename(&f)))
+        .unwrap_or_else(|| "message.eml".to_string());
+    let name = if name.to_lowercase().ends_with(".eml") { name } else { format!("{}.eml", name) };
+    let file_name = unique_name_in(attachments_dir, &format!("{}_{}", name_prefix, name));
+    let path = attachments_dir.join(&file_name);
+    fs::write(&path, part.get_body_raw().unwrap_or_default())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(file_name)
+}
 
-    // Route the email — extract domain from the From address for matching.
-    // Uses the first email address found; falls back to empty string on parse failure.
-    let email_addresses = extract_emails(Some(&frontmatter.from));
+/// Routing input for a message. The domain comes from the first address in
+/// `from_field` (empty when none parses); a missing date routes as the epoch.
+fn email_meta(
+    from_field: &str,
+    subject: &str,
+    account: &str,
+    date_obj: Option<DateTime<FixedOffset>>,
+    email_type: &str,
+) -> EmailMeta {
+    let email_addresses = extract_emails(Some(from_field));
     let sender_addr = email_addresses.first().map(|s| s.as_str()).unwrap_or("");
     let domain = sender_addr
         .rfind('@')
         .map(|i| sender_addr[i + 1..].to_string())
         .unwrap_or_default();
 
-    let meta = EmailMeta {
+    EmailMeta {
         from: sender_addr.to_string(),
         domain,
-        subject: frontmatter.subject.clone(),
-        account: account.name.clone(),
-        // date_obj is Option<DateTime<FixedOffset>>; use epoch on None
+        subject: subject.to_string(),
+        account: account.to_string(),
+        email_type: email_type.to_string(),
         date: date_obj.unwrap_or_else(|| {
             chrono::DateTime::from_timestamp(0, 0)
                 .expect("epoch is valid")
                 .fixed_offset()
         }),
-    };
-
-    let decision = route_email(&meta, dests);
-
-    Ok(Some((filepath, decision)))
-}
-
-/// Convert HTML to Markdown using htmd. Returns empty string on failure.
-fn html_to_markdown(html: &str) -> String {
-    htmd::convert(html).unwrap_or_default()
+    }
 }
 
 /// Stats returned by `fix_html_bodies`.
@@ -534,53 +953,28 @@ pub fn fix_html_bodies(
     Ok(stats)
 }
 
-/// Extract the body portion of a `.md` file (content after the closing `---`).
+/// Extract the body portion of a `.md` file (content after the note header,
+/// YAML or Logseq properties).
 pub(crate) fn extract_md_body(content: &str) -> &str {
-    // Skip the opening ---
-    let after_open = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")).unwrap_or(content);
-    // Find the closing ---
-    if let Some(idx) = after_open.find("\n---\n") {
-        &after_open[idx + 5..]
-    } else if let Some(idx) = after_open.find("\n---\r\n") {
-        &after_open[idx + 6..]
-    } else {
-        content
-    }
+    note_body(content)
 }
 
-/// Extract the body from a parsed email.
-pub(crate) fn extract_body(mail: &ParsedMail) -> String {
-    if mail.subparts.is_empty() {
-        // Not multipart
-        mail.get_body().unwrap_or_default()
-    } else {
-        // Multipart - look for text/plain or text/html
-        let mut body = String::new();
-
-        for part in &mail.subparts {
-            let content_type = part
-                .headers
-                .get_first_value("Content-Type")
-                .unwrap_or_default()
-                .to_lowercase();
-
-            if content_type.starts_with("text/plain") {
-                body = part.get_body().unwrap_or_default();
-                break;
-            } else if content_type.starts_with("text/html") && body.is_empty() {
-                let raw_html = part.get_body().unwrap_or_default();
-                body = html_to_markdown(&raw_html);
-            } else if content_type.starts_with("multipart/") {
-                // Recurse into nested multipart
-                let nested_body = extract_body(part);
-                if !nested_body.is_empty() && body.is_empty() {
-                    body = nested_body;
-                }
-            }
-        }
-
-        body
+/// First `text/html` part of an email (inline, not an attachment), if any.
+pub(crate) fn extract_html(mail: &ParsedMail) -> Option<String> {
+    let content_type = mail
+        .headers
+        .get_first_value("Content-Type")
+        .unwrap_or_default()
+        .to_lowercase();
+    let disposition = mail
+        .headers
+        .get_first_value("Content-Disposition")
+        .unwrap_or_default()
+        .to_lowercase();
+    if content_type.starts_with("text/html") && !disposition.starts_with("attachment") {
+        return mail.get_body().ok().filter(|b| !b.trim().is_empty());
     }
+    mail.subparts.iter().find_map(extract_html)
 }
 
 /// Extract attachments from a parsed email.
@@ -589,6 +983,9 @@ pub(crate) fn extract_body(mail: &ParsedMail) -> String {
 /// using the flat filename scheme `<stem>__<hash>_<safe_name>`. When two attachments share the
 /// same original name (identical hash), a numeric suffix `_2`, `_3`, … is appended to avoid
 /// clobbering. The bare filename (no directory prefix) is pushed into `attachments`.
+///
+/// Parts referenced from the body by their `Content-ID` are saved even without
+/// a name or disposition, and their file is recorded in `inline_images` (see `cid`).
 fn extract_attachments(
     mail: &ParsedMail,
     attachments_dir: &Path,
@@ -596,14 +993,22 @@ fn extract_attachments(
     skip_signature_images: bool,
     debug_mode: bool,
     attachments: &mut Vec<String>,
+    inline_images: &mut InlineImages,
 ) -> Result<()> {
     for part in &mail.subparts {
         let content_disposition = part
             .headers
             .get_first_value("Content-Disposition")
             .unwrap_or_default();
+        let content_id = cid::content_id(part);
+        let referenced = content_id.as_deref().is_some_and(|id| inline_images.is_referenced(id));
 
-        if content_disposition.is_empty() && part.subparts.is_empty() {
+        if content_disposition.is_empty() && part.subparts.is_empty() && !referenced {
+            continue;
+        }
+        // Child notes (or `.eml` fallbacks) and calendars (clean `.ics`),
+        // see `export_to_markdown`
+        if is_embedded_message(part) || is_invitation_calendar(part) {
             continue;
         }
 
@@ -611,7 +1016,13 @@ fn extract_attachments(
         let has_attachment_disposition = content_disposition.to_lowercase().contains("attachment")
             || content_disposition.to_lowercase().contains("inline");
 
-        if let Some(filename) = extract_attachment_filename(part) {
+        // Unnamed inline image: named after its Content-ID
+        let filename = extract_attachment_filename(part).or_else(|| {
+            let id = content_id.as_deref().filter(|_| referenced)?;
+            Some(cid::fallback_filename(id, &part.ctype.mimetype))
+        });
+
+        if let Some(filename) = filename {
             let decoded_filename = decode_mime_filename(&filename);
 
             if has_attachment_disposition || !filename.is_empty() {
@@ -623,7 +1034,9 @@ fn extract_attachments(
                 let payload = part.get_body_raw().unwrap_or_default();
 
                 // Check if this is a signature image that should be skipped
+                // (images shown in the body are kept when they look like content)
                 if skip_signature_images
+                    && !(referenced && cid::is_content_image(&decoded_filename, payload.len()))
                     && is_signature_image(
                         Some(&decoded_filename),
                         &content_type,
@@ -641,31 +1054,18 @@ fn extract_attachments(
                     continue;
                 }
 
+                // `winmail.dat`: its files are saved instead when it can be decoded
+                if tnef::is_tnef_part(part)
+                    && save_tnef_files(&payload, attachments_dir, name_prefix, debug_mode, attachments)?
+                {
+                    continue;
+                }
+
                 if !payload.is_empty() {
-                    let safe_filename = sanitize_filename(&decoded_filename);
-                    // Flat naming scheme: <date>_<original-name> — readable, with the
-                    // email date as prefix. Collisions are disambiguated below; a second
-                    // safety pass in `route::move_email` handles cross-email collisions
-                    // when several emails are routed into the same destination folder.
-                    let base_full_filename = format!("{}_{}", name_prefix, safe_filename);
-
-                    // Numeric suffix loop on real path collision — suffix inserted before extension
-                    // so `invoice.pdf` → `invoice_2.pdf`, not `invoice.pdf_2`.
-                    let mut full_filename = base_full_filename.clone();
-                    let mut suffix = 2u32;
-                    while attachments_dir.join(&full_filename).exists() {
-                        full_filename = match base_full_filename.rsplit_once('.') {
-                            Some((stem, ext)) => format!("{}_{}.{}", stem, suffix, ext),
-                            None => format!("{}_{}", base_full_filename, suffix),
-                        };
-                        suffix += 1;
+                    let bare_link = save_attachment(attachments_dir, name_prefix, &decoded_filename, &payload)?;
+                    if let Some(id) = &content_id {
+                        inline_images.insert(id, &bare_link);
                     }
-
-                    let filepath = attachments_dir.join(&full_filename);
-                    fs::write(&filepath, &payload)?;
-
-                    // Store bare filename — same-folder relative link; normalize \ → / at write time
-                    let bare_link = full_filename.replace('\\', "/");
                     attachments.push(bare_link);
                 } else if debug_mode {
                     println!(
@@ -685,6 +1085,7 @@ fn extract_attachments(
                 skip_signature_images,
                 debug_mode,
                 attachments,
+                inline_images,
             )?;
         }
     }
@@ -692,6 +1093,64 @@ fn extract_attachments(
     Ok(())
 }
 
+/// Write an attachment as `<date>_<original-name>` in `attachments_dir` and
+/// return its bare file name.
+fn save_attachment(attachments_dir: &Path, name_prefix: &str, filename: &str, payload: &[u8]) -> Result<String> {
+    let safe_filename = sanitize_filename(filename);
+    // Flat naming scheme: <date>_<original-name> — readable, with the
+    // email date as prefix. Collisions are disambiguated below; a second
+    // safety pass in `route::move_email` handles cross-email collisions
+    // when several emails are routed into the same destination folder.
+    let base_full_filename = format!("{}_{}", name_prefix, safe_filename);
+
+    // Numeric suffix on real path collision — suffix inserted before extension
+    // so `invoice.pdf` → `invoice_2.pdf`, not `invoice.pdf_2`.
+    let full_filename = unique_name_in(attachments_dir, &base_full_filename);
+
+    let filepath = attachments_dir.join(&full_filename);
+    fs::write(&filepath, payload)?;
+
+    // Store bare filename — same-folder relative link; normalize \ → / at write time
+    Ok(full_filename.replace('\\', "/"))
+}
+
+/// Save the files of a TNEF stream as attachments. Returns `false` (the
+/// stream is then saved as is) when it cannot be decoded or holds no file
+/// and no body.
+fn save_tnef_files(
+    payload: &[u8],
+    attachments_dir: &Path,
+    name_prefix: &str,
+    debug_mode: bool,
+    attachments: &mut Vec<String>,
+) -> Result<bool> {
+    let decoded = match tnef::parse(payload) {
+        Ok(decoded) if !decoded.files.is_empty() || decoded.body.is_some() => decoded,
+        Ok(_) => return Ok(false),
+        Err(e) => {
+            if debug_mode {
+                println!("    winmail.dat kept as is: {:#}", e);
+            }
+            return Ok(false);
+        }
+    };
+    for (i, file) in decoded.files.iter().enumerate() {
+        let name = file
+            .name
+            .clone()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| format!("winmail_{}.bin", i + 1));
+        attachments.push(save_attachment(attachments_dir, name_prefix, &name, &file.data)?);
+    }
+    Ok(true)
+}
+
+/// A calendar part with an event: saved as a clean `.ics` instead.
+fn is_invitation_calendar(part: &ParsedMail) -> bool {
+    calendar::is_calendar_part(part)
+        && part.get_body().ok().and_then(|text| calendar::parse(&text)).is_some()
+}
+
 /// Extract filename from an attachment part.
 fn extract_attachment_filename(part: &ParsedMail) -> Option<String> {
     // Try Content-Disposition header first
@@ -750,6 +1209,34 @@ pub struct ImapExporter {
     debug_mode: bool,
     network_config: NetworkConfig,  // [4][5]
     is_gmail: bool,
+    /// Where each exported note's message is left on the server, by staging
+    /// path — used by `mirror_routed` after routing.
+    server_copies: HashMap<PathBuf, ServerCopy>,
+}
+
+/// Outcome of `ImapExporter::mirror_routed`.
+#[derive(Debug, Default, Clone)]
+pub struct MirrorReport {
+    pub moved: usize,
+    pub planned: usize,
+    pub skipped: usize,
+    pub errors: usize,
+}
+
+/// Outcome of `ImapExporter::delete_verified`.
+#[derive(Debug, Default, Clone)]
+pub struct DeletionReport {
+    pub deleted: usize,
+    /// Notes still in staging: kept pending until they are routed.
+    pub waiting: usize,
+    /// Notes missing or modified: the messages are kept on the server.
+    pub rejected: usize,
+    /// Part of `deleted` only flagged `\Deleted`: the server lacks UIDPLUS,
+    /// so the expunge is left to the mail client.
+    pub flagged_only: usize,
+    /// Messages no longer in their folder (moved or deleted elsewhere):
+    /// dropped from the pending list, not journaled.
+    pub gone: usize,
 }
 
 impl ImapExporter {
@@ -760,6 +1247,7 @@ impl ImapExporter {
             debug_mode,
             network_config: NetworkConfig::default(),  // [4][5]
             is_gmail: false,
+            server_copies: HashMap::new(),
         }
     }
 
@@ -877,40 +1365,71 @@ impl ImapExporter {
 
     /// List all folders.
     ///
-    /// Returns the raw IMAP name (modified UTF-7, used for `SELECT`) and a decoded
-    /// display name (used for local paths, `ignored_folders` matching and logging).
+    /// Returns the raw IMAP name (modified UTF-7, used for `SELECT`), a decoded
+    /// display name (used for `ignored_folders` matching and logging) and the
+    /// hierarchy delimiter announced by the server (used for local paths).
     /// Folders with the `\Noselect` attribute (e.g. Gmail's `[Gmail]` parent) are
     /// filtered out because they cannot be opened with `SELECT`.
+    ///
+    /// When the server supports `NAMESPACE`, folders of shared and other-users
+    /// namespaces are dropped unless enabled in `namespaces:`, in which case each
+    /// enabled prefix is listed too. With `subscribed_only`, folders missing from
+    /// the `LSUB` response are dropped (attributes still come from `LIST`).
     pub fn list_folders(&mut self) -> Result<Vec<FolderName>> {
         let session = self.session.as_mut().context("Not connected")?;
+        let settings = &self.account.namespaces;
 
         if self.debug_mode {
             println!("Listing folders...");
         }
 
-        let folders = session.list(None, Some("*"))?;
-        let folder_names: Vec<FolderName> = folders
-            .iter()
-            .filter(|f| {
-                let attrs = f.attributes();
-                !attrs.contains(&NameAttribute::NoSelect)
-                    && !attrs.contains(&NameAttribute::Junk)
-                    && !attrs.contains(&NameAttribute::Trash)
-                    && !attrs.contains(&NameAttribute::Drafts)
-                    && !attrs.contains(&NameAttribute::All)
-                    && !attrs.contains(&NameAttribute::Flagged)
-                    && !attrs.iter().any(|a| {
-                        matches!(a, NameAttribute::Extension(s) if s.eq_ignore_ascii_case("Important"))
-                    })
-                    // Gmail does not always declare \Important via SPECIAL-USE — filter by known names
-                    && !matches!(f.name(), "[Gmail]/Important" | "[Google Mail]/Important")
-            })
-            .map(|f| {
+        let namespaces = if session.capabilities()?.has_str("NAMESPACE") {
+            match session.run_command_and_read_response("NAMESPACE") {
+                Ok(data) => parse_namespace_response(&data),
+                Err(e) => {
+                    if self.debug_mode {
+                        println!("NAMESPACE failed, listing personal folders only: {}", e);
+                    }
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        let mut patterns = vec!["*".to_string()];
+        for ns in &namespaces {
+            if ns.kind != NamespaceKind::Personal && !ns.prefix.is_empty() && settings.exports(ns.kind) {
+                patterns.push(format!("\"{}*\"", ns.prefix.replace('\\', "\\\\").replace('"', "\\\"")));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut folder_names: Vec<FolderName> = Vec::new();
+        for pattern in &patterns {
+            let folders = session.list(None, Some(pattern))?;
+            let subscribed: Option<HashSet<String>> = if self.account.subscribed_only {
+                Some(session.lsub(None, Some(pattern))?.iter().map(|n| n.name().to_string()).collect())
+            } else {
+                None
+            };
+
+            for f in folders.iter().filter(|f| is_exportable_mailbox(f)) {
                 let raw = f.name().to_string();
-                let display = decode_imap_utf7(f.name());
-                FolderName { raw, display }
-            })
-            .collect();
+                if subscribed.as_ref().is_some_and(|s| !s.contains(&raw)) || !seen.insert(raw.clone()) {
+                    continue;
+                }
+                let namespace = namespace_of(&raw, &namespaces)
+                    .filter(|ns| ns.kind != NamespaceKind::Personal)
+                    .cloned();
+                if namespace.as_ref().is_some_and(|ns| !settings.exports(ns.kind)) {
+                    continue;
+                }
+                let display = decode_imap_utf7(&raw);
+                let delimiter = f.delimiter().map(str::to_string);
+                folder_names.push(FolderName { raw, display, delimiter, namespace });
+            }
+        }
 
         if self.debug_mode {
             println!("Found {} folders", folder_names.len());
@@ -919,7 +1438,8 @@ impl ImapExporter {
         Ok(folder_names)
     }
 
-    /// Export a single folder.
+    /// Export a single folder with `account`, the folder's effective settings
+    /// (see `folder_rules::account_for_folder`).
     ///
     /// Returns `(stats, decisions)` where `decisions` is the list of route proposals
     /// for every email written during this folder's export. The caller accumulates
@@ -927,12 +1447,14 @@ impl ImapExporter {
     pub fn export_folder(
         &mut self,
         folder: &FolderName,
+        account: &Account,
         mut contacts_collector: Option<&mut ContactsCollector>,
+        mut signature_history: Option<&mut SignatureHistory>,
         cancel_token: Option<&AtomicBool>,
         dests: &[Destination],
     ) -> Result<(ExportStats, Vec<(PathBuf, RouteDecision)>)> {
-        let base_export_directory = PathBuf::from(&self.account.export_directory);
-        let export_directory = base_export_directory.join(folder.display.replace('.', "/"));
+        let base_export_directory = PathBuf::from(&account.export_directory);
+        let export_directory = base_export_directory.join(local_folder_path(account, folder));
 
         // Session borrow is scoped to a block so it ends before the gmail expunge dispatch,
         // which needs to re-borrow self.session via expunge_gmail_all_mail().
@@ -942,19 +1464,33 @@ impl ImapExporter {
             // Select folder using the raw IMAP name (modified UTF-7)
             let mailbox = session.select(&folder.raw)?;
             let message_count = mailbox.exists as usize;
+            let uid_validity = mailbox.uid_validity;
 
             if self.debug_mode {
                 println!("  {} messages in folder", message_count);
             }
 
-            // Search for all messages
-            let uids = session.search("ALL")?;
+            // Search for the messages to export (all, or those without the
+            // `after_export.keyword` when skip_existing is on)
+            let uids = session.search(account.after_export.search_criteria(account.skip_existing))?;
             let uids_vec: Vec<_> = uids.into_iter().collect();
+            let keyword_skipped = message_count.saturating_sub(uids_vec.len());
+
+            // Notes already on disk, indexed once by frontmatter identity
+            let mut index = if account.skip_existing {
+                ExportIndex::scan(&export_directory, &account.field_keys)
+            } else {
+                ExportIndex::default()
+            };
+
+            // Phase 1 of `delete_after_export`: messages to delete once their
+            // note is verified (see `deletion`); nothing is flagged here
+            let mut pending: Vec<PendingDeletion> = Vec::new();
 
             // Pre-filter: batch fetch headers, skip already-exported without downloading body
-            let (filtered_uids, pre_skipped, already_exported_uids) = if self.account.skip_existing && !uids_vec.is_empty() {
+            let (filtered_uids, pre_skipped, already_exported_uids) = if account.skip_existing && !uids_vec.is_empty() {
                 let seq_set = uids_vec.iter().map(|u| u.to_string()).collect::<Vec<_>>().join(",");
-                match session.fetch(&seq_set, "RFC822.HEADER") {
+                match session.fetch(&seq_set, "(UID RFC822.HEADER)") {
                     Ok(headers) => {
                         let mut skip_set = HashSet::new();
                         for message in headers.iter() {
@@ -963,10 +1499,19 @@ impl ImapExporter {
                             }
                             let (skip, analysis) = should_skip_from_headers(
                                 message.header().unwrap_or(&[]),
-                                &export_directory,
+                                &index,
                             );
-                            if skip {
+                            if let Some(key) = skip {
                                 skip_set.insert(message.message);
+                                // Archived by a previous run: deleted once its note is verified
+                                if account.delete_after_export {
+                                    let note = key.message_id.as_deref().and_then(|id| index.note_path(id));
+                                    if let (Some(uid), Some(note)) = (message.uid, note) {
+                                        pending.extend(pending_deletion(
+                                            account, folder, uid, uid_validity, key.message_id.clone(), note,
+                                        ));
+                                    }
+                                }
                                 // Collect contacts from skipped emails too
                                 if let (Some(collector), Some(a)) = (contacts_collector.as_deref_mut(), analysis) {
                                     for contact in a.contacts {
@@ -1000,7 +1545,19 @@ impl ImapExporter {
             let mut progress = ProgressIndicator::new(&folder.display, total_to_process);
             let mut stats = ExportStats::default();
             let mut folder_decisions: Vec<(PathBuf, RouteDecision)> = Vec::new();
-            stats.skipped += pre_skipped;
+            stats.skipped += pre_skipped + keyword_skipped;
+            // Messages now safely on disk (exported or found already exported),
+            // the target of `after_export` actions
+            let mut processed: Vec<u32> = already_exported_uids.clone();
+            // Mailbox holding the messages once the folder is done (None: deleted)
+            let server_mailbox = if account.delete_after_export {
+                None
+            } else {
+                match account.after_export.move_to.as_deref().filter(|t| *t != folder.display) {
+                    Some(target) => Some(encode_imap_utf7(target)),
+                    None => Some(folder.raw.clone()),
+                }
+            };
 
             for (_idx, uid) in filtered_uids.into_iter().enumerate() {
                 if cancel_token.map_or(false, |t| t.load(Ordering::Relaxed)) {
@@ -1009,7 +1566,7 @@ impl ImapExporter {
 
                 // [4] Retry logic for fetch
                 let fetch_result = with_retry(&self.network_config, "fetch", || {
-                    session.fetch(uid.to_string(), "RFC822")
+                    session.fetch(uid.to_string(), "(UID FLAGS BODY.PEEK[])")
                 });
 
                 let messages = match fetch_result {
@@ -1026,31 +1583,64 @@ impl ImapExporter {
 
                 for message in messages.iter() {
                     if let Some(body) = message.body() {
+                        let flags: Vec<String> = message
+                            .flags()
+                            .iter()
+                            .filter(|f| !matches!(f, Flag::Recent | Flag::Deleted))
+                            .map(|f| f.to_string())
+                            .collect();
                         let mut ctx = ExportContext {
                             export_directory: &export_directory,
                             base_export_directory: &base_export_directory,
-                            account: &self.account,
+                            account,
                             debug_mode: self.debug_mode,
                             dests,
+                            folder: &folder.display,
+                            index: Some(&mut index),
+                            flags: &flags,
+                            signatures: signature_history.as_deref_mut(),
                         };
                         let result = export_to_markdown(
                             body,
-                            vec![folder.display.clone()],
+                            vec![folder_tag(account, folder)],
                             contacts_collector.as_deref_mut(),
                             &mut ctx,
                         );
 
+                        let message_id = mailparse::parse_headers(body)
+                            .ok()
+                            .and_then(|(headers, _)| headers.get_first_value("Message-ID"))
+                            .map(|id| normalize_message_id(&id))
+                            .filter(|id| !id.is_empty());
+                        // Where the message's note is on disk, for the deletion check
+                        let mut note = None;
+
                         match result {
                             Ok(Some((path, decision))) => {
                                 stats.exported += 1;
+                                if let (Some(mailbox), Some(message_id)) = (&server_mailbox, &message_id) {
+                                    self.server_copies.insert(
+                                        path.clone(),
+                                        ServerCopy { mailbox: mailbox.clone(), message_id: message_id.clone() },
+                                    );
+                                }
+                                note = Some(path.clone());
                                 folder_decisions.push((path, decision));
+                                processed.push(uid);
+                            }
+                            Ok(None) => {
+                                stats.skipped += 1;
+                                note = message_id
+                                    .as_deref()
+                                    .and_then(|id| index.note_path(id))
+                                    .map(Path::to_path_buf);
+                                processed.push(uid);
                             }
-                            Ok(None) => stats.skipped += 1,
                             Err(e) => {
                                 // Malformed messages (RFC-invalid MIME, broken headers, etc.)
-                                // are counted as skipped rather than errored: they cannot be
-                                // exported by design and should not contribute to the error
-                                // count that signals transient/recoverable failures.
+                                // cannot be exported by design and do not count as errors,
+                                // which signal transient/recoverable failures. Either way the
+                                // message is kept in quarantine for `retry-failed`.
                                 let is_malformed =
                                     e.downcast_ref::<mailparse::MailParseError>().is_some();
                                 if self.debug_mode {
@@ -1060,55 +1650,118 @@ impl ImapExporter {
                                         "Error exporting message"
                                     };
                                     println!("  {} {}: {:#}", label, uid, e);
-                                    let dump_dir = base_export_directory.join("_failed");
-                                    if fs::create_dir_all(&dump_dir).is_ok() {
-                                        let dump_path = dump_dir.join(format!(
-                                            "{}_uid_{}.eml",
-                                            sanitize_filename(&folder.display),
-                                            uid
-                                        ));
-                                        let _ = fs::write(&dump_path, body);
-                                        println!("  Raw message dumped to {}", dump_path.display());
+                                }
+                                let mut record = QuarantineRecord {
+                                    account: account.name.clone(),
+                                    folder: folder.display.clone(),
+                                    delimiter: folder.delimiter.clone(),
+                                    uid: message.uid,
+                                    uid_validity,
+                                    message_id: message_id.clone(),
+                                    stage: FailureStage::Export,
+                                    errors: Vec::new(),
+                                    export_directory: export_directory.clone(),
+                                    tags: vec![folder_tag(account, folder)],
+                                    flags: flags.clone(),
+                                    quarantined_at: Utc::now(),
+                                    retries: 0,
+                                };
+                                record.set_error(&e);
+                                match quarantine::quarantine(&base_export_directory, &record, body) {
+                                    Ok(path) => {
+                                        stats.quarantined += 1;
+                                        if self.debug_mode {
+                                            println!("  Raw message quarantined to {}", path.display());
+                                        }
+                                    }
+                                    Err(qe) => {
+                                        eprintln!(
+                                            "Warning: could not quarantine message {} of {}: {:#}",
+                                            uid, folder.display, qe
+                                        );
+                                        if is_malformed {
+                                            stats.skipped += 1;
+                                        }
                                     }
                                 }
-                                if is_malformed {
-                                    stats.skipped += 1;
-                                } else {
+                                if !is_malformed {
                                     stats.errors += 1;
                                 }
                             }
                         }
-                    }
-                }
 
-                // Delete after export if requested.
-                // IMAP flag is set here (server-side); local `.md` files remain in staging
-                // until route decisions are applied in the caller — the deferred move (D6)
-                // ensures routing always precedes any local file removal.
-                if self.account.delete_after_export {
-                    session.store(uid.to_string(), "+FLAGS (\\Deleted)")?;
+                        if account.delete_after_export {
+                            if let (Some(uid), Some(note)) = (message.uid, note) {
+                                pending.extend(pending_deletion(
+                                    account, folder, uid, uid_validity, message_id, &note,
+                                ));
+                            }
+                        }
+                    }
                 }
 
                 // [3] Update progress
                 progress.inc();
             }
 
-            // Mark already-exported (skipped) messages for deletion too.
-            // They were safely archived in a previous run; with delete_after_export
-            // the intent is to clean up the server, not just newly exported messages.
-            if self.account.delete_after_export && !already_exported_uids.is_empty() {
-                let seq_set = already_exported_uids
-                    .iter()
-                    .map(|u| u.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",");
-                session.store(&seq_set, "+FLAGS (\\Deleted)")?;
+            // Record the deletions; the messages stay on the server until
+            // `delete_verified` finds their notes at their final paths
+            if let Err(e) = deletion::add_pending(&deletion::pending_path(), pending) {
+                eprintln!("Warning: could not record pending deletions: {:#}", e);
+            }
+
+            // Post-export server actions, batched once per folder: flags first,
+            // then the move, which renumbers the remaining messages.
+            let actions = &account.after_export;
+            if !actions.is_noop() && !processed.is_empty() {
+                let seq_set = sequence_set(&processed);
+                if let Some(flags) = actions.flags() {
+                    session
+                        .store(&seq_set, format!("+FLAGS ({})", flags))
+                        .context("after_export: store flags")?;
+                }
+                if let Some(target) = actions.move_to.as_deref().filter(|t| *t != folder.display) {
+                    let mailbox = encode_imap_utf7(target);
+                    let capabilities = session.capabilities()?;
+                    if capabilities.has_str("MOVE") {
+                        session
+                            .mv(&seq_set, &mailbox)
+                            .with_context(|| format!("after_export: move to {}", target))?;
+                    } else {
+                        // COPY + \Deleted fallback for servers without MOVE. Only
+                        // the copied UIDs are expunged (UIDPLUS): a plain EXPUNGE
+                        // would also remove messages other clients flagged.
+                        let can_uid_expunge = capabilities.has_str("UIDPLUS");
+                        session
+                            .copy(&seq_set, &mailbox)
+                            .with_context(|| format!("after_export: copy to {}", target))?;
+                        session.store(&seq_set, "+FLAGS (\\Deleted)")?;
+                        if can_uid_expunge {
+                            let uids: Vec<u32> = session
+                                .fetch(&seq_set, "UID")
+                                .context("after_export: fetch copied UIDs")?
+                                .iter()
+                                .filter_map(|m| m.uid)
+                                .collect();
+                            if !uids.is_empty() {
+                                session
+                                    .uid_expunge(sequence_set(&uids))
+                                    .context("after_export: expunge moved messages")?;
+                            }
+                        } else {
+                            println!(
+                                "  after_export: server lacks UIDPLUS — {} message(s) copied to {} stay flagged \\Deleted in {} until a mail client expunges it",
+                                processed.len(), target, folder.display
+                            );
+                        }
+                    }
+                }
             }
 
             // [3] Finish progress indicator
             progress.finish_with_message(&format!(
-                "{} exported, {} skipped, {} errors",
-                stats.exported, stats.skipped, stats.errors
+                "{} exported, {} skipped, {} errors, {} quarantined",
+                stats.exported, stats.skipped, stats.errors, stats.quarantined
             ));
 
             (stats, folder_decisions)
@@ -1116,22 +1769,270 @@ impl ImapExporter {
         };
 
         let (stats, folder_decisions) = stats_and_decisions;
+        Ok((stats, folder_decisions))
+    }
 
-        // Expunge deleted messages
-        if self.account.delete_after_export {
-            if self.is_gmail {
-                self.expunge_gmail_all_mail().context("gmail all mail expunge")?;
-            } else {
-                let session = self.session.as_mut().context("Not connected")?;
-                session.expunge().context("expunge folder")?;
+    /// File routed notes' messages into IMAP folders mirroring their
+    /// destinations (see `mirror`). `routed` holds the `(staging_path, decision)`
+    /// pairs that `route::apply_decision` applied successfully.
+    pub fn mirror_routed(&mut self, routed: &[(PathBuf, RouteDecision)]) -> Result<MirrorReport> {
+        let settings = self.account.mirror_routing.clone();
+        let session = self.session.as_mut().context("Not connected")?;
+        let mut report = MirrorReport::default();
+
+        // `LIST "" ""` returns the hierarchy delimiter
+        let delimiter = session
+            .list(None, Some("\"\""))
+            .context("list hierarchy delimiter")?
+            .iter()
+            .find_map(|n| n.delimiter().map(str::to_string));
+
+        let mut moves = Vec::new();
+        let mut target_levels: HashMap<String, Vec<String>> = HashMap::new();
+        for (staging_path, decision) in routed {
+            if decision.is_default && !settings.include_default {
+                continue;
             }
+            let levels = mirror_levels(&decision.rel_path, settings.root.as_deref());
+            let (Some(copy), Some(levels)) = (self.server_copies.get(staging_path), levels) else {
+                report.skipped += 1;
+                continue;
+            };
+            let target = mailbox_name(&levels, delimiter.as_deref());
+            target_levels.insert(target.clone(), levels);
+            moves.push(PlannedMove { source: copy.clone(), target });
         }
 
-        Ok((stats, folder_decisions))
-    }
+        let groups = group_moves(&moves);
+        if settings.dry_run {
+            for ((source, target), ids) in &groups {
+                println!("  [dry-run] {} message(s): {} -> {}", ids.len(), decode_imap_utf7(source), target);
+                report.planned += ids.len();
+            }
+            return Ok(report);
+        }
 
-    /// Export all folders for the account.
-    ///
+        let capabilities = session.capabilities()?;
+        let can_move = capabilities.has_str("MOVE");
+        let can_uid_expunge = capabilities.has_str("UIDPLUS");
+        let mut existing: HashSet<String> = session
+            .list(None, Some("*"))
+            .context("list folders")?
+            .iter()
+            .map(|n| n.name().to_string())
+            .collect();
+
+        for ((source, target), ids) in &groups {
+            let result = (|| -> Result<usize> {
+                for mailbox in mailbox_ancestors(&target_levels[target], delimiter.as_deref()) {
+                    let raw = encode_imap_utf7(&mailbox);
+                    if !existing.contains(&raw) {
+                        session.create(&raw).with_context(|| format!("create {}", mailbox))?;
+                        existing.insert(raw);
+                    }
+                }
+                session.select(source).with_context(|| format!("select {}", source))?;
+                let mut uids: Vec<u32> = Vec::new();
+                for id in ids {
+                    let query = format!("HEADER Message-ID {}", quote_imap(&format!("<{}>", id)));
+                    uids.extend(session.uid_search(query)?);
+                }
+                if uids.is_empty() {
+                    return Ok(0);
+                }
+                let uid_set = sequence_set(&uids);
+                let raw_target = encode_imap_utf7(target);
+                if can_move {
+                    session.uid_mv(&uid_set, &raw_target)?;
+                } else {
+                    // Only the copied UIDs are expunged (UIDPLUS): a plain EXPUNGE
+                    // would also remove messages other clients flagged.
+                    session.uid_copy(&uid_set, &raw_target)?;
+                    session.uid_store(&uid_set, "+FLAGS (\\Deleted)")?;
+                    if can_uid_expunge {
+                        session.uid_expunge(&uid_set)?;
+                    } else {
+                        println!(
+                            "  mirror_routing: server lacks UIDPLUS — {} message(s) copied to {} stay flagged \\Deleted in {} until a mail client expunges it",
+                            uids.len(), target, decode_imap_utf7(source)
+                        );
+                    }
+                }
+                Ok(uids.len())
+            })();
+            match result {
+                Ok(moved) => {
+                    report.moved += moved;
+                    report.skipped += ids.len().saturating_sub(moved);
+                }
+                Err(e) => {
+                    eprintln!("Warning: could not file messages into {}: {:#}", target, e);
+                    report.errors += ids.len();
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Phase 2 of `delete_after_export` (see `deletion`): flag `\Deleted` and
+    /// `UID EXPUNGE` the account's pending messages whose note is verified at its
+    /// final path, and append them to the deletion journal. `notes_dir` is the
+    /// routing root (`settings.notes_dir`); notes outside it wait for routing.
+    /// Pending records may come from a `folders:` override, so callers run
+    /// this whatever the account-level `delete_after_export` says.
+    pub fn delete_verified(&mut self, notes_dir: Option<&Path>) -> Result<DeletionReport> {
+        let pending_path = deletion::pending_path();
+        let mut report = DeletionReport::default();
+        let (mine, mut kept): (Vec<PendingDeletion>, Vec<PendingDeletion>) = deletion::load_pending(&pending_path)?
+            .into_iter()
+            .partition(|p| p.account == self.account.name);
+        if mine.is_empty() {
+            return Ok(report);
+        }
+
+        // Verified deletions grouped by folder and UIDVALIDITY
+        let mut ready: BTreeMap<(String, Option<u32>), Vec<PendingDeletion>> = BTreeMap::new();
+        for p in mine {
+            match deletion::verify(&p, notes_dir) {
+                Verification::Ready => ready.entry((p.folder.clone(), p.uid_validity)).or_default().push(p),
+                Verification::AwaitingRouting => {
+                    report.waiting += 1;
+                    kept.push(p);
+                }
+                Verification::Rejected(reason) => {
+                    eprintln!("Warning: message kept on the server (uid {} in {}): {}", p.uid, decode_imap_utf7(&p.folder), reason);
+                    report.rejected += 1;
+                }
+            }
+        }
+        if ready.is_empty() {
+            deletion::save_pending(&pending_path, &kept)?;
+            return Ok(report);
+        }
+
+        let journal = deletion::journal_path();
+        {
+            let session = self.session.as_mut().context("Not connected")?;
+            // Without UIDPLUS only the flag is set: a plain EXPUNGE would also
+            // remove messages flagged `\Deleted` by other clients
+            let expunge = if self.is_gmail {
+                Expunge::Gmail
+            } else if session.capabilities()?.has_str("UIDPLUS") {
+                Expunge::Uid
+            } else {
+                Expunge::FlaggedOnly
+            };
+            for ((folder, uid_validity), records) in ready {
+                let result = (|| -> Result<HashSet<u32>> {
+                    let mailbox = session.select(&folder).with_context(|| format!("select {}", folder))?;
+                    if uid_validity.is_some() && mailbox.uid_validity != uid_validity {
+                        anyhow::bail!("UIDVALIDITY changed, UIDs no longer name the exported messages");
+                    }
+                    let uids: Vec<u32> = records.iter().map(|r| r.uid).collect();
+                    let uid_set = sequence_set(&uids);
+                    session.uid_store(&uid_set, "+FLAGS (\\Deleted)")?;
+                    // UIDs missing from the folder (moved or expunged elsewhere)
+                    // were not flagged: only the others are deleted
+                    let flagged: HashSet<u32> = session
+                        .uid_fetch(&uid_set, "FLAGS")
+                        .with_context(|| format!("fetch flags in {}", folder))?
+                        .iter()
+                        .filter(|m| m.flags().contains(&Flag::Deleted))
+                        .filter_map(|m| m.uid)
+                        .collect();
+                    if expunge == Expunge::Uid && !flagged.is_empty() {
+                        let flagged_uids: Vec<u32> = flagged.iter().copied().collect();
+                        session
+                            .uid_expunge(sequence_set(&flagged_uids))
+                            .with_context(|| format!("uid expunge {}", folder))?;
+                    }
+                    Ok(flagged)
+                })();
+                match result {
+                    Ok(flagged) => {
+                        let (records, gone): (Vec<PendingDeletion>, Vec<PendingDeletion>) =
+                            records.into_iter().partition(|r| flagged.contains(&r.uid));
+                        if !gone.is_empty() {
+                            eprintln!(
+                                "Warning: {} message(s) no longer in {}, not deleted: {}",
+                                gone.len(),
+                                decode_imap_utf7(&folder),
+                                gone.iter().map(|r| r.uid.to_string()).collect::<Vec<_>>().join(", ")
+                            );
+                            report.gone += gone.len();
+                        }
+                        let now = chrono::Utc::now();
+                        let entries: Vec<DeletionRecord> = records
+                            .iter()
+                            .map(|r| DeletionRecord {
+                                account: r.account.clone(),
+                                folder: decode_imap_utf7(&r.folder),
+                                uid: r.uid,
+                                message_id: r.message_id.clone(),
+                                note_path: r.note_path.clone(),
+                                deleted_at: now,
+                                expunge,
+                            })
+                            .collect();
+                        deletion::append_journal(&journal, &entries)?;
+                        report.deleted += records.len();
+                        if expunge == Expunge::FlaggedOnly {
+                            report.flagged_only += records.len();
+                        }
+                    }
+                    Err(e) => {
+                        // Kept pending, retried on the next run
+                        eprintln!("Warning: could not delete messages in {}: {:#}", decode_imap_utf7(&folder), e);
+                        kept.extend(records);
+                    }
+                }
+            }
+        }
+        deletion::save_pending(&pending_path, &kept)?;
+
+        // Gmail only removes a message from every label when expunged from All Mail
+        if self.is_gmail && report.deleted > 0 {
+            self.expunge_gmail_all_mail().context("gmail all mail expunge")?;
+        }
+        Ok(report)
+    }
+
+    /// Raw name of `mailbox` (decoded UTF-8), created when the server does
+    /// not list it yet.
+    pub fn ensure_mailbox(&mut self, mailbox: &str) -> Result<String> {
+        let session = self.session.as_mut().context("Not connected")?;
+        let raw = encode_imap_utf7(mailbox);
+        let exists = session
+            .list(None, Some(&quote_imap(&raw)))
+            .context("list mailboxes")?
+            .iter()
+            .any(|n| n.name() == raw);
+        if !exists {
+            session.create(&raw).with_context(|| format!("create {}", mailbox))?;
+        }
+        Ok(raw)
+    }
+
+    /// `APPEND` a message to `raw_mailbox` with its internal date and flags.
+    pub fn append_message(&mut self, raw_mailbox: &str, message: &RebuiltMessage) -> Result<()> {
+        let session = self.session.as_mut().context("Not connected")?;
+        let mut append = session.append(raw_mailbox, &message.bytes);
+        append.flags(
+            message
+                .flags
+                .iter()
+                .map(|f| Flag::from(f.as_str()))
+                .filter(|f| !matches!(f, Flag::Recent | Flag::Deleted)),
+        );
+        if let Some(date) = message.date {
+            append.internal_date(date);
+        }
+        append.finish().with_context(|| format!("append to {}", decode_imap_utf7(raw_mailbox)))?;
+        Ok(())
+    }
+
+    /// Export all folders for the account.
+    ///
     /// Returns `(folder_stats, decisions)` where `decisions` accumulates all
     /// `(staging_path, RouteDecision)` pairs produced during the export.
     /// The `.md` files remain in staging (D6 — deferred move); the caller is
@@ -1160,17 +2061,30 @@ impl ImapExporter {
             } else {
                 None
             };
+            let history_path = SignatureHistory::path();
+            let mut signature_history = self
+                .account
+                .signatures
+                .strip
+                .then(|| SignatureHistory::load(&history_path));
 
             let folders = self.list_folders()?;
             let total_folders = folders.len();
             let mut folder_index = 0usize;
 
             for folder in folders {
-                // Skip ignored folders (matched against the decoded display name)
-                if self.account.ignored_folders.contains(&folder.display) {
+                // Skip ignored / non-included / excluded folders (decoded display name)
+                if !folder_selected(&self.account, &folder) {
                     println!("Ignored folder: {}", folder.display);
                     continue;
                 }
+                // Effective settings for this folder (`folders:` overrides)
+                let folder_account = account_for_folder(&self.account, &folder);
+                // Overlapping patterns are only checked one by one at load time
+                if let Err(e) = validate_server_actions(&folder_account) {
+                    eprintln!("Skipping {}: {}", folder.display, e);
+                    continue;
+                }
 
                 folder_index += 1;
                 if let Some(cb) = on_progress {
@@ -1181,14 +2095,16 @@ impl ImapExporter {
 
                 let (stats, folder_decisions) = self.export_folder(
                     &folder,
+                    &folder_account,
                     contacts_collector.as_mut(),
+                    signature_history.as_mut(),
                     cancel_token,
                     &dests,
                 )?;
                 if let Some(s) = on_status {
                     s(&format!(
-                        "{} — {} exportés, {} ignorés, {} erreurs",
-                        folder.display, stats.exported, stats.skipped, stats.errors
+                        "{} — {} exportés, {} ignorés, {} erreurs, {} en quarantaine",
+                        folder.display, stats.exported, stats.skipped, stats.errors, stats.quarantined
                     ));
                 }
                 all_decisions.extend(folder_decisions);
@@ -1199,14 +2115,13 @@ impl ImapExporter {
                 }
             }
 
+            if let Some(history) = &signature_history {
+                history.save(&history_path)?;
+            }
+
             // Generate contacts file if enabled — centralized in _local/contacts/
             if let Some(collector) = contacts_collector {
-                let export_dir = PathBuf::from(&self.account.export_directory);
-                let contacts_dir = export_dir
-                    .parent()
-                    .unwrap_or(&export_dir)
-                    .join("_local")
-                    .join("contacts");
+                let contacts_dir = local_dir(&self.account.export_directory).join("contacts");
                 fs::create_dir_all(&contacts_dir)?;
                 let filepath = collector.generate_csv(&contacts_dir, &self.account.name)?;
                 println!("Generated contacts file: {}", filepath.display());
@@ -1224,6 +2139,60 @@ impl ImapExporter {
         run_result
     }
 
+    /// `export --dry-run`: the plan of what `export_account` would do (see
+    /// `plan`). Folders are opened with `EXAMINE` and only headers fetched;
+    /// nothing is written locally or changed on the server.
+    pub fn plan_account(&mut self) -> Result<Vec<PlanEntry>> {
+        let dests: Vec<Destination> = crate::route::load_destinations();
+        let mut entries = Vec::new();
+
+        for folder in self.list_folders()? {
+            if !folder_selected(&self.account, &folder) {
+                continue;
+            }
+            let account = account_for_folder(&self.account, &folder);
+            if let Err(e) = validate_server_actions(&account) {
+                eprintln!("Skipping {}: {}", folder.display, e);
+                continue;
+            }
+            let export_directory =
+                PathBuf::from(&account.export_directory).join(local_folder_path(&account, &folder));
+            let index = if account.skip_existing {
+                ExportIndex::scan(&export_directory, &account.field_keys)
+            } else {
+                ExportIndex::default()
+            };
+
+            let session = self.session.as_mut().context("Not connected")?;
+            let mailbox = session
+                .examine(&folder.raw)
+                .with_context(|| format!("examine {}", folder.display))?;
+            if mailbox.exists == 0 {
+                continue;
+            }
+            let searched: HashSet<u32> = session
+                .search(account.after_export.search_criteria(account.skip_existing))?
+                .into_iter()
+                .collect();
+            let headers = session
+                .fetch("1:*", "(UID RFC822.HEADER)")
+                .with_context(|| format!("fetch headers in {}", folder.display))?;
+            for message in headers.iter() {
+                let mut entry = plan_message(
+                    message.header().unwrap_or(&[]),
+                    searched.contains(&message.message),
+                    &index,
+                    &account,
+                    &folder.display,
+                    &dests,
+                );
+                entry.uid = message.uid;
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
     /// Disconnect from the server.
     pub fn disconnect(&mut self) -> Result<()> {
         if let Some(mut session) = self.session.take() {
@@ -1233,27 +2202,81 @@ impl ImapExporter {
     }
 }
 
+/// Whether a listed mailbox can be exported: selectable and not a special-use
+/// folder (junk, trash, drafts, all mail, flagged, important).
+fn is_exportable_mailbox(f: &imap::types::Name) -> bool {
+    let attrs = f.attributes();
+    !attrs.contains(&NameAttribute::NoSelect)
+        && !attrs.contains(&NameAttribute::Junk)
+        && !attrs.contains(&NameAttribute::Trash)
+        && !attrs.contains(&NameAttribute::Drafts)
+        && !attrs.contains(&NameAttribute::All)
+        && !attrs.contains(&NameAttribute::Flagged)
+        && !attrs.iter().any(|a| {
+            matches!(a, NameAttribute::Extension(s) if s.eq_ignore_ascii_case("Important"))
+        })
+        // Gmail does not always declare \Important via SPECIAL-USE — filter by known names
+        && !matches!(f.name(), "[Gmail]/Important" | "[Google Mail]/Important")
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ExportStats {
     pub exported: usize,
     pub skipped: usize,
     pub errors: usize,
+    /// Failed messages kept in `_failed/` (see `quarantine`).
+    pub quarantined: usize,
 }
 
 /// A mailbox name as returned by the IMAP `LIST` response.
 ///
 /// `raw` is the modified UTF-7 name as sent by the server and must be used
 /// for IMAP commands like `SELECT`. `display` is the decoded UTF-8 form used
-/// for local paths, logging and matching against `ignored_folders`.
+/// for logging and matching against `ignored_folders`. `delimiter` is the
+/// hierarchy delimiter from the same response (`None` for a flat namespace).
+/// `namespace` is set for folders of a shared or other-users namespace only.
 #[derive(Debug, Clone)]
 pub struct FolderName {
     pub raw: String,
     pub display: String,
+    pub delimiter: Option<String>,
+    pub namespace: Option<Namespace>,
+}
+
+impl FolderName {
+    /// Hierarchy levels of the decoded name, split on the server delimiter.
+    pub fn segments(&self) -> Vec<&str> {
+        match self.delimiter.as_deref().filter(|d| !d.is_empty()) {
+            Some(delimiter) => self.display.split(delimiter).collect(),
+            None => vec![self.display.as_str()],
+        }
+    }
+
+    /// The decoded name with levels joined by `/`, e.g. `INBOX.Clients` → `INBOX/Clients`.
+    pub fn path_form(&self) -> String {
+        self.segments().join("/")
+    }
+
+    /// Levels below the namespace prefix (all levels for a personal folder),
+    /// e.g. `Shared/support/INBOX` → `support`, `INBOX`.
+    pub fn relative_segments(&self) -> Vec<&str> {
+        let Some(namespace) = &self.namespace else {
+            return self.segments();
+        };
+        let prefix = decode_imap_utf7(&namespace.prefix);
+        let relative = self.display.strip_prefix(prefix.as_str()).unwrap_or(&self.display);
+        match self.delimiter.as_deref().filter(|d| !d.is_empty()) {
+            Some(delimiter) => relative.split(delimiter).filter(|s| !s.is_empty()).collect(),
+            None => vec![relative],
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::body::BodyPreference;
+    use crate::embedded::EmbeddedNotes;
 
     #[test]
     fn test_is_gmail_server_gmail() {
@@ -1317,16 +2340,100 @@ mod tests {
         let subfolder = temp.path().join("direct");
         fs::create_dir_all(&subfolder).unwrap();
 
-        let md_content = "---\nsubject_hash: abc123\ndate: 2024-01-15\n---\nBody";
-        fs::write(subfolder.join("email_2024-01-15_alice_to_bob_abc123.md"), md_content).unwrap();
+        // Legacy note: no message_id, arbitrary filename
+        let md_content = "---\nfrom: Alice <alice@example.com>\ndate: 2024-01-15T10:00:00+00:00\nsubject_hash: abc123\n---\nBody";
+        fs::write(subfolder.join("renamed by hand.md"), md_content).unwrap();
 
+        let date = DateTime::parse_from_rfc3339("2024-01-15T18:30:00+00:00").ok();
+        // Inclusive: same day, sender and subject hash — even with a Message-ID
         assert!(email_already_exported(
-            "2024-01-15",
-            "alice",
-            "bob",
-            "abc123",
+            &EmailKey::new(Some("<new@example.com>"), date, "alice@example.com", "abc123"),
             temp.path(),
+            &FieldKeys::builtin(),
         ));
+        // Exclusive: another sender is a different message
+        assert!(!email_already_exported(
+            &EmailKey::new(None, date, "carol@example.com", "abc123"),
+            temp.path(),
+            &FieldKeys::builtin(),
+        ));
+    }
+
+    #[test]
+    fn test_export_index_prefers_message_id() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let md_content = "---\nfrom: alice@example.com\ndate: 2024-01-15T10:00:00+00:00\nsubject: Hi\nmessage_id: one@example.com\nsubject_hash: abc123\n---\nBody";
+        fs::write(temp.path().join("a.md"), md_content).unwrap();
+        let index = ExportIndex::scan(temp.path(), &FieldKeys::builtin());
+        let date = DateTime::parse_from_rfc3339("2024-01-15T10:00:00+00:00").ok();
+
+        // Inclusive: same Message-ID, angle brackets ignored
+        assert!(index.contains(&EmailKey::new(Some("<one@example.com>"), None, "", "")));
+        // Exclusive: same day/sender/subject but a different Message-ID
+        assert!(!index.contains(&EmailKey::new(Some("<two@example.com>"), date, "alice@example.com", "abc123")));
+        // Inclusive: message without Message-ID falls back to the header key
+        assert!(index.contains(&EmailKey::new(None, date, "alice@example.com", "abc123")));
+        // The note is found again by Message-ID (for delete_after_export)
+        assert_eq!(index.note_path("one@example.com"), Some(temp.path().join("a.md").as_path()));
+        assert_eq!(index.note_path("two@example.com"), None);
+    }
+
+    #[test]
+    fn test_export_index_reads_renamed_keys_passed_in() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let md_content = "---\nfrom: alice@example.com\nmsgid: one@example.com\n---\nBody";
+        fs::write(temp.path().join("a.md"), md_content).unwrap();
+        let key = EmailKey::new(Some("<one@example.com>"), None, "", "");
+
+        // Exclusive: `msgid` is unknown to the built-in keys
+        assert!(!ExportIndex::scan(temp.path(), &FieldKeys::builtin()).contains(&key));
+        // Inclusive: the caller's keys carry the rename
+        let mut keys = FieldKeys::builtin();
+        let rename = BTreeMap::from([("message_id".to_string(), "msgid".to_string())]);
+        keys.add_renames(&rename);
+        assert!(ExportIndex::scan(temp.path(), &keys).contains(&key));
+    }
+
+    #[test]
+    fn test_plan_message_decisions() {
+        use crate::route::MatchRule;
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let md_content = "---\nfrom: alice@example.com\ndate: 2024-01-15T10:00:00+00:00\nsubject: Hi\nmessage_id: one@example.com\nsubject_hash: abc123\n---\nBody";
+        fs::write(temp.path().join("a.md"), md_content).unwrap();
+        let index = ExportIndex::scan(temp.path(), &FieldKeys::builtin());
+        let mut account = make_account(temp.path().to_str().unwrap());
+        account.skip_existing = true;
+        account.delete_after_export = true;
+        let dests = vec![Destination {
+            path: "Perso/Banque".to_string(),
+            rules: vec![MatchRule::Domain("bank.example".to_string())],
+            is_default: false,
+        }];
+        let headers = |id: &str, from: &str| {
+            format!("From: {from}\r\nSubject: Relevé\r\nDate: Mon, 01 Jun 2026 10:00:00 +0200\r\nMessage-ID: <{id}>\r\n\r\n").into_bytes()
+        };
+
+        let new = plan_message(&headers("two@example.com", "Bank <info@bank.example>"), true, &index, &account, "INBOX", &dests);
+        assert_eq!(new.action, PlanAction::Export);
+        assert_eq!(new.route.as_deref(), Some("Perso/Banque/2026/06"));
+        assert!(new.rule.is_some());
+        assert!(new.delete);
+
+        let known = plan_message(&headers("one@example.com", "alice@example.com"), true, &index, &account, "INBOX", &dests);
+        assert_eq!(known.action, PlanAction::AlreadyExported);
+        assert_eq!(known.route, None);
+        assert!(known.delete, "existing note found again: deleted once verified");
+
+        // Exclusive: filtered by the keyword search, never deleted
+        let kept = plan_message(&headers("three@example.com", "bob@example.com"), false, &index, &account, "INBOX", &dests);
+        assert_eq!(kept.action, PlanAction::KeywordSkipped);
+        assert!(!kept.delete);
     }
 
     #[test]
@@ -1384,7 +2491,7 @@ mod tests {
     fn test_extract_body_prefers_text_plain_over_html() {
         let raw = make_multipart_email("Hello plain text", "<p>Hello HTML</p>");
         let mail = mailparse::parse_mail(&raw).unwrap();
-        let body = extract_body(&mail);
+        let body = extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default());
 
         // Inclusive: must contain the plain-text content
         assert!(body.contains("Hello plain text"), "body should contain plain text: got {:?}", body);
@@ -1399,7 +2506,7 @@ mod tests {
             "From: a@b.com\r\nTo: c@d.com\r\nSubject: S\r\nDate: Mon, 01 Jan 2024 12:00:00 +0000\r\nContent-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n--{boundary}\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>Only HTML body</p>\r\n--{boundary}--\r\n"
         ).into_bytes();
         let mail = mailparse::parse_mail(&raw).unwrap();
-        let body = extract_body(&mail);
+        let body = extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default());
 
         // Inclusive: HTML was converted — the text content should be present
         assert!(body.contains("Only HTML body"), "body should contain converted HTML text: got {:?}", body);
@@ -1413,7 +2520,7 @@ mod tests {
             "a@b.com", "c@d.com", "Simple", "text/plain; charset=utf-8", "Simple body content",
         );
         let mail = mailparse::parse_mail(&raw).unwrap();
-        let body = extract_body(&mail);
+        let body = extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default());
 
         assert!(body.contains("Simple body content"), "body should contain text: got {:?}", body);
         assert!(!body.contains("Content-Type"), "body should not contain header lines: got {:?}", body);
@@ -1428,7 +2535,7 @@ mod tests {
             "From: a@b.com\r\nTo: c@d.com\r\nSubject: Nested\r\nDate: Mon, 01 Jan 2024 12:00:00 +0000\r\nContent-Type: multipart/mixed; boundary=\"{outer_boundary}\"\r\n\r\n--{outer_boundary}\r\nContent-Type: multipart/alternative; boundary=\"{inner_boundary}\"\r\n\r\n--{inner_boundary}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nNested plain text body\r\n--{inner_boundary}\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>Nested HTML</p>\r\n--{inner_boundary}--\r\n--{outer_boundary}--\r\n"
         ).into_bytes();
         let mail = mailparse::parse_mail(&raw).unwrap();
-        let body = extract_body(&mail);
+        let body = extract_body(&mail, BodyPreference::Plain, &EmbeddedNotes::default());
 
         assert!(body.contains("Nested plain text body"), "nested body should be extracted: got {:?}", body);
         assert!(!body.contains("<p>"), "should not contain raw HTML in nested case: got {:?}", body);
@@ -1451,6 +2558,27 @@ mod tests {
             skip_signature_images: false,
             delete_after_export: false,
             cleanup_empty_dirs: false,
+            filename_template: crate::filename::DEFAULT_FILENAME_TEMPLATE.to_string(),
+            filename_policy: Default::default(),
+            frontmatter: Default::default(),
+            field_keys: crate::frontmatter::FieldKeys::builtin(),
+            notes_dir: None,
+            output_format: Default::default(),
+            body_preference: Default::default(),
+            crypto: Default::default(),
+            signatures: Default::default(),
+            quoted_history: Default::default(),
+            cleaner: Default::default(),
+            skip_attachments: false,
+            keep_raw: false,
+            include_folders: vec![],
+            exclude_folders: vec![],
+            folder_overrides: Default::default(),
+            folder_map: Default::default(),
+            subscribed_only: false,
+            namespaces: Default::default(),
+            after_export: Default::default(),
+            mirror_routing: Default::default(),
         }
     }
 
@@ -1476,6 +2604,10 @@ mod tests {
             account: &account,
             debug_mode: false,
             dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
         };
         let result = export_to_markdown(
             &raw,
@@ -1497,6 +2629,386 @@ mod tests {
         assert!(content.contains("Test body"), "body should appear after frontmatter");
     }
 
+    #[test]
+    fn test_export_to_markdown_flags_degraded_message() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let account = make_account(&export_dir.to_string_lossy());
+        // Broken part header: unparseable even after repair
+        let raw = b"From: Alice <alice@example.com>\r\nTo: bob@example.com\r\nSubject: Vieux mail\r\n\
+                    Date: Mon, 01 Jan 2024 12:00:00 +0000\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+                    --b\r\n bad part header\r\n\r\ncorps d'origine\r\n--b--\r\n";
+
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(raw, vec![], None, &mut ctx).unwrap().expect("exported");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("parse_degraded: true"));
+        assert!(content.contains("subject: Vieux mail"));
+        assert!(content.contains("corps d'origine"));
+
+        // Exclusive: a well-formed message carries no flag
+        let raw = make_raw_email("a@example.com", "b@example.com", "Propre", "text/plain", "ok");
+        let (path, _) = export_to_markdown(&raw, vec![], None, &mut ctx).unwrap().expect("exported");
+        assert!(!fs::read_to_string(&path).unwrap().contains("parse_degraded"));
+    }
+
+    #[test]
+    fn test_export_to_markdown_resolves_cid_images() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let mut account = make_account(&export_dir.to_string_lossy());
+        account.skip_signature_images = true;
+        account.body_preference = BodyPreference::Html;
+
+        // A 20 KB screenshot (no name, no disposition) and a small signature logo
+        let shot = "A".repeat(20 * 1024);
+        let raw = format!(
+            "From: a@example.com\r\nSubject: Bug\r\nDate: Mon, 02 Mar 2026 10:00:00 +0000\r\n\
+             Content-Type: multipart/related; boundary=R\r\n\r\n\
+             --R\r\nContent-Type: text/html\r\n\r\n\
+             <p>Voici l'erreur :</p><img src=\"cid:shot@x\" alt=\"Erreur\"><p>--</p><img src=\"cid:logo@x\" alt=\"logo\">\r\n\
+             --R\r\nContent-Type: image/png\r\nContent-ID: <shot@x>\r\n\r\n{shot}\r\n\
+             --R\r\nContent-Type: image/png; name=logo.png\r\nContent-Disposition: inline; filename=logo.png\r\n\
+             Content-ID: <logo@x>\r\n\r\nPNG\r\n--R--\r\n"
+        );
+
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(raw.as_bytes(), vec![], None, &mut ctx).unwrap().expect("exported");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("![Erreur](2026-03-02_shot.png)"), "got {:?}", content);
+        assert!(export_dir.join("2026-03-02_shot.png").exists());
+        // Exclusive: the signature logo is skipped and its embed removed
+        assert!(!export_dir.join("2026-03-02_logo.png").exists());
+        assert!(!content.contains("cid:"));
+    }
+
+    #[test]
+    fn test_export_to_markdown_invitation() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let account = make_account(&export_dir.to_string_lossy());
+
+        let ics = "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\n\
+                   SUMMARY:Revue de projet\r\nDTSTART:20260310T140000Z\r\nDTEND:20260310T150000Z\r\n\
+                   LOCATION:Salle B\r\nORGANIZER;CN=Alice:mailto:alice@example.com\r\n\
+                   ATTENDEE;CN=Bob:mailto:bob@example.com\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let raw = format!(
+            "From: alice@example.com\r\nSubject: Invitation\r\nDate: Mon, 02 Mar 2026 10:00:00 +0000\r\n\
+             Content-Type: multipart/mixed; boundary=M\r\n\r\n\
+             --M\r\nContent-Type: multipart/alternative; boundary=A\r\n\r\n\
+             --A\r\nContent-Type: text/plain\r\n\r\nOrdre du jour en PJ\r\n\
+             --A\r\nContent-Type: text/calendar; method=REQUEST\r\n\r\n{ics}\
+             --A--\r\n\
+             --M\r\nContent-Type: application/ics; name=invite.ics\r\n\
+             Content-Disposition: attachment; filename=invite.ics\r\n\r\n{ics}\
+             --M--\r\n"
+        );
+
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(raw.as_bytes(), vec![], None, &mut ctx).unwrap().expect("exported");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("email_type: invitation"), "got {:?}", content);
+        assert!(content.contains("event_start:"));
+        assert!(content.contains("organizer: Alice <alice@example.com>"));
+        assert!(content.contains("### Invitation : Revue de projet"));
+        assert!(content.contains("Ordre du jour en PJ"));
+        // One `.ics` file per note: the attached copy of the invite is not duplicated
+        assert!(export_dir.join("2026-03-02_invitation.ics").exists());
+        assert!(!export_dir.join("2026-03-02_invite.ics").exists());
+    }
+
+    #[test]
+    fn test_export_to_markdown_decodes_winmail_dat() {
+        use base64::Engine;
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let account = make_account(&export_dir.to_string_lossy());
+
+        // TNEF stream: uncompressed RTF body (`MELA`) and one attached file
+        let attr = |level: u8, id: u32, value: &[u8]| {
+            let mut out = vec![level];
+            out.extend(id.to_le_bytes());
+            out.extend((value.len() as u32).to_le_bytes());
+            out.extend(value);
+            out.extend([0, 0]);
+            out
+        };
+        let rtf = b"{\\rtf1\\ansi Bonjour,\\par voir le rapport.}";
+        let mut compressed = ((rtf.len() + 12) as u32).to_le_bytes().to_vec();
+        compressed.extend((rtf.len() as u32).to_le_bytes());
+        compressed.extend(b"MELA");
+        compressed.extend([0; 4]);
+        compressed.extend(rtf);
+        compressed.resize(compressed.len() + (4 - compressed.len() % 4) % 4, 0);
+        let mut props = vec![1, 0, 0, 0, 0x02, 0x01, 0x09, 0x10, 1, 0, 0, 0];
+        props.extend(((rtf.len() + 16) as u32).to_le_bytes());
+        props.extend(&compressed);
+        let mut stream = vec![0x78, 0x9F, 0x3E, 0x22, 1, 0];
+        stream.extend(attr(1, 0x0006_9003, &props));
+        stream.extend(attr(2, 0x0006_9002, &[0; 14]));
+        stream.extend(attr(2, 0x0001_8010, b"Rapport.pdf\0"));
+        stream.extend(attr(2, 0x0006_800F, b"%PDF-1.4 rapport"));
+        let winmail = base64::engine::general_purpose::STANDARD.encode(&stream);
+
+        let raw = format!(
+            "From: a@example.com\r\nSubject: Rapport\r\nDate: Mon, 02 Mar 2026 10:00:00 +0000\r\n\
+             Content-Type: multipart/mixed; boundary=M\r\n\r\n\
+             --M\r\nContent-Type: application/ms-tnef; name=winmail.dat\r\n\
+             Content-Disposition: attachment; filename=winmail.dat\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n{winmail}\r\n--M--\r\n"
+        );
+
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(raw.as_bytes(), vec![], None, &mut ctx).unwrap().expect("exported");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("Bonjour,\nvoir le rapport."), "got {:?}", content);
+        assert_eq!(fs::read(export_dir.join("2026-03-02_Rapport.pdf")).unwrap(), b"%PDF-1.4 rapport");
+        // Exclusive: the decoded stream itself is not kept
+        assert!(!export_dir.join("2026-03-02_winmail.dat").exists());
+    }
+
+    #[test]
+    fn test_export_to_markdown_repairs_charset() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let account = make_account(&export_dir.to_string_lossy());
+
+        // Subject encoded twice, body declared UTF-8 but sent as Windows-1252
+        let mut raw = b"From: a@example.com\r\nSubject: =?utf-8?Q?R=C3=83=C2=A9union_d=C3=A2=E2=82=AC=E2=84=A2=C3=83=C2=A9quipe?=\r\n\
+            Date: Mon, 02 Mar 2026 10:00:00 +0000\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n"
+            .to_vec();
+        raw.extend(b"Ordre du jour : bilan de l'ann\xe9e, r\xe9sum\xe9 \x80\r\n");
+
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(&raw, vec![], None, &mut ctx).unwrap().expect("exported");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("subject: Réunion d’équipe"), "got {:?}", content);
+        assert!(content.contains("bilan de l'année, résumé €"), "got {:?}", content);
+        assert!(content.contains("charset_repaired: true"));
+    }
+
+    #[test]
+    fn test_export_to_markdown_strips_signature_block() {
+        use crate::signatures::{SignatureKeep, SignatureSettings};
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("mail").join("perso");
+        let mut account = make_account(&export_dir.to_string_lossy());
+        account.signatures = SignatureSettings { strip: true, keep: SignatureKeep::Frontmatter, ..Default::default() };
+
+        let raw = |subject: &str, text: &str| {
+            format!(
+                "From: Jean Dupont <jean@acme.example>\r\nSubject: {subject}\r\nDate: Mon, 02 Mar 2026 10:00:00 +0000\r\n\
+                 Content-Type: text/plain; charset=utf-8\r\n\r\n{text}\r\n\r\nJean Dupont\r\nACME SAS\r\n\
+                 +33 1 23 45 67 89\r\n"
+            )
+        };
+        let mut history = SignatureHistory::default();
+        let mut export = |account: &Account, subject: &str, text: &str| {
+            let mut ctx = ExportContext {
+                export_directory: &export_dir,
+                base_export_directory: &export_dir,
+                account,
+                debug_mode: false,
+                dests: &[],
+                folder: "INBOX",
+                index: None,
+                flags: &[],
+                signatures: Some(&mut history),
+            };
+            let (path, _) = export_to_markdown(raw(subject, text).as_bytes(), vec![], None, &mut ctx)
+                .unwrap()
+                .expect("exported");
+            fs::read_to_string(path).unwrap()
+        };
+
+        // First message: nothing to compare with yet
+        let first = export(&account, "Devis", "Voici le devis.");
+        assert!(first.contains("ACME SAS") && !first.contains("signature_block"));
+
+        let second = export(&account, "Facture", "Voici la facture.");
+        let body = second.split("\n---\n").nth(1).unwrap();
+        assert!(body.contains("Voici la facture.") && !body.contains("ACME SAS"), "got {:?}", second);
+        assert!(second.contains("signature_block: |-\n  Jean Dupont\n  ACME SAS\n  +33 1 23 45 67 89\n"), "got {:?}", second);
+
+        // Kept in the sender's contact note instead
+        account.signatures.keep = SignatureKeep::Contact;
+        let third = export(&account, "Relance", "Voici la relance.");
+        assert!(!third.contains("signature_block") && !third.contains("ACME SAS"));
+        let note = fs::read_to_string(temp.path().join("mail/_local/signatures/jean@acme.example.md")).unwrap();
+        assert!(note.starts_with("---\ncontact: jean@acme.example\nupdated: 2026-03-02\n---\nJean Dupont\nACME SAS"));
+    }
+
+    #[test]
+    fn test_export_to_markdown_folds_quoted_history() {
+        use crate::cleaner::QuotedHistory;
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let mut account = make_account(&export_dir.to_string_lossy());
+        account.quoted_history = QuotedHistory::Fold;
+
+        let reply = |id: &str, in_reply_to: &str| {
+            format!(
+                "From: bob@example.com\r\nSubject: RE: Devis\r\nDate: Tue, 04 Jun 2026 09:00:00 +0000\r\n\
+                 Message-ID: <{id}>\r\nIn-Reply-To: <{in_reply_to}>\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n\
+                 C'est validé.\r\n\r\nLe lun. 3 juin 2026 à 10:00, Alice <alice@example.com> a écrit :\r\n\
+                 > Voici le devis.\r\n"
+            )
+        };
+        let mut index = ExportIndex::default();
+        let mut export = |account: &Account, raw: &str| {
+            let mut ctx = ExportContext {
+                export_directory: &export_dir,
+                base_export_directory: temp.path(),
+                account,
+                debug_mode: false,
+                dests: &[],
+                folder: "INBOX",
+                index: Some(&mut index),
+                flags: &[],
+                signatures: None,
+            };
+            let (path, _) = export_to_markdown(raw.as_bytes(), vec![], None, &mut ctx).unwrap().expect("exported");
+            (path.file_stem().unwrap().to_string_lossy().into_owned(), fs::read_to_string(&path).unwrap())
+        };
+
+        // Earlier message unknown: folded under its attribution line
+        let (_, folded) = export(&account, &reply("r1@example.com", "devis@example.com"));
+        assert!(
+            folded.contains(
+                "C'est validé.\n\n<details>\n<summary>Le lun. 3 juin 2026 à 10:00, Alice &lt;alice@example.com&gt; a écrit :</summary>\n\n\
+                 Le lun. 3 juin 2026 à 10:00, Alice <alice@example.com> a écrit :\n> Voici le devis.\n\n</details>"
+            ),
+            "got {:?}",
+            folded
+        );
+
+        // Earlier message exported: linked instead
+        let original = "From: alice@example.com\r\nSubject: Devis\r\nDate: Mon, 03 Jun 2026 10:00:00 +0000\r\n\
+                        Message-ID: <devis@example.com>\r\n\r\nVoici le devis.\r\n";
+        let (stem, _) = export(&account, original);
+        account.quoted_history = QuotedHistory::Cut;
+        let (_, linked) = export(&account, &reply("r2@example.com", "devis@example.com"));
+        assert!(linked.contains(&format!("C'est validé.\n\n> [[{}|Le lun. 3 juin 2026 à 10:00, Alice <alice@example.com> a écrit :]]", stem)));
+        assert!(!linked.contains("> Voici le devis."));
+    }
+
+    #[test]
+    fn test_export_to_markdown_decrypts_pgp_mime() {
+        use std::process::Command;
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let home = temp.path().join("gnupg");
+        fs::create_dir(&home).unwrap();
+        let gpg = |args: &[&str]| Command::new("gpg").arg("--homedir").arg(&home).arg("--batch").args(args).output();
+        // GnuPG missing: nothing to check
+        let Ok(keygen) = gpg(&["--passphrase", "", "--quick-gen-key", "Alice <alice@example.com>", "default", "default", "never"])
+        else {
+            return;
+        };
+        assert!(keygen.status.success());
+
+        let clear = temp.path().join("clear");
+        fs::write(&clear, "Content-Type: text/plain\r\n\r\nLe code est 1234.\r\n").unwrap();
+        let clear = clear.to_string_lossy();
+        let encrypted = gpg(&["--armor", "--trust-model", "always", "-r", "alice@example.com", "--output", "-", "--encrypt", &clear])
+            .unwrap();
+        let encrypted = String::from_utf8(encrypted.stdout).unwrap();
+
+        let export_dir = temp.path().join("out");
+        let mut account = make_account(&export_dir.to_string_lossy());
+        account.crypto.gnupg_home = Some(home.to_string_lossy().into_owned());
+        let raw = format!(
+            "From: alice@example.com\r\nSubject: Code\r\nDate: Mon, 02 Mar 2026 10:00:00 +0000\r\n\
+             Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=E\r\n\r\n\
+             --E\r\nContent-Type: application/pgp-encrypted\r\n\r\nVersion: 1\r\n\
+             --E\r\nContent-Type: application/octet-stream; name=encrypted.asc\r\n\
+             Content-Disposition: inline; filename=encrypted.asc\r\n\r\n{encrypted}\r\n--E--\r\n"
+        );
+
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(raw.as_bytes(), vec![], None, &mut ctx).unwrap().expect("exported");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("Le code est 1234."), "got {:?}", content);
+        assert!(content.contains("subject: Code"));
+        // Exclusive: the encrypted payload is no longer an attachment
+        assert!(!export_dir.join("2026-03-02_encrypted.asc").exists());
+    }
+
     #[test]
     fn test_export_to_markdown_names_attachment_with_date_prefix() {
         use tempfile::TempDir;
@@ -1517,6 +3029,10 @@ mod tests {
             account: &account,
             debug_mode: false,
             dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
         };
         let (md_path, _decision) = export_to_markdown(&raw, vec![], None, &mut ctx)
             .unwrap()
@@ -1563,6 +3079,10 @@ mod tests {
             account: &account,
             debug_mode: false,
             dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
         };
         let (first_path, _decision) = export_to_markdown(&raw, vec![], None, &mut ctx)
             .unwrap()
@@ -1575,6 +3095,88 @@ mod tests {
         // Second export — should be skipped
         let second = export_to_markdown(&raw, vec![], None, &mut ctx).unwrap();
         assert!(second.is_none(), "second export should return None when skip_existing is true");
+
+        // Changing the filename template must not defeat duplicate detection
+        let renamed = crate::config::Account {
+            filename_template: "{subject_slug}".to_string(),
+            ..account.clone()
+        };
+        let mut ctx = ExportContext { account: &renamed, ..ctx };
+        let third = export_to_markdown(&raw, vec![], None, &mut ctx).unwrap();
+        assert!(third.is_none(), "template change must not re-export an existing note");
+    }
+
+    #[test]
+    fn test_export_to_markdown_uses_filename_template() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let mut account = make_account(&export_dir.to_string_lossy());
+        account.filename_template = "{date:%Y%m%d}_{from_domain}_{subject_slug:30}".to_string();
+
+        let raw = make_raw_email(
+            "Alice <alice@example.com>",
+            "bob@example.com",
+            "Re: Réunion: ordre du jour",
+            "text/plain; charset=utf-8",
+            "Body",
+        );
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(&raw, vec![], None, &mut ctx).unwrap().unwrap();
+
+        // Inclusive: placeholders rendered, reply prefix dropped
+        assert_eq!(
+            path.file_name().unwrap().to_string_lossy(),
+            "20240101_example.com_réunion-ordre-du-jour.md"
+        );
+        // Inclusive: Message-ID-less message still gets a subject_hash key
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("subject_hash:"));
+    }
+
+    #[test]
+    fn test_export_to_markdown_html_format_keeps_html_part_and_skips_duplicates() {
+        use tempfile::TempDir;
+
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("out");
+        let mut account = make_account(&export_dir.to_string_lossy());
+        account.output_format = OutputFormat::Html;
+        account.skip_existing = true;
+
+        let raw = make_multipart_email("Plain body", "<p><b>Rich</b> body</p><script>x()</script>");
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: temp.path(),
+            account: &account,
+            debug_mode: false,
+            dests: &[],
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (path, _) = export_to_markdown(&raw, vec![], None, &mut ctx).unwrap().unwrap();
+
+        // Inclusive: .html note with the sanitized original HTML part
+        assert_eq!(path.extension().unwrap(), "html");
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("<p><b>Rich</b> body</p>"), "{}", content);
+        // Exclusive: script dropped
+        assert!(!content.contains("x()"));
+        // Duplicate detection reads the HTML metadata block
+        assert!(export_to_markdown(&raw, vec![], None, &mut ctx).unwrap().is_none());
     }
 
     // ── Phase 3 — fix_html_bodies / extract_md_body ─────────────────────────────
@@ -0,0 +1,76 @@
+//! Embedded messages (`message/rfc822`, e.g. forwarded as attachment).
+//!
+//! Each embedded message is exported as its own child note, next to its
+//! parent, with its own header, attachments and routing proposal (the
+//! original sender's, not the forwarder's). The parent lists the child note
+//! in `attachments:` and links it from its body, under the embedded message's
+//! headers; `route::apply_embedded` then files the child by its own decision.
+//! An embedded message that cannot be exported is kept as an `.eml` attachment.
+
+use mailparse::ParsedMail;
+use std::collections::HashMap;
+
+/// Child note file names of the embedded messages of a note.
+#[derive(Debug, Default)]
+pub struct EmbeddedNotes {
+    /// Digest of the part's raw bytes → bare file name of the child note.
+    files: HashMap<String, String>,
+}
+
+fn key(part: &ParsedMail) -> String {
+    format!("{:x}", md5::compute(part.raw_bytes))
+}
+
+impl EmbeddedNotes {
+    /// Record that the embedded message `part` was exported as `file`.
+    pub fn insert(&mut self, part: &ParsedMail, file: &str) {
+        self.files.insert(key(part), file.to_string());
+    }
+
+    /// Child note of the embedded message `part`, if it was exported.
+    pub fn file(&self, part: &ParsedMail) -> Option<&str> {
+        self.files.get(&key(part)).map(String::as_str)
+    }
+}
+
+pub fn is_embedded_message(part: &ParsedMail) -> bool {
+    part.ctype.mimetype.eq_ignore_ascii_case("message/rfc822")
+}
+
+/// Every `message/rfc822` part of `mail`, in document order. Messages nested
+/// in an embedded message are left to that message's own export.
+pub fn embedded_messages<'a, 'b>(mail: &'b ParsedMail<'a>) -> Vec<&'b ParsedMail<'a>> {
+    let mut found = Vec::new();
+    for part in &mail.subparts {
+        if is_embedded_message(part) {
+            found.push(part);
+        } else {
+            found.extend(embedded_messages(part));
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_messages_in_document_order() {
+        let raw = b"Content-Type: multipart/mixed; boundary=M\r\n\r\n\
+            --M\r\nContent-Type: text/plain\r\n\r\nVoir PJ\r\n\
+            --M\r\nContent-Type: message/rfc822\r\nContent-Disposition: attachment; filename=a.eml\r\n\r\n\
+            Subject: A\r\n\r\nfirst\r\n\
+            --M\r\nContent-Type: multipart/mixed; boundary=N\r\n\r\n\
+            --N\r\nContent-Type: message/rfc822\r\n\r\nSubject: B\r\n\r\nsecond\r\n--N--\r\n\
+            --M--\r\n";
+        let mail = mailparse::parse_mail(raw).unwrap();
+        let found = embedded_messages(&mail);
+        assert_eq!(found.len(), 2);
+
+        let mut notes = EmbeddedNotes::default();
+        notes.insert(found[1], "2026-03-02_B.md");
+        assert_eq!(notes.file(found[1]), Some("2026-03-02_B.md"));
+        assert_eq!(notes.file(found[0]), None);
+    }
+}
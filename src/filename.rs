@@ -0,0 +1,576 @@
+//! Note filename templates and the portable-name policy.
+//!
+//! A template such as `{date:%Y%m%d}_{from_domain}_{subject_slug:40}` is rendered
+//! from the message headers, then passed through [`FilenamePolicy`] so the result
+//! is valid on every platform the vault may be synced to (Windows reserved names,
+//! forbidden characters, path length, Unicode normalization form).
+//!
+//! Filenames carry no identity: duplicate detection reads the frontmatter
+//! (`message_id`, or `date`/`from`/`subject_hash` for older notes), so changing
+//! the template never re-exports an archive.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::utils::{extract_emails, get_short_name, hash_md5_prefix, subject_extract};
+
+/// Template reproducing the historical `email_<date>_<from>_<Extract>_to_<to>` scheme.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "email_{date}_{from_short}_{subject_extract}_to_{to_short}";
+
+/// Placeholders accepted inside `{…}`.
+pub const PLACEHOLDERS: &[&str] = &[
+    "date",
+    "from_name",
+    "from_addr",
+    "from_domain",
+    "from_short",
+    "to_name",
+    "to_short",
+    "subject",
+    "subject_slug",
+    "subject_extract",
+    "message_id_hash",
+    "account",
+    "folder",
+];
+
+/// Default length of `{subject_slug}` when no `:N` is given.
+const DEFAULT_SLUG_LEN: usize = 60;
+
+/// Longest single path component accepted by common filesystems (bytes).
+const MAX_COMPONENT_BYTES: usize = 255;
+
+/// Names Windows refuses as a file stem, whatever the extension.
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Shortest stem kept when the destination directory uses up `max_path_len`.
+const MIN_STEM_CHARS: usize = 12;
+
+/// Separators collapsed when a placeholder renders empty.
+const SEPARATORS: &[char] = &['_', '-', '.', ' '];
+
+static REPLY_PREFIX_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*(?:(?:re|fwd?|tr|aw|wg|rv)\s*(?:\[\d+\])?\s*:\s*)+").expect("static regex")
+});
+
+/// Unicode normalization form applied to generated names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeForm {
+    /// Composed form — what Windows and Linux tools expect.
+    #[default]
+    Nfc,
+    /// Decomposed form — what older macOS (HFS+) stores.
+    Nfd,
+    /// Keep the header bytes as decoded.
+    None,
+}
+
+/// Portable-name policy applied to every generated note filename.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilenamePolicy {
+    /// Unicode normalization form (`nfc`, `nfd` or `none`).
+    pub unicode_form: UnicodeForm,
+    /// Transliterate non-ASCII characters (`é` → `e`, `ß` → `ss`).
+    pub transliterate: bool,
+    /// Maximum length, in characters, of the full note path (directory included).
+    pub max_path_len: usize,
+}
+
+impl Default for FilenamePolicy {
+    fn default() -> Self {
+        FilenamePolicy {
+            unicode_form: UnicodeForm::Nfc,
+            transliterate: false,
+            // Below Windows' historical MAX_PATH (260) to leave room for attachments.
+            max_path_len: 240,
+        }
+    }
+}
+
+/// Header values a template can draw from.
+#[derive(Debug, Clone, Default)]
+pub struct NoteNameFields<'a> {
+    pub date: Option<DateTime<FixedOffset>>,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub subject: &'a str,
+    pub message_id: Option<&'a str>,
+    pub account: &'a str,
+    pub folder: &'a str,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder { name: String, arg: Option<String> },
+}
+
+/// Split a template into literal text and `{name[:arg]}` placeholders.
+/// `{{` and `}}` are literal braces.
+fn parse_template(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => inner.push(ch),
+                        None => return Err(format!("unclosed placeholder '{{{}'", inner)),
+                    }
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                let (name, arg) = match inner.split_once(':') {
+                    Some((n, a)) => (n.trim().to_string(), Some(a.to_string())),
+                    None => (inner.trim().to_string(), None),
+                };
+                segments.push(Segment::Placeholder { name, arg });
+            }
+            '}' => return Err("unmatched '}' (use '}}' for a literal brace)".to_string()),
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+/// Check a template for syntax errors, unknown placeholders and bad arguments.
+pub fn validate_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("filename_template cannot be empty".to_string());
+    }
+    for segment in parse_template(template)? {
+        let Segment::Placeholder { name, arg } = segment else {
+            continue;
+        };
+        if !PLACEHOLDERS.contains(&name.as_str()) {
+            return Err(format!(
+                "unknown placeholder '{{{}}}' (known: {})",
+                name,
+                PLACEHOLDERS.join(", ")
+            ));
+        }
+        match (name.as_str(), arg) {
+            (_, None) => {}
+            ("date", Some(fmt)) => {
+                if StrftimeItems::new(&fmt).any(|i| matches!(i, Item::Error)) {
+                    return Err(format!("invalid date format '{}' in '{{date:…}}'", fmt));
+                }
+            }
+            (_, Some(len)) => {
+                if !matches!(len.trim().parse::<usize>(), Ok(n) if n > 0) {
+                    return Err(format!(
+                        "'{{{}:{}}}': the argument must be a positive length",
+                        name, len
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Render `template` from `fields`. The result is not yet portable — pass it
+/// through [`portable_stem`] / [`unique_note_path`] before touching the disk.
+///
+/// When a placeholder renders empty, the separator that would follow it is
+/// dropped so `a_{empty}_b` yields `a_b`. An invalid template falls back to
+/// [`DEFAULT_FILENAME_TEMPLATE`] (settings are validated at load time).
+pub fn render_template(template: &str, fields: &NoteNameFields) -> String {
+    let segments = parse_template(template)
+        .or_else(|_| parse_template(DEFAULT_FILENAME_TEMPLATE))
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    let mut prev_empty = false;
+    for segment in segments {
+        match segment {
+            Segment::Literal(text) => {
+                let mut text = text.as_str();
+                if prev_empty {
+                    if let (Some(last), Some(first)) = (out.chars().last(), text.chars().next()) {
+                        if last == first && SEPARATORS.contains(&first) {
+                            text = &text[first.len_utf8()..];
+                        }
+                    }
+                }
+                out.push_str(text);
+                prev_empty = false;
+            }
+            Segment::Placeholder { name, arg } => {
+                let value = placeholder_value(&name, arg.as_deref(), fields);
+                prev_empty = value.is_empty();
+                out.push_str(&value);
+            }
+        }
+    }
+    out.trim_matches(SEPARATORS).to_string()
+}
+
+fn placeholder_value(name: &str, arg: Option<&str>, f: &NoteNameFields) -> String {
+    let max_len = arg.and_then(|a| a.trim().parse::<usize>().ok());
+    let value = match name {
+        "date" => {
+            let fmt = arg.unwrap_or("%Y-%m-%d");
+            match f.date {
+                Some(d) if !StrftimeItems::new(fmt).any(|i| matches!(i, Item::Error)) => {
+                    d.format(fmt).to_string()
+                }
+                Some(d) => d.format("%Y-%m-%d").to_string(),
+                None => "unknown-date".to_string(),
+            }
+        }
+        "from_name" => display_name(f.from),
+        "from_addr" => first_address(f.from),
+        "from_domain" => {
+            let addr = first_address(f.from);
+            addr.rsplit_once('@').map(|(_, d)| d.to_string()).unwrap_or_default()
+        }
+        "from_short" => get_short_name(Some(f.from)),
+        "to_name" => display_name(f.to),
+        "to_short" => get_short_name(Some(f.to)),
+        "subject" => strip_reply_prefixes(f.subject).trim().to_string(),
+        "subject_slug" => {
+            return truncate_trim(&slugify(f.subject), max_len.unwrap_or(DEFAULT_SLUG_LEN));
+        }
+        "subject_extract" => subject_extract(f.subject),
+        "message_id_hash" => match f.message_id.map(normalize_message_id) {
+            Some(id) if !id.is_empty() => hash_md5_prefix(&id, 10),
+            _ => hash_md5_prefix(
+                &format!(
+                    "{}|{}|{}",
+                    f.date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+                    f.from,
+                    f.subject
+                ),
+                10,
+            ),
+        },
+        "account" => f.account.to_string(),
+        "folder" => f.folder.to_string(),
+        _ => return String::new(),
+    };
+    match max_len {
+        Some(n) if name != "date" => truncate_trim(&value, n),
+        _ => value,
+    }
+}
+
+/// Trim surrounding whitespace and angle brackets from a `Message-ID` value.
+pub fn normalize_message_id(raw: &str) -> String {
+    raw.trim().trim_start_matches('<').trim_end_matches('>').trim().to_string()
+}
+
+fn first_address(field: &str) -> String {
+    extract_emails(Some(field)).into_iter().next().unwrap_or_default()
+}
+
+/// Display name of the first mailbox, or its local part when there is none.
+fn display_name(field: &str) -> String {
+    let first = field.split(',').next().unwrap_or("").trim();
+    if let Some(idx) = first.find('<') {
+        let name = first[..idx].trim().trim_matches('"').trim();
+        if !name.is_empty() {
+            return name.to_string();
+        }
+    }
+    first_address(field)
+        .split('@')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+fn strip_reply_prefixes(subject: &str) -> &str {
+    match REPLY_PREFIX_RE.find(subject) {
+        Some(m) => &subject[m.end()..],
+        None => subject,
+    }
+}
+
+/// Lowercase, dash-separated slug of the subject without `Re:`/`Fwd:` prefixes.
+fn slugify(subject: &str) -> String {
+    let mut out = String::new();
+    let mut pending_dash = false;
+    for c in strip_reply_prefixes(subject).chars() {
+        if c.is_alphanumeric() {
+            if pending_dash && !out.is_empty() {
+                out.push('-');
+            }
+            pending_dash = false;
+            out.extend(c.to_lowercase());
+        } else {
+            pending_dash = true;
+        }
+    }
+    out
+}
+
+fn truncate_trim(s: &str, max_chars: usize) -> String {
+    let truncated: String = s.chars().take(max_chars).collect();
+    truncated.trim_end_matches(SEPARATORS).to_string()
+}
+
+/// Make a rendered name safe as a single path component on every platform.
+///
+/// Applies the Unicode form and optional transliteration, replaces characters
+/// forbidden on Windows (and path separators) with `_`, drops control
+/// characters and trailing dots/spaces, and suffixes Windows reserved device
+/// names (`CON`, `NUL`, `COM1`…) with `_`. Never returns an empty string.
+pub fn portable_stem(raw: &str, policy: &FilenamePolicy) -> String {
+    let normalized: String = match policy.unicode_form {
+        UnicodeForm::Nfc => raw.nfc().collect(),
+        UnicodeForm::Nfd => raw.nfd().collect(),
+        UnicodeForm::None => raw.to_string(),
+    };
+    let text = if policy.transliterate {
+        deunicode::deunicode(&normalized)
+    } else {
+        normalized
+    };
+
+    let replaced: String = text
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            _ => c,
+        })
+        .collect();
+
+    let stem = fix_reserved(replaced.trim().trim_end_matches(['.', ' ']));
+    if stem.is_empty() {
+        "email".to_string()
+    } else {
+        stem
+    }
+}
+
+fn fix_reserved(stem: &str) -> String {
+    let base = stem.split('.').next().unwrap_or("");
+    if WINDOWS_RESERVED.iter().any(|r| r.eq_ignore_ascii_case(base.trim_end())) {
+        format!("{}_", stem)
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Truncate `s` so it fits in `max_chars` characters and `max_bytes` UTF-8 bytes.
+fn truncate_to_fit(s: &str, max_chars: usize, max_bytes: usize) -> &str {
+    let mut end = 0;
+    for (count, (idx, c)) in s.char_indices().enumerate() {
+        if count >= max_chars || idx + c.len_utf8() > max_bytes {
+            break;
+        }
+        end = idx + c.len_utf8();
+    }
+    &s[..end]
+}
+
+/// Pick a free path `dir/<stem>[_N].<ext>` honouring the policy's length limits.
+///
+/// `stem` is made portable first; on collision a `_2`, `_3`… counter is appended,
+/// truncating the stem (never the counter or extension) when the limit is reached.
+/// The path length is measured in `final_dir`, where routing will move the note
+/// (`dir` itself for an unrouted note). A stem truncated down to nothing is
+/// replaced by `fallback` (e.g. the email's date) so no note is named `.md`.
+pub fn unique_note_path(
+    dir: &Path,
+    final_dir: &Path,
+    stem: &str,
+    fallback: &str,
+    ext: &str,
+    policy: &FilenamePolicy,
+) -> PathBuf {
+    let stem = portable_stem(stem, policy);
+    let fallback = portable_stem(fallback, policy);
+    let dir_chars = final_dir.to_string_lossy().chars().count() + 1;
+    let path_budget = policy.max_path_len.saturating_sub(dir_chars);
+
+    let mut counter = 1u32;
+    loop {
+        let suffix = if counter == 1 {
+            format!(".{}", ext)
+        } else {
+            format!("_{}.{}", counter, ext)
+        };
+        // Keep at least a readable prefix even when the directory alone is too deep.
+        let max_chars = path_budget.saturating_sub(suffix.chars().count()).max(MIN_STEM_CHARS);
+        let max_bytes = MAX_COMPONENT_BYTES.saturating_sub(suffix.len());
+        let fit = |stem: &str| fix_reserved(truncate_to_fit(stem, max_chars, max_bytes).trim_end_matches(['.', ' ']));
+        let fitted = match fit(&stem) {
+            fitted if fitted.is_empty() => fit(&fallback),
+            fitted => fitted,
+        };
+        let candidate = dir.join(format!("{}{}", fitted, suffix));
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields<'a>() -> NoteNameFields<'a> {
+        NoteNameFields {
+            date: DateTime::parse_from_rfc3339("2024-03-05T10:00:00+00:00").ok(),
+            from: "Alice Martin <alice@example.com>",
+            to: "Bob <bob@corp.fr>",
+            subject: "Re: Fwd: Devis été 2024 — validation",
+            message_id: Some("<abc.123@example.com>"),
+            account: "Work",
+            folder: "INBOX",
+        }
+    }
+
+    #[test]
+    fn test_default_template_matches_legacy_scheme() {
+        let f = NoteNameFields { subject: "", ..fields() };
+        let name = render_template(DEFAULT_FILENAME_TEMPLATE, &f);
+        // Inclusive: legacy layout with the empty extract collapsed
+        assert_eq!(name, format!("email_2024-03-05_{}_to_{}",
+            get_short_name(Some(f.from)), get_short_name(Some(f.to))));
+        // Exclusive: no doubled separator left by the empty placeholder
+        assert!(!name.contains("__"));
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let name = render_template("{date:%Y%m%d}_{from_domain}_{subject_slug:20}", &fields());
+        assert_eq!(name, "20240305_example.com_devis-été-2024-valid");
+    }
+
+    #[test]
+    fn test_render_from_name_account_folder() {
+        let name = render_template("{account}/{folder} {from_name}", &fields());
+        assert_eq!(name, "Work/INBOX Alice Martin");
+    }
+
+    #[test]
+    fn test_message_id_hash_ignores_angle_brackets() {
+        let a = render_template("{message_id_hash}", &fields());
+        let b = render_template(
+            "{message_id_hash}",
+            &NoteNameFields { message_id: Some("abc.123@example.com"), ..fields() },
+        );
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 10);
+    }
+
+    #[test]
+    fn test_literal_braces() {
+        assert_eq!(render_template("{{x}}_{account}", &fields()), "{x}_Work");
+    }
+
+    #[test]
+    fn test_validate_template_rejects_unknown_placeholder() {
+        assert!(validate_template("{date}_{sender}").is_err());
+        assert!(validate_template("{date").is_err());
+        assert!(validate_template("{subject_slug:abc}").is_err());
+        assert!(validate_template("{date:%Q}").is_err());
+        assert!(validate_template(DEFAULT_FILENAME_TEMPLATE).is_ok());
+    }
+
+    #[test]
+    fn test_portable_stem_windows_reserved_and_forbidden() {
+        let p = FilenamePolicy::default();
+        assert_eq!(portable_stem("CON", &p), "CON_");
+        assert_eq!(portable_stem("nul.txt", &p), "nul.txt_");
+        assert_eq!(portable_stem("a/b:c?d. ", &p), "a_b_c_d");
+        // Exclusive: names merely starting with a reserved word are untouched
+        assert_eq!(portable_stem("CONTRACT", &p), "CONTRACT");
+        assert_eq!(portable_stem("...", &p), "email");
+    }
+
+    #[test]
+    fn test_portable_stem_normalization_and_transliteration() {
+        let decomposed = "e\u{301}te\u{301}";
+        let nfc = portable_stem(decomposed, &FilenamePolicy::default());
+        assert_eq!(nfc, "été");
+        let ascii = portable_stem(
+            decomposed,
+            &FilenamePolicy { transliterate: true, ..FilenamePolicy::default() },
+        );
+        assert_eq!(ascii, "ete");
+    }
+
+    #[test]
+    fn test_unique_note_path_respects_max_path_len_and_counter() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let policy = FilenamePolicy { max_path_len: 80, ..FilenamePolicy::default() };
+        let long = "x".repeat(300);
+
+        let first = unique_note_path(temp.path(), temp.path(), &long, "date", "md", &policy);
+        assert!(first.to_string_lossy().chars().count() <= 80, "{:?}", first);
+        std::fs::write(&first, "").unwrap();
+
+        let second = unique_note_path(temp.path(), temp.path(), &long, "date", "md", &policy);
+        assert_ne!(first, second);
+        assert!(second.to_string_lossy().ends_with("_2.md"));
+        assert!(second.to_string_lossy().chars().count() <= 80);
+    }
+
+    #[test]
+    fn test_unique_note_path_caps_component_bytes() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let policy = FilenamePolicy { max_path_len: 10_000, ..FilenamePolicy::default() };
+        let path = unique_note_path(temp.path(), temp.path(), &"é".repeat(300), "date", "md", &policy);
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.len() <= MAX_COMPONENT_BYTES, "{} bytes", name.len());
+        assert!(name.ends_with(".md"));
+    }
+
+    #[test]
+    fn test_unique_note_path_measures_final_destination() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let final_dir = temp.path().join("notes").join("Pro/Clients/Acme/2026/06");
+        let policy = FilenamePolicy {
+            max_path_len: final_dir.to_string_lossy().chars().count() + 40,
+            ..FilenamePolicy::default()
+        };
+        let path = unique_note_path(temp.path(), &final_dir, &"x".repeat(300), "date", "md", &policy);
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        // Inclusive: the note fits once moved into its destination
+        assert!(final_dir.join(&name).to_string_lossy().chars().count() <= policy.max_path_len);
+        // Exclusive: the budget is not the shorter staging directory's
+        assert!(name.len() < 40, "{}", name);
+    }
+
+    #[test]
+    fn test_unique_note_path_never_leaves_an_empty_stem() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let policy = FilenamePolicy { max_path_len: 1, ..FilenamePolicy::default() };
+        let stem = format!("{}x", ".".repeat(MIN_STEM_CHARS));
+        let path = unique_note_path(temp.path(), temp.path(), &stem, "2026-06-25", "md", &policy);
+        assert_eq!(path.file_name().unwrap().to_string_lossy(), "2026-06-25.md");
+    }
+}
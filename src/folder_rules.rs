@@ -0,0 +1,521 @@
+//! Folder selection patterns, per-folder behaviour overrides and the mapping
+//! of server folders to local paths.
+//!
+//! Patterns are matched against the decoded folder name (`FolderName::display`),
+//! and also against its path form (server hierarchy delimiter read as `/`):
+//!
+//! - glob (default): `Sent`, `INBOX/*`, `Archives/**` — `*` stays within one
+//!   level, `**` crosses levels; `X/**` also matches `X` itself
+//! - regex: `re:^Archives/20(1|2)\d$`
+//!
+//! Matching is case-sensitive. Overrides (`folders:` in settings.yaml) are
+//! applied from the least to the most specific matching pattern (shortest
+//! pattern first), so `INBOX/Archive` wins over `INBOX/**`.
+//!
+//! `folder_map:` renames server folders locally. The most specific matching
+//! source wins; each `*` of the target takes the next wildcard capture of a
+//! glob source (`INBOX.Clients.*: Clients/*`), and `re:` sources, matched
+//! against the path form only, expand `$1`-style groups. Unmapped folders
+//! keep their own hierarchy.
+
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::after_export::AfterExport;
+use crate::body::BodyPreference;
+use crate::cleaner::QuotedHistory;
+use crate::config::Account;
+use crate::email_export::FolderName;
+use crate::filename::validate_template;
+use crate::output::OutputFormat;
+use crate::utils::sanitize_filename;
+
+const REGEX_PREFIX: &str = "re:";
+
+const GLOB_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Behaviour that can be overridden for a subset of an account's folders.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FolderOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quoted_history: Option<QuotedHistory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_existing: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_signature_images: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_attachments: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_after_export: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_preference: Option<BodyPreference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_export: Option<AfterExport>,
+}
+
+impl FolderOverrides {
+    fn apply(&self, account: &mut Account) {
+        if let Some(v) = self.quote_depth {
+            account.quote_depth = v;
+        }
+        if let Some(v) = self.quoted_history {
+            account.quoted_history = v;
+        }
+        if let Some(v) = self.skip_existing {
+            account.skip_existing = v;
+        }
+        if let Some(v) = self.skip_signature_images {
+            account.skip_signature_images = v;
+        }
+        if let Some(v) = self.skip_attachments {
+            account.skip_attachments = v;
+        }
+        if let Some(v) = self.delete_after_export {
+            account.delete_after_export = v;
+        }
+        if let Some(v) = &self.filename_template {
+            account.filename_template = v.clone();
+        }
+        if let Some(v) = self.output_format {
+            account.output_format = v;
+        }
+        if let Some(v) = self.body_preference {
+            account.body_preference = v;
+        }
+        if let Some(v) = &self.after_export {
+            account.after_export = v.clone();
+        }
+    }
+}
+
+/// Reject patterns that do not compile.
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err("empty folder pattern".to_string());
+    }
+    match pattern.strip_prefix(REGEX_PREFIX) {
+        Some(re) => Regex::new(re)
+            .map(|_| ())
+            .map_err(|e| format!("invalid folder regex {:?}: {}", pattern, e)),
+        None => Pattern::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("invalid folder glob {:?}: {}", pattern, e)),
+    }
+}
+
+fn matches_name(pattern: &str, name: &str) -> bool {
+    if let Some(re) = pattern.strip_prefix(REGEX_PREFIX) {
+        return Regex::new(re).is_ok_and(|re| re.is_match(name));
+    }
+    if let Some(parent) = pattern.strip_suffix("/**") {
+        if matches_name(parent, name) {
+            return true;
+        }
+    }
+    Pattern::new(pattern).is_ok_and(|p| p.matches_with(name, GLOB_OPTIONS))
+}
+
+/// Whether `pattern` matches the folder display name or its path form.
+pub fn pattern_matches(pattern: &str, folder: &FolderName) -> bool {
+    if matches_name(pattern, &folder.display) {
+        return true;
+    }
+    let path = folder.path_form();
+    path != folder.display && matches_name(pattern, &path)
+}
+
+/// Whether a folder is exported: not listed in `ignored_folders`, matching
+/// `include_folders` when set, and matching no `exclude_folders` pattern.
+pub fn folder_selected(account: &Account, folder: &FolderName) -> bool {
+    if account.ignored_folders.contains(&folder.display) {
+        return false;
+    }
+    if !account.include_folders.is_empty()
+        && !account.include_folders.iter().any(|p| pattern_matches(p, folder))
+    {
+        return false;
+    }
+    !account.exclude_folders.iter().any(|p| pattern_matches(p, folder))
+}
+
+/// Effective settings for one folder: the account with every matching
+/// `folders:` override applied, least specific first.
+pub fn account_for_folder(account: &Account, folder: &FolderName) -> Account {
+    let mut matching: Vec<(&String, &FolderOverrides)> = account
+        .folder_overrides
+        .iter()
+        .filter(|(pattern, _)| pattern_matches(pattern, folder))
+        .collect();
+    matching.sort_by_key(|(pattern, _)| pattern.len());
+    let mut effective = account.clone();
+    for (_, overrides) in matching {
+        overrides.apply(&mut effective);
+    }
+    effective
+}
+
+/// Compile a `folder_map` glob source into an anchored regex with one
+/// capture group per wildcard; `*` and `?` stay within one level of `separator`.
+fn map_glob_regex(pattern: &str, separator: &str) -> Regex {
+    let level = format!("[^{}]", regex::escape(separator));
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str("(.*)");
+            }
+            '*' => re.push_str(&format!("({}*)", level)),
+            '?' => re.push_str(&format!("({})", level)),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).expect("escaped glob is a valid regex")
+}
+
+fn map_glob_wildcards(pattern: &str) -> usize {
+    pattern.replace("**", "*").matches(['*', '?']).count()
+}
+
+/// Target of the `folder_map` entry matching `folder`, as a `/`-separated path.
+fn mapped_target(account: &Account, folder: &FolderName) -> Option<String> {
+    let mut sources: Vec<(&String, &String)> = account.folder_map.iter().collect();
+    sources.sort_by_key(|(source, _)| std::cmp::Reverse(source.len()));
+    let delimiter = folder.delimiter.as_deref().filter(|d| !d.is_empty());
+    let path = folder.path_form();
+
+    for (source, target) in sources {
+        if let Some(re) = source.strip_prefix(REGEX_PREFIX) {
+            let Ok(re) = Regex::new(re) else { continue };
+            if let Some(caps) = re.captures(&path) {
+                let mut expanded = String::new();
+                caps.expand(target, &mut expanded);
+                return Some(expanded);
+            }
+            continue;
+        }
+        // Display form first (wildcards stop at the server delimiter), then path form
+        let mut forms = vec![(folder.display.as_str(), delimiter.unwrap_or("/"))];
+        if path != folder.display {
+            forms.push((path.as_str(), "/"));
+        }
+        for (name, separator) in forms {
+            let Some(caps) = map_glob_regex(source, separator).captures(name) else {
+                continue;
+            };
+            let mut captures = caps.iter().skip(1).flatten().map(|m| {
+                let text = m.as_str();
+                match delimiter {
+                    Some(d) if separator == d => text.replace(d, "/"),
+                    _ => text.to_string(),
+                }
+            });
+            let mut mapped = String::new();
+            for c in target.chars() {
+                match c {
+                    '*' => mapped.push_str(&captures.next().unwrap_or_default()),
+                    c => mapped.push(c),
+                }
+            }
+            return Some(mapped);
+        }
+    }
+    None
+}
+
+fn namespace_tag_prefix<'a>(account: &'a Account, folder: &FolderName) -> Option<&'a str> {
+    folder
+        .namespace
+        .as_ref()
+        .and_then(|ns| account.namespaces.tag_prefix(ns.kind))
+}
+
+/// Folder tag written to notes: the display name, or `<tag_prefix>/<levels>`
+/// for a shared or other-users folder (`shared/support/INBOX`).
+pub fn folder_tag(account: &Account, folder: &FolderName) -> String {
+    match namespace_tag_prefix(account, folder) {
+        Some(prefix) => format!("{}/{}", prefix, folder.relative_segments().join("/")),
+        None => folder.display.clone(),
+    }
+}
+
+/// Local directory of `folder`, relative to the account export directory.
+///
+/// Uses the `folder_map` target when one matches, otherwise the server
+/// hierarchy — under `@<tag_prefix>/` for a shared or other-users folder.
+/// Every level is sanitized; empty, `.` and `..` levels are dropped, so a
+/// folder mapped to `""` lands in the export directory itself.
+pub fn local_folder_path(account: &Account, folder: &FolderName) -> PathBuf {
+    let levels: Vec<String> = match (mapped_target(account, folder), namespace_tag_prefix(account, folder)) {
+        (Some(target), _) => target.split('/').map(str::to_string).collect(),
+        (None, Some(prefix)) => std::iter::once(format!("@{}", prefix))
+            .chain(folder.relative_segments().into_iter().map(str::to_string))
+            .collect(),
+        (None, None) => folder.segments().into_iter().map(str::to_string).collect(),
+    };
+    levels
+        .iter()
+        .map(|level| sanitize_filename(level.trim()))
+        .filter(|level| !level.is_empty() && level != "." && level != "..")
+        .collect()
+}
+
+/// Reject server actions that cannot be combined. Also run on the effective
+/// settings of each folder, where overlapping `folders:` patterns merge.
+pub fn validate_server_actions(account: &Account) -> Result<(), String> {
+    account.after_export.validate()?;
+    if account.delete_after_export && account.after_export.move_to.is_some() {
+        return Err("after_export.move_to and delete_after_export are mutually exclusive".to_string());
+    }
+    Ok(())
+}
+
+/// Validate an account's folder patterns and overrides.
+pub fn validate_account(account: &Account) -> Result<(), String> {
+    account.namespaces.validate()?;
+    account.mirror_routing.validate()?;
+    validate_server_actions(account)?;
+    for pattern in account
+        .include_folders
+        .iter()
+        .chain(&account.exclude_folders)
+        .chain(account.folder_overrides.keys())
+    {
+        validate_pattern(pattern)?;
+    }
+    for (pattern, overrides) in &account.folder_overrides {
+        let mut effective = account.clone();
+        overrides.apply(&mut effective);
+        validate_server_actions(&effective).map_err(|e| format!("folder {:?}: {}", pattern, e))?;
+        if let Some(template) = &overrides.filename_template {
+            validate_template(template)
+                .map_err(|e| format!("folder {:?}: invalid filename_template: {}", pattern, e))?;
+        }
+    }
+    for (source, target) in &account.folder_map {
+        if source.trim().is_empty() {
+            return Err("folder_map: empty source pattern".to_string());
+        }
+        match source.strip_prefix(REGEX_PREFIX) {
+            Some(re) => {
+                Regex::new(re).map_err(|e| format!("folder_map: invalid regex {:?}: {}", source, e))?;
+            }
+            None => {
+                if target.matches('*').count() > map_glob_wildcards(source) {
+                    return Err(format!(
+                        "folder_map {:?}: target {:?} has more `*` than the source has wildcards",
+                        source, target
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespace::{Namespace, NamespaceKind};
+    use std::collections::BTreeMap;
+
+    fn folder(display: &str, delimiter: &str) -> FolderName {
+        FolderName {
+            raw: display.to_string(),
+            display: display.to_string(),
+            delimiter: Some(delimiter.to_string()),
+            namespace: None,
+        }
+    }
+
+    fn account() -> Account {
+        serde_yaml::from_str(
+            "name: test\nserver: imap.example.com\nport: 993\nusername: u\nexport_directory: /tmp/x\n\
+             quote_depth: 1\nskip_existing: true\ncollect_contacts: false\nskip_signature_images: false\n\
+             delete_after_export: false\ncleanup_empty_dirs: true\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_glob_patterns() {
+        assert!(pattern_matches("Sent", &folder("Sent", "/")));
+        assert!(!pattern_matches("Sent", &folder("Sent/2024", "/")));
+        assert!(pattern_matches("Sent/**", &folder("Sent", "/")));
+        assert!(pattern_matches("Sent/**", &folder("Sent/2024/Q1", "/")));
+        assert!(pattern_matches("INBOX/*", &folder("INBOX/Archive", "/")));
+        assert!(!pattern_matches("INBOX/*", &folder("INBOX/Archive/2020", "/")));
+        // Path form of a `.`-delimited name
+        assert!(pattern_matches("INBOX/*", &folder("INBOX.Archive", ".")));
+        // Exclusive: a dot inside a `/`-delimited name is not a level
+        assert!(!pattern_matches("v1/*", &folder("v1.2 release", "/")));
+        // Exclusive: case-sensitive
+        assert!(!pattern_matches("sent", &folder("Sent", "/")));
+    }
+
+    #[test]
+    fn test_regex_patterns() {
+        assert!(pattern_matches(r"re:^Archives/20(1|2)\d$", &folder("Archives/2019", "/")));
+        assert!(!pattern_matches(r"re:^Archives/20(1|2)\d$", &folder("Archives/2031", "/")));
+        assert!(validate_pattern("re:(").is_err());
+        assert!(validate_pattern("[").is_err());
+        assert!(validate_pattern("Sent/**").is_ok());
+    }
+
+    #[test]
+    fn test_folder_selected_include_exclude() {
+        let mut acc = account();
+        acc.ignored_folders = vec!["Trash".to_string()];
+        acc.include_folders = vec!["INBOX/**".to_string(), "Sent".to_string()];
+        acc.exclude_folders = vec!["re:/Spam$".to_string()];
+
+        assert!(folder_selected(&acc, &folder("INBOX", "/")));
+        assert!(folder_selected(&acc, &folder("INBOX/Clients", "/")));
+        assert!(folder_selected(&acc, &folder("Sent", "/")));
+        // Exclusive: not included, excluded, ignored
+        assert!(!folder_selected(&acc, &folder("Drafts", "/")));
+        assert!(!folder_selected(&acc, &folder("INBOX/Spam", "/")));
+        assert!(!folder_selected(&acc, &folder("Trash", "/")));
+    }
+
+    #[test]
+    fn test_account_for_folder_most_specific_wins() {
+        let mut acc = account();
+        acc.folder_overrides = BTreeMap::from([
+            (
+                "INBOX/**".to_string(),
+                FolderOverrides { quote_depth: Some(3), delete_after_export: Some(false), ..Default::default() },
+            ),
+            (
+                "INBOX/Archive".to_string(),
+                FolderOverrides { delete_after_export: Some(true), ..Default::default() },
+            ),
+            (
+                "Newsletters".to_string(),
+                FolderOverrides { skip_attachments: Some(true), ..Default::default() },
+            ),
+        ]);
+
+        let archive = account_for_folder(&acc, &folder("INBOX/Archive", "/"));
+        assert_eq!(archive.quote_depth, 3);
+        assert!(archive.delete_after_export);
+
+        let inbox = account_for_folder(&acc, &folder("INBOX", "/"));
+        assert_eq!(inbox.quote_depth, 3);
+        assert!(!inbox.delete_after_export);
+
+        let news = account_for_folder(&acc, &folder("Newsletters", "/"));
+        assert!(news.skip_attachments);
+        // Exclusive: unmatched folder keeps the account values
+        let sent = account_for_folder(&acc, &folder("Sent", "/"));
+        assert_eq!(sent.quote_depth, 1);
+        assert!(!sent.skip_attachments);
+    }
+
+    #[test]
+    fn test_overlapping_overrides_validated_once_merged() {
+        let mut acc = account();
+        acc.folder_overrides = BTreeMap::from([
+            (
+                "Archives/**".to_string(),
+                FolderOverrides {
+                    after_export: Some(AfterExport { move_to: Some("Old".to_string()), ..Default::default() }),
+                    ..Default::default()
+                },
+            ),
+            (
+                "Archives/2024".to_string(),
+                FolderOverrides { delete_after_export: Some(true), ..Default::default() },
+            ),
+        ]);
+        // Each override is valid on its own
+        assert!(validate_account(&acc).is_ok());
+        // Exclusive: the folder matching both combines move_to and deletion
+        assert!(validate_server_actions(&account_for_folder(&acc, &folder("Archives/2024", "/"))).is_err());
+        assert!(validate_server_actions(&account_for_folder(&acc, &folder("Archives/2023", "/"))).is_ok());
+
+        // The CLI override is checked against the account's move_to
+        acc.after_export.move_to = Some("Old".to_string());
+        acc.delete_after_export = true;
+        assert!(validate_account(&acc).is_err());
+    }
+
+    #[test]
+    fn test_local_folder_path_follows_server_delimiter() {
+        let acc = account();
+        assert_eq!(local_folder_path(&acc, &folder("Projects/v1.2 release", "/")), PathBuf::from("Projects/v1.2 release"));
+        assert_eq!(local_folder_path(&acc, &folder("INBOX.Clients.Acme", ".")), PathBuf::from("INBOX/Clients/Acme"));
+        // Levels are sanitized and traversal is dropped
+        assert_eq!(local_folder_path(&acc, &folder("INBOX.a/b.Re: x", ".")), PathBuf::from("INBOX/a_b/Re_ x"));
+        assert_eq!(local_folder_path(&acc, &folder("../../etc", "/")), PathBuf::from("etc"));
+    }
+
+    #[test]
+    fn test_folder_map_renames_and_flattens() {
+        let mut acc = account();
+        acc.folder_map = BTreeMap::from([
+            ("[Gmail]/Sent Mail".to_string(), "Sent".to_string()),
+            ("INBOX.Clients.*".to_string(), "Clients/*".to_string()),
+            ("INBOX.Projects.**".to_string(), "Projects/*".to_string()),
+            (r"re:^Archives/(\d{4})$".to_string(), "Old/$1".to_string()),
+            ("INBOX".to_string(), "".to_string()),
+        ]);
+
+        assert_eq!(local_folder_path(&acc, &folder("[Gmail]/Sent Mail", "/")), PathBuf::from("Sent"));
+        assert_eq!(local_folder_path(&acc, &folder("INBOX.Clients.Acme", ".")), PathBuf::from("Clients/Acme"));
+        assert_eq!(local_folder_path(&acc, &folder("INBOX.Projects.A.B", ".")), PathBuf::from("Projects/A/B"));
+        assert_eq!(local_folder_path(&acc, &folder("Archives.2019", ".")), PathBuf::from("Old/2019"));
+        assert_eq!(local_folder_path(&acc, &folder("INBOX", ".")), PathBuf::new());
+        // Exclusive: `*` stops at the delimiter, unmapped folders keep their hierarchy
+        assert_eq!(
+            local_folder_path(&acc, &folder("INBOX.Clients.Acme.2024", ".")),
+            PathBuf::from("INBOX/Clients/Acme/2024")
+        );
+        assert_eq!(local_folder_path(&acc, &folder("INBOX.Other", ".")), PathBuf::from("INBOX/Other"));
+    }
+
+    #[test]
+    fn test_validate_folder_map() {
+        let mut acc = account();
+        acc.folder_map = BTreeMap::from([("INBOX.*".to_string(), "Mail/*".to_string())]);
+        assert!(validate_account(&acc).is_ok());
+        acc.folder_map = BTreeMap::from([("Sent".to_string(), "Sent/*".to_string())]);
+        assert!(validate_account(&acc).is_err());
+        acc.folder_map = BTreeMap::from([("re:(".to_string(), "x".to_string())]);
+        assert!(validate_account(&acc).is_err());
+    }
+
+    #[test]
+    fn test_shared_namespace_path_and_tag_do_not_collide() {
+        let mut acc = account();
+        acc.namespaces.shared = true;
+        let mut shared = folder("Shared/support/INBOX", "/");
+        shared.namespace = Some(Namespace {
+            kind: NamespaceKind::Shared,
+            prefix: "Shared/".to_string(),
+            delimiter: Some("/".to_string()),
+        });
+        let personal = folder("support/INBOX", "/");
+
+        assert_eq!(local_folder_path(&acc, &shared), PathBuf::from("@shared/support/INBOX"));
+        assert_eq!(folder_tag(&acc, &shared), "shared/support/INBOX");
+        // Exclusive: the personal folder of the same name keeps its own path and tag
+        assert_eq!(local_folder_path(&acc, &personal), PathBuf::from("support/INBOX"));
+        assert_eq!(folder_tag(&acc, &personal), "support/INBOX");
+    }
+}
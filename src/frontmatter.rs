@@ -0,0 +1,1089 @@
+//! Frontmatter schema, dialects and the shared note-header reader.
+//!
+//! `EmailFrontmatter` is the canonical record of a note. A [`FrontmatterSchema`]
+//! (resolved from the `frontmatter:` settings section) decides which canonical
+//! fields are written, under which keys, with which date format and list style,
+//! and in which dialect:
+//!
+//! - `yaml`     — YAML between `---` lines (historical output, the default)
+//! - `obsidian` — YAML with `aliases`, `created`, `[[Contact]]` links, space-free tags
+//! - `logseq`   — `key:: value` property block
+//! - `hugo`     — YAML with `title`, `date` and `draft: false`
+//!
+//! Readers go through [`NoteHeader`], which accepts every header syntax (YAML,
+//! Logseq properties, and the Org drawer / JSON metadata of the other
+//! `output` formats) and resolves renamed keys via [`FieldKeys`], so routing,
+//! duplicate detection and the tray keep working whatever wrote the note.
+
+use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::email_export::EmailFrontmatter;
+use crate::utils::extract_emails;
+
+/// Canonical fields a schema can emit.
+pub const FIELDS: &[&str] = &[
+    "from",
+    "from_address",
+    "to",
+    "date",
+    "subject",
+    "aliases",
+    "message_id",
+    "subject_hash",
+    "tags",
+    "attachments",
+    "email_type",
+    "social_links",
+    "flags",
+    "parse_degraded",
+    "event_start",
+    "event_end",
+    "location",
+    "organizer",
+    "attendees",
+    "status",
+    "signature",
+    "charset_repaired",
+    "signature_block",
+];
+
+/// Fields and order of the historical `EmailFrontmatter` serialization.
+const DEFAULT_FIELDS: &[&str] = &[
+    "from",
+    "to",
+    "date",
+    "subject",
+    "message_id",
+    "subject_hash",
+    "tags",
+    "attachments",
+    "email_type",
+    "social_links",
+    "flags",
+    "parse_degraded",
+    "event_start",
+    "event_end",
+    "location",
+    "organizer",
+    "attendees",
+    "status",
+    "signature",
+    "charset_repaired",
+    "signature_block",
+];
+
+/// `date_format` value selecting RFC 3339 (`2024-01-15T10:00:00+01:00`).
+const RFC3339: &str = "rfc3339";
+
+static LOGSEQ_PROPERTY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^([A-Za-z0-9_][A-Za-z0-9_-]*):: ?(.*)$").expect("static regex")
+});
+
+static ORG_PROPERTY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^:([A-Za-z0-9_][A-Za-z0-9_-]*):(?: (.*))?$").expect("static regex")
+});
+
+static ORG_FILE_LINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[file:([^\]]+)\]\]").expect("static regex"));
+
+/// Opening tag of the metadata block embedded in standalone HTML notes.
+pub(crate) const HTML_METADATA_OPEN: &str =
+    r#"<script type="application/json" id="email-metadata">"#;
+
+/// Output dialect of the note header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    #[default]
+    #[serde(alias = "default")]
+    Yaml,
+    Obsidian,
+    Logseq,
+    Hugo,
+}
+
+/// How YAML lists are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListStyle {
+    /// One `- item` per line.
+    #[default]
+    Block,
+    /// Inline `[a, b]`.
+    Flow,
+}
+
+/// `frontmatter:` section of settings.yaml (in `defaults` or per account).
+/// Unset fields fall back to the dialect preset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontmatterSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialect: Option<Dialect>,
+    /// Canonical fields to emit, in order (see [`FIELDS`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    /// Canonical field → key written in the note (e.g. `date: created`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename: Option<BTreeMap<String, String>>,
+    /// `rfc3339` or a `strftime` pattern such as `%Y-%m-%d`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_style: Option<ListStyle>,
+    /// Write `from`/`to` as `[[Contact]]` wikilinks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact_links: Option<bool>,
+    /// Constant fields added to every note (e.g. `type: email`).
+    #[serde(rename = "static", skip_serializing_if = "Option::is_none")]
+    pub static_fields: Option<BTreeMap<String, Value>>,
+}
+
+/// Key-by-key merge of two optional maps; `over` wins.
+fn merge_maps<V: Clone>(
+    over: &Option<BTreeMap<String, V>>,
+    base: &Option<BTreeMap<String, V>>,
+) -> Option<BTreeMap<String, V>> {
+    match (over, base) {
+        (None, None) => None,
+        _ => {
+            let mut merged = base.clone().unwrap_or_default();
+            merged.extend(over.clone().unwrap_or_default());
+            Some(merged)
+        }
+    }
+}
+
+impl FrontmatterSettings {
+    /// Field-wise merge: `self` wins, `base` fills the gaps. `rename` and
+    /// `static` are merged key by key.
+    pub fn merged(&self, base: &FrontmatterSettings) -> FrontmatterSettings {
+        FrontmatterSettings {
+            dialect: self.dialect.or(base.dialect),
+            fields: self.fields.clone().or_else(|| base.fields.clone()),
+            rename: merge_maps(&self.rename, &base.rename),
+            date_format: self.date_format.clone().or_else(|| base.date_format.clone()),
+            list_style: self.list_style.or(base.list_style),
+            contact_links: self.contact_links.or(base.contact_links),
+            static_fields: merge_maps(&self.static_fields, &base.static_fields),
+        }
+    }
+
+    /// Apply the dialect preset and built-in defaults.
+    pub fn resolve(&self) -> FrontmatterSchema {
+        let dialect = self.dialect.unwrap_or_default();
+        let s = self.merged(&dialect.preset());
+        let contact_links = s.contact_links.unwrap_or(false);
+        let mut fields = s
+            .fields
+            .unwrap_or_else(|| DEFAULT_FIELDS.iter().map(|f| f.to_string()).collect());
+        // A wikilinked sender loses its address — keep it for routing.
+        if contact_links && !fields.iter().any(|f| f == "from_address") {
+            if let Some(pos) = fields.iter().position(|f| f == "from") {
+                fields.insert(pos + 1, "from_address".to_string());
+            }
+        }
+        FrontmatterSchema {
+            dialect,
+            fields,
+            rename: s.rename.unwrap_or_default(),
+            date_format: s.date_format.unwrap_or_else(|| RFC3339.to_string()),
+            list_style: s.list_style.unwrap_or_default(),
+            contact_links,
+            static_fields: s.static_fields.unwrap_or_default(),
+        }
+    }
+}
+
+impl Dialect {
+    fn preset(self) -> FrontmatterSettings {
+        let fields = |list: &[&str]| Some(list.iter().map(|f| f.to_string()).collect());
+        let rename = |pairs: &[(&str, &str)]| {
+            Some(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+        };
+        match self {
+            Dialect::Yaml => FrontmatterSettings::default(),
+            Dialect::Obsidian => FrontmatterSettings {
+                fields: fields(&[
+                    "aliases", "from", "from_address", "to", "date", "subject", "message_id",
+                    "subject_hash", "tags", "attachments", "email_type", "social_links", "flags",
+                    "parse_degraded", "event_start", "event_end", "location", "organizer", "attendees",
+                    "status", "signature", "charset_repaired", "signature_block",
+                ]),
+                rename: rename(self.renames()),
+                date_format: Some("%Y-%m-%dT%H:%M:%S".to_string()),
+                contact_links: Some(true),
+                ..Default::default()
+            },
+            Dialect::Logseq => FrontmatterSettings {
+                fields: fields(&[
+                    "from", "from_address", "to", "date", "subject", "message_id",
+                    "subject_hash", "tags", "attachments", "email_type", "flags",
+                    "parse_degraded", "event_start", "event_end", "location", "organizer", "attendees",
+                    "status", "signature", "charset_repaired", "signature_block",
+                ]),
+                date_format: Some("%Y-%m-%d".to_string()),
+                contact_links: Some(true),
+                ..Default::default()
+            },
+            Dialect::Hugo => FrontmatterSettings {
+                fields: fields(&[
+                    "subject", "date", "from", "to", "message_id", "subject_hash", "tags",
+                    "attachments", "email_type", "social_links", "flags",
+                    "parse_degraded", "event_start", "event_end", "location", "organizer", "attendees",
+                    "status", "signature", "charset_repaired", "signature_block",
+                ]),
+                rename: rename(self.renames()),
+                static_fields: Some(BTreeMap::from([("draft".to_string(), Value::Bool(false))])),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Keys the preset renames (canonical → written key).
+    fn renames(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Dialect::Yaml | Dialect::Logseq => &[],
+            Dialect::Obsidian => &[("date", "created")],
+            Dialect::Hugo => &[("subject", "title")],
+        }
+    }
+}
+
+/// Fully-resolved schema used by the exporter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrontmatterSchema {
+    pub dialect: Dialect,
+    pub fields: Vec<String>,
+    pub rename: BTreeMap<String, String>,
+    pub date_format: String,
+    pub list_style: ListStyle,
+    pub contact_links: bool,
+    pub static_fields: BTreeMap<String, Value>,
+}
+
+impl Default for FrontmatterSchema {
+    fn default() -> Self {
+        FrontmatterSettings::default().resolve()
+    }
+}
+
+impl FrontmatterSchema {
+    /// Key written for a canonical field.
+    pub fn key<'a>(&'a self, field: &'a str) -> &'a str {
+        self.rename.get(field).map(String::as_str).unwrap_or(field)
+    }
+
+    /// Reject unknown fields, bad date formats and colliding keys.
+    pub fn validate(&self) -> Result<(), String> {
+        for field in self.fields.iter().chain(self.rename.keys()) {
+            if !FIELDS.contains(&field.as_str()) {
+                return Err(format!(
+                    "unknown frontmatter field '{}' (known: {})",
+                    field,
+                    FIELDS.join(", ")
+                ));
+            }
+        }
+        for key in self.rename.values().chain(self.static_fields.keys()) {
+            if key.trim().is_empty() || key.contains([':', ' ', '\n']) {
+                return Err(format!("invalid frontmatter key {:?}", key));
+            }
+        }
+        if self.date_format != RFC3339
+            && StrftimeItems::new(&self.date_format).any(|i| matches!(i, Item::Error))
+        {
+            return Err(format!("invalid frontmatter date_format '{}'", self.date_format));
+        }
+        let mut seen = std::collections::HashSet::new();
+        for key in self.fields.iter().map(|f| self.key(f)) {
+            if !seen.insert(key) {
+                return Err(format!("frontmatter key '{}' is written twice", key));
+            }
+        }
+        if let Some(key) = self.static_fields.keys().find(|k| seen.contains(k.as_str())) {
+            return Err(format!("static frontmatter field '{}' collides with an email field", key));
+        }
+        Ok(())
+    }
+
+    fn format_date(&self, date: &DateTime<FixedOffset>) -> String {
+        if self.date_format == RFC3339 {
+            date.to_rfc3339()
+        } else {
+            date.format(&self.date_format).to_string()
+        }
+    }
+}
+
+// ── Writer ───────────────────────────────────────────────────────────────────
+
+/// `[[Name]]` links for every mailbox of an address header.
+fn contact_links(field: &str) -> String {
+    let names: Vec<String> = match mailparse::addrparse(field) {
+        Ok(list) => list
+            .iter()
+            .flat_map(|addr| match addr {
+                mailparse::MailAddr::Single(info) => vec![info.clone()],
+                mailparse::MailAddr::Group(group) => group.addrs.clone(),
+            })
+            .map(|info| info.display_name.filter(|n| !n.trim().is_empty()).unwrap_or(info.addr))
+            .collect(),
+        Err(_) => extract_emails(Some(field)),
+    };
+    names
+        .iter()
+        .map(|n| n.replace(['[', ']', '|', '#', '^'], "").trim().to_string())
+        .filter(|n| !n.is_empty())
+        .map(|n| format!("[[{}]]", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Obsidian tags: no spaces, no `#`, only letters, digits, `-`, `_` and `/`.
+fn obsidian_tag(tag: &str) -> String {
+    tag.trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/'))
+        .collect()
+}
+
+fn string_list(items: &[String]) -> Value {
+    Value::Sequence(items.iter().cloned().map(Value::String).collect())
+}
+
+/// Canonical value of `field`, or `None` when it should be omitted.
+fn field_value(
+    field: &str,
+    fm: &EmailFrontmatter,
+    date: Option<DateTime<FixedOffset>>,
+    schema: &FrontmatterSchema,
+) -> Option<Value> {
+    let text = |s: &str| Some(Value::String(s.to_string()));
+    match field {
+        "from" if schema.contact_links => text(&contact_links(&fm.from)),
+        "from" => text(&fm.from),
+        "from_address" => extract_emails(Some(&fm.from)).into_iter().next().map(Value::String),
+        "to" if schema.contact_links => text(&contact_links(&fm.to)),
+        "to" => text(&fm.to),
+        "date" => match date {
+            Some(d) => text(&schema.format_date(&d)),
+            None => text(&fm.date),
+        },
+        "subject" => text(&fm.subject),
+        "aliases" if fm.subject.trim().is_empty() => None,
+        "aliases" => Some(string_list(std::slice::from_ref(&fm.subject))),
+        "message_id" => fm.message_id.clone().map(Value::String),
+        "subject_hash" => text(&fm.subject_hash),
+        "tags" if schema.dialect == Dialect::Obsidian => {
+            let tags: Vec<String> = fm
+                .tags
+                .iter()
+                .map(|t| obsidian_tag(t))
+                .filter(|t| !t.is_empty())
+                .collect();
+            Some(string_list(&tags))
+        }
+        "tags" => Some(string_list(&fm.tags)),
+        "attachments" => Some(string_list(&fm.attachments)),
+        "email_type" => fm.email_type.clone().map(Value::String),
+        "flags" if fm.flags.is_empty() => None,
+        "flags" => Some(string_list(&fm.flags)),
+        "parse_degraded" if !fm.parse_degraded => None,
+        "parse_degraded" => Some(Value::Bool(true)),
+        "event_start" => fm.event.as_ref().and_then(|e| e.start.clone()).map(Value::String),
+        "event_end" => fm.event.as_ref().and_then(|e| e.end.clone()).map(Value::String),
+        "location" => fm.event.as_ref().and_then(|e| e.location.clone()).map(Value::String),
+        "organizer" => fm.event.as_ref().and_then(|e| e.organizer.clone()).map(Value::String),
+        "attendees" => fm
+            .event
+            .as_ref()
+            .filter(|e| !e.attendees.is_empty())
+            .map(|e| string_list(&e.attendees)),
+        "status" => fm.event.as_ref().and_then(|e| e.status.clone()).map(Value::String),
+        "signature" => fm.signature.map(|s| Value::String(s.to_string())),
+        "charset_repaired" if !fm.charset_repaired => None,
+        "charset_repaired" => Some(Value::Bool(true)),
+        "signature_block" => fm.signature_block.clone().map(Value::String),
+        "social_links" => fm.social_links.as_ref().map(|links| {
+            Value::Mapping(
+                links
+                    .iter()
+                    .map(|(k, v)| (Value::String(k.clone()), Value::String(v.clone())))
+                    .collect(),
+            )
+        }),
+        _ => None,
+    }
+}
+
+/// Ordered `(key, value)` entries of a note header.
+pub(crate) fn header_entries(
+    fm: &EmailFrontmatter,
+    date: Option<DateTime<FixedOffset>>,
+    schema: &FrontmatterSchema,
+) -> Vec<(String, Value)> {
+    let mut entries: Vec<(String, Value)> = schema
+        .fields
+        .iter()
+        .filter_map(|f| field_value(f, fm, date, schema).map(|v| (schema.key(f).to_string(), v)))
+        .collect();
+    for (key, value) in &schema.static_fields {
+        if !entries.iter().any(|(k, _)| k == key) {
+            entries.push((key.clone(), value.clone()));
+        }
+    }
+    entries
+}
+
+/// Inline YAML form of a scalar, quoted when a flow list would misread it.
+fn inline_yaml(value: &Value) -> String {
+    let plain = serde_yaml::to_string(value).unwrap_or_default().trim_end().to_string();
+    match value {
+        Value::String(s) if plain != *s || s.contains([',', '[', ']', '{', '}']) => {
+            serde_json::to_string(s).unwrap_or(plain)
+        }
+        _ => plain,
+    }
+}
+
+fn yaml_entry(key: &str, value: &Value, style: ListStyle) -> String {
+    match (value, style) {
+        (Value::Sequence(items), ListStyle::Flow) if !items.is_empty() => {
+            let items: Vec<String> = items.iter().map(inline_yaml).collect();
+            format!("{}: [{}]\n", inline_yaml(&Value::String(key.to_string())), items.join(", "))
+        }
+        _ => {
+            let mut map = Mapping::new();
+            map.insert(Value::String(key.to_string()), value.clone());
+            serde_yaml::to_string(&map).unwrap_or_default()
+        }
+    }
+}
+
+/// Single-line rendering of a value (Logseq properties, Org drawers).
+pub(crate) fn flat_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.replace(['\r', '\n'], " "),
+        Value::Sequence(items) => items.iter().map(flat_value).collect::<Vec<_>>().join(", "),
+        Value::Mapping(map) => map
+            .iter()
+            .map(|(k, v)| format!("{}: {}", flat_value(k), flat_value(v)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => inline_yaml(other),
+    }
+}
+
+fn logseq_entry(key: &str, value: &Value) -> String {
+    format!("{}:: {}\n", key, flat_value(value))
+}
+
+/// Render the note header (delimiters and trailing blank line included).
+pub fn render_header(
+    fm: &EmailFrontmatter,
+    date: Option<DateTime<FixedOffset>>,
+    schema: &FrontmatterSchema,
+) -> String {
+    let entries = header_entries(fm, date, schema);
+    match schema.dialect {
+        Dialect::Logseq => {
+            let mut out: String = entries.iter().map(|(k, v)| logseq_entry(k, v)).collect();
+            out.push('\n');
+            out
+        }
+        _ => {
+            let mut out = String::from("---\n");
+            for (key, value) in &entries {
+                out.push_str(&yaml_entry(key, value, schema.list_style));
+            }
+            out.push_str("---\n\n");
+            out
+        }
+    }
+}
+
+// ── Reader ───────────────────────────────────────────────────────────────────
+
+/// Header syntax of an existing note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderStyle {
+    /// YAML between `---` lines.
+    Yaml,
+    /// Logseq `key:: value` lines at the top of the page.
+    Logseq,
+    /// Org-mode `:PROPERTIES:` drawer at the top of the file.
+    Org,
+    /// JSON object: a JSON note, or the metadata block of an HTML note.
+    Json,
+}
+
+/// Byte layout of a note: header text, insertion point for new keys, body.
+struct NoteLayout<'a> {
+    style: HeaderStyle,
+    /// Offset of `header` in the note.
+    start: usize,
+    header: &'a str,
+    /// Offset where `\n<new entry>` can be inserted to extend the header.
+    insert_at: usize,
+    body: &'a str,
+}
+
+fn layout(content: &str) -> Option<NoteLayout<'_>> {
+    let open = if content.starts_with("---\n") {
+        Some(4)
+    } else if content.starts_with("---\r\n") {
+        Some(5)
+    } else {
+        None
+    };
+    if let Some(open) = open {
+        let rest = &content[open..];
+        let end = rest.find("\n---")?;
+        let after = &rest[end + 4..];
+        let body = after
+            .strip_prefix("\r\n")
+            .or_else(|| after.strip_prefix('\n'))
+            .unwrap_or(after);
+        return Some(NoteLayout {
+            style: HeaderStyle::Yaml,
+            start: open,
+            header: &rest[..end],
+            insert_at: open + end,
+            body,
+        });
+    }
+
+    if let Some(rest) = content.strip_prefix(":PROPERTIES:") {
+        let start = ":PROPERTIES:".len();
+        let end = rest.find("\n:END:")?;
+        let after = &rest[end + "\n:END:".len()..];
+        let body = after
+            .strip_prefix("\r\n")
+            .or_else(|| after.strip_prefix('\n'))
+            .unwrap_or(after);
+        return Some(NoteLayout {
+            style: HeaderStyle::Org,
+            start,
+            header: &rest[..end],
+            insert_at: start + end,
+            body,
+        });
+    }
+    if content.starts_with('{') {
+        // JSON note: the whole first line is the header, no body.
+        let end = content.find('\n').unwrap_or(content.len());
+        return Some(NoteLayout {
+            style: HeaderStyle::Json,
+            start: 0,
+            header: &content[..end],
+            insert_at: end,
+            body: "",
+        });
+    }
+    if content.starts_with("<!DOCTYPE html>") {
+        let start = content.find(HTML_METADATA_OPEN)? + HTML_METADATA_OPEN.len();
+        let end = start + content[start..].find("</script>")?;
+        return Some(NoteLayout {
+            style: HeaderStyle::Json,
+            start,
+            header: &content[start..end],
+            insert_at: end,
+            body: &content[end + "</script>".len()..],
+        });
+    }
+
+    let mut end = 0;
+    for line in content.split_inclusive('\n') {
+        if !LOGSEQ_PROPERTY_RE.is_match(line.trim_end()) {
+            break;
+        }
+        end += line.len();
+    }
+    if end == 0 {
+        return None;
+    }
+    let header = &content[..end];
+    // The writer separates properties from the body with one blank line.
+    let after = &content[end..];
+    let body = after
+        .strip_prefix("\r\n")
+        .or_else(|| after.strip_prefix('\n'))
+        .unwrap_or(after);
+    Some(NoteLayout {
+        style: HeaderStyle::Logseq,
+        start: 0,
+        header,
+        insert_at: header.trim_end_matches(['\r', '\n']).len(),
+        body,
+    })
+}
+
+/// Body of a note (everything after the header), or the whole content when
+/// there is no header.
+pub fn note_body(content: &str) -> &str {
+    layout(content).map(|l| l.body).unwrap_or(content)
+}
+
+/// Keys under which each canonical field may appear in existing notes.
+#[derive(Debug, Clone, Default)]
+pub struct FieldKeys {
+    aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl FieldKeys {
+    /// Canonical names plus every dialect preset rename (`created`, `title`).
+    pub fn builtin() -> Self {
+        let mut keys = FieldKeys::default();
+        for dialect in [Dialect::Obsidian, Dialect::Hugo] {
+            for (field, key) in dialect.renames() {
+                keys.add(field, key);
+            }
+        }
+        keys
+    }
+
+    /// Built-in keys plus the renames of `defaults` and every account.
+    pub fn from_settings(settings: &crate::config::Settings) -> Self {
+        let mut keys = FieldKeys::builtin();
+        let sections = std::iter::once(&settings.defaults).chain(settings.accounts.values());
+        for fm in sections.filter_map(|b| b.frontmatter.as_ref()) {
+            keys.add_renames(fm.rename.iter().flatten());
+        }
+        keys
+    }
+
+    pub fn add_renames<'a>(&mut self, renames: impl IntoIterator<Item = (&'a String, &'a String)>) {
+        for (field, key) in renames {
+            self.add(field, key);
+        }
+    }
+
+    fn add(&mut self, field: &str, key: &str) {
+        let list = self.aliases.entry(field.to_string()).or_default();
+        if key != field && !list.iter().any(|k| k == key) {
+            list.push(key.to_string());
+        }
+    }
+
+    /// Canonical name first, then renamed keys in declaration order.
+    pub fn candidates<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a str> {
+        std::iter::once(field).chain(
+            self.aliases
+                .get(field)
+                .into_iter()
+                .flatten()
+                .map(String::as_str),
+        )
+    }
+}
+
+/// Parsed header of an existing note.
+#[derive(Debug, Clone)]
+pub struct NoteHeader {
+    pub style: HeaderStyle,
+    values: BTreeMap<String, Value>,
+}
+
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Sequence(items) => items.first().and_then(scalar_string),
+        _ => None,
+    }
+}
+
+impl NoteHeader {
+    /// Parse the header of `content`.
+    ///
+    /// Returns `None` when the note has no header, `Some(Err(_))` when the YAML
+    /// block is malformed.
+    pub fn parse(content: &str) -> Option<Result<NoteHeader>> {
+        let layout = layout(content)?;
+        let values = match layout.style {
+            HeaderStyle::Yaml => {
+                if layout.header.trim().is_empty() {
+                    BTreeMap::new()
+                } else {
+                    match serde_yaml::from_str::<Mapping>(layout.header) {
+                        Ok(map) => map
+                            .into_iter()
+                            .filter_map(|(k, v)| scalar_string(&k).map(|k| (k, v)))
+                            .collect(),
+                        Err(e) => {
+                            return Some(Err(anyhow::anyhow!(
+                                "failed to parse frontmatter YAML: {}",
+                                e
+                            )))
+                        }
+                    }
+                }
+            }
+            HeaderStyle::Logseq => layout
+                .header
+                .lines()
+                .filter_map(|line| LOGSEQ_PROPERTY_RE.captures(line.trim_end()))
+                .map(|c| (c[1].to_string(), Value::String(c[2].trim().to_string())))
+                .collect(),
+            // Org property names are case-insensitive; the writer upper-cases them.
+            HeaderStyle::Org => layout
+                .header
+                .lines()
+                .filter_map(|line| ORG_PROPERTY_RE.captures(line.trim()))
+                .map(|c| {
+                    let value = c.get(2).map_or("", |m| m.as_str()).trim();
+                    (c[1].to_lowercase(), Value::String(value.to_string()))
+                })
+                .collect(),
+            HeaderStyle::Json => match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(
+                layout.header,
+            ) {
+                Ok(map) => map
+                    .into_iter()
+                    .filter_map(|(k, v)| serde_yaml::to_value(v).ok().map(|v| (k, v)))
+                    .collect(),
+                Err(e) => {
+                    return Some(Err(anyhow::anyhow!("failed to parse note metadata JSON: {}", e)))
+                }
+            },
+        };
+        Some(Ok(NoteHeader { style: layout.style, values }))
+    }
+
+    /// [`NoteHeader::parse`], treating a malformed header as absent.
+    pub fn read(content: &str) -> Option<NoteHeader> {
+        NoteHeader::parse(content).and_then(|r| r.ok())
+    }
+
+    /// Whether `key` (as written, not canonical) is present.
+    pub fn has_key(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    fn raw(&self, field: &str, keys: &FieldKeys) -> Option<&Value> {
+        keys.candidates(field).find_map(|k| self.values.get(k))
+    }
+
+    /// Scalar value of a canonical field; empty strings count as absent.
+    pub fn get_str(&self, field: &str, keys: &FieldKeys) -> Option<String> {
+        self.raw(field, keys)
+            .and_then(scalar_string)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// List value of a canonical field. Logseq lists are comma-separated, Org
+    /// lists are `[[file:…]]` links (or comma-separated values).
+    pub fn get_list(&self, field: &str, keys: &FieldKeys) -> Vec<String> {
+        match self.raw(field, keys) {
+            Some(Value::Sequence(items)) => items.iter().filter_map(scalar_string).collect(),
+            Some(Value::String(s)) if self.style == HeaderStyle::Org && s.contains("[[file:") => {
+                ORG_FILE_LINK_RE.captures_iter(s).map(|c| c[1].to_string()).collect()
+            }
+            Some(Value::String(s)) if matches!(self.style, HeaderStyle::Logseq | HeaderStyle::Org) => s
+                .split(',')
+                .map(|i| i.trim().to_string())
+                .filter(|i| !i.is_empty())
+                .collect(),
+            Some(Value::String(s)) if !s.trim().is_empty() => vec![s.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sender address: `from_address` when written, else the first address of `from`.
+    pub fn sender_address(&self, keys: &FieldKeys) -> String {
+        self.get_str("from_address", keys)
+            .or_else(|| {
+                self.get_str("from", keys)
+                    .and_then(|from| extract_emails(Some(&from)).into_iter().next())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Parsed `date`, whatever `date_format` wrote it.
+    pub fn date(&self, keys: &FieldKeys) -> Option<DateTime<FixedOffset>> {
+        self.get_str("date", keys).and_then(|d| parse_note_date(&d))
+    }
+}
+
+/// Parse a frontmatter date: RFC 3339, RFC 2822, naive date-times and plain
+/// dates (taken as UTC), optionally wrapped in a `[[…]]` page link.
+pub fn parse_note_date(raw: &str) -> Option<DateTime<FixedOffset>> {
+    let s = raw.trim().trim_start_matches("[[").trim_end_matches("]]").trim();
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d);
+    }
+    if let Ok(d) = DateTime::parse_from_rfc2822(s) {
+        return Some(d);
+    }
+    const NAIVE: &[&str] = &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+    for fmt in NAIVE {
+        if let Ok(d) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Some(d.and_utc().fixed_offset());
+        }
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc().fixed_offset())
+}
+
+/// Add `fields` to the header of the note at `md_path`, keeping keys the note
+/// already has. Used for per-destination static fields once a note is routed.
+pub fn append_fields(md_path: &Path, fields: &BTreeMap<String, Value>) -> Result<()> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let content = fs::read_to_string(md_path)
+        .with_context(|| format!("failed to read {}", md_path.display()))?;
+    let Some(layout) = layout(&content) else {
+        return Ok(());
+    };
+    if layout.style == HeaderStyle::Json {
+        return append_json_fields(md_path, &content, &layout, fields);
+    }
+    let header = NoteHeader::read(&content);
+    let mut addition = String::new();
+    for (key, value) in fields {
+        let present = match layout.style {
+            HeaderStyle::Org => header.as_ref().is_some_and(|h| h.has_key(&key.to_lowercase())),
+            _ => header.as_ref().is_some_and(|h| h.has_key(key)),
+        };
+        if present {
+            continue;
+        }
+        let entry = match layout.style {
+            HeaderStyle::Yaml => yaml_entry(key, value, ListStyle::Block),
+            HeaderStyle::Org => org_entry(key, value),
+            _ => logseq_entry(key, value),
+        };
+        addition.push('\n');
+        addition.push_str(entry.trim_end_matches('\n'));
+    }
+    if addition.is_empty() {
+        return Ok(());
+    }
+    // An empty YAML header has no line before the closing `---`.
+    let at = layout.insert_at;
+    let addition = if layout.style == HeaderStyle::Yaml && layout.header.is_empty() {
+        addition.trim_start_matches('\n').to_string() + "\n"
+    } else {
+        addition
+    };
+    let updated = format!("{}{}{}", &content[..at], addition, &content[at..]);
+    fs::write(md_path, updated)
+        .with_context(|| format!("failed to write {}", md_path.display()))
+}
+
+/// `:KEY: value` line of an Org property drawer.
+pub(crate) fn org_entry(key: &str, value: &Value) -> String {
+    format!(":{}: {}\n", key.to_uppercase(), flat_value(value))
+}
+
+/// JSON metadata, escaped so it can sit inside an HTML `<script>` block.
+pub(crate) fn metadata_json(map: &serde_json::Map<String, serde_json::Value>) -> String {
+    serde_json::to_string(map)
+        .unwrap_or_else(|_| "{}".to_string())
+        .replace('<', "\\u003c")
+}
+
+/// [`append_fields`] for JSON headers (JSON note, HTML metadata).
+fn append_json_fields(
+    path: &Path,
+    content: &str,
+    layout: &NoteLayout<'_>,
+    fields: &BTreeMap<String, Value>,
+) -> Result<()> {
+    let mut map: serde_json::Map<String, serde_json::Value> = serde_json::from_str(layout.header)
+        .with_context(|| format!("failed to parse note metadata in {}", path.display()))?;
+    let mut changed = false;
+    for (key, value) in fields {
+        if !map.contains_key(key) {
+            map.insert(key.clone(), serde_json::to_value(value)?);
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(());
+    }
+    let end = layout.start + layout.header.len();
+    let updated = format!("{}{}{}", &content[..layout.start], metadata_json(&map), &content[end..]);
+    fs::write(path, updated).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> EmailFrontmatter {
+        EmailFrontmatter {
+            from: "\"Martin, Alice\" <alice@example.com>".to_string(),
+            to: "Bob <bob@corp.fr>, carol@corp.fr".to_string(),
+            date: "2024-03-05T10:30:00+01:00".to_string(),
+            subject: "Devis 2024".to_string(),
+            message_id: Some("abc@example.com".to_string()),
+            subject_hash: "a1b2c3".to_string(),
+            tags: vec!["INBOX".to_string(), "Clients Pro".to_string()],
+            attachments: vec!["2024-03-05_devis.pdf".to_string()],
+            email_type: Some("direct".to_string()),
+            social_links: None,
+            flags: vec![],
+            parse_degraded: false,
+            event: None,
+            signature: None,
+            charset_repaired: false,
+            signature_block: None,
+        }
+    }
+
+    fn date() -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339("2024-03-05T10:30:00+01:00").ok()
+    }
+
+    fn schema(yaml: &str) -> FrontmatterSchema {
+        serde_yaml::from_str::<FrontmatterSettings>(yaml).unwrap().resolve()
+    }
+
+    #[test]
+    fn test_default_schema_matches_struct_serialization() {
+        let fm = sample();
+        let header = render_header(&fm, date(), &FrontmatterSchema::default());
+        let legacy = format!("---\n{}---\n\n", serde_yaml::to_string(&fm).unwrap());
+        assert_eq!(header, legacy);
+    }
+
+    #[test]
+    fn test_obsidian_preset() {
+        let header = render_header(&sample(), date(), &schema("dialect: obsidian"));
+        // Inclusive: aliases, created, wikilinks, address kept, tags without spaces
+        assert!(header.contains("aliases:\n- Devis 2024\n"), "{}", header);
+        assert!(header.contains("created: 2024-03-05T10:30:00\n"), "{}", header);
+        assert!(header.contains("from: '[[Martin, Alice]]'"), "{}", header);
+        assert!(header.contains("from_address: alice@example.com"), "{}", header);
+        assert!(header.contains("to: '[[Bob]], [[carol@corp.fr]]'"), "{}", header);
+        assert!(header.contains("- Clients-Pro"), "{}", header);
+        // Exclusive: no canonical `date:` key
+        assert!(!header.contains("\ndate:"), "{}", header);
+    }
+
+    #[test]
+    fn test_logseq_preset_roundtrip() {
+        let content = format!(
+            "{}Body line",
+            render_header(&sample(), date(), &schema("dialect: logseq"))
+        );
+        assert!(content.starts_with("from:: [[Martin, Alice]]\n"), "{}", content);
+        assert!(!content.contains("---"));
+
+        let keys = FieldKeys::builtin();
+        let header = NoteHeader::read(&content).unwrap();
+        assert_eq!(header.style, HeaderStyle::Logseq);
+        assert_eq!(header.sender_address(&keys), "alice@example.com");
+        assert_eq!(header.get_list("attachments", &keys), vec!["2024-03-05_devis.pdf"]);
+        assert_eq!(header.date(&keys).unwrap().format("%Y-%m").to_string(), "2024-03");
+        assert_eq!(note_body(&content), "Body line");
+    }
+
+    #[test]
+    fn test_hugo_preset() {
+        let header = render_header(&sample(), date(), &schema("dialect: hugo"));
+        assert!(header.starts_with("---\ntitle: Devis 2024\ndate: 2024-03-05T10:30:00+01:00\n"), "{}", header);
+        assert!(header.contains("draft: false\n"), "{}", header);
+    }
+
+    #[test]
+    fn test_custom_fields_rename_and_flow_lists() {
+        let s = schema(
+            "fields: [subject, date, tags, attachments]\nrename:\n  attachments: files\ndate_format: \"%d/%m/%Y\"\nlist_style: flow\nstatic:\n  type: email\n",
+        );
+        let header = render_header(&sample(), date(), &s);
+        assert_eq!(
+            header,
+            "---\nsubject: Devis 2024\ndate: 05/03/2024\ntags: [INBOX, Clients Pro]\nfiles: [2024-03-05_devis.pdf]\ntype: email\n---\n\n"
+        );
+
+        // Readers resolve the renamed key once told about it
+        let mut keys = FieldKeys::builtin();
+        keys.add_renames(s.rename.iter());
+        let parsed = NoteHeader::read(&header).unwrap();
+        assert_eq!(parsed.get_list("attachments", &keys), vec!["2024-03-05_devis.pdf"]);
+        // Exclusive: without the rename the list is not found
+        assert!(parsed.get_list("attachments", &FieldKeys::builtin()).is_empty());
+    }
+
+    #[test]
+    fn test_flow_list_quotes_commas() {
+        let mut fm = sample();
+        fm.tags = vec!["a, b".to_string()];
+        let header = render_header(&fm, date(), &schema("list_style: flow"));
+        assert!(header.contains("tags: [\"a, b\"]"), "{}", header);
+        let parsed = NoteHeader::read(&header).unwrap();
+        assert_eq!(parsed.get_list("tags", &FieldKeys::builtin()), vec!["a, b"]);
+    }
+
+    #[test]
+    fn test_settings_merge_account_over_defaults() {
+        let defaults: FrontmatterSettings =
+            serde_yaml::from_str("dialect: obsidian\nstatic:\n  source: imap\n").unwrap();
+        let account: FrontmatterSettings =
+            serde_yaml::from_str("rename:\n  subject: title\nstatic:\n  team: sales\n").unwrap();
+        let s = account.merged(&defaults).resolve();
+        assert_eq!(s.dialect, Dialect::Obsidian);
+        assert_eq!(s.key("date"), "created");
+        assert_eq!(s.key("subject"), "title");
+        assert_eq!(s.static_fields.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_and_colliding() {
+        assert!(schema("fields: [from, sender]").validate().is_err());
+        assert!(schema("rename:\n  date: subject\n").validate().is_err());
+        assert!(schema("static:\n  from: x\n").validate().is_err());
+        assert!(schema("date_format: \"%Q\"").validate().is_err());
+        assert!(schema("dialect: obsidian").validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_note_date_formats() {
+        for raw in ["2024-03-05T10:30:00+01:00", "2024-03-05T10:30:00", "2024-03-05", "[[2024-03-05]]"] {
+            let d = parse_note_date(raw).unwrap_or_else(|| panic!("{}", raw));
+            assert_eq!(d.format("%Y-%m-%d").to_string(), "2024-03-05");
+        }
+        assert!(parse_note_date("not a date").is_none());
+    }
+
+    #[test]
+    fn test_append_fields_yaml_and_logseq() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let fields = BTreeMap::from([
+            ("client".to_string(), Value::String("Acme".to_string())),
+            ("subject".to_string(), Value::String("ignored".to_string())),
+        ]);
+
+        let yaml = temp.path().join("a.md");
+        fs::write(&yaml, "---\nsubject: Hi\n---\n\nBody").unwrap();
+        append_fields(&yaml, &fields).unwrap();
+        assert_eq!(fs::read_to_string(&yaml).unwrap(), "---\nsubject: Hi\nclient: Acme\n---\n\nBody");
+
+        let logseq = temp.path().join("b.md");
+        fs::write(&logseq, "subject:: Hi\n\nBody").unwrap();
+        append_fields(&logseq, &fields).unwrap();
+        assert_eq!(fs::read_to_string(&logseq).unwrap(), "subject:: Hi\nclient:: Acme\n\nBody");
+    }
+}
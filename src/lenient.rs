@@ -0,0 +1,270 @@
+//! Tolerant recovery for messages `mailparse` rejects or misreads.
+//!
+//! `recover` leaves well-formed messages alone. Otherwise it rebuilds the
+//! top-level header block (`repair`): line endings normalized (bare LF, lone
+//! CR), overhanging or garbage lines folded into the previous header or
+//! dropped, 8-bit header values decoded with a guessed charset (the message's
+//! `charset`, else windows-1252), and a multipart boundary inferred from the
+//! body when the declared one is missing or never used. When the repaired
+//! message still does not parse (e.g. a broken part header), `degraded`
+//! keeps the best-effort headers and the undecoded body as a plain-text
+//! message, and the note is flagged `parse_degraded: true`.
+
+use encoding_rs::{Encoding, WINDOWS_1252};
+use mailparse::ParsedMail;
+
+/// Headers kept in a degraded message.
+const DEGRADED_HEADERS: &[&str] = &["From", "To", "Cc", "Date", "Subject", "Message-ID"];
+
+/// Bytes to export in place of a malformed message.
+#[derive(Debug)]
+pub struct Recovered {
+    pub bytes: Vec<u8>,
+    /// Not parseable even after repair: best-effort headers, undecoded body.
+    pub degraded: bool,
+}
+
+/// A repaired or degraded copy of `raw`, or `None` when it parses cleanly.
+pub fn recover(raw: &[u8]) -> Option<Recovered> {
+    match mailparse::parse_mail(raw) {
+        Ok(mail) if !needs_repair(raw, &mail) => return None,
+        _ => {}
+    }
+    let repaired = repair(raw);
+    if mailparse::parse_mail(&repaired).is_ok() {
+        return Some(Recovered { bytes: repaired, degraded: false });
+    }
+    Some(Recovered { bytes: degraded(raw), degraded: true })
+}
+
+/// Parsed, but misread: 8-bit non-UTF-8 headers (decoded as Latin-1 by
+/// `mailparse`) or a multipart message whose parts were not found.
+fn needs_repair(raw: &[u8], mail: &ParsedMail) -> bool {
+    let (lines, _) = split_message(raw);
+    let eight_bit = lines
+        .iter()
+        .any(|l| !l.is_ascii() && std::str::from_utf8(l).is_err());
+    eight_bit || (mail.ctype.mimetype.starts_with("multipart/") && mail.subparts.is_empty())
+}
+
+/// Header lines (without line endings) and the body. The header block ends
+/// at the first empty line; CRLF, LF and lone CR all end a line.
+fn split_message(raw: &[u8]) -> (Vec<&[u8]>, &[u8]) {
+    let mut lines = Vec::new();
+    let (mut start, mut i) = (0, 0);
+    while i < raw.len() {
+        let end = match raw[i] {
+            b'\r' if raw.get(i + 1) == Some(&b'\n') => i + 2,
+            b'\r' | b'\n' => i + 1,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        if i == start {
+            return (lines, &raw[end..]);
+        }
+        lines.push(&raw[start..i]);
+        start = end;
+        i = end;
+    }
+    if start < raw.len() {
+        lines.push(&raw[start..]);
+    }
+    (lines, &[])
+}
+
+/// Unfold header lines into `(name, raw value)` fields. Continuation and
+/// garbage lines join the previous field; those before any field are dropped.
+fn header_fields(lines: &[&[u8]]) -> Vec<(String, Vec<u8>)> {
+    let mut fields: Vec<(String, Vec<u8>)> = Vec::new();
+    for line in lines {
+        let name_end = line.iter().position(|&b| b == b':');
+        let name = name_end.map(|end| &line[..end]).filter(|name| {
+            !name.is_empty() && name.iter().all(|&b| b.is_ascii_graphic())
+        });
+        match (name, fields.last_mut()) {
+            (Some(name), _) if !line.starts_with(b" ") && !line.starts_with(b"\t") => {
+                let value = line[name.len() + 1..].trim_ascii().to_vec();
+                fields.push((String::from_utf8_lossy(name).into_owned(), value));
+            }
+            (_, Some((_, value))) => {
+                value.push(b' ');
+                value.extend_from_slice(line.trim_ascii());
+            }
+            (_, None) => {}
+        }
+    }
+    fields
+}
+
+fn field<'a>(fields: &'a [(String, Vec<u8>)], name: &str) -> Option<&'a [u8]> {
+    fields
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_slice())
+}
+
+/// Charset of the message (`Content-Type` parameter), windows-1252 when
+/// absent or unknown (a superset of the Latin-1 legacy mailers used).
+fn guess_charset(fields: &[(String, Vec<u8>)]) -> &'static Encoding {
+    field(fields, "Content-Type")
+        .map(|v| mailparse::parse_content_type(&String::from_utf8_lossy(v)))
+        .and_then(|ct| Encoding::for_label(ct.charset.as_bytes()))
+        .filter(|e| *e != encoding_rs::UTF_8)
+        .unwrap_or(WINDOWS_1252)
+}
+
+/// UTF-8 text of a header value or body: as is when valid UTF-8, else
+/// decoded with `charset`.
+fn decode(bytes: &[u8], charset: &'static Encoding) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => charset.decode(bytes).0.into_owned(),
+    }
+}
+
+/// Delimiter of the first line of `body` that looks like a multipart
+/// boundary (`--xyz`, or `--xyz--` when only the closing one is left).
+fn infer_boundary(body: &[u8]) -> Option<String> {
+    body_lines(body).into_iter().find_map(|line| {
+        let line = String::from_utf8_lossy(line);
+        let delimiter = line.trim_end().strip_prefix("--")?;
+        let delimiter = delimiter.strip_suffix("--").unwrap_or(delimiter);
+        (!delimiter.is_empty() && !delimiter.contains(char::is_whitespace)).then(|| delimiter.to_string())
+    })
+}
+
+/// Every line of a body, empty ones included.
+fn body_lines(body: &[u8]) -> Vec<&[u8]> {
+    body.split(|&b| b == b'\n')
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .collect()
+}
+
+fn has_delimiter(body: &[u8], boundary: &str) -> bool {
+    let delimiter = format!("--{}", boundary);
+    body_lines(body).iter().any(|l| l.starts_with(delimiter.as_bytes()))
+}
+
+/// `Content-Type` value with a usable boundary: the declared one when the
+/// body uses it, else one inferred from the body; without any delimiter the
+/// body is read as a single text part.
+fn repair_content_type(value: &str, body: &[u8]) -> String {
+    let ct = mailparse::parse_content_type(value);
+    if !ct.mimetype.starts_with("multipart/") {
+        return value.to_string();
+    }
+    let declared = ct.params.get("boundary").filter(|b| has_delimiter(body, b));
+    match declared.cloned().or_else(|| infer_boundary(body)) {
+        Some(boundary) => {
+            let mut params: Vec<String> = ct
+                .params
+                .iter()
+                .filter(|(k, _)| *k != "boundary")
+                .map(|(k, v)| format!("{}=\"{}\"", k, v))
+                .collect();
+            params.sort();
+            params.insert(0, ct.mimetype.clone());
+            params.push(format!("boundary=\"{}\"", boundary));
+            params.join("; ")
+        }
+        None => format!("text/plain; charset=\"{}\"", ct.charset),
+    }
+}
+
+/// `raw` with its top-level header block rebuilt (see the module docs).
+pub fn repair(raw: &[u8]) -> Vec<u8> {
+    let (lines, body) = split_message(raw);
+    let fields = header_fields(&lines);
+    let charset = guess_charset(&fields);
+
+    let mut out = Vec::with_capacity(raw.len());
+    for (name, value) in &fields {
+        let mut value = decode(value, charset);
+        if name.eq_ignore_ascii_case("Content-Type") {
+            value = repair_content_type(&value, body);
+        }
+        out.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    out.extend_from_slice(b"\r\n");
+    out.extend_from_slice(body);
+    out
+}
+
+/// Plain-text message from the best-effort headers and the undecoded body.
+pub fn degraded(raw: &[u8]) -> Vec<u8> {
+    let (lines, body) = split_message(raw);
+    let fields = header_fields(&lines);
+    let charset = guess_charset(&fields);
+    // No header block at all: the whole message is the body
+    let body = if fields.is_empty() { raw } else { body };
+
+    let mut out = String::new();
+    for name in DEGRADED_HEADERS {
+        if let Some(value) = field(&fields, name) {
+            out.push_str(&format!("{}: {}\r\n", name, decode(value, charset)));
+        }
+    }
+    out.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    out.push_str("Content-Transfer-Encoding: 8bit\r\n\r\n");
+    out.push_str(&decode(body, charset).replace('\r', ""));
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mailparse::MailHeaderMap;
+
+    #[test]
+    fn test_clean_message_is_left_alone() {
+        assert!(recover(b"From: a@example.com\r\nSubject: Hi\r\n\r\nBody\r\n").is_none());
+    }
+
+    #[test]
+    fn test_repair_header_block() {
+        // Overhanging first line, lone CR ending the headers, 8-bit Latin-1 subject
+        let raw = b" stray continuation\nFrom: Zo\xeb <zoe@example.com>\nSubject: R\xe9union\r\
+                    Content-Type: text/plain; charset=iso-8859-1\r\r\nCaf\xe9\n";
+        let recovered = recover(raw).expect("malformed");
+        assert!(!recovered.degraded);
+        let mail = mailparse::parse_mail(&recovered.bytes).unwrap();
+        assert_eq!(mail.headers.get_first_value("From").unwrap(), "Zoë <zoe@example.com>");
+        assert_eq!(mail.headers.get_first_value("Subject").unwrap(), "Réunion");
+        assert_eq!(mail.get_body().unwrap().trim(), "Café");
+    }
+
+    #[test]
+    fn test_repair_infers_missing_boundary() {
+        let raw = b"From: a@example.com\r\nContent-Type: multipart/alternative\r\n\r\n\
+                    --b1\r\nContent-Type: text/plain\r\n\r\nplain part\r\n--b1--\r\n";
+        let recovered = recover(raw).expect("no parts found");
+        let mail = mailparse::parse_mail(&recovered.bytes).unwrap();
+        assert_eq!(mail.subparts.len(), 1);
+        assert_eq!(mail.subparts[0].get_body().unwrap().trim(), "plain part");
+
+        // Exclusive: no delimiter at all — read as one text part
+        let raw = b"From: a@example.com\r\nContent-Type: multipart/mixed; boundary=zz\r\n\r\njust text\r\n";
+        let mail_bytes = recover(raw).unwrap().bytes;
+        let mail = mailparse::parse_mail(&mail_bytes).unwrap();
+        assert_eq!(mail.ctype.mimetype, "text/plain");
+        assert_eq!(mail.get_body().unwrap().trim(), "just text");
+    }
+
+    #[test]
+    fn test_degraded_keeps_headers_and_raw_body() {
+        // The part header starts with a space: mailparse rejects the whole message
+        let raw = b"From: a@example.com\r\nSubject: Broken\r\nX-Junk: 1\r\n\
+                    Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+                    --b\r\n bad part header\r\n\r\n=E9t=E9\r\n--b--\r\n";
+        let recovered = recover(raw).unwrap();
+        assert!(recovered.degraded);
+        let mail = mailparse::parse_mail(&recovered.bytes).unwrap();
+        assert_eq!(mail.headers.get_first_value("Subject").unwrap(), "Broken");
+        assert!(mail.headers.get_first_value("X-Junk").is_none());
+        // Undecoded: quoted-printable and delimiters kept as is
+        let body = mail.get_body().unwrap();
+        assert!(body.contains("--b\n bad part header") && body.contains("=E9t=E9"));
+    }
+}
@@ -4,7 +4,31 @@ pub mod destinations;  // YAML storage for routing destinations
 pub mod dest_cmd;      // `dest` CLI subcommand (list, add, suggest)
 pub mod config;
 pub mod utils;
+pub mod filename;     // Note filename templates and portable-name policy
+pub mod frontmatter;  // Frontmatter schema, dialects and note-header reader
+pub mod output;       // Note output formats (Markdown, Org, JSON, HTML)
+pub mod body;         // Body selection in MIME trees (`body_preference`)
+pub mod cid;          // Inline images: `cid:` references → saved attachment files
+pub mod embedded;     // Embedded messages (`message/rfc822`) exported as child notes
+pub mod calendar;     // Calendar invitations (`text/calendar`) to event fields and `.ics`
+pub mod tnef;         // TNEF (`winmail.dat`) attachments and RTF body
+pub mod crypto;       // PGP/MIME and S/MIME: decryption and signature status
+pub mod signatures;   // Signature block detection and stripping (`signatures:`)
+pub mod folder_rules; // Folder include/exclude patterns and per-folder overrides
+pub mod after_export;  // Post-export server actions (move, keyword, \Seen)
+pub mod deletion;      // Two-phase safe deletion and deletion journal
+pub mod deleted_cmd;   // `deleted` CLI subcommand (list, restore-info)
+pub mod mirror;        // Server-side filing: routing tree mirrored onto IMAP folders
+pub mod rebuild;       // Rebuild RFC 5322 messages from notes (restore)
+pub mod restore_cmd;   // `restore` CLI subcommand (APPEND notes to a mailbox)
+pub mod mbox_cmd;      // `export-mbox` CLI subcommand (notes to mbox / .eml files)
+pub mod plan;          // `export --dry-run` plan (decisions, routes, deletions)
+pub mod quarantine;    // Failed messages kept in `_failed/` with an error report
+pub mod retry_cmd;     // `retry-failed` CLI subcommand (re-export quarantined messages)
+pub mod namespace;    // IMAP NAMESPACE discovery, shared/other-users namespaces
+pub mod lenient;      // Tolerant recovery for malformed MIME messages
 pub mod cleaner;      // Email body cleaner pipeline
+pub mod clean_cmd;    // `clean` CLI subcommand (cleaned body, `--explain` per stage)
 pub mod thunderbird;  // [1] Import automatique depuis Thunderbird
 pub mod network;      // [3][4] Progress indicator et retry logic
 #[cfg(feature = "tray")]
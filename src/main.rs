@@ -2,9 +2,20 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use std::path::{Path, PathBuf};
 
+use email_to_markdown::clean_cmd;
 use email_to_markdown::config::{self, Config, Settings};
+use email_to_markdown::deleted_cmd;
+use email_to_markdown::deletion;
 use email_to_markdown::dest_cmd;
+use email_to_markdown::destinations;
 use email_to_markdown::email_export::ImapExporter;
+use email_to_markdown::folder_rules;
+use email_to_markdown::frontmatter;
+use email_to_markdown::mbox_cmd;
+use email_to_markdown::plan;
+use email_to_markdown::quarantine;
+use email_to_markdown::restore_cmd;
+use email_to_markdown::retry_cmd;
 use email_to_markdown::route;
 use email_to_markdown::thunderbird;  // [1] Import Thunderbird
 
@@ -65,6 +76,19 @@ enum Commands {
         #[arg(long)]
         delete_after_export: bool,
 
+        /// List the IMAP moves `mirror_routing` would make, without moving anything
+        #[arg(long)]
+        mirror_dry_run: bool,
+
+        /// Show what the export would do (exports, routes, deletions) without
+        /// writing notes or changing anything on the server
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With --dry-run: print the plan as JSON instead of a table
+        #[arg(long, requires = "dry_run")]
+        json: bool,
+
         /// Path to config file (default: platform config dir)
         #[arg(short, long)]
         config: Option<PathBuf>,
@@ -77,6 +101,21 @@ enum Commands {
     /// Manage routing destinations (list, add)
     Dest(dest_cmd::DestArgs),
 
+    /// Inspect the deletion journal (list, restore-info)
+    Deleted(deleted_cmd::DeletedArgs),
+
+    /// Append notes back to an IMAP mailbox (original message when kept, else rebuilt)
+    Restore(restore_cmd::RestoreArgs),
+
+    /// Write notes as an mbox file (or .eml files) for another mail client
+    ExportMbox(mbox_cmd::ExportMboxArgs),
+
+    /// Export again the messages kept in quarantine (`_failed/`) after a failure
+    RetryFailed(retry_cmd::RetryFailedArgs),
+
+    /// Run the body cleaner on a message (`--explain`: what each stage changed)
+    Clean(clean_cmd::CleanArgs),
+
     /// Run as system tray application (requires --features tray)
     #[cfg(feature = "tray")]
     Tray,
@@ -223,6 +262,9 @@ fn main() -> Result<()> {
             account,
             list_accounts,
             delete_after_export,
+            mirror_dry_run,
+            dry_run,
+            json,
             config,
             debug,
         } => {
@@ -276,6 +318,10 @@ fn main() -> Result<()> {
                 return Ok(());
             }
 
+            if dry_run {
+                return print_plan(accounts_to_export, delete_after_export, json, debug);
+            }
+
             println!("Exporting {} account(s)", accounts_to_export.len());
 
             for mut account in accounts_to_export {
@@ -290,6 +336,15 @@ fn main() -> Result<()> {
                 }
 
                 account.delete_after_export = delete_after_export || account.delete_after_export;
+                // `--delete-after-export` must not combine with `after_export.move_to`
+                if let Err(e) = folder_rules::validate_account(&account) {
+                    println!("Error for {}: {}", account.name, e);
+                    continue;
+                }
+                if mirror_dry_run {
+                    account.mirror_routing.enabled = true;
+                    account.mirror_routing.dry_run = true;
+                }
 
                 let mut exporter = ImapExporter::new(account.clone(), debug);
 
@@ -301,24 +356,67 @@ fn main() -> Result<()> {
                                 let total_skipped: usize = results.values().map(|s| s.skipped).sum();
                                 let total_errors: usize = results.values().map(|s| s.errors).sum();
 
+                                let total_quarantined: usize = results.values().map(|s| s.quarantined).sum();
+
                                 println!(
-                                    "\nExport completed for {}: {} exported, {} skipped, {} errors",
-                                    account.name, total_exported, total_skipped, total_errors
+                                    "\nExport completed for {}: {} exported, {} skipped, {} errors, {} quarantined",
+                                    account.name, total_exported, total_skipped, total_errors, total_quarantined
                                 );
+                                let base_dir = Path::new(&account.export_directory);
+                                let in_quarantine = quarantine::list(base_dir).len();
+                                if in_quarantine > 0 {
+                                    println!(
+                                        "Quarantine: {} message(s) in {} — run `email-to-markdown retry-failed --account {}`",
+                                        in_quarantine,
+                                        quarantine::quarantine_dir(base_dir).display(),
+                                        account.name
+                                    );
+                                }
 
                                 // CLI mode (D8): apply routing decisions automatically, no review.
                                 // Pipeline order: Export → route decisions accumulated above → apply now.
-                                // IMAP deletion flags were set during Export; local .md files remain
-                                // in staging until this apply step moves them into notes_dir.
+                                // Local .md files remain in staging until this apply step moves them
+                                // into notes_dir; deletions wait for the notes' final paths.
                                 let settings = Settings::load(&config::settings_path())
                                     .unwrap_or_default();
                                 if let Some(notes_dir_str) = &settings.notes_dir {
                                     let notes_dir = PathBuf::from(notes_dir_str);
+                                    // Per-destination static frontmatter fields; a missing or
+                                    // broken file was already reported by the export itself.
+                                    let dest_cfg = destinations::load_yaml(&route::destinations_path())
+                                        .unwrap_or_default();
                                     let mut moved = 0usize;
                                     let mut apply_errors = 0usize;
+                                    let mut routed = Vec::new();
+                                    let mut relocated = Vec::new();
                                     for (staging_path, decision) in &decisions {
-                                        match route::apply_decision(staging_path, &decision.rel_path, &notes_dir) {
-                                            Ok(()) => moved += 1,
+                                        match route::apply_decision(staging_path, &decision.rel_path, &notes_dir, &account.field_keys) {
+                                            Ok(final_path) => {
+                                                moved += 1;
+                                                routed.push((staging_path.clone(), decision.clone()));
+                                                relocated.push((staging_path.clone(), final_path.clone()));
+                                                // Child notes of embedded messages, filed on their own
+                                                let children = route::apply_embedded(
+                                                    &final_path,
+                                                    &decision.embedded,
+                                                    &notes_dir,
+                                                    &account.field_keys,
+                                                );
+                                                let notes = std::iter::once((final_path.clone(), decision.rel_path.clone()))
+                                                    .chain(children);
+                                                for (note_path, rel_path) in notes {
+                                                    if let Some(fields) =
+                                                        destinations::static_fields_for(&dest_cfg, &rel_path)
+                                                    {
+                                                        if let Err(e) = frontmatter::append_fields(&note_path, fields) {
+                                                            eprintln!(
+                                                                "Warning: could not add destination fields to {}: {:#}",
+                                                                note_path.display(), e
+                                                            );
+                                                        }
+                                                    }
+                                                }
+                                            }
                                             Err(e) => {
                                                 apply_errors += 1;
                                                 eprintln!(
@@ -334,6 +432,24 @@ fn main() -> Result<()> {
                                             moved, apply_errors
                                         );
                                     }
+                                    if let Err(e) = deletion::relocate_pending(&deletion::pending_path(), &relocated) {
+                                        eprintln!("Warning: could not update pending deletions: {:#}", e);
+                                    }
+
+                                    // Server-side filing: mirror the routing tree onto IMAP folders
+                                    if account.mirror_routing.enabled && !routed.is_empty() {
+                                        match exporter.mirror_routed(&routed) {
+                                            Ok(report) if account.mirror_routing.dry_run => println!(
+                                                "Mirror (dry run): {} move(s) planned, {} not filed",
+                                                report.planned, report.skipped
+                                            ),
+                                            Ok(report) => println!(
+                                                "Mirror: {} message(s) filed on the server, {} not filed, {} errors",
+                                                report.moved, report.skipped, report.errors
+                                            ),
+                                            Err(e) => eprintln!("Warning: server-side filing failed: {:#}", e),
+                                        }
+                                    }
                                 } else if !decisions.is_empty() {
                                     println!(
                                         "Note: notes_dir not configured in settings.yaml — \
@@ -341,6 +457,32 @@ fn main() -> Result<()> {
                                         decisions.len()
                                     );
                                 }
+
+                                // Delete only the messages whose note is verified at its final path.
+                                // Runs whatever the account-level setting: a `folders:` override
+                                // may have enabled `delete_after_export` for some folders only.
+                                match exporter.delete_verified(settings.notes_dir.as_deref().map(Path::new)) {
+                                    Ok(report) if report.deleted + report.waiting + report.rejected > 0 => {
+                                        println!(
+                                            "Deletion: {} message(s) deleted from the server, {} awaiting routing, {} kept (note missing or modified)",
+                                            report.deleted, report.waiting, report.rejected
+                                        );
+                                        if report.flagged_only > 0 {
+                                            println!(
+                                                "Note: the server lacks UIDPLUS — {} of them are only flagged \\Deleted until a mail client expunges the folder",
+                                                report.flagged_only
+                                            );
+                                        }
+                                        if report.gone > 0 {
+                                            println!(
+                                                "Note: {} message(s) were no longer in their folder and were not deleted",
+                                                report.gone
+                                            );
+                                        }
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => eprintln!("Warning: deletion failed: {:#}", e),
+                                }
                             }
                             Err(e) => {
                                 println!("Export failed for {}: {}", account.name, e);
@@ -362,6 +504,26 @@ fn main() -> Result<()> {
             dest_cmd::run(args)?;
         }
 
+        Commands::Deleted(args) => {
+            deleted_cmd::run(args)?;
+        }
+
+        Commands::Restore(args) => {
+            restore_cmd::run(args)?;
+        }
+
+        Commands::ExportMbox(args) => {
+            mbox_cmd::run(args)?;
+        }
+
+        Commands::RetryFailed(args) => {
+            retry_cmd::run(args)?;
+        }
+
+        Commands::Clean(args) => {
+            clean_cmd::run(args)?;
+        }
+
         #[cfg(feature = "tray")]
         Commands::Tray => {
             println!("Starting system tray application...");
@@ -371,3 +533,50 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// `export --dry-run`: connect to each account, compute the plan and print it
+/// (table or JSON on stdout; progress and errors go to stderr).
+fn print_plan(
+    accounts: Vec<config::Account>,
+    delete_after_export: bool,
+    json: bool,
+    debug: bool,
+) -> Result<()> {
+    let mut entries = Vec::new();
+    for mut account in accounts {
+        if account.password.is_none() {
+            eprintln!("Error for {}: No password found. Check your .env file.", account.name);
+            continue;
+        }
+        account.delete_after_export = delete_after_export || account.delete_after_export;
+        if let Err(e) = folder_rules::validate_account(&account) {
+            eprintln!("Error for {}: {}", account.name, e);
+            continue;
+        }
+        eprintln!("Planning account: {} ...", account.name);
+
+        let mut exporter = ImapExporter::new(account.clone(), debug);
+        if let Err(e) = exporter.connect() {
+            eprintln!("Connection error for {}: {}", account.name, e);
+            continue;
+        }
+        match exporter.plan_account() {
+            Ok(account_entries) => entries.extend(account_entries),
+            Err(e) => eprintln!("Plan failed for {}: {:#}", account.name, e),
+        }
+        if let Err(e) = exporter.disconnect() {
+            eprintln!("Warning: Disconnect error: {}", e);
+        }
+    }
+
+    if json {
+        println!("{}", plan::render_json(&entries)?);
+    } else {
+        print!("{}", plan::render_table(&entries));
+        let settings = Settings::load(&config::settings_path()).unwrap_or_default();
+        if settings.notes_dir.is_none() && entries.iter().any(|e| e.route.is_some()) {
+            println!("Note: notes_dir not configured in settings.yaml — exported notes would stay in staging");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! `export-mbox` CLI subcommand: hand notes to another mail client.
+//!
+//! Every note under the given paths becomes a message (see `rebuild`: the
+//! original kept by `keep_raw`, or one rebuilt from the header, body and
+//! attachments, keeping `Message-ID` and `Date`). Written as one mbox file
+//! (mboxrd: `From ` lines quoted with `>`) or one `.eml` file per note.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::Args;
+use mailparse::MailHeaderMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Settings};
+use crate::frontmatter::FieldKeys;
+use crate::rebuild::{message_for_note, RebuiltMessage};
+use crate::restore_cmd::collect_notes;
+use crate::utils::{extract_emails, sanitize_filename};
+
+#[derive(Args)]
+#[command(group = clap::ArgGroup::new("target").required(true).args(["output", "eml_dir"]))]
+pub struct ExportMboxArgs {
+    /// Notes, or directories of notes (e.g. a notes_dir subtree)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// mbox file to write
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Write one .eml file per note into this directory instead
+    #[arg(long)]
+    pub eml_dir: Option<PathBuf>,
+}
+
+/// One mbox entry: `From ` separator, message with LF line ends and `From `
+/// lines quoted (mboxrd), blank line.
+pub fn mbox_entry(message: &RebuiltMessage) -> Vec<u8> {
+    let sender = mailparse::parse_headers(&message.bytes)
+        .ok()
+        .and_then(|(headers, _)| headers.get_first_value("From"))
+        .and_then(|from| extract_emails(Some(&from)).into_iter().next())
+        .unwrap_or_else(|| "MAILER-DAEMON".to_string());
+    let date = message
+        .date
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now)
+        .format("%a %b %e %H:%M:%S %Y");
+
+    // Bytes, not text: a kept original may carry 8-bit non-UTF-8 parts
+    let mut out = format!("From {} {}\n", sender, date).into_bytes();
+    let body = message.bytes.strip_suffix(b"\n").unwrap_or(&message.bytes);
+    for line in body.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let unquoted = &line[line.iter().take_while(|&&b| b == b'>').count()..];
+        if unquoted.starts_with(b"From ") {
+            out.push(b'>');
+        }
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    }
+    out.push(b'\n');
+    out
+}
+
+/// `.eml` file name for a note, unique inside `dir`.
+fn eml_path(dir: &Path, note: &Path) -> PathBuf {
+    let stem = sanitize_filename(&note.file_stem().unwrap_or_default().to_string_lossy());
+    let mut path = dir.join(format!("{}.eml", stem));
+    let mut suffix = 2u32;
+    while path.exists() {
+        path = dir.join(format!("{}_{}.eml", stem, suffix));
+        suffix += 1;
+    }
+    path
+}
+
+/// Entry point dispatched from `main`.
+pub fn run(args: ExportMboxArgs) -> Result<()> {
+    let keys = FieldKeys::from_settings(&Settings::load(&config::settings_path()).unwrap_or_default());
+    let notes = collect_notes(&args.paths, &keys);
+    if notes.is_empty() {
+        println!("No notes found.");
+        return Ok(());
+    }
+
+    let mut mbox = match &args.output {
+        Some(path) => Some(
+            fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?,
+        ),
+        None => None,
+    };
+    if let Some(dir) = &args.eml_dir {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+
+    let (mut written, mut errors) = (0usize, 0usize);
+    for note in &notes {
+        let message = match message_for_note(note, &keys) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("Warning: could not convert {}: {:#}", note.display(), e);
+                errors += 1;
+                continue;
+            }
+        };
+        if let Some(file) = mbox.as_mut() {
+            file.write_all(&mbox_entry(&message))
+                .with_context(|| format!("failed to write {}", note.display()))?;
+        } else if let Some(dir) = &args.eml_dir {
+            let path = eml_path(dir, note);
+            fs::write(&path, &message.bytes)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        written += 1;
+    }
+
+    let target = args.output.as_ref().or(args.eml_dir.as_ref()).map(|p| p.display());
+    if let Some(target) = target {
+        println!("export-mbox: {} message(s) written to {}, {} errors", written, target, errors);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_mbox_entry_quotes_from_lines() {
+        let message = RebuiltMessage {
+            bytes: b"From: Alice <alice@example.com>\r\nSubject: Hi\r\n\r\nFrom now on\r\n>From here\r\nok\r\n".to_vec(),
+            date: DateTime::parse_from_rfc3339("2024-01-15T10:00:00+01:00").ok(),
+            flags: Vec::new(),
+            original: false,
+        };
+        let entry = String::from_utf8(mbox_entry(&message)).unwrap();
+        assert!(entry.starts_with("From alice@example.com Mon Jan 15 09:00:00 2024\n"));
+        assert!(entry.contains("\n>From now on\n>>From here\nok\n"));
+        // Exclusive: header lines are not quoted, no CR left
+        assert!(entry.contains("2024\nFrom: Alice <alice@example.com>\n"));
+        assert!(!entry.contains('\r'));
+    }
+}
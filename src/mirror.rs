@@ -0,0 +1,143 @@
+//! Server-side filing: mirror the routing tree onto IMAP folders.
+//!
+//! When `mirror_routing.enabled` is set, every note routed by the CLI export
+//! (`route::apply_decision`) has its source message moved to the IMAP folder
+//! matching its destination, without the `<year>/<month>` suffix:
+//! `Pro/Clients/Acme/2026/06` → `Pro/Clients/Acme` (under `root` when set).
+//! Missing folders are created level by level. Messages are found again by
+//! `Message-ID` in the mailbox they were left in after export (the source
+//! folder, or `after_export.move_to`); messages without one, or deleted by
+//! `delete_after_export`, are not filed. `dry_run` only lists the planned moves.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::route::ends_with_year_month;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorSettings {
+    pub enabled: bool,
+    /// Parent mailbox of the mirrored tree (decoded UTF-8), e.g. `Filed`.
+    /// Destinations are created at the top level when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// Also file messages routed to the default destination.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub include_default: bool,
+    /// List the planned moves without touching the server.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+impl MirrorSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.root.as_deref().is_some_and(|r| r.trim().is_empty()) {
+            return Err("mirror_routing: empty root mailbox".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Where an exported message can be found on the server after the export.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServerCopy {
+    /// Raw (modified UTF-7) mailbox name.
+    pub mailbox: String,
+    /// `Message-ID` without angle brackets.
+    pub message_id: String,
+}
+
+/// One planned server-side move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedMove {
+    pub source: ServerCopy,
+    /// Decoded target mailbox, levels joined by the server delimiter.
+    pub target: String,
+}
+
+/// Target mailbox levels for a routing destination: the `rel_path` without
+/// its `<year>/<month>` suffix, under `root`. `None` when nothing is left.
+pub fn mirror_levels(rel_path: &str, root: Option<&str>) -> Option<Vec<String>> {
+    let trimmed = rel_path.trim_matches('/');
+    let mut segments: Vec<&str> = trimmed.split('/').filter(|s| !s.is_empty()).collect();
+    if ends_with_year_month(trimmed) {
+        segments.truncate(segments.len().saturating_sub(2));
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    let root_levels = root.into_iter().flat_map(|r| r.split('/')).filter(|s| !s.is_empty());
+    Some(root_levels.chain(segments).map(str::to_string).collect())
+}
+
+/// Join mailbox levels with the server delimiter; a delimiter inside a level
+/// is replaced with `_` so it does not create an extra level.
+pub fn mailbox_name(levels: &[String], delimiter: Option<&str>) -> String {
+    match delimiter.filter(|d| !d.is_empty()) {
+        Some(d) => levels.iter().map(|l| l.replace(d, "_")).collect::<Vec<_>>().join(d),
+        None => levels.join("_"),
+    }
+}
+
+/// The mailbox and its ancestors, outermost first: `A.B.C` → `A`, `A.B`, `A.B.C`.
+pub fn mailbox_ancestors(levels: &[String], delimiter: Option<&str>) -> Vec<String> {
+    (1..=levels.len()).map(|n| mailbox_name(&levels[..n], delimiter)).collect()
+}
+
+/// Group planned moves by `(source mailbox, target)`, in a stable order.
+pub fn group_moves(moves: &[PlannedMove]) -> BTreeMap<(String, String), Vec<String>> {
+    let mut groups: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for m in moves {
+        groups
+            .entry((m.source.mailbox.clone(), m.target.clone()))
+            .or_default()
+            .push(m.source.message_id.clone());
+    }
+    groups
+}
+
+/// Quote a string for an IMAP command argument.
+pub fn quote_imap(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_levels_strip_year_month() {
+        assert_eq!(
+            mirror_levels("Pro/Clients/Acme/2026/06", None).unwrap(),
+            vec!["Pro", "Clients", "Acme"]
+        );
+        assert_eq!(mirror_levels("Perso/Banque", Some("Filed")).unwrap(), vec!["Filed", "Perso", "Banque"]);
+        // Exclusive: a bare dated path leaves nothing to mirror
+        assert!(mirror_levels("2026/06", None).is_none());
+    }
+
+    #[test]
+    fn test_mailbox_name_uses_delimiter() {
+        let levels = vec!["Pro".to_string(), "v1.2".to_string()];
+        assert_eq!(mailbox_name(&levels, Some("/")), "Pro/v1.2");
+        assert_eq!(mailbox_name(&levels, Some(".")), "Pro.v1_2");
+        assert_eq!(mailbox_ancestors(&levels, Some(".")), vec!["Pro", "Pro.v1_2"]);
+    }
+
+    #[test]
+    fn test_group_moves() {
+        let mv = |mailbox: &str, id: &str, target: &str| PlannedMove {
+            source: ServerCopy { mailbox: mailbox.into(), message_id: id.into() },
+            target: target.into(),
+        };
+        let groups = group_moves(&[mv("INBOX", "a@x", "Pro"), mv("INBOX", "b@x", "Pro"), mv("Sent", "c@x", "Pro")]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[&("INBOX".to_string(), "Pro".to_string())], vec!["a@x", "b@x"]);
+    }
+
+    #[test]
+    fn test_quote_imap() {
+        assert_eq!(quote_imap(r#"<a"b\c@x>"#), r#""<a\"b\\c@x>""#);
+    }
+}
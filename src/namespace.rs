@@ -0,0 +1,256 @@
+//! IMAP `NAMESPACE` (RFC 2342) discovery and namespace settings.
+//!
+//! The server answers with three groups — personal, other users, shared —
+//! each a list of `(prefix delimiter)` pairs or `NIL`:
+//!
+//! `* NAMESPACE (("" "/")) (("Other Users/" "/")) (("Shared/" "/"))`
+//!
+//! Personal folders are always exported. Other-users and shared namespaces are
+//! opt-in (`namespaces:` in settings.yaml); their folders get a tag prefix and
+//! a local directory of their own (`@<tag_prefix>/…`) so they never collide
+//! with personal folders of the same name.
+
+use serde::{Deserialize, Serialize};
+
+/// The three namespace groups of a `NAMESPACE` response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamespaceKind {
+    Personal,
+    OtherUsers,
+    Shared,
+}
+
+/// One namespace announced by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    pub kind: NamespaceKind,
+    /// Raw (modified UTF-7) prefix, e.g. `Shared/` or `INBOX.`.
+    pub prefix: String,
+    pub delimiter: Option<String>,
+}
+
+/// Which non-personal namespaces are exported, and how they are labelled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamespaceSettings {
+    /// Export the shared namespaces (team mailboxes).
+    pub shared: bool,
+    /// Export other users' namespaces the account has been granted access to.
+    pub other_users: bool,
+    /// Tag prefix and local directory name (`@shared/…`) for shared folders.
+    pub shared_tag_prefix: String,
+    /// Tag prefix and local directory name (`@users/…`) for other users' folders.
+    pub other_users_tag_prefix: String,
+}
+
+impl Default for NamespaceSettings {
+    fn default() -> Self {
+        Self {
+            shared: false,
+            other_users: false,
+            shared_tag_prefix: "shared".to_string(),
+            other_users_tag_prefix: "users".to_string(),
+        }
+    }
+}
+
+impl NamespaceSettings {
+    /// Whether folders of this namespace kind are exported.
+    pub fn exports(&self, kind: NamespaceKind) -> bool {
+        match kind {
+            NamespaceKind::Personal => true,
+            NamespaceKind::OtherUsers => self.other_users,
+            NamespaceKind::Shared => self.shared,
+        }
+    }
+
+    /// Tag prefix of a non-personal namespace kind (`None` for personal).
+    pub fn tag_prefix(&self, kind: NamespaceKind) -> Option<&str> {
+        match kind {
+            NamespaceKind::Personal => None,
+            NamespaceKind::OtherUsers => Some(&self.other_users_tag_prefix),
+            NamespaceKind::Shared => Some(&self.shared_tag_prefix),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let shared = self.shared_tag_prefix.trim();
+        let users = self.other_users_tag_prefix.trim();
+        if shared.is_empty() || users.is_empty() {
+            return Err("namespaces: tag prefixes must not be empty".to_string());
+        }
+        if shared == users {
+            return Err(format!("namespaces: shared and other-users tag prefixes are both {:?}", shared));
+        }
+        if [shared, users].iter().any(|p| p.contains(['/', '\\'])) {
+            return Err("namespaces: tag prefixes must not contain path separators".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Nil,
+    Str(String),
+}
+
+fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' => {
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => s.extend(chars.next()),
+                        '"' => break,
+                        c => s.push(c),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut atom = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                if atom.eq_ignore_ascii_case("NIL") {
+                    tokens.push(Token::Nil);
+                } else {
+                    tokens.push(Token::Str(atom));
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// Parse the untagged `NAMESPACE` line out of a raw command response.
+///
+/// Namespace extension data (RFC 2342 §5) is skipped. Returns an empty list
+/// when no `NAMESPACE` line is present.
+pub fn parse_namespace_response(data: &[u8]) -> Vec<Namespace> {
+    let text = String::from_utf8_lossy(data);
+    let Some(line) = text.lines().find_map(|l| {
+        let rest = l.strip_prefix("* ")?;
+        rest.get(..9)
+            .filter(|keyword| keyword.eq_ignore_ascii_case("NAMESPACE"))
+            .map(|_| &rest[9..])
+    }) else {
+        return Vec::new();
+    };
+
+    let kinds = [NamespaceKind::Personal, NamespaceKind::OtherUsers, NamespaceKind::Shared];
+    let mut namespaces = Vec::new();
+    let mut group = 0;
+    let mut depth = 0;
+    // Strings seen at depth 2 of the current group: prefix then delimiter
+    let mut current: Vec<Option<String>> = Vec::new();
+
+    for token in tokenize(line) {
+        match token {
+            Token::Nil if depth == 0 => group += 1,
+            Token::Open => {
+                depth += 1;
+                if depth == 2 {
+                    current.clear();
+                }
+            }
+            Token::Close => {
+                if depth == 2 && group < kinds.len() {
+                    if let Some(Some(prefix)) = current.first() {
+                        namespaces.push(Namespace {
+                            kind: kinds[group],
+                            prefix: prefix.clone(),
+                            delimiter: current.get(1).cloned().flatten(),
+                        });
+                    }
+                }
+                depth -= 1;
+                if depth == 0 {
+                    group += 1;
+                }
+            }
+            Token::Nil if depth == 2 => current.push(None),
+            Token::Str(s) if depth == 2 => current.push(Some(s)),
+            _ => {}
+        }
+    }
+    namespaces
+}
+
+/// The namespace a raw mailbox name belongs to: the longest non-empty prefix
+/// that matches (the prefix itself without its trailing delimiter counts too).
+/// Names outside every non-empty prefix belong to the personal namespace.
+pub fn namespace_of<'a>(raw: &str, namespaces: &'a [Namespace]) -> Option<&'a Namespace> {
+    namespaces
+        .iter()
+        .filter(|ns| !ns.prefix.is_empty())
+        .filter(|ns| {
+            let bare = ns
+                .delimiter
+                .as_deref()
+                .and_then(|d| ns.prefix.strip_suffix(d))
+                .unwrap_or(&ns.prefix);
+            raw.starts_with(&ns.prefix) || raw == bare
+        })
+        .max_by_key(|ns| ns.prefix.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_namespace_response() {
+        let data = b"* NAMESPACE ((\"\" \"/\")) ((\"Other Users/\" \"/\")) ((\"Shared/\" \"/\" \"X-PARAM\" (\"a\")))\r\n";
+        let ns = parse_namespace_response(data);
+        assert_eq!(ns.len(), 3);
+        assert_eq!(ns[0], Namespace { kind: NamespaceKind::Personal, prefix: String::new(), delimiter: Some("/".into()) });
+        assert_eq!(ns[1].kind, NamespaceKind::OtherUsers);
+        assert_eq!(ns[1].prefix, "Other Users/");
+        assert_eq!(ns[2].kind, NamespaceKind::Shared);
+        assert_eq!(ns[2].prefix, "Shared/");
+    }
+
+    #[test]
+    fn test_parse_namespace_response_nil_groups() {
+        let ns = parse_namespace_response(b"* NAMESPACE ((\"INBOX.\" \".\")) NIL ((\"#shared.\" \".\")(\"#public.\" \".\"))\r\n");
+        assert_eq!(ns.len(), 3);
+        assert_eq!(ns[0].prefix, "INBOX.");
+        assert_eq!(ns[1].kind, NamespaceKind::Shared);
+        assert_eq!(ns[2].prefix, "#public.");
+        // Exclusive: no NAMESPACE line
+        assert!(parse_namespace_response(b"* OK hello\r\n").is_empty());
+    }
+
+    #[test]
+    fn test_namespace_of_longest_prefix() {
+        let ns = parse_namespace_response(b"* NAMESPACE ((\"INBOX.\" \".\")) NIL ((\"INBOX.shared.\" \".\"))\r\n");
+        assert_eq!(namespace_of("INBOX.shared.support", &ns).unwrap().kind, NamespaceKind::Shared);
+        assert_eq!(namespace_of("INBOX.shared", &ns).unwrap().kind, NamespaceKind::Shared);
+        assert_eq!(namespace_of("INBOX.Sent", &ns).unwrap().kind, NamespaceKind::Personal);
+        // Exclusive: outside every prefix
+        assert!(namespace_of("Archive", &ns).is_none());
+    }
+
+    #[test]
+    fn test_namespace_settings_validate() {
+        assert!(NamespaceSettings::default().validate().is_ok());
+        let same = NamespaceSettings { other_users_tag_prefix: "shared".into(), ..Default::default() };
+        assert!(same.validate().is_err());
+        let slash = NamespaceSettings { shared_tag_prefix: "a/b".into(), ..Default::default() };
+        assert!(slash.validate().is_err());
+    }
+}
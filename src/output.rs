@@ -0,0 +1,413 @@
+//! Note output formats.
+//!
+//! `export_to_markdown` builds one canonical record per email (frontmatter,
+//! cleaned body, attachment list) and hands it to [`render_note`], which
+//! writes it in the account's `output_format`:
+//!
+//! - `markdown` — header (see `frontmatter`) + Markdown body (default)
+//! - `org`      — Org-mode note: `:PROPERTIES:` drawer, `#+TITLE:`, Org links
+//! - `json`     — one single-line JSON document (metadata + `body`), for scripts
+//! - `html`     — standalone page with the sanitized original HTML part
+//!
+//! Every format is one file per email with its attachments as siblings, and
+//! its metadata is readable through `frontmatter::NoteHeader`, so routing,
+//! duplicate detection and attachment moves do not depend on the format.
+
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::email_export::EmailFrontmatter;
+use crate::frontmatter::{
+    flat_value, header_entries, metadata_json, org_entry, render_header, FrontmatterSchema,
+    HTML_METADATA_OPEN,
+};
+
+/// Extensions of every note format (used by directory scans).
+pub const NOTE_EXTENSIONS: &[&str] = &["md", "org", "json", "html"];
+
+/// Output format of exported notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    #[serde(alias = "md")]
+    Markdown,
+    Org,
+    Json,
+    Html,
+}
+
+impl OutputFormat {
+    /// File extension of a note in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::Org => "org",
+            OutputFormat::Json => "json",
+            OutputFormat::Html => "html",
+        }
+    }
+}
+
+/// Whether `path` looks like an exported note (any format).
+pub fn is_note_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| NOTE_EXTENSIONS.contains(&e))
+}
+
+/// Whether a file is an exported note: any `.md`, or another note format
+/// whose metadata parses (an attached `.html` page is not a note). A JSON
+/// note also carries its `body`, unlike an attached `.json` file.
+pub fn is_note_content(path: &Path, content: &str) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("md") => true,
+        Some("json") => crate::frontmatter::NoteHeader::read(content).is_some_and(|h| h.has_key("body")),
+        _ => is_note_file(path) && crate::frontmatter::NoteHeader::read(content).is_some(),
+    }
+}
+
+/// Canonical content of one exported email.
+pub struct NoteParts<'a> {
+    pub frontmatter: &'a EmailFrontmatter,
+    pub date: Option<DateTime<FixedOffset>>,
+    /// Cleaned body (Markdown-flavoured plain text).
+    pub body: &'a str,
+    /// Original `text/html` part, when the email has one.
+    pub html: Option<&'a str>,
+}
+
+/// Render a complete note file.
+pub fn render_note(format: OutputFormat, note: &NoteParts<'_>, schema: &FrontmatterSchema) -> String {
+    match format {
+        OutputFormat::Markdown => render_markdown(note, schema),
+        OutputFormat::Org => render_org(note, schema),
+        OutputFormat::Json => render_json(note, schema),
+        OutputFormat::Html => render_html(note, schema),
+    }
+}
+
+fn file_name(attachment: &str) -> String {
+    Path::new(attachment)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+// ── Markdown ─────────────────────────────────────────────────────────────────
+
+fn render_markdown(note: &NoteParts<'_>, schema: &FrontmatterSchema) -> String {
+    let mut out = render_header(note.frontmatter, note.date, schema);
+    out.push_str(note.body);
+    let attachments = &note.frontmatter.attachments;
+    if !attachments.is_empty() {
+        out.push_str("\n\n### Pieces jointes :\n");
+        for attachment in attachments {
+            out.push_str(&format!("- [{}]({})\n", file_name(attachment), attachment));
+        }
+    }
+    out
+}
+
+// ── Org-mode ─────────────────────────────────────────────────────────────────
+
+static MD_REF_DEF_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\[(\d+)\]: (\S+)[ \t]*$\n?").expect("static regex"));
+static MD_REF_LINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[([^\]\n]+)\]\[(\d+)\]").expect("static regex"));
+static MD_INLINE_LINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[([^\]\n]+)\]\(([^)\s]+)\)").expect("static regex"));
+static MD_BULLET_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^([ \t]*)[*+] ").expect("static regex"));
+static MD_HEADING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^(#{1,6}) +(.*)$").expect("static regex"));
+
+/// Convert the cleaned Markdown body to Org syntax: reference and inline
+/// links become `[[url][text]]`, `*`/`+` bullets become `-`, `#` headings
+/// become `*` headlines. Everything else is left as-is.
+pub fn markdown_to_org(body: &str) -> String {
+    let refs: HashMap<String, String> = MD_REF_DEF_RE
+        .captures_iter(body)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect();
+    let text = MD_REF_DEF_RE.replace_all(body, "");
+    let text = MD_REF_LINK_RE.replace_all(&text, |c: &regex::Captures| match refs.get(&c[2]) {
+        Some(url) => format!("[[{}][{}]]", url, &c[1]),
+        None => c[0].to_string(),
+    });
+    let text = MD_INLINE_LINK_RE.replace_all(&text, "[[$2][$1]]");
+    let text = MD_BULLET_RE.replace_all(&text, "$1- ");
+    let text = MD_HEADING_RE.replace_all(&text, |c: &regex::Captures| {
+        format!("{} {}", "*".repeat(c[1].len()), &c[2])
+    });
+    let mut out = text.trim_end().to_string();
+    out.push('\n');
+    out
+}
+
+/// Org tag: letters, digits, `_`, `@`, `#`, `%` only.
+fn org_tag(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '%') { c } else { '_' })
+        .collect()
+}
+
+fn render_org(note: &NoteParts<'_>, schema: &FrontmatterSchema) -> String {
+    let fm = note.frontmatter;
+    let attachments_key = schema.key("attachments");
+    let tags_key = schema.key("tags");
+
+    let mut out = String::from(":PROPERTIES:\n");
+    for (key, value) in header_entries(fm, note.date, schema) {
+        if key == tags_key {
+            continue; // written as #+FILETAGS
+        }
+        if key == attachments_key {
+            if let Value::Sequence(items) = &value {
+                if items.is_empty() {
+                    continue;
+                }
+                let links: Vec<String> =
+                    items.iter().map(|i| format!("[[file:{}]]", flat_value(i))).collect();
+                out.push_str(&format!(":{}: {}\n", key.to_uppercase(), links.join(" ")));
+                continue;
+            }
+        }
+        out.push_str(&org_entry(&key, &value));
+    }
+    out.push_str(":END:\n");
+    out.push_str(&format!("#+TITLE: {}\n", fm.subject.replace(['\r', '\n'], " ")));
+    if let Some(date) = note.date {
+        out.push_str(&format!("#+DATE: {}\n", date.format("[%Y-%m-%d %a %H:%M]")));
+    }
+    let tags: Vec<String> = fm.tags.iter().map(|t| org_tag(t)).filter(|t| !t.is_empty()).collect();
+    if !tags.is_empty() {
+        out.push_str(&format!("#+FILETAGS: :{}:\n", tags.join(":")));
+    }
+    out.push('\n');
+    out.push_str(&markdown_to_org(note.body));
+    if !fm.attachments.is_empty() {
+        out.push_str("\n* Pièces jointes\n");
+        for attachment in &fm.attachments {
+            out.push_str(&format!("- [[file:{}][{}]]\n", attachment, file_name(attachment)));
+        }
+    }
+    out
+}
+
+// ── JSON ─────────────────────────────────────────────────────────────────────
+
+fn metadata_map(
+    note: &NoteParts<'_>,
+    schema: &FrontmatterSchema,
+) -> serde_json::Map<String, serde_json::Value> {
+    header_entries(note.frontmatter, note.date, schema)
+        .into_iter()
+        .filter_map(|(k, v)| serde_json::to_value(v).ok().map(|v| (k, v)))
+        .collect()
+}
+
+fn render_json(note: &NoteParts<'_>, schema: &FrontmatterSchema) -> String {
+    let mut record = metadata_map(note, schema);
+    record.insert("body".to_string(), serde_json::Value::String(note.body.to_string()));
+    let mut out = serde_json::to_string(&record).unwrap_or_else(|_| "{}".to_string());
+    out.push('\n');
+    out
+}
+
+// ── Standalone HTML ──────────────────────────────────────────────────────────
+
+/// Sanitize an email's HTML part: scripts, event handlers, forms, `<style>`
+/// blocks and unsafe URLs are removed; inline styles and legacy layout
+/// attributes are kept so the message looks like it did in a mail client.
+pub fn sanitize_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tags(["font", "center"])
+        .add_generic_attributes([
+            "style", "class", "align", "valign", "bgcolor", "width", "height", "border",
+            "cellpadding", "cellspacing", "color", "face", "size",
+        ])
+        .clean(html)
+        .to_string()
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:2em auto;padding:0 1em}\
+.email-header th{text-align:left;padding-right:1em;vertical-align:top}\
+.email-body pre{white-space:pre-wrap}";
+
+fn render_html(note: &NoteParts<'_>, schema: &FrontmatterSchema) -> String {
+    use html_escape::{encode_double_quoted_attribute as attr, encode_text as text};
+    let fm = note.frontmatter;
+
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", text(&fm.subject)));
+    out.push_str(HTML_METADATA_OPEN);
+    out.push_str(&metadata_json(&metadata_map(note, schema)));
+    out.push_str("</script>\n");
+    out.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", HTML_STYLE));
+
+    out.push_str("<table class=\"email-header\">\n");
+    let date = note.date.map(|d| d.to_rfc2822()).unwrap_or_else(|| fm.date.clone());
+    for (label, value) in [("De", &fm.from), ("À", &fm.to), ("Date", &date), ("Sujet", &fm.subject)] {
+        out.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, text(value)));
+    }
+    out.push_str("</table>\n<hr>\n<div class=\"email-body\">\n");
+    match note.html {
+        Some(html) => out.push_str(&sanitize_html(html)),
+        None => out.push_str(&format!("<pre>{}</pre>", text(note.body))),
+    }
+    out.push_str("\n</div>\n");
+    if !fm.attachments.is_empty() {
+        out.push_str("<h2>Pièces jointes</h2>\n<ul>\n");
+        for attachment in &fm.attachments {
+            out.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                attr(attachment),
+                text(&file_name(attachment))
+            ));
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontmatter::{note_body, FieldKeys, HeaderStyle, NoteHeader};
+
+    fn sample() -> EmailFrontmatter {
+        EmailFrontmatter {
+            from: "Alice <alice@example.com>".to_string(),
+            to: "bob@corp.fr".to_string(),
+            date: "2024-03-05T10:30:00+01:00".to_string(),
+            subject: "Devis <2024>".to_string(),
+            message_id: Some("abc@example.com".to_string()),
+            subject_hash: "a1b2c3".to_string(),
+            tags: vec!["INBOX".to_string(), "Clients Pro".to_string()],
+            attachments: vec!["2024-03-05_devis.pdf".to_string()],
+            email_type: Some("direct".to_string()),
+            social_links: None,
+            flags: vec![],
+            parse_degraded: false,
+            event: None,
+            signature: None,
+            charset_repaired: false,
+            signature_block: None,
+        }
+    }
+
+    fn parts<'a>(fm: &'a EmailFrontmatter, body: &'a str, html: Option<&'a str>) -> NoteParts<'a> {
+        NoteParts {
+            frontmatter: fm,
+            date: DateTime::parse_from_rfc3339(&fm.date).ok(),
+            body,
+            html,
+        }
+    }
+
+    /// Every format exposes the same metadata to the shared reader.
+    fn assert_readable(content: &str, style: HeaderStyle) {
+        let keys = FieldKeys::builtin();
+        let header = NoteHeader::read(content).expect("header");
+        assert_eq!(header.style, style);
+        assert_eq!(header.sender_address(&keys), "alice@example.com");
+        assert_eq!(header.get_str("message_id", &keys).as_deref(), Some("abc@example.com"));
+        assert_eq!(header.get_list("attachments", &keys), vec!["2024-03-05_devis.pdf"]);
+        assert_eq!(header.date(&keys).unwrap().format("%Y-%m").to_string(), "2024-03");
+    }
+
+    #[test]
+    fn test_markdown_matches_header_plus_body() {
+        let fm = sample();
+        let note = render_note(OutputFormat::Markdown, &parts(&fm, "Hello", None), &Default::default());
+        assert!(note.starts_with("---\nfrom: Alice <alice@example.com>\n"));
+        assert!(note.ends_with("Hello\n\n### Pieces jointes :\n- [2024-03-05_devis.pdf](2024-03-05_devis.pdf)\n"));
+        assert_readable(&note, HeaderStyle::Yaml);
+    }
+
+    #[test]
+    fn test_org_note_layout_and_reader() {
+        let fm = sample();
+        let body = "See [the offer][1] and [docs](https://docs.example.com).\n* item\n## Details\n\n[1]: https://example.com/offer\n";
+        let note = render_note(OutputFormat::Org, &parts(&fm, body, None), &Default::default());
+
+        assert!(note.starts_with(":PROPERTIES:\n:FROM: Alice <alice@example.com>\n"), "{}", note);
+        assert!(note.contains(":ATTACHMENTS: [[file:2024-03-05_devis.pdf]]\n"), "{}", note);
+        assert!(note.contains(":END:\n#+TITLE: Devis <2024>\n#+DATE: [2024-03-05 Tue 10:30]\n"), "{}", note);
+        assert!(note.contains("#+FILETAGS: :INBOX:Clients_Pro:\n"), "{}", note);
+        assert!(note.contains("See [[https://example.com/offer][the offer]] and [[https://docs.example.com][docs]]."));
+        assert!(note.contains("\n- item\n** Details\n"), "{}", note);
+        assert!(note.contains("- [[file:2024-03-05_devis.pdf][2024-03-05_devis.pdf]]\n"));
+        // Exclusive: no leftover Markdown reference definitions, no TAGS property
+        assert!(!note.contains("[1]:"));
+        assert!(!note.contains(":TAGS:"));
+
+        assert_readable(&note, HeaderStyle::Org);
+        assert!(note_body(&note).starts_with("#+TITLE:"));
+    }
+
+    #[test]
+    fn test_json_note_is_one_line() {
+        let fm = sample();
+        let note = render_note(OutputFormat::Json, &parts(&fm, "Line 1\nLine 2", None), &Default::default());
+        assert_eq!(note.lines().count(), 1);
+        let record: serde_json::Value = serde_json::from_str(&note).unwrap();
+        assert_eq!(record["body"], "Line 1\nLine 2");
+        assert_eq!(record["subject"], "Devis <2024>");
+        assert_eq!(record["tags"][1], "Clients Pro");
+        assert_readable(&note, HeaderStyle::Json);
+    }
+
+    #[test]
+    fn test_html_is_sanitized_and_standalone() {
+        let fm = sample();
+        let html = "<p style=\"color:red\" onclick=\"steal()\">Bonjour</p><script>alert(1)</script><a href=\"javascript:x()\">lien</a>";
+        let note = render_note(OutputFormat::Html, &parts(&fm, "Bonjour", Some(html)), &Default::default());
+
+        assert!(note.starts_with("<!DOCTYPE html>"));
+        assert!(note.contains("<title>Devis &lt;2024&gt;</title>"), "{}", note);
+        assert!(note.contains("<p style=\"color:red\">Bonjour</p>"), "{}", note);
+        assert!(note.contains("<a href=\"2024-03-05_devis.pdf\">"));
+        // Exclusive: active content removed; metadata cannot close its <script>
+        assert!(!note.contains("onclick") && !note.contains("alert(1)") && !note.contains("javascript:"));
+        assert!(!note.contains("Devis <2024>"));
+
+        assert_readable(&note, HeaderStyle::Json);
+    }
+
+    #[test]
+    fn test_html_without_html_part_uses_escaped_text() {
+        let fm = sample();
+        let note = render_note(OutputFormat::Html, &parts(&fm, "a < b", None), &Default::default());
+        assert!(note.contains("<pre>a &lt; b</pre>"));
+    }
+
+    #[test]
+    fn test_is_note_file() {
+        assert!(is_note_file(Path::new("a/b.org")));
+        assert!(is_note_file(Path::new("b.json")));
+        assert!(!is_note_file(Path::new("b.pdf")));
+        assert!(!is_note_file(Path::new("README")));
+        // An attached web page is not a note; an exported one is
+        assert!(!is_note_content(Path::new("x.html"), "<!DOCTYPE html><p>page</p>"));
+        assert!(is_note_content(Path::new("x.md"), "no header"));
+        let fm = sample();
+        let html = render_note(OutputFormat::Html, &parts(&fm, "b", None), &Default::default());
+        assert!(is_note_content(Path::new("x.html"), &html));
+        // Likewise for an attached `.json` file
+        assert!(!is_note_content(Path::new("x.json"), "{\"subject\":\"data\"}"));
+        let json = render_note(OutputFormat::Json, &parts(&fm, "b", None), &Default::default());
+        assert!(is_note_content(Path::new("x.json"), &json));
+    }
+}
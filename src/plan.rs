@@ -0,0 +1,177 @@
+//! `export --dry-run`: what an export would do, read from message headers.
+//!
+//! The folders are opened read-only (`EXAMINE`) and only headers are
+//! fetched: no note is written, no flag set, nothing moved or deleted. Each
+//! message gets the decision the export would take (export, already
+//! exported, filtered by the `after_export.keyword`), its routing target
+//! under `notes_dir` and whether `delete_after_export` would delete it once
+//! its note is verified (see `deletion`).
+
+use serde::Serialize;
+
+/// What the export would do with a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanAction {
+    /// Written as a new note, then routed.
+    Export,
+    /// A note with the same identity is already in the export directory.
+    AlreadyExported,
+    /// Left out by the search: the message carries `after_export.keyword`.
+    KeywordSkipped,
+}
+
+impl PlanAction {
+    fn label(self) -> &'static str {
+        match self {
+            PlanAction::Export => "export",
+            PlanAction::AlreadyExported => "exists",
+            PlanAction::KeywordSkipped => "keyword",
+        }
+    }
+}
+
+/// One message of the plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanEntry {
+    pub account: String,
+    /// Decoded folder name.
+    pub folder: String,
+    pub uid: Option<u32>,
+    pub action: PlanAction,
+    /// RFC 3339 date, when the `Date:` header parses.
+    pub date: Option<String>,
+    pub from: String,
+    pub subject: String,
+    /// Routing target relative to `notes_dir` (exported messages only).
+    pub route: Option<String>,
+    /// Matching `destinations.txt` rule; `None` for the default path.
+    pub rule: Option<String>,
+    /// Deleted from the server by `delete_after_export` once its note is
+    /// verified at its final path.
+    pub delete: bool,
+}
+
+/// Counts over a plan, for the summary line.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PlanSummary {
+    pub export: usize,
+    pub already_exported: usize,
+    pub keyword_skipped: usize,
+    pub delete: usize,
+}
+
+pub fn summarize(entries: &[PlanEntry]) -> PlanSummary {
+    let mut summary = PlanSummary::default();
+    for entry in entries {
+        match entry.action {
+            PlanAction::Export => summary.export += 1,
+            PlanAction::AlreadyExported => summary.already_exported += 1,
+            PlanAction::KeywordSkipped => summary.keyword_skipped += 1,
+        }
+        if entry.delete {
+            summary.delete += 1;
+        }
+    }
+    summary
+}
+
+/// Cut `text` to `width` characters, marking the cut with `…`.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(width.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+/// Plain-text table, one line per message, followed by a summary line.
+pub fn render_table(entries: &[PlanEntry]) -> String {
+    let mut out = format!(
+        "{:<8} {:<7} {:<24} {:>7} {:<10} {:<32} {:<40} {}\n",
+        "ACTION", "DELETE", "FOLDER", "UID", "DATE", "ROUTE", "SUBJECT", "ACCOUNT"
+    );
+    for entry in entries {
+        let date = entry.date.as_deref().map(|d| d.get(..10).unwrap_or(d)).unwrap_or("-");
+        let route = match (&entry.route, &entry.rule) {
+            (Some(route), None) => format!("{} (default)", route),
+            (Some(route), Some(_)) => route.clone(),
+            (None, _) => "-".to_string(),
+        };
+        out.push_str(&format!(
+            "{:<8} {:<7} {:<24} {:>7} {:<10} {:<32} {:<40} {}\n",
+            entry.action.label(),
+            if entry.delete { "yes" } else { "-" },
+            truncate(&entry.folder, 24),
+            entry.uid.map(|u| u.to_string()).unwrap_or_else(|| "-".to_string()),
+            date,
+            truncate(&route, 32),
+            truncate(&entry.subject, 40),
+            entry.account,
+        ));
+    }
+    let summary = summarize(entries);
+    out.push_str(&format!(
+        "\nPlan: {} to export, {} already exported, {} filtered by keyword, {} to delete from the server\n",
+        summary.export, summary.already_exported, summary.keyword_skipped, summary.delete
+    ));
+    out
+}
+
+/// The entries as a pretty-printed JSON array.
+pub fn render_json(entries: &[PlanEntry]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action: PlanAction, delete: bool) -> PlanEntry {
+        PlanEntry {
+            account: "Pro".to_string(),
+            folder: "INBOX".to_string(),
+            uid: Some(42),
+            action,
+            date: Some("2026-06-01T10:00:00+02:00".to_string()),
+            from: "alice@example.com".to_string(),
+            subject: "Relevé de compte".to_string(),
+            route: (action == PlanAction::Export).then(|| "Perso/Banque/2026/06".to_string()),
+            rule: (action == PlanAction::Export).then(|| "domain:example.com".to_string()),
+            delete,
+        }
+    }
+
+    #[test]
+    fn test_render_table_and_summary() {
+        let entries = vec![
+            entry(PlanAction::Export, true),
+            entry(PlanAction::AlreadyExported, true),
+            entry(PlanAction::KeywordSkipped, false),
+        ];
+        let table = render_table(&entries);
+        assert!(table.contains("export   yes"));
+        assert!(table.contains("Perso/Banque/2026/06"));
+        assert!(table.contains("2026-06-01"));
+        assert!(table.contains("keyword  -"));
+        assert!(table.ends_with(
+            "Plan: 1 to export, 1 already exported, 1 filtered by keyword, 2 to delete from the server\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_json_uses_snake_case_actions() {
+        let json = render_json(&[entry(PlanAction::AlreadyExported, false)]).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value[0]["action"], "already_exported");
+        assert_eq!(value[0]["uid"], 42);
+        assert!(value[0]["route"].is_null());
+    }
+
+    #[test]
+    fn test_truncate_marks_cut() {
+        assert_eq!(truncate("abcdef", 4), "abc…");
+        assert_eq!(truncate("abc", 4), "abc");
+    }
+}
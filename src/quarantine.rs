@@ -0,0 +1,204 @@
+//! Quarantine for messages whose export fails.
+//!
+//! Every failed message is kept as `<export_directory>/_failed/<name>.eml`
+//! with a report `<name>.json` ([`QuarantineRecord`]: folder, UID, failing
+//! stage, error chain). Nothing is lost in normal runs, and `retry-failed`
+//! re-runs the export of each quarantined message, e.g. after an upgrade.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::sanitize_filename;
+
+/// Directory under the account's `export_directory`.
+pub const QUARANTINE_DIR: &str = "_failed";
+
+/// Where the export failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureStage {
+    /// MIME parsing or decoding (`mailparse`): malformed message.
+    Parse,
+    /// Writing the note or its attachments.
+    Write,
+    /// Any other failure while building the note.
+    Export,
+}
+
+impl FailureStage {
+    pub fn of(error: &anyhow::Error) -> Self {
+        if error.chain().any(|e| e.is::<mailparse::MailParseError>()) {
+            FailureStage::Parse
+        } else if error.chain().any(|e| e.is::<std::io::Error>()) {
+            FailureStage::Write
+        } else {
+            FailureStage::Export
+        }
+    }
+}
+
+/// Report written next to a quarantined message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuarantineRecord {
+    pub account: String,
+    /// Decoded folder name.
+    pub folder: String,
+    /// Hierarchy delimiter of the folder (for `folders:` overrides on retry).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid_validity: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub stage: FailureStage,
+    /// Error chain, outermost context first.
+    pub errors: Vec<String>,
+    /// Directory the note would have been written to.
+    pub export_directory: PathBuf,
+    /// Tags of the note (the folder tag).
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// IMAP flags of the message (`flags` field of the note).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+    pub quarantined_at: DateTime<Utc>,
+    /// Failed `retry-failed` runs.
+    #[serde(default)]
+    pub retries: u32,
+}
+
+impl QuarantineRecord {
+    /// Record the failure `error` (stage and chain), replacing any earlier one.
+    pub fn set_error(&mut self, error: &anyhow::Error) {
+        self.stage = FailureStage::of(error);
+        self.errors = error.chain().map(|e| e.to_string()).collect();
+    }
+}
+
+pub fn quarantine_dir(base_export_directory: &Path) -> PathBuf {
+    base_export_directory.join(QUARANTINE_DIR)
+}
+
+/// Report path of a quarantined message.
+pub fn report_path(eml: &Path) -> PathBuf {
+    eml.with_extension("json")
+}
+
+/// Keep `raw` and its report in the quarantine of `base_export_directory`.
+/// Returns the path of the `.eml`.
+pub fn quarantine(base_export_directory: &Path, record: &QuarantineRecord, raw: &[u8]) -> Result<PathBuf> {
+    let dir = quarantine_dir(base_export_directory);
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let stem = match record.uid {
+        Some(uid) => format!("{}_uid_{}", sanitize_filename(&record.folder), uid),
+        None => sanitize_filename(&record.folder),
+    };
+    let mut eml = dir.join(format!("{}.eml", stem));
+    let mut suffix = 2u32;
+    while eml.exists() {
+        eml = dir.join(format!("{}_{}.eml", stem, suffix));
+        suffix += 1;
+    }
+    fs::write(&eml, raw).with_context(|| format!("failed to write {}", eml.display()))?;
+    save_record(&eml, record)?;
+    Ok(eml)
+}
+
+pub fn save_record(eml: &Path, record: &QuarantineRecord) -> Result<()> {
+    let path = report_path(eml);
+    let json = serde_json::to_string_pretty(record)?;
+    fs::write(&path, json).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Quarantined messages of `base_export_directory` with their reports,
+/// sorted by path. Messages without a readable report are left out.
+pub fn list(base_export_directory: &Path) -> Vec<(PathBuf, QuarantineRecord)> {
+    let Ok(entries) = fs::read_dir(quarantine_dir(base_export_directory)) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(PathBuf, QuarantineRecord)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "eml") && p.is_file())
+        .filter_map(|eml| {
+            let report = fs::read_to_string(report_path(&eml)).ok()?;
+            let record = serde_json::from_str(&report).ok()?;
+            Some((eml, record))
+        })
+        .collect();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+/// Drop a message from the quarantine once it is exported.
+pub fn release(eml: &Path) -> Result<()> {
+    fs::remove_file(eml).with_context(|| format!("failed to remove {}", eml.display()))?;
+    let report = report_path(eml);
+    if report.exists() {
+        fs::remove_file(&report).with_context(|| format!("failed to remove {}", report.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(uid: Option<u32>) -> QuarantineRecord {
+        QuarantineRecord {
+            account: "Pro".to_string(),
+            folder: "INBOX/Clients".to_string(),
+            delimiter: Some("/".to_string()),
+            uid,
+            uid_validity: Some(7),
+            message_id: None,
+            stage: FailureStage::Export,
+            errors: Vec::new(),
+            export_directory: PathBuf::from("/tmp/Pro/INBOX/Clients"),
+            tags: vec!["INBOX/Clients".to_string()],
+            flags: Vec::new(),
+            quarantined_at: Utc::now(),
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_quarantine_list_release_roundtrip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let a = quarantine(temp.path(), &record(Some(12)), b"Subject: a\r\n\r\n").unwrap();
+        let b = quarantine(temp.path(), &record(Some(12)), b"Subject: b\r\n\r\n").unwrap();
+        assert_ne!(a, b, "same folder and UID must not overwrite");
+        // A legacy dump without report is not listed
+        fs::write(quarantine_dir(temp.path()).join("old_uid_1.eml"), b"x").unwrap();
+
+        let listed = list(temp.path());
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].0, a);
+        assert_eq!((listed[0].1.uid, listed[0].1.uid_validity), (Some(12), Some(7)));
+
+        release(&a).unwrap();
+        assert!(!a.exists() && !report_path(&a).exists());
+        assert_eq!(list(temp.path()).len(), 1);
+    }
+
+    #[test]
+    fn test_failure_stage_from_error_chain() {
+        let parse = anyhow::Error::new(mailparse::MailParseError::Generic("bad header"))
+            .context("Failed to parse email");
+        assert_eq!(FailureStage::of(&parse), FailureStage::Parse);
+
+        let io = anyhow::Error::new(std::io::Error::other("disk full")).context("write note");
+        assert_eq!(FailureStage::of(&io), FailureStage::Write);
+
+        let mut r = record(None);
+        r.set_error(&io);
+        assert_eq!(r.stage, FailureStage::Write);
+        assert_eq!(r.errors, vec!["write note".to_string(), "disk full".to_string()]);
+        assert_eq!(FailureStage::of(&anyhow::anyhow!("other")), FailureStage::Export);
+    }
+}
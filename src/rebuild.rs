@@ -0,0 +1,350 @@
+//! Turn an exported note back into an RFC 5322 message.
+//!
+//! The original message is used as-is when it was kept next to the note
+//! (`keep_raw`, `<note>.eml`). Otherwise a MIME message is rebuilt from the
+//! header fields (`from`, `to`, `date`, `subject`, `message_id`), the note
+//! body (`text/html` for HTML notes, `text/plain` otherwise) and the sibling
+//! files listed in `attachments:`. Used by `restore`.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use mailparse::MailHeaderMap;
+
+use crate::frontmatter::{note_body, FieldKeys, NoteHeader};
+use crate::utils::extract_emails;
+
+/// `<date>_` prefix added to attachment file names at export.
+static DATE_PREFIX_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:\d{4}-\d{2}-\d{2}|unknown-date)_").expect("static regex"));
+
+static HTML_BODY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<div class="email-body">\n(.*)\n</div>\n"#).expect("static regex")
+});
+
+/// A message ready to be appended to a mailbox.
+#[derive(Debug, Clone)]
+pub struct RebuiltMessage {
+    pub bytes: Vec<u8>,
+    /// Internal date to set (the message's `Date`).
+    pub date: Option<DateTime<FixedOffset>>,
+    /// IMAP flags recorded in the note (`flags` field).
+    pub flags: Vec<String>,
+    /// Whether `bytes` is the original message kept by `keep_raw`.
+    pub original: bool,
+}
+
+/// Where `keep_raw` stores the original message of a note.
+pub fn raw_message_path(note: &Path) -> PathBuf {
+    note.with_extension("eml")
+}
+
+/// The message for `note`: the kept original when present, else rebuilt.
+/// Header fields are read under `keys`.
+pub fn message_for_note(note: &Path, keys: &FieldKeys) -> Result<RebuiltMessage> {
+    let content =
+        fs::read_to_string(note).with_context(|| format!("failed to read {}", note.display()))?;
+    let header = NoteHeader::read(&content)
+        .with_context(|| format!("no readable header in {}", note.display()))?;
+    let flags = header.get_list("flags", keys);
+
+    let raw_path = raw_message_path(note);
+    if raw_path.symlink_metadata().is_ok_and(|m| m.file_type().is_file()) {
+        let bytes =
+            fs::read(&raw_path).with_context(|| format!("failed to read {}", raw_path.display()))?;
+        let date = mailparse::parse_headers(&bytes)
+            .ok()
+            .and_then(|(headers, _)| headers.get_first_value("Date"))
+            .and_then(|d| DateTime::parse_from_rfc2822(d.trim()).ok())
+            .or_else(|| header.date(keys));
+        return Ok(RebuiltMessage { bytes, date, flags, original: true });
+    }
+
+    let date = header.date(keys);
+    let bytes = build_message(note, &content, &header, keys)?;
+    Ok(RebuiltMessage { bytes, date, flags, original: false })
+}
+
+/// Rebuild a MIME message from a note's header, body and attachments.
+pub fn build_message(note: &Path, content: &str, header: &NoteHeader, keys: &FieldKeys) -> Result<Vec<u8>> {
+    let (body, is_html) = note_text(note, content, header, keys);
+
+    let mut out = String::new();
+    let from = header.get_str("from", keys).unwrap_or_default();
+    out.push_str(&format!("From: {}\r\n", address_header(&from, &header.sender_address(keys))));
+    let to = header.get_str("to", keys).unwrap_or_default();
+    out.push_str(&format!("To: {}\r\n", address_header(&to, "")));
+    if let Some(date) = header.date(keys) {
+        out.push_str(&format!("Date: {}\r\n", date.to_rfc2822()));
+    }
+    out.push_str(&format!("Subject: {}\r\n", encode_word(&header.get_str("subject", keys).unwrap_or_default())));
+    if let Some(id) = header.get_str("message_id", keys) {
+        out.push_str(&format!("Message-ID: <{}>\r\n", id.trim_start_matches('<').trim_end_matches('>')));
+    }
+    out.push_str("MIME-Version: 1.0\r\n");
+
+    let parent = note.parent().unwrap_or(Path::new("."));
+    let attachments: Vec<(String, Vec<u8>)> = header
+        .get_list("attachments", keys)
+        .iter()
+        .filter_map(|link| {
+            let path = parent.join(link.replace('/', std::path::MAIN_SEPARATOR_STR));
+            // Only sibling files (same rule as `route::move_email`), no symlinks
+            if path.parent() != Some(parent) || !path.symlink_metadata().is_ok_and(|m| m.file_type().is_file()) {
+                eprintln!("warning: attachment {:?} of {} not found; skipped", link, note.display());
+                return None;
+            }
+            let name = path.file_name()?.to_string_lossy();
+            let name = DATE_PREFIX_RE.replace(&name, "").into_owned();
+            fs::read(&path).ok().map(|data| (name, data))
+        })
+        .collect();
+
+    let text_part = text_part(&body, is_html);
+    if attachments.is_empty() {
+        out.push_str(&text_part);
+        return Ok(out.into_bytes());
+    }
+
+    let boundary = format!("=_e2m_{:x}", md5::compute(content.as_bytes()));
+    out.push_str(&format!("Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n", boundary));
+    out.push_str("This is a multi-part message in MIME format.\r\n");
+    out.push_str(&format!("--{}\r\n", boundary));
+    out.push_str(&text_part);
+    for (name, data) in &attachments {
+        out.push_str(&format!("\r\n--{}\r\n", boundary));
+        out.push_str(&format!("Content-Type: {}; name=\"{}\"\r\n", content_type_for(name), encode_word(name)));
+        out.push_str(&format!("Content-Disposition: attachment; {}\r\n", filename_param(name)));
+        out.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+        out.push_str(&base64_lines(data));
+    }
+    out.push_str(&format!("\r\n--{}--\r\n", boundary));
+    Ok(out.into_bytes())
+}
+
+/// Body text of a note in any output format, without the attachment list
+/// appended by the writer, and whether it is HTML.
+fn note_text(note: &Path, content: &str, header: &NoteHeader, keys: &FieldKeys) -> (String, bool) {
+    match note.extension().and_then(|e| e.to_str()) {
+        Some("json") => (header.get_str("body", keys).unwrap_or_default(), false),
+        Some("html") => match HTML_BODY_RE.captures(content) {
+            Some(c) => (c[1].to_string(), true),
+            None => (note_body(content).to_string(), true),
+        },
+        Some("org") => {
+            let body = note_body(content);
+            let body = body.split("\n* Pièces jointes\n").next().unwrap_or(body);
+            let text: Vec<&str> = body.lines().skip_while(|l| l.starts_with("#+")).collect();
+            (text.join("\n").trim_start_matches('\n').to_string(), false)
+        }
+        _ => {
+            let body = note_body(content);
+            (body.split("\n\n### Pieces jointes :\n").next().unwrap_or(body).to_string(), false)
+        }
+    }
+}
+
+fn text_part(body: &str, is_html: bool) -> String {
+    let subtype = if is_html { "html" } else { "plain" };
+    let body = body.replace("\r\n", "\n").replace('\n', "\r\n");
+    if body.is_ascii() && body.lines().all(|l| l.len() <= 998) {
+        format!("Content-Type: text/{}; charset=us-ascii\r\nContent-Transfer-Encoding: 7bit\r\n\r\n{}\r\n", subtype, body)
+    } else {
+        format!(
+            "Content-Type: text/{}; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+            subtype,
+            base64_lines(body.as_bytes())
+        )
+    }
+}
+
+/// Address header from a frontmatter value: display names encoded, falling
+/// back to `fallback_addr` when the value has no address (`[[Contact]]` links).
+fn address_header(value: &str, fallback_addr: &str) -> String {
+    let value = value.replace("[[", "").replace("]]", "");
+    let entries: Vec<String> = match mailparse::addrparse(&value) {
+        Ok(list) => list
+            .iter()
+            .flat_map(|addr| match addr {
+                mailparse::MailAddr::Single(info) => vec![info.clone()],
+                mailparse::MailAddr::Group(group) => group.addrs.clone(),
+            })
+            .filter(|info| info.addr.contains('@'))
+            .map(|info| match info.display_name.filter(|n| !n.trim().is_empty()) {
+                Some(name) => format!("{} <{}>", display_name(&name), info.addr),
+                None => info.addr,
+            })
+            .collect(),
+        Err(_) => extract_emails(Some(&value)),
+    };
+    match (entries.is_empty(), fallback_addr.is_empty()) {
+        (false, _) => entries.join(", "),
+        (true, false) if value.trim().is_empty() => fallback_addr.to_string(),
+        (true, false) => format!("{} <{}>", display_name(value.trim()), fallback_addr),
+        (true, true) => "undisclosed-recipients:;".to_string(),
+    }
+}
+
+fn display_name(name: &str) -> String {
+    if !name.is_ascii() {
+        encode_word(name)
+    } else if name.chars().any(|c| "()<>[]:;@\\,.\"".contains(c)) {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_string()
+    }
+}
+
+/// Longest UTF-8 text in one encoded word: 45 bytes give 60 base64 chars,
+/// the most that fits the 75-char limit of RFC 2047 with `=?UTF-8?B?…?=`.
+const ENCODED_WORD_BYTES: usize = 45;
+
+/// RFC 2047 encoded words for non-ASCII header text, split at char boundaries
+/// into words of at most 75 chars, folded one per line.
+pub fn encode_word(text: &str) -> String {
+    if text.is_ascii() {
+        return text.replace(['\r', '\n'], " ");
+    }
+    let mut words = Vec::new();
+    let mut start = 0;
+    for (idx, c) in text.char_indices() {
+        if idx + c.len_utf8() - start > ENCODED_WORD_BYTES {
+            words.push(&text[start..idx]);
+            start = idx;
+        }
+    }
+    words.push(&text[start..]);
+    words
+        .iter()
+        .map(|word| format!("=?UTF-8?B?{}?=", STANDARD.encode(word.as_bytes())))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+/// `filename=` parameter, RFC 2231 encoded when not plain ASCII.
+fn filename_param(name: &str) -> String {
+    if name.is_ascii() && !name.contains('"') {
+        format!("filename=\"{}\"", name)
+    } else {
+        let encoded: String = name
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+                _ => format!("%{:02X}", b),
+            })
+            .collect();
+        format!("filename*=UTF-8''{}", encoded)
+    }
+}
+
+/// Base64 wrapped at 76 columns, CRLF line ends.
+fn base64_lines(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / 38 + 2);
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// MIME type guessed from the file extension.
+pub fn content_type_for(name: &str) -> &'static str {
+    let ext = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "ics" => "text/calendar",
+        "zip" => "application/zip",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "eml" => "message/rfc822",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuild_markdown_note_with_attachment() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let note = temp.path().join("note.md");
+        fs::write(
+            &note,
+            "---\nfrom: Éloïse Martin <eloise@example.com>\nto: bob@example.com\ndate: 2024-01-15T10:00:00+01:00\n\
+             subject: Devis\nmessage_id: abc@example.com\nattachments:\n- 2024-01-15_devis.pdf\nflags:\n- \\Seen\n---\n\n\
+             Bonjour\n\n### Pieces jointes :\n- [2024-01-15_devis.pdf](2024-01-15_devis.pdf)\n",
+        )
+        .unwrap();
+        fs::write(temp.path().join("2024-01-15_devis.pdf"), b"%PDF-1.4").unwrap();
+
+        let message = message_for_note(&note, &FieldKeys::builtin()).unwrap();
+        assert!(!message.original);
+        assert_eq!(message.flags, vec!["\\Seen"]);
+        let parsed = mailparse::parse_mail(&message.bytes).unwrap();
+        assert_eq!(parsed.headers.get_first_value("Message-ID").unwrap(), "<abc@example.com>");
+        assert_eq!(parsed.headers.get_first_value("From").unwrap(), "Éloïse Martin <eloise@example.com>");
+        assert_eq!(parsed.subparts.len(), 2);
+        assert_eq!(parsed.subparts[0].get_body().unwrap().trim(), "Bonjour");
+        assert_eq!(parsed.subparts[1].get_content_disposition().params["filename"], "devis.pdf");
+        assert_eq!(parsed.subparts[1].get_body_raw().unwrap(), b"%PDF-1.4");
+    }
+
+    #[test]
+    fn test_kept_original_wins() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let note = temp.path().join("note.md");
+        fs::write(&note, "---\nfrom: a@example.com\nsubject: Hi\n---\n\nRebuilt\n").unwrap();
+        let raw = b"From: a@example.com\r\nDate: Mon, 15 Jan 2024 10:00:00 +0100\r\nSubject: Hi\r\n\r\nOriginal\r\n";
+        fs::write(raw_message_path(&note), raw).unwrap();
+
+        let message = message_for_note(&note, &FieldKeys::builtin()).unwrap();
+        assert!(message.original);
+        assert_eq!(message.bytes, raw);
+        assert_eq!(message.date.unwrap().to_rfc3339(), "2024-01-15T10:00:00+01:00");
+    }
+
+    #[test]
+    fn test_long_encoded_header_is_split_and_folded() {
+        let subject = "Réunion trimestrielle — budget prévisionnel et arbitrages de l'équipe été 2026";
+        let encoded = encode_word(subject);
+        let words: Vec<&str> = encoded.split("\r\n ").collect();
+        assert!(words.len() > 1);
+        assert!(words.iter().all(|w| w.len() <= 75 && w.starts_with("=?UTF-8?B?")), "{:?}", words);
+        // Inclusive: the words decode back to the subject, no char cut in two
+        let decoded: String = words
+            .iter()
+            .map(|w| String::from_utf8(STANDARD.decode(&w[10..w.len() - 2]).unwrap()).unwrap())
+            .collect();
+        assert_eq!(decoded, subject);
+        let raw = format!("Subject: {}\r\n", encoded);
+        let (header, _) = mailparse::parse_header(raw.as_bytes()).unwrap();
+        assert_eq!(header.get_value(), subject);
+        // Exclusive: plain ASCII is left unencoded
+        assert_eq!(encode_word("Devis"), "Devis");
+    }
+
+    #[test]
+    fn test_address_header_without_address() {
+        // Contact links keep the name only: the sender address comes from `from_address`
+        assert_eq!(address_header("[[Bob]]", "bob@example.com"), "Bob <bob@example.com>");
+        // Exclusive: recipients without any address
+        assert_eq!(address_header("[[Alice]]", ""), "undisclosed-recipients:;");
+    }
+}
@@ -0,0 +1,218 @@
+//! `restore` CLI subcommand: append notes back to an IMAP mailbox.
+//!
+//! Each note becomes a message (see `rebuild`): the original kept by
+//! `keep_raw` when present, otherwise one rebuilt from the note. It is
+//! appended with the note's date as internal date and its recorded flags.
+
+use anyhow::{Context, Result};
+use clap::Args;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+use crate::config::{self, Config, Settings};
+use crate::email_export::ImapExporter;
+use crate::frontmatter::{FieldKeys, NoteHeader};
+use crate::output::is_note_content;
+use crate::rebuild::{message_for_note, raw_message_path};
+use crate::route::is_child_note;
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// Notes, or directories of notes (e.g. a notes_dir subtree)
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Account whose server receives the messages
+    #[arg(long)]
+    pub account: String,
+
+    /// Target mailbox, created when missing
+    #[arg(long, default_value = "Restored")]
+    pub folder: String,
+
+    /// List the messages that would be restored without connecting
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Path to config file (default: system config dir)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Enable debug mode (verbose IMAP output)
+    #[arg(short, long)]
+    pub debug: bool,
+}
+
+/// Notes under `paths`, sorted. Directories are walked without following
+/// symlinks; `.`/`_`-prefixed directories (`_deleted`, `_failed`) are skipped.
+/// The child note of an embedded message is left out when its parent, also
+/// collected, already carries it (see [`carried_children`]).
+pub fn collect_notes(paths: &[PathBuf], keys: &FieldKeys) -> Vec<PathBuf> {
+    let mut notes: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        for entry in WalkDir::new(path)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0
+                    || !e.file_type().is_dir()
+                    || !e.file_name().to_string_lossy().starts_with(['.', '_'])
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            let is_note = std::fs::read_to_string(entry.path())
+                .is_ok_and(|content| is_note_content(entry.path(), &content));
+            if is_note {
+                notes.push(entry.into_path());
+            }
+        }
+    }
+    notes.sort();
+    notes.dedup();
+    let children = carried_children(&notes, keys);
+    notes.retain(|note| !fs::canonicalize(note).is_ok_and(|path| children.contains(&path)));
+    notes
+}
+
+/// Child notes (canonical paths) of embedded messages that their parent's
+/// message already holds: inside the original kept by `keep_raw`, or as an
+/// attached sibling file (see `rebuild`). A child filed elsewhere by routing
+/// is not part of a rebuilt parent and stays a message of its own.
+fn carried_children(notes: &[PathBuf], keys: &FieldKeys) -> HashSet<PathBuf> {
+    let mut children = HashSet::new();
+    for note in notes {
+        let Some(dir) = note.parent() else { continue };
+        let Some(header) = fs::read_to_string(note).ok().and_then(|c| NoteHeader::read(&c)) else {
+            continue;
+        };
+        let original = raw_message_path(note).is_file();
+        for link in header.get_list("attachments", keys) {
+            let child = dir.join(link.replace('/', std::path::MAIN_SEPARATOR_STR));
+            let carried = original || child.parent() == Some(dir);
+            if carried && child != *note && is_child_note(&child) {
+                if let Ok(path) = fs::canonicalize(&child) {
+                    children.insert(path);
+                }
+            }
+        }
+    }
+    children
+}
+
+/// Entry point dispatched from `main`.
+pub fn run(args: RestoreArgs) -> Result<()> {
+    let keys = FieldKeys::from_settings(&Settings::load(&config::settings_path()).unwrap_or_default());
+    let notes = collect_notes(&args.paths, &keys);
+    if notes.is_empty() {
+        println!("No notes found.");
+        return Ok(());
+    }
+
+    if args.dry_run {
+        for note in &notes {
+            match message_for_note(note, &keys) {
+                Ok(message) => println!(
+                    "{}  {}  {}  {}",
+                    if message.original { "original" } else { "rebuilt " },
+                    message.date.map(|d| d.to_rfc3339()).unwrap_or_else(|| "-".into()),
+                    if message.flags.is_empty() { "-".to_string() } else { message.flags.join(" ") },
+                    note.display()
+                ),
+                Err(e) => println!("error     {}: {:#}", note.display(), e),
+            }
+        }
+        println!("{} note(s) would be restored to {} ({}).", notes.len(), args.folder, args.account);
+        return Ok(());
+    }
+
+    let config_path = args.config.unwrap_or_else(config::accounts_yaml_path);
+    let config = Config::load(&config_path).context("Failed to load configuration")?;
+    let account = config
+        .accounts
+        .iter()
+        .find(|a| a.name.eq_ignore_ascii_case(&args.account))
+        .with_context(|| format!("Account '{}' not found", args.account))?
+        .clone();
+    if account.password.is_none() {
+        anyhow::bail!("No password found for {}. Check your .env file.", account.name);
+    }
+
+    let mut exporter = ImapExporter::new(account, args.debug);
+    exporter.connect().context("Failed to connect to IMAP server")?;
+    let result = append_all(&mut exporter, &notes, &args.folder, &keys);
+    if let Err(e) = exporter.disconnect() {
+        println!("Warning: Disconnect error: {}", e);
+    }
+    let (restored, errors) = result?;
+    println!("Restore: {} message(s) appended to {}, {} errors", restored, args.folder, errors);
+    Ok(())
+}
+
+fn append_all(
+    exporter: &mut ImapExporter,
+    notes: &[PathBuf],
+    folder: &str,
+    keys: &FieldKeys,
+) -> Result<(usize, usize)> {
+    let mailbox = exporter.ensure_mailbox(folder)?;
+    let (mut restored, mut errors) = (0usize, 0usize);
+    for note in notes {
+        match message_for_note(note, keys).and_then(|m| exporter.append_message(&mailbox, &m)) {
+            Ok(()) => restored += 1,
+            Err(e) => {
+                eprintln!("Warning: could not restore {}: {:#}", note.display(), e);
+                errors += 1;
+            }
+        }
+    }
+    Ok((restored, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_notes_skips_underscore_dirs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().join("Pro");
+        fs::create_dir_all(root.join("2026/06/_deleted")).unwrap();
+        fs::write(root.join("2026/06/a.md"), "---\nsubject: A\n---\n").unwrap();
+        fs::write(root.join("2026/06/a.eml"), "Subject: A\r\n\r\n").unwrap();
+        fs::write(root.join("2026/06/2026-06-01_page.html"), "<html></html>").unwrap();
+        fs::write(root.join("2026/06/_deleted/b.md"), "---\nsubject: B\n---\n").unwrap();
+
+        let notes = collect_notes(&[root.clone(), root.join("2026/06/a.md")], &FieldKeys::builtin());
+        // Inclusive: the note, once; exclusive: kept original, attached page, _deleted
+        assert_eq!(notes, vec![root.join("2026/06/a.md")]);
+    }
+
+    #[test]
+    fn test_collect_notes_skips_children_carried_by_their_parent() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().join("2026/06");
+        let elsewhere = temp.path().join("Perso/2026/06");
+        fs::create_dir_all(&dir).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(
+            dir.join("parent.md"),
+            "---\nsubject: Fwd\nattachments:\n- child.md\n- ../../Perso/2026/06/filed.md\n---\n",
+        )
+        .unwrap();
+        fs::write(dir.join("child.md"), "---\nsubject: Devis\n---\n").unwrap();
+        fs::write(elsewhere.join("filed.md"), "---\nsubject: Facture\n---\n").unwrap();
+
+        let keys = FieldKeys::builtin();
+        let notes = collect_notes(&[temp.path().to_path_buf()], &keys);
+        // Inclusive: the parent, and the child routed away from it (not in a rebuilt parent)
+        assert_eq!(notes, vec![dir.join("parent.md"), elsewhere.join("filed.md")]);
+
+        // Exclusive: the kept original holds every embedded message
+        fs::write(dir.join("parent.eml"), "Subject: Fwd\r\n\r\n").unwrap();
+        let notes = collect_notes(&[temp.path().to_path_buf()], &keys);
+        assert_eq!(notes, vec![dir.join("parent.md")]);
+    }
+}
@@ -0,0 +1,195 @@
+//! `retry-failed` CLI subcommand: re-export quarantined messages.
+//!
+//! Each message kept in `<export_directory>/_failed/` (see `quarantine`) is
+//! exported again from its `.eml`, without connecting to the server, with the
+//! folder, tags and flags of its report. Exported (or meanwhile exported)
+//! messages leave the quarantine and their notes are routed like a CLI
+//! export; messages that still fail get their report updated.
+
+use anyhow::{Context, Result};
+use clap::Args;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Account, Config, Settings};
+use crate::destinations;
+use crate::email_export::{export_to_markdown, ExportContext, FolderName};
+use crate::folder_rules::account_for_folder;
+use crate::frontmatter::{self, FieldKeys};
+use crate::quarantine::{self, QuarantineRecord};
+use crate::route::{self, Destination, RouteDecision};
+use crate::utils::encode_imap_utf7;
+
+#[derive(Args)]
+pub struct RetryFailedArgs {
+    /// Only this account (default: all accounts)
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// List the quarantined messages and their errors without retrying
+    #[arg(long)]
+    pub list: bool,
+
+    /// Path to config file (default: system config dir)
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Enable debug mode (verbose export output)
+    #[arg(short, long)]
+    pub debug: bool,
+}
+
+/// Outcome of `retry_account`.
+#[derive(Debug, Default)]
+pub struct RetryReport {
+    pub exported: usize,
+    /// Already exported by a later run: dropped from the quarantine.
+    pub already_exported: usize,
+    pub failed: usize,
+    /// `(staging_path, decision)` of the new notes, to route.
+    pub decisions: Vec<(PathBuf, RouteDecision)>,
+}
+
+/// Export again every quarantined message of `account`.
+pub fn retry_account(account: &Account, dests: &[Destination], debug: bool) -> RetryReport {
+    let base_export_directory = PathBuf::from(&account.export_directory);
+    let mut report = RetryReport::default();
+
+    for (eml, mut record) in quarantine::list(&base_export_directory) {
+        let result = fs::read(&eml)
+            .with_context(|| format!("failed to read {}", eml.display()))
+            .and_then(|raw| retry_one(&raw, &record, account, &base_export_directory, dests, debug));
+        match result {
+            Ok(exported) => {
+                match exported {
+                    Some(decision) => {
+                        report.exported += 1;
+                        report.decisions.push(decision);
+                    }
+                    None => report.already_exported += 1,
+                }
+                if let Err(e) = quarantine::release(&eml) {
+                    eprintln!("Warning: {:#}", e);
+                }
+            }
+            Err(e) => {
+                report.failed += 1;
+                if debug {
+                    println!("  Still failing {}: {:#}", eml.display(), e);
+                }
+                record.set_error(&e);
+                record.retries += 1;
+                if let Err(e) = quarantine::save_record(&eml, &record) {
+                    eprintln!("Warning: {:#}", e);
+                }
+            }
+        }
+    }
+    report
+}
+
+fn retry_one(
+    raw: &[u8],
+    record: &QuarantineRecord,
+    account: &Account,
+    base_export_directory: &Path,
+    dests: &[Destination],
+    debug: bool,
+) -> Result<Option<(PathBuf, RouteDecision)>> {
+    // Effective settings of the message's folder (`folders:` overrides)
+    let folder = FolderName {
+        raw: encode_imap_utf7(&record.folder),
+        display: record.folder.clone(),
+        delimiter: record.delimiter.clone(),
+        namespace: None,
+    };
+    let folder_account = account_for_folder(account, &folder);
+    let mut ctx = ExportContext {
+        export_directory: &record.export_directory,
+        base_export_directory,
+        account: &folder_account,
+        debug_mode: debug,
+        dests,
+        folder: &record.folder,
+        index: None,
+        flags: &record.flags,
+        signatures: None,
+    };
+    export_to_markdown(raw, record.tags.clone(), None, &mut ctx)
+}
+
+/// Entry point dispatched from `main`.
+pub fn run(args: RetryFailedArgs) -> Result<()> {
+    let config_path = args.config.unwrap_or_else(config::accounts_yaml_path);
+    let config = Config::load(&config_path).context("Failed to load configuration")?;
+    let accounts: Vec<&Account> = config
+        .accounts
+        .iter()
+        .filter(|a| args.account.as_ref().is_none_or(|name| a.name.eq_ignore_ascii_case(name)))
+        .collect();
+    if accounts.is_empty() {
+        anyhow::bail!("No matching account in {}", config_path.display());
+    }
+
+    if args.list {
+        for account in &accounts {
+            for (eml, record) in quarantine::list(Path::new(&account.export_directory)) {
+                println!(
+                    "{}  {:<6} {}  uid {}  retries {}  {}\n    {}",
+                    account.name,
+                    format!("{:?}", record.stage).to_lowercase(),
+                    record.folder,
+                    record.uid.map(|u| u.to_string()).unwrap_or_else(|| "-".into()),
+                    record.retries,
+                    eml.display(),
+                    record.errors.join(": ")
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let dests = route::load_destinations();
+    let settings = Settings::load(&config::settings_path()).unwrap_or_default();
+    for account in accounts {
+        let report = retry_account(account, &dests, args.debug);
+        println!(
+            "retry-failed {}: {} exported, {} already exported, {} still failing",
+            account.name, report.exported, report.already_exported, report.failed
+        );
+        match &settings.notes_dir {
+            Some(notes_dir) => route_notes(&report.decisions, Path::new(notes_dir), &account.field_keys),
+            None if !report.decisions.is_empty() => println!(
+                "Note: notes_dir not configured in settings.yaml — {} emails remain in staging (not routed)",
+                report.decisions.len()
+            ),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Move the new notes (and the child notes of their embedded messages) into
+/// `notes_dir`, with their destination's static frontmatter fields, as the
+/// CLI export does.
+fn route_notes(decisions: &[(PathBuf, RouteDecision)], notes_dir: &Path, keys: &FieldKeys) {
+    let dest_cfg = destinations::load_yaml(&route::destinations_path()).unwrap_or_default();
+    for (staging_path, decision) in decisions {
+        match route::apply_decision(staging_path, &decision.rel_path, notes_dir, keys) {
+            Ok(final_path) => {
+                let children = route::apply_embedded(&final_path, &decision.embedded, notes_dir, keys);
+                for (note_path, rel_path) in std::iter::once((final_path, decision.rel_path.clone())).chain(children) {
+                    if let Some(fields) = destinations::static_fields_for(&dest_cfg, &rel_path) {
+                        if let Err(e) = frontmatter::append_fields(&note_path, fields) {
+                            eprintln!(
+                                "Warning: could not add destination fields to {}: {:#}",
+                                note_path.display(), e
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => eprintln!("Warning: could not route {}: {:#}", staging_path.display(), e),
+        }
+    }
+}
@@ -5,6 +5,8 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
+use crate::frontmatter::{FieldKeys, NoteHeader};
+
 /// Safe path-segment shape — alphanumerics, space, dash, underscore, dot.
 /// Used to validate user-typed notes destinations against path-traversal.
 static SAFE_PATH_SEGMENT_RE: LazyLock<Regex> =
@@ -64,96 +66,63 @@ fn relative_path_from(from_dir: &Path, to: &Path) -> PathBuf {
     result
 }
 
-/// Rewrite attachment paths in a `.md` file's YAML frontmatter so they are
-/// relative to `new_parent_dir` instead of `old_parent_dir`.
+/// Rewrite attachment paths in a `.md` file's header so they are relative to
+/// `new_parent_dir` instead of `old_parent_dir`.
 /// Both `old_parent_dir` and `new_parent_dir` must be absolute paths.
+///
+/// The attachment list is read through [`NoteHeader`] (any dialect, renamed
+/// `attachments` key included); each link whose relative path changes is then
+/// replaced in place inside the header, so the original layout (block or flow
+/// list, Logseq property) is preserved.
 pub fn rewrite_attachment_paths(
     md_path: &Path,
     old_parent_dir: &Path,
     new_parent_dir: &Path,
+    keys: &FieldKeys,
 ) -> Result<()> {
     let content = fs::read_to_string(md_path)
         .with_context(|| format!("failed to read {}", md_path.display()))?;
 
-    // Only process files that have a YAML frontmatter block.
-    let Some(rest) = content.strip_prefix("---\n") else {
+    // Only process files that have a header block.
+    let Some(Ok(list)) = parse_frontmatter_attachments(&content, keys) else {
         return Ok(());
     };
-    let Some(end) = rest.find("\n---") else {
-        return Ok(());
-    };
-    let frontmatter = &rest[..end];
-    let after_frontmatter = &rest[end + 4..]; // skip "\n---"
-
-    // Rewrite each line that is an attachment list item: "  - <path>"
-    // We look for lines inside an `attachments:` block.
-    // TODO: unify with serde_yaml parse (see plan Phase 2) — rewrite_attachment_paths uses a
-    // line-parser because it must emit rewritten YAML lines (different data shape from parsing
-    // the list alone). Refactoring the enumeration step to use parse_frontmatter_attachments
-    // would require a two-pass approach (parse then reserialize) — not done here.
-    let mut in_attachments = false;
-    let mut new_frontmatter = String::with_capacity(frontmatter.len());
-
-    for line in frontmatter.lines() {
-        let trimmed = line.trim_start();
-        if trimmed.starts_with("attachments:") {
-            in_attachments = true;
-            new_frontmatter.push_str(line);
-            new_frontmatter.push('\n');
-            continue;
+    let body = crate::frontmatter::note_body(&content);
+    let (header, body) = content.split_at(content.len() - body.len());
+
+    let mut new_header = header.to_string();
+    for link in &list {
+        // Absolute attachment path from old_parent_dir
+        let abs = old_parent_dir.join(link.replace('/', std::path::MAIN_SEPARATOR_STR));
+        // Relative path from new_parent_dir to abs
+        let rel = relative_path_from(new_parent_dir, &abs);
+        let rel_str = rel.to_string_lossy().replace('\\', "/");
+        if rel_str != *link {
+            new_header = new_header.replace(link.as_str(), &rel_str);
         }
-        if in_attachments && trimmed.starts_with("- ") {
-            // Extract the path after "- "
-            let path_str = trimmed.trim_start_matches("- ");
-            // Absolute attachment path from old_parent_dir
-            let abs = old_parent_dir.join(path_str.replace('/', std::path::MAIN_SEPARATOR_STR));
-            // Relative path from new_parent_dir to abs
-            let rel = relative_path_from(new_parent_dir, &abs);
-            let rel_str = rel.to_string_lossy().replace('\\', "/");
-            // Preserve original indentation
-            let indent: String = line.chars().take_while(|c| *c == ' ').collect();
-            new_frontmatter.push_str(&format!("{}- {}\n", indent, rel_str));
-            continue;
-        }
-        // A non-list line ends the attachments block
-        if in_attachments && !trimmed.starts_with('-') {
-            in_attachments = false;
-        }
-        new_frontmatter.push_str(line);
-        new_frontmatter.push('\n');
+    }
+    if new_header == header {
+        return Ok(());
     }
 
-    let new_content = format!("---\n{}---{}", new_frontmatter, after_frontmatter);
-    fs::write(md_path, new_content)
+    fs::write(md_path, format!("{}{}", new_header, body))
         .with_context(|| format!("failed to write updated frontmatter to {}", md_path.display()))?;
     Ok(())
 }
 
-/// Extract the `attachments:` list from `.md` content's YAML frontmatter.
+/// Extract the attachment list from a note's header.
 ///
-/// Uses `serde_yaml` deserialization so that YAML-quoted names (e.g.
-/// `- 'invoice #5.pdf'`) are correctly unquoted — a line-parser would return
-/// the quoted string verbatim, causing the file to go missing silently.
+/// Goes through [`NoteHeader`], so YAML-quoted names (e.g. `- 'invoice #5.pdf'`)
+/// are correctly unquoted, Logseq property blocks are understood, and an
+/// `attachments` key renamed in the frontmatter settings is still found.
 ///
-/// Returns `None` if no valid frontmatter delimiters are present.
+/// Returns `None` if the note has no header.
 /// Returns `Some(Err(_))` if the YAML block is malformed.
-/// Returns `Some(Ok(vec))` on success (empty when `attachments:` is absent).
-fn parse_frontmatter_attachments(content: &str) -> Option<Result<Vec<String>>> {
-    #[derive(serde::Deserialize)]
-    struct AttachmentsHead {
-        #[serde(default)]
-        attachments: Vec<String>,
-    }
-
-    let rest = content.strip_prefix("---\n")?;
-    let end = rest.find("\n---")?;
-    let frontmatter = &rest[..end];
-
-    Some(
-        serde_yaml::from_str::<AttachmentsHead>(frontmatter)
-            .map(|h| h.attachments)
-            .map_err(|e| anyhow::anyhow!("failed to parse frontmatter YAML: {}", e)),
-    )
+/// Returns `Some(Ok(vec))` on success (empty when the list is absent).
+fn parse_frontmatter_attachments(content: &str, keys: &FieldKeys) -> Option<Result<Vec<String>>> {
+    NoteHeader::parse(content).map(|header| {
+        header.map(|h| h.get_list("attachments", keys))
+    })
 }
 
 /// Move a `.md` file and its sibling attachment files into `dest_dir`.
@@ -166,13 +135,26 @@ fn parse_frontmatter_attachments(content: &str) -> Option<Result<Vec<String>>> {
 /// Steps:
 /// 1. Reject symlinks: if `md_path` is a symlink, return `Err` immediately (no FS mutation).
 /// 2. Read the `.md` content and extract the attachment list via `parse_frontmatter_attachments`.
-/// 3. Move each attachment from the source directory into `dest_dir`.
+/// 3. Move each attachment from the source directory into `dest_dir`. Child notes
+///    of embedded messages are left in place: `apply_embedded` files them.
 /// 4. Move the `.md` itself into `dest_dir`.
 /// 5. Rewrite attachment paths in the moved `.md` (same-folder bare links stay unchanged).
 ///
 /// The move is attempted with `fs::rename`; if that crosses device boundaries the
 /// fallback is `fs::copy` + `fs::remove_file`.
-pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
+///
+/// `keys` resolves a renamed `attachments` key (see [`FieldKeys`]).
+///
+/// Returns the final path of the `.md`.
+pub fn move_email(md_path: &Path, dest_dir: &Path, keys: &FieldKeys) -> Result<PathBuf> {
+    let md_name = md_path
+        .file_name()
+        .with_context(|| format!("md_path has no file name: {}", md_path.display()))?;
+    move_email_as(md_path, dest_dir, &md_name.to_string_lossy(), keys)
+}
+
+/// [`move_email`], writing the `.md` as `md_name` inside `dest_dir`.
+fn move_email_as(md_path: &Path, dest_dir: &Path, md_name: &str, keys: &FieldKeys) -> Result<PathBuf> {
     // --- Symlink guard (project rule 02-rust-filesystem-safety) ---
     let meta = md_path
         .symlink_metadata()
@@ -201,7 +183,7 @@ pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
             );
             vec![]
         }
-        Ok(content) => match parse_frontmatter_attachments(&content) {
+        Ok(content) => match parse_frontmatter_attachments(&content, keys) {
             // No frontmatter delimiters — normal for emails without attachments.
             None => vec![],
             Some(Err(e)) => {
@@ -241,6 +223,10 @@ pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
         if !att_src.exists() {
             continue;
         }
+        // Filed by `apply_embedded`, with its own attachments
+        if is_child_note(&att_src) {
+            continue;
+        }
 
         let original_name = match att_src.file_name() {
             Some(n) => n.to_string_lossy().into_owned(),
@@ -276,11 +262,7 @@ pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
     }
 
     // --- Move the .md file ---
-    let md_dest = dest_dir.join(
-        md_path
-            .file_name()
-            .with_context(|| format!("md_path has no file name: {}", md_path.display()))?,
-    );
+    let md_dest = dest_dir.join(md_name);
     if fs::rename(md_path, &md_dest).is_err() {
         fs::copy(md_path, &md_dest).with_context(|| {
             format!(
@@ -294,6 +276,12 @@ pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
         })?;
     }
 
+    // --- Move the kept original message (`keep_raw`) alongside ---
+    let raw_src = crate::rebuild::raw_message_path(md_path);
+    if raw_src.symlink_metadata().is_ok_and(|m| m.file_type().is_file()) {
+        move_file(&raw_src, &crate::rebuild::raw_message_path(&md_dest))?;
+    }
+
     // --- Update links for any attachment renamed on collision ---
     // Each link (frontmatter list item + body markdown link) is the bare file name,
     // so a whole-file substring replace is sufficient and safe (names are unique and
@@ -325,7 +313,7 @@ pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
     // The .md and its attachments are now co-located in dest_dir. Passing dest_dir as
     // both old and new parent means bare links stay unchanged (relative_path_from is
     // identity when old == new).
-    if let Err(e) = rewrite_attachment_paths(&md_dest, dest_dir, dest_dir) {
+    if let Err(e) = rewrite_attachment_paths(&md_dest, dest_dir, dest_dir, keys) {
         eprintln!(
             "warning: could not update attachment paths in {}: {}",
             md_dest.display(),
@@ -333,15 +321,36 @@ pub fn move_email(md_path: &Path, dest_dir: &Path) -> Result<()> {
         );
     }
 
+    Ok(md_dest)
+}
+
+/// Whether an attachment of a note is itself a note with a header: the child
+/// note of an embedded message (see `embedded`).
+pub fn is_child_note(path: &Path) -> bool {
+    crate::output::is_note_file(path)
+        && fs::read_to_string(path).is_ok_and(|content| {
+            crate::output::is_note_content(path, &content) && NoteHeader::read(&content).is_some()
+        })
+}
+
+/// Rename `src` to `dest`, falling back to copy + remove across devices.
+fn move_file(src: &Path, dest: &Path) -> Result<()> {
+    if fs::rename(src, dest).is_err() {
+        fs::copy(src, dest).with_context(|| {
+            format!("failed to copy {} to {}", src.display(), dest.display())
+        })?;
+        fs::remove_file(src)
+            .with_context(|| format!("failed to remove {} after copy", src.display()))?;
+    }
     Ok(())
 }
 
 /// Pick a file name inside `dir` that does not collide with an existing entry.
 ///
 /// Returns `file_name` unchanged when free; otherwise inserts a numeric suffix
-/// before the extension (`invoice.pdf` → `invoice_2.pdf`, `invoice_3.pdf`, …),
-/// mirroring the staging-time collision loop in `email_export::extract_attachments`.
-fn unique_name_in(dir: &Path, file_name: &str) -> String {
+/// before the extension (`invoice.pdf` → `invoice_2.pdf`, `invoice_3.pdf`, …).
+/// Also names attachments at staging time (`email_export::extract_attachments`).
+pub(crate) fn unique_name_in(dir: &Path, file_name: &str) -> String {
     if !dir.join(file_name).exists() {
         return file_name.to_string();
     }
@@ -377,7 +386,7 @@ fn unique_name_in(dir: &Path, file_name: &str) -> String {
 ///
 /// Attachment relocation uses `fs::rename`; on cross-device failure it falls back
 /// to `fs::copy` + `fs::remove_file` (parity with `move_email`).
-pub fn delete_email(md_path: &Path) -> Result<()> {
+pub fn delete_email(md_path: &Path, keys: &FieldKeys) -> Result<()> {
     // --- Symlink guard (project rule 02-rust-filesystem-safety) ---
     let meta = md_path
         .symlink_metadata()
@@ -402,7 +411,7 @@ pub fn delete_email(md_path: &Path) -> Result<()> {
             );
             vec![]
         }
-        Ok(content) => match parse_frontmatter_attachments(&content) {
+        Ok(content) => match parse_frontmatter_attachments(&content, keys) {
             None => vec![],
             Some(Err(e)) => {
                 eprintln!(
@@ -468,6 +477,18 @@ pub fn delete_email(md_path: &Path) -> Result<()> {
         }
     }
 
+    // --- Relocate the kept original message (`keep_raw`) too ---
+    let raw_src = crate::rebuild::raw_message_path(md_path);
+    if let (true, Some(raw_name)) = (
+        raw_src.symlink_metadata().is_ok_and(|m| m.file_type().is_file()),
+        raw_src.file_name(),
+    ) {
+        fs::create_dir_all(&deleted_dir).with_context(|| {
+            format!("failed to create {}", deleted_dir.display())
+        })?;
+        move_file(&raw_src, &deleted_dir.join(raw_name))?;
+    }
+
     // --- Remove the .md file ---
     fs::remove_file(md_path)
         .with_context(|| format!("failed to remove {}", md_path.display()))?;
@@ -570,6 +591,8 @@ pub struct EmailMeta {
     pub subject: String,
     /// Account name that received this email (IMAP account identifier).
     pub account: String,
+    /// `email_type` of the note (`direct`, `invitation`…).
+    pub email_type: String,
     /// Parsed send date from the email.
     pub date: DateTime<FixedOffset>,
 }
@@ -584,6 +607,9 @@ pub struct RouteDecision {
     pub matched_rule: Option<String>,
     /// `true` when no rule matched and the fallback path was used.
     pub is_default: bool,
+    /// Child notes of the email's embedded messages (staging path, own
+    /// decision), filed by `apply_embedded` once the note is routed.
+    pub embedded: Vec<(PathBuf, RouteDecision)>,
 }
 
 /// A match condition inside a `destinations.txt` entry.
@@ -597,6 +623,8 @@ pub enum MatchRule {
     Subject(String),
     /// Matches the IMAP account name (exact, case-sensitive).
     Account(String),
+    /// Matches the `email_type` of the note (case-insensitive), e.g. `invitation`.
+    Type(String),
 }
 
 /// A single entry from `destinations.txt`.
@@ -678,6 +706,12 @@ pub fn parse_destinations(content: &str) -> Result<Vec<Destination>> {
                     } else {
                         rules.push(MatchRule::Account(a.to_string()));
                     }
+                } else if let Some(t) = token.strip_prefix("type:") {
+                    if t.is_empty() {
+                        eprintln!("warning: destinations.txt — empty type value in {:?}", raw_line);
+                    } else {
+                        rules.push(MatchRule::Type(t.to_string()));
+                    }
                 } else {
                     eprintln!(
                         "warning: destinations.txt — unknown attribute token {:?} in line {:?}, skipping",
@@ -749,6 +783,7 @@ pub fn route_email(meta: &EmailMeta, dests: &[Destination]) -> RouteDecision {
                     meta.subject.to_lowercase().contains(&k.to_lowercase())
                 }
                 MatchRule::Account(a) => meta.account == *a,
+                MatchRule::Type(t) => meta.email_type.eq_ignore_ascii_case(t),
             };
 
             if matched {
@@ -758,6 +793,7 @@ pub fn route_email(meta: &EmailMeta, dests: &[Destination]) -> RouteDecision {
                     rel_path,
                     matched_rule: Some(rule_desc),
                     is_default: false,
+                    embedded: Vec::new(),
                 };
             }
         }
@@ -770,6 +806,7 @@ pub fn route_email(meta: &EmailMeta, dests: &[Destination]) -> RouteDecision {
             rel_path,
             matched_rule: None,
             is_default: true,
+            embedded: Vec::new(),
         };
     }
 
@@ -778,6 +815,7 @@ pub fn route_email(meta: &EmailMeta, dests: &[Destination]) -> RouteDecision {
         rel_path: format!("{}/{}/{}", DEFAULT_BASE, year, month),
         matched_rule: None,
         is_default: true,
+        embedded: Vec::new(),
     }
 }
 
@@ -825,15 +863,159 @@ pub fn ensure_year_month(rel_path: &str, year: &str, month: &str) -> String {
 /// `rel_path` is joined onto `notes_dir` via `join_safe_segments` (anti-traversal).
 /// Missing directories are created with `fs::create_dir_all` (D4).
 /// `move_email` handles the `.md` + its referenced attachment siblings.
-pub fn apply_decision(staging_md: &Path, rel_path: &str, notes_dir: &Path) -> Result<()> {
+/// Returns the final path of the `.md`.
+pub fn apply_decision(
+    staging_md: &Path,
+    rel_path: &str,
+    notes_dir: &Path,
+    keys: &FieldKeys,
+) -> Result<PathBuf> {
     let dest_dir = join_safe_segments(notes_dir, rel_path)
         .with_context(|| format!("invalid routing path {:?}", rel_path))?;
     fs::create_dir_all(&dest_dir)
         .with_context(|| format!("failed to create directory {}", dest_dir.display()))?;
-    move_email(staging_md, &dest_dir)
+    move_email(staging_md, &dest_dir, keys)
         .with_context(|| format!("failed to move {} to {}", staging_md.display(), dest_dir.display()))
 }
 
+/// File the child notes of a routed note by their own decisions.
+///
+/// Child notes stay in staging when their parent moves (see `move_email`);
+/// each one is moved to its own destination with its own attachments, kept
+/// original and children, under a non-colliding name, and the parent's links
+/// to it — attachments list and body — are rewritten to the path it landed
+/// at. Failures are warnings: the child is then put next to its parent,
+/// still linked.
+///
+/// Returns the final path and routing path of every child filed.
+pub fn apply_embedded(
+    parent_final: &Path,
+    embedded: &[(PathBuf, RouteDecision)],
+    notes_dir: &Path,
+    keys: &FieldKeys,
+) -> Vec<(PathBuf, String)> {
+    let mut filed = Vec::new();
+    let Some(parent_dir) = parent_final.parent() else {
+        return filed;
+    };
+    for (staging_child, decision) in embedded {
+        let Some(name) = staging_child.file_name() else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if staging_child.symlink_metadata().is_err() {
+            eprintln!("warning: child note {} not found", staging_child.display());
+            continue;
+        }
+        let move_into = |dir: &Path| -> Result<PathBuf> {
+            fs::create_dir_all(dir).with_context(|| format!("failed to create directory {}", dir.display()))?;
+            move_email_as(staging_child, dir, &unique_name_in(dir, &name), keys)
+        };
+        let routed = join_safe_segments(notes_dir, &decision.rel_path)
+            .with_context(|| format!("invalid routing path {:?}", decision.rel_path))
+            .and_then(|dir| move_into(&dir));
+        let child_final = match routed {
+            Ok(path) => {
+                filed.push((path.clone(), decision.rel_path.clone()));
+                path
+            }
+            Err(e) => {
+                eprintln!("warning: could not route child note {}: {:#}", staging_child.display(), e);
+                match move_into(parent_dir) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        eprintln!("warning: could not move child note {}: {:#}", staging_child.display(), e);
+                        continue;
+                    }
+                }
+            }
+        };
+        let new_link = relative_path_from(parent_dir, &child_final)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if new_link != name {
+            let updated = fs::read_to_string(parent_final)
+                .map(|content| relink_attachment(&content, &name, &new_link, keys))
+                .and_then(|content| fs::write(parent_final, content));
+            if let Err(e) = updated {
+                eprintln!("warning: could not update child note link in {}: {}", parent_final.display(), e);
+            }
+        }
+        filed.extend(apply_embedded(&child_final, &decision.embedded, notes_dir, keys));
+    }
+    filed
+}
+
+/// Point a note's links to one attachment at `new`: the entry of its header
+/// `attachments` list (any dialect, renamed key included) and the link targets
+/// of the body (Markdown, Org, HTML). Other mentions of the name — body text,
+/// another field, a longer attachment name containing it — are left alone.
+fn relink_attachment(content: &str, old: &str, new: &str, keys: &FieldKeys) -> String {
+    let mut out = content.to_string();
+    for key in keys.candidates("attachments") {
+        if let Some(range) = attachments_entry(&out, key) {
+            let entry = replace_delimited(&out[range.clone()], old, new);
+            out.replace_range(range, &entry);
+            break;
+        }
+    }
+    let angled = if new.contains([' ', '(', ')']) { format!("<{}>", new) } else { new.to_string() };
+    out.replace(&format!("](<{}>)", old), &format!("](<{}>)", new))
+        .replace(&format!("]({})", old), &format!("]({})", angled))
+        .replace(&format!("[[file:{}]", old), &format!("[[file:{}]", new))
+        .replace(&format!("href=\"{}\"", old), &format!("href=\"{}\"", new))
+}
+
+/// Byte range of the `key` entry in a note's header: the YAML key line and
+/// its block items, the Logseq property or Org drawer line, or the JSON array.
+fn attachments_entry(content: &str, key: &str) -> Option<std::ops::Range<usize>> {
+    let json_key = format!("\"{}\":[", key);
+    if let Some(pos) = content.find(&json_key) {
+        let end = pos + content[pos..].find(']')? + 1;
+        return Some(pos..end);
+    }
+    let header_len = content.len() - crate::frontmatter::note_body(content).len();
+    let org_key = format!(":{}:", key.to_uppercase());
+    let mut offset = 0;
+    let mut lines = content[..header_len].split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        let start = offset;
+        offset += line.len();
+        let is_key = line.strip_prefix(key).is_some_and(|rest| rest.starts_with(':'))
+            || line.starts_with(&org_key);
+        if !is_key {
+            continue;
+        }
+        // YAML block items follow their key, indented or as `- ` lines
+        for item in lines.by_ref() {
+            if !item.starts_with([' ', '-']) {
+                break;
+            }
+            offset += item.len();
+        }
+        return Some(start..offset);
+    }
+    None
+}
+
+/// Replace `old` in a list entry only where it is a whole list item:
+/// between delimiters (`- `, `[`, `, `, quotes, `file:`) and an item end.
+fn replace_delimited(entry: &str, old: &str, new: &str) -> String {
+    let mut out = String::with_capacity(entry.len());
+    let mut rest = entry;
+    while let Some(pos) = rest.find(old) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + old.len()..].chars().next();
+        let starts = matches!(before, Some(' ' | '[' | ',' | '\'' | '"' | ':'));
+        let ends = matches!(after, None | Some('\n' | '\r' | ',' | ']' | '\'' | '"'));
+        out.push_str(&rest[..pos]);
+        out.push_str(if starts && ends { new } else { old });
+        rest = &rest[pos + old.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
 // ── AI extension point ────────────────────────────────────────────────────────
 
 /// AI-assisted routing no-op.
@@ -0,0 +1,453 @@
+//! Signature blocks: detected at the end of the sender's own text and removed
+//! from the note body (`signatures:` settings, off by default).
+//!
+//! Only the sender's own text is searched — the quoted history of a reply is
+//! left alone. Detection, from the most to the least reliable:
+//! - the RFC 3676 delimiter (`-- ` on its own line), at every level;
+//! - `medium`: the tail shared with the sender's previous messages
+//!   ([`SignatureHistory`]), then the contact and short lines after a closing
+//!   phrase (« Cordialement », « Best regards »…), the closing phrase and the
+//!   name line under it being kept;
+//! - `high`: trailing paragraphs of contact details (phone, address, links)
+//!   and legal mentions, without any delimiter.
+//!
+//! The removed block can be kept in the `signature_block:` field or in a
+//! per-contact note (`keep`).
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::cleaner;
+
+/// Previous tails remembered per sender.
+const HISTORY_TAILS: usize = 5;
+
+/// Non-empty lines of a message's tail remembered per sender.
+const TAIL_LINES: usize = 25;
+
+/// Short lines other than contact details allowed after a closing phrase
+/// (title, company…).
+const CLOSING_SHORT_LINES: usize = 4;
+
+/// Closing phrases, alone on their line.
+static CLOSING_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:bien\s+)?(?:cordialement|[àa]\s+bient[ôo]t|amicalement|bien\s+[àa]\s+(?:vous|toi)|(?:sinc[èe]res|meilleures|bonnes)\s+salutations|salutations(?:\s+distingu[ée]es)?|bonne\s+(?:journ[ée]e|soir[ée]e|fin\s+de\s+journ[ée]e)|merci(?:\s+(?:beaucoup|d['’]avance|par\s+avance))?|bises|(?:best|kind|warm)\s+regards|regards|best(?:\s+wishes)?|cheers|many\s+thanks|thanks(?:\s+again)?|thank\s+you|sincerely|yours(?:\s+(?:truly|sincerely))?|respectfully)[\s,.!]*$",
+    )
+    .expect("static regex")
+});
+
+/// Contact details: phone number, address, link or their labels.
+static CONTACT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(?:\+?\d[\d .()/-]{7,}\d|@|https?://|www\.|\b(?:t[ée]l|mobile|portable|fax|phone|mob|gsm|linkedin|adresse|address|si[èe]ge)\b)",
+    )
+    .expect("static regex")
+});
+
+/// First line of a legal or environmental mention.
+static LEGAL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:ce\s+(?:message|courriel|mail|e-mail)|le\s+pr[ée]sent\s+(?:message|courriel)|this\s+(?:e-?mail|message)|confidential|avis\s+de\s+confidentialit[ée]|disclaimer|avertissement|p?\s*(?:pensez\s+[àa]\s+l['’]environnement|please\s+consider\s+the\s+environment))",
+    )
+    .expect("static regex")
+});
+
+/// How far detection goes beyond the `-- ` delimiter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggressiveness {
+    /// RFC 3676 delimiter only.
+    Low,
+    /// Delimiter, tail repeated across the sender's messages, closing phrases.
+    #[default]
+    Medium,
+    /// Also contact-detail and legal paragraphs without a delimiter.
+    High,
+}
+
+impl Aggressiveness {
+    /// Longest block removed, in non-empty lines.
+    fn max_lines(self) -> usize {
+        match self {
+            Aggressiveness::Low => 10,
+            Aggressiveness::Medium => 15,
+            Aggressiveness::High => 30,
+        }
+    }
+}
+
+/// Where a removed signature block is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureKeep {
+    /// Dropped.
+    #[default]
+    None,
+    /// `signature_block:` field of the note.
+    Frontmatter,
+    /// `_local/signatures/<address>.md`, the sender's latest signature.
+    Contact,
+}
+
+/// Signature block detection (`signatures:`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignatureSettings {
+    /// Remove signature blocks from the note body.
+    pub strip: bool,
+    pub aggressiveness: Aggressiveness,
+    pub keep: SignatureKeep,
+}
+
+/// Tails of the previous messages of each sender, for the repeated-block
+/// detection. Saved between runs in the configuration directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SignatureHistory {
+    /// Sender address → last tails (trimmed non-empty lines), oldest first.
+    senders: BTreeMap<String, Vec<Vec<String>>>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl SignatureHistory {
+    pub fn path() -> PathBuf {
+        crate::config::app_config_dir().join("signature_history.json")
+    }
+
+    /// History saved at `path`; empty when missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the history if it changed since it was loaded.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let json = serde_json::to_string(self).context("failed to serialize signature history")?;
+        fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn tails(&self, sender: &str) -> &[Vec<String>] {
+        self.senders.get(sender).map_or(&[], Vec::as_slice)
+    }
+
+    fn record(&mut self, sender: &str, tail: Vec<String>) {
+        let tails = self.senders.entry(sender.to_string()).or_default();
+        tails.push(tail);
+        if tails.len() > HISTORY_TAILS {
+            tails.remove(0);
+        }
+        self.changed = true;
+    }
+}
+
+/// Body without its signature block, and the block removed.
+#[derive(Debug, PartialEq)]
+pub struct Stripped {
+    pub body: String,
+    pub block: Option<String>,
+}
+
+fn is_quote(line: &str) -> bool {
+    line.trim_start().starts_with('>')
+}
+
+/// Lines written by the sender: before the quoted history of a top-posted
+/// reply, after it for a bottom-posted one.
+fn own_region(lines: &[&str]) -> Range<usize> {
+    let is_history = |i: usize| {
+        is_quote(lines[i]) || cleaner::is_forwarded_separator(lines[i]) || cleaner::reply_header_at(lines, i).is_some()
+    };
+    let first = lines.iter().position(|l| !l.trim().is_empty());
+    let start = match first {
+        Some(i) if is_history(i) => lines.iter().rposition(|l| is_quote(l)).map_or(i + 1, |q| q + 1),
+        _ => 0,
+    };
+    let end = (start..lines.len()).find(|&i| is_history(i)).unwrap_or(lines.len());
+    start..end
+}
+
+/// Indices of the non-empty lines of `region`.
+fn non_empty(lines: &[&str], region: Range<usize>) -> Vec<usize> {
+    region.filter(|&i| !lines[i].trim().is_empty()).collect()
+}
+
+/// A name under a closing phrase: a few words, no digits nor addresses.
+fn is_name_line(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty()
+        && line.chars().count() <= 40
+        && line.split_whitespace().count() <= 5
+        && !line.contains(|c: char| c.is_ascii_digit() || c == '@' || c == ':')
+}
+
+/// Skip a leading closing phrase and the name under it: they stay in the body.
+fn after_closing(lines: &[&str], start: usize, end: usize) -> usize {
+    let mut i = start;
+    if i < end && CLOSING_RE.is_match(lines[i].trim()) {
+        i += 1;
+        while i < end && lines[i].trim().is_empty() {
+            i += 1;
+        }
+        if i < end && is_name_line(lines[i]) {
+            i += 1;
+        }
+    }
+    i
+}
+
+/// Block starting at `start` and ending with the own text, if it fits.
+fn block(lines: &[&str], start: usize, end: usize, level: Aggressiveness) -> Option<Range<usize>> {
+    let count = non_empty(lines, start..end).len();
+    (count > 0 && count <= level.max_lines()).then_some(start..end)
+}
+
+fn by_delimiter(lines: &[&str], own: &Range<usize>, level: Aggressiveness) -> Option<Range<usize>> {
+    let delimiter = own.clone().rev().find(|&i| lines[i].trim_end() == "--")?;
+    let count = non_empty(lines, delimiter + 1..own.end).len();
+    (count <= level.max_lines()).then_some(delimiter..own.end)
+}
+
+/// Trimmed non-empty lines ending the sender's own text.
+fn tail(lines: &[&str], own: &Range<usize>) -> Vec<String> {
+    let own_lines = non_empty(lines, own.clone());
+    own_lines[own_lines.len().saturating_sub(TAIL_LINES)..]
+        .iter()
+        .map(|&i| lines[i].trim().to_string())
+        .collect()
+}
+
+/// Longest tail shared with one of the sender's previous messages. The whole
+/// message repeated (notifications) is not a signature.
+fn by_repetition(lines: &[&str], own: &Range<usize>, tails: &[Vec<String>], level: Aggressiveness) -> Option<Range<usize>> {
+    let current = tail(lines, own);
+    if tails.contains(&current) {
+        return None;
+    }
+    let own_lines = non_empty(lines, own.clone());
+    let shared = tails
+        .iter()
+        .map(|tail| {
+            let common = own_lines
+                .iter()
+                .rev()
+                .zip(tail.iter().rev())
+                .take_while(|(&i, previous)| lines[i].trim() == previous.as_str())
+                .count();
+            if common == tail.len() { 0 } else { common }
+        })
+        .max()
+        .unwrap_or(0);
+    if shared < 2 || shared >= own_lines.len() {
+        return None;
+    }
+    let first = own_lines[own_lines.len() - shared];
+    let text: usize = own_lines[own_lines.len() - shared..].iter().map(|&i| lines[i].trim().len()).sum();
+    if text < 20 {
+        return None;
+    }
+    block(lines, after_closing(lines, first, own.end), own.end, level)
+}
+
+/// A line of a signature under a closing phrase: contact details, or a short
+/// line that is not a sentence (title, company).
+fn is_short_line(line: &str) -> bool {
+    let line = line.trim();
+    let sentence = line.ends_with(['?', '!']) || (line.ends_with('.') && line.split_whitespace().count() >= 5);
+    line.chars().count() <= 60 && !sentence
+}
+
+/// A closing phrase near the end, followed only by contact details and a few
+/// short lines (legal mentions too at the high level): a « Merci ! » opening
+/// the message is not a signature.
+fn by_closing_phrase(lines: &[&str], own: &Range<usize>, level: Aggressiveness) -> Option<Range<usize>> {
+    let closing = own.clone().rev().find(|&i| CLOSING_RE.is_match(lines[i].trim()))?;
+    let start = after_closing(lines, closing, own.end);
+    let block = block(lines, start, own.end, level)?;
+    let mut legal = false;
+    let mut short = 0;
+    for i in block.clone() {
+        let line = lines[i].trim();
+        if line.is_empty() {
+            legal = false;
+            continue;
+        }
+        legal |= level == Aggressiveness::High && LEGAL_RE.is_match(line);
+        if legal || CONTACT_RE.is_match(line) {
+            continue;
+        }
+        if !is_short_line(line) {
+            return None;
+        }
+        short += 1;
+    }
+    (short <= CLOSING_SHORT_LINES).then_some(block)
+}
+
+/// Trailing paragraphs of contact details or legal mentions, keeping at least
+/// one paragraph of text.
+fn by_contact_details(lines: &[&str], own: &Range<usize>, level: Aggressiveness) -> Option<Range<usize>> {
+    let mut paragraphs: Vec<Range<usize>> = Vec::new();
+    let mut i = own.start;
+    while i < own.end {
+        if lines[i].trim().is_empty() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < own.end && !lines[i].trim().is_empty() {
+            i += 1;
+        }
+        paragraphs.push(start..i);
+    }
+    let is_signature = |p: &Range<usize>| {
+        let contact = p.clone().filter(|&i| CONTACT_RE.is_match(lines[i])).count();
+        LEGAL_RE.is_match(lines[p.start].trim()) || (p.len() <= 8 && contact * 2 >= p.len())
+    };
+    let kept = paragraphs.iter().rev().take_while(|p| is_signature(p)).count();
+    if kept == 0 || kept == paragraphs.len() {
+        return None;
+    }
+    let first = paragraphs[paragraphs.len() - kept].start;
+    block(lines, first, own.end, level)
+}
+
+/// Lines of the signature block of `lines`, if any.
+fn find(lines: &[&str], tails: &[Vec<String>], level: Aggressiveness) -> Option<Range<usize>> {
+    let own = own_region(lines);
+    let found = by_delimiter(lines, &own, level);
+    if level == Aggressiveness::Low {
+        return found;
+    }
+    found
+        .or_else(|| by_repetition(lines, &own, tails, level))
+        .or_else(|| by_closing_phrase(lines, &own, level))
+        .or_else(|| (level == Aggressiveness::High).then(|| by_contact_details(lines, &own, level)).flatten())
+}
+
+/// Remove the signature block of `body`, sent by `sender` (address). The
+/// tail of the sender's own text is added to `history`.
+pub fn strip(body: &str, sender: &str, settings: &SignatureSettings, history: Option<&mut SignatureHistory>) -> Stripped {
+    let lines: Vec<&str> = body.lines().collect();
+    let sender = sender.trim().to_lowercase();
+    let tails = match (&history, sender.is_empty()) {
+        (Some(history), false) => history.tails(&sender),
+        _ => &[],
+    };
+    let found = find(&lines, tails, settings.aggressiveness);
+
+    if let (Some(history), false) = (history, sender.is_empty()) {
+        let tail = tail(&lines, &own_region(&lines));
+        if tail.len() >= 2 {
+            history.record(&sender, tail);
+        }
+    }
+
+    let Some(range) = found else {
+        return Stripped { body: body.to_string(), block: None };
+    };
+    let block = lines[range.clone()].join("\n");
+    let block = block.trim().trim_start_matches("--").trim();
+    let mut kept: Vec<&str> = lines[..range.start].to_vec();
+    while kept.last().is_some_and(|l| l.trim().is_empty()) {
+        kept.pop();
+    }
+    if range.end < lines.len() {
+        kept.push("");
+        kept.extend(&lines[range.end..]);
+    }
+    let mut stripped = kept.join("\n");
+    if body.ends_with('\n') {
+        stripped.push('\n');
+    }
+    Stripped {
+        body: stripped,
+        block: (!block.is_empty()).then(|| block.to_string()),
+    }
+}
+
+/// Write the per-contact note of `sender` (`keep: contact`) under `dir`,
+/// replacing the previous signature.
+pub fn write_contact_note(dir: &Path, sender: &str, date: &str, block: &str) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let sender = sender.trim().to_lowercase();
+    let path = dir.join(format!("{}.md", crate::utils::sanitize_filename(&sender)));
+    let content = format!("---\ncontact: {}\nupdated: {}\n---\n{}\n", sender, date, block);
+    fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(aggressiveness: Aggressiveness) -> SignatureSettings {
+        SignatureSettings { strip: true, aggressiveness, keep: SignatureKeep::None }
+    }
+
+    #[test]
+    fn test_strip_delimiter_keeps_quoted_history() {
+        let body = "Voici le devis.\n\n-- \nJean Dupont\nTél : 01 23 45 67 89\n\nLe 2 mars 2026, Alice a écrit :\n> Bonjour\n";
+        let stripped = strip(body, "jean@example.com", &settings(Aggressiveness::Low), None);
+        assert_eq!(stripped.body, "Voici le devis.\n\nLe 2 mars 2026, Alice a écrit :\n> Bonjour\n");
+        assert_eq!(stripped.block.as_deref(), Some("Jean Dupont\nTél : 01 23 45 67 89"));
+    }
+
+    #[test]
+    fn test_strip_closing_phrase_keeps_name() {
+        let body = "Le rapport est en pièce jointe.\n\nCordialement,\nJean Dupont\nDirecteur commercial\nACME SAS — 12 rue de la Paix, Paris\n";
+        let stripped = strip(body, "", &settings(Aggressiveness::Medium), None);
+        assert_eq!(stripped.body, "Le rapport est en pièce jointe.\n\nCordialement,\nJean Dupont\n");
+        assert_eq!(stripped.block.as_deref(), Some("Directeur commercial\nACME SAS — 12 rue de la Paix, Paris"));
+        // Exclusive: the low level only trusts the delimiter
+        assert_eq!(strip(body, "", &settings(Aggressiveness::Low), None).block, None);
+    }
+
+    #[test]
+    fn test_strip_closing_phrase_followed_by_text() {
+        let body = "Bonjour Paul,\n\nMerci !\n\nPeux-tu m'envoyer le rapport financier demain matin ?\nJe dois absolument le présenter lundi au comité.\n";
+        for level in [Aggressiveness::Medium, Aggressiveness::High] {
+            let stripped = strip(body, "", &settings(level), None);
+            assert_eq!(stripped.block, None);
+            assert_eq!(stripped.body, body);
+        }
+    }
+
+    #[test]
+    fn test_strip_repeated_tail_across_messages() {
+        let mut history = SignatureHistory::default();
+        let first = "Merci pour la réunion.\nJ. Martin | Acme\nwww.acme.example\n";
+        let second = "Le devis est validé.\nJ. Martin | Acme\nwww.acme.example\n";
+        let level = settings(Aggressiveness::Medium);
+        assert_eq!(strip(first, "JM@acme.example", &level, Some(&mut history)).block, None);
+        let stripped = strip(second, "jm@acme.example", &level, Some(&mut history));
+        assert_eq!(stripped.body, "Le devis est validé.\n");
+        assert_eq!(stripped.block.as_deref(), Some("J. Martin | Acme\nwww.acme.example"));
+        // Exclusive: an identical notification is not a signature
+        assert_eq!(strip(second, "jm@acme.example", &level, Some(&mut history)).block, None);
+    }
+
+    #[test]
+    fn test_strip_contact_details_only_when_high() {
+        let body = "Bien reçu, merci.\n\nJean Dupont\nMobile : +33 6 12 34 56 78\n\nCe message et ses pièces jointes sont confidentiels.\n";
+        assert_eq!(strip(body, "", &settings(Aggressiveness::Medium), None).block, None);
+        let stripped = strip(body, "", &settings(Aggressiveness::High), None);
+        assert_eq!(stripped.body, "Bien reçu, merci.\n");
+        assert!(stripped.block.unwrap().ends_with("sont confidentiels."));
+    }
+}
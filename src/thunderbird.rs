@@ -233,6 +233,27 @@ fn parse_prefs_js(content: &str) -> Result<Vec<Account>> {
             skip_signature_images: true,
             delete_after_export: false,
             cleanup_empty_dirs: true,
+            filename_template: crate::filename::DEFAULT_FILENAME_TEMPLATE.to_string(),
+            filename_policy: Default::default(),
+            frontmatter: Default::default(),
+            field_keys: crate::frontmatter::FieldKeys::builtin(),
+            notes_dir: None,
+            output_format: Default::default(),
+            body_preference: Default::default(),
+            crypto: Default::default(),
+            signatures: Default::default(),
+            quoted_history: Default::default(),
+            cleaner: Default::default(),
+            skip_attachments: false,
+            keep_raw: false,
+            include_folders: vec![],
+            exclude_folders: vec![],
+            folder_overrides: Default::default(),
+            folder_map: Default::default(),
+            subscribed_only: false,
+            namespaces: Default::default(),
+            after_export: Default::default(),
+            mirror_routing: Default::default(),
         });
     }
 
@@ -0,0 +1,556 @@
+//! TNEF (`winmail.dat`, `application/ms-tnef`) decoding.
+//!
+//! Outlook with rich text turned on wraps the attachments and the RTF body of
+//! a message in a single TNEF stream (MS-OXTNEF). [`parse`] walks its
+//! attributes: each attached file becomes a [`TnefFile`], saved by
+//! `extract_attachments` like any other attachment, and the compressed RTF
+//! body (MS-OXRTFCP) is decompressed and converted to plain text, used as the
+//! note body when the message has no other one. A stream that cannot be
+//! decoded is saved as is.
+
+use anyhow::{bail, Context, Result};
+use encoding_rs::{Encoding, UTF_16LE, WINDOWS_1252};
+use mailparse::ParsedMail;
+
+const SIGNATURE: u32 = 0x223E_9F78;
+
+// Attribute ids (type << 16 | id)
+const ATT_BODY: u32 = 0x0002_800C;
+const ATT_ATTACH_DATA: u32 = 0x0006_800F;
+const ATT_ATTACH_TITLE: u32 = 0x0001_8010;
+const ATT_ATTACH_REND_DATA: u32 = 0x0006_9002;
+const ATT_MAPI_PROPS: u32 = 0x0006_9003;
+const ATT_ATTACHMENT: u32 = 0x0006_9005;
+const ATT_OEM_CODEPAGE: u32 = 0x0006_9007;
+
+// MAPI property ids
+const PR_BODY: u16 = 0x1000;
+const PR_RTF_COMPRESSED: u16 = 0x1009;
+const PR_DISPLAY_NAME: u16 = 0x3001;
+const PR_ATTACH_DATA_BIN: u16 = 0x3701;
+const PR_ATTACH_LONG_FILENAME: u16 = 0x3707;
+
+// MAPI property types
+const PT_STRING8: u16 = 0x001E;
+const PT_UNICODE: u16 = 0x001F;
+const PT_BINARY: u16 = 0x0102;
+const PT_OBJECT: u16 = 0x000D;
+
+/// Initial dictionary of compressed RTF (MS-OXRTFCP §2.1.2.1).
+const RTF_PREBUF: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}{\\f0\\fnil \\froman \\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArialTimes New RomanCourier{\\colortbl\\red0\\green0\\blue0\r\n\\par \\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+
+/// RTF groups whose content is not text.
+const RTF_SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl", "colortbl", "stylesheet", "info", "pict", "object", "header", "footer", "headerl",
+    "headerr", "footerl", "footerr", "listtable", "listoverridetable", "rsidtbl", "generator",
+    "xmlnstbl", "themedata", "colorschememapping", "latentstyles", "datastore", "fldinst",
+    "filetbl", "revtbl", "pgdsctbl", "mmathPr",
+];
+
+/// A file attached inside a TNEF stream.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TnefFile {
+    pub name: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Decoded content of a TNEF stream.
+#[derive(Debug, Default)]
+pub struct Tnef {
+    /// Attached files with a payload, in stream order.
+    pub files: Vec<TnefFile>,
+    /// Message body: the RTF body as text, else the plain-text body.
+    pub body: Option<String>,
+}
+
+/// `application/ms-tnef` parts, or `winmail.dat` whatever its type.
+pub fn is_tnef_part(part: &ParsedMail) -> bool {
+    let mimetype = part.ctype.mimetype.to_lowercase();
+    mimetype == "application/ms-tnef"
+        || mimetype == "application/vnd.ms-tnef"
+        || part
+            .ctype
+            .params
+            .get("name")
+            .is_some_and(|name| name.eq_ignore_ascii_case("winmail.dat"))
+}
+
+/// Body of the first decodable TNEF part of `mail`, if it has one.
+pub fn find_body(mail: &ParsedMail) -> Option<String> {
+    if is_tnef_part(mail) {
+        let data = mail.get_body_raw().ok()?;
+        return parse(&data).ok()?.body;
+    }
+    mail.subparts.iter().find_map(find_body)
+}
+
+/// Little-endian reader over a byte slice; every read is bounds-checked.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len());
+        let Some(end) = end else {
+            bail!("TNEF stream truncated at offset {}", self.pos);
+        };
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// `len` bytes followed by their padding to a multiple of 4.
+    fn padded(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.bytes(len)?;
+        self.bytes((4 - len % 4) % 4)?;
+        Ok(bytes)
+    }
+}
+
+/// One MAPI property of an `attMAPIProps` / `attAttachment` attribute.
+struct Prop<'a> {
+    id: u16,
+    ptype: u16,
+    values: Vec<&'a [u8]>,
+}
+
+fn mapi_props(data: &[u8]) -> Result<Vec<Prop<'_>>> {
+    let mut r = Reader::new(data);
+    let count = r.u32()?;
+    let mut props = Vec::new();
+    for _ in 0..count {
+        let ptype = r.u16()?;
+        let id = r.u16()?;
+        if id >= 0x8000 {
+            // Named property: GUID, then a numeric id or a UTF-16 name
+            r.bytes(16)?;
+            if r.u32()? == 0 {
+                r.u32()?;
+            } else {
+                let len = r.u32()? as usize;
+                r.padded(len)?;
+            }
+        }
+        let base = ptype & 0x0FFF;
+        let mut values = Vec::new();
+        if matches!(base, PT_STRING8 | PT_UNICODE | PT_BINARY | PT_OBJECT) {
+            for _ in 0..r.u32()? {
+                let len = r.u32()? as usize;
+                values.push(r.padded(len)?);
+            }
+        } else {
+            let size = match base {
+                0x0001..=0x0004 | 0x000A | 0x000B => 4,
+                0x0005..=0x0007 | 0x0014 | 0x0040 => 8,
+                0x0048 => 16,
+                other => bail!("unsupported MAPI property type {:#06x}", other),
+            };
+            let n = if ptype & 0x1000 != 0 { r.u32()? } else { 1 };
+            for _ in 0..n {
+                values.push(r.bytes(size)?);
+            }
+        }
+        props.push(Prop { id, ptype, values });
+    }
+    Ok(props)
+}
+
+/// Text of a string property (or an 8-bit attribute), NUL-terminated.
+fn string_value(bytes: &[u8], ptype: u16, codepage: &'static Encoding) -> String {
+    let text = if ptype & 0x0FFF == PT_UNICODE {
+        UTF_16LE.decode(bytes).0
+    } else {
+        codepage.decode(bytes).0
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+/// Encoding of a Windows code page (`attOemCodepage`, `\ansicpg`).
+fn codepage_encoding(codepage: u32) -> &'static Encoding {
+    let label = match codepage {
+        65001 => "utf-8".to_string(),
+        932 => "shift_jis".to_string(),
+        936 => "gbk".to_string(),
+        949 => "euc-kr".to_string(),
+        950 => "big5".to_string(),
+        cp => format!("windows-{}", cp),
+    };
+    Encoding::for_label(label.as_bytes()).unwrap_or(WINDOWS_1252)
+}
+
+/// Decode a TNEF stream into its attached files and body.
+pub fn parse(data: &[u8]) -> Result<Tnef> {
+    let mut r = Reader::new(data);
+    if r.u32()? != SIGNATURE {
+        bail!("not a TNEF stream");
+    }
+    r.u16()?; // legacy key
+
+    let mut codepage = WINDOWS_1252;
+    let mut files: Vec<TnefFile> = Vec::new();
+    let mut rtf = None;
+    let mut text = None;
+    while !r.is_empty() {
+        let _level = r.u8()?;
+        let id = r.u32()?;
+        let len = r.u32()? as usize;
+        let value = r.bytes(len)?;
+        r.u16()?; // checksum
+
+        match id {
+            ATT_OEM_CODEPAGE if value.len() >= 4 => {
+                codepage = codepage_encoding(u32::from_le_bytes([value[0], value[1], value[2], value[3]]));
+            }
+            ATT_BODY => text = Some(string_value(value, PT_STRING8, codepage)),
+            ATT_ATTACH_REND_DATA => files.push(TnefFile::default()),
+            ATT_ATTACH_TITLE => {
+                let file = current(&mut files);
+                if file.name.is_none() {
+                    file.name = Some(string_value(value, PT_STRING8, codepage));
+                }
+            }
+            ATT_ATTACH_DATA => current(&mut files).data = value.to_vec(),
+            ATT_ATTACHMENT => {
+                let file = current(&mut files);
+                for prop in mapi_props(value).context("invalid attachment properties")? {
+                    let Some(&first) = prop.values.first() else { continue };
+                    match prop.id {
+                        // The long name wins over the 8.3 `attAttachTitle`
+                        PR_ATTACH_LONG_FILENAME => file.name = Some(string_value(first, prop.ptype, codepage)),
+                        PR_DISPLAY_NAME if file.name.is_none() => {
+                            file.name = Some(string_value(first, prop.ptype, codepage));
+                        }
+                        // Embedded messages (`PT_OBJECT`) are not extracted
+                        PR_ATTACH_DATA_BIN if prop.ptype == PT_BINARY && file.data.is_empty() => {
+                            file.data = first.to_vec();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            ATT_MAPI_PROPS => {
+                for prop in mapi_props(value).context("invalid message properties")? {
+                    let Some(&first) = prop.values.first() else { continue };
+                    match prop.id {
+                        PR_RTF_COMPRESSED => rtf = Some(first),
+                        PR_BODY if text.is_none() => text = Some(string_value(first, prop.ptype, codepage)),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let body = rtf
+        .and_then(|compressed| decompress_rtf(compressed).ok())
+        .map(|rtf| rtf_to_text(&rtf))
+        .or(text)
+        .filter(|body| !body.trim().is_empty());
+    files.retain(|file| !file.data.is_empty());
+    Ok(Tnef { files, body })
+}
+
+/// The attachment being described, created when a stream omits `attAttachRendData`.
+fn current(files: &mut Vec<TnefFile>) -> &mut TnefFile {
+    if files.is_empty() {
+        files.push(TnefFile::default());
+    }
+    let last = files.len() - 1;
+    &mut files[last]
+}
+
+/// Decompress a `PR_RTF_COMPRESSED` value (LZFu, or uncompressed `MELA`).
+pub fn decompress_rtf(data: &[u8]) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data);
+    let comp_size = r.u32()? as usize;
+    let raw_size = r.u32()? as usize;
+    let comp_type = r.u32()?;
+    r.u32()?; // CRC
+    let end = comp_size.saturating_add(4).min(data.len());
+    let input = data.get(16..end).unwrap_or_default();
+
+    match comp_type {
+        0x414C_454D => return Ok(input.to_vec()),
+        0x7546_5A4C => {}
+        other => bail!("unknown compressed RTF type {:#010x}", other),
+    }
+
+    let mut dict = [0u8; 4096];
+    dict[..RTF_PREBUF.len()].copy_from_slice(RTF_PREBUF);
+    let mut write = RTF_PREBUF.len();
+    // The header size is untrusted: a 2-byte token expands to at most 17 bytes
+    let mut out = Vec::with_capacity(raw_size.min(input.len().saturating_mul(9)));
+    let mut i = 0;
+    'input: while i < input.len() {
+        let control = input[i];
+        i += 1;
+        for bit in 0..8 {
+            if i >= input.len() {
+                break 'input;
+            }
+            if control & (1 << bit) == 0 {
+                let byte = input[i];
+                i += 1;
+                out.push(byte);
+                dict[write] = byte;
+                write = (write + 1) % dict.len();
+                continue;
+            }
+            let Some(token) = input.get(i..i + 2) else { break 'input };
+            i += 2;
+            let token = u16::from_be_bytes([token[0], token[1]]) as usize;
+            let offset = token >> 4;
+            if offset == write {
+                break 'input; // end marker
+            }
+            for k in 0..(token & 0xF) + 2 {
+                let byte = dict[(offset + k) % dict.len()];
+                out.push(byte);
+                dict[write] = byte;
+                write = (write + 1) % dict.len();
+            }
+        }
+    }
+    out.truncate(raw_size);
+    Ok(out)
+}
+
+/// Plain text of an RTF document: formatting and non-text groups dropped,
+/// paragraphs as line breaks, `\'hh` and `\uN` characters decoded. Encapsulated
+/// HTML (`\fromhtml`) renders through its RTF text, its `\*\htmltag` groups skipped.
+pub fn rtf_to_text(rtf: &[u8]) -> String {
+    let mut out = RtfText {
+        text: String::new(),
+        bytes: Vec::new(),
+        encoding: WINDOWS_1252,
+    };
+    // (skipping, \uc) of the enclosing groups
+    let mut stack = Vec::new();
+    let mut skip = false;
+    let mut uc = 1;
+    let mut fallback = 0; // characters left to skip after a `\uN`
+    let mut i = 0;
+    while i < rtf.len() {
+        let c = rtf[i];
+        i += 1;
+        match c {
+            b'{' => stack.push((skip, uc)),
+            b'}' => (skip, uc) = stack.pop().unwrap_or((skip, uc)),
+            b'\r' | b'\n' => {}
+            b'\\' => {
+                let Some(&next) = rtf.get(i) else { break };
+                if next.is_ascii_alphabetic() {
+                    let start = i;
+                    while rtf.get(i).is_some_and(u8::is_ascii_alphabetic) {
+                        i += 1;
+                    }
+                    let word = String::from_utf8_lossy(&rtf[start..i]).into_owned();
+                    let num_start = i;
+                    if rtf.get(i) == Some(&b'-') {
+                        i += 1;
+                    }
+                    while rtf.get(i).is_some_and(u8::is_ascii_digit) {
+                        i += 1;
+                    }
+                    let param = std::str::from_utf8(&rtf[num_start..i]).ok().and_then(|n| n.parse::<i32>().ok());
+                    if rtf.get(i) == Some(&b' ') {
+                        i += 1;
+                    }
+                    if word == "bin" {
+                        // Raw binary data, never text
+                        i += param.unwrap_or(0).max(0) as usize;
+                        continue;
+                    }
+                    if skip {
+                        continue;
+                    }
+                    match word.as_str() {
+                        w if RTF_SKIPPED_DESTINATIONS.contains(&w) => skip = true,
+                        "ansicpg" => out.encoding = codepage_encoding(param.unwrap_or(1252) as u32),
+                        "uc" => uc = param.unwrap_or(1).max(0) as usize,
+                        "u" => {
+                            let code = param.unwrap_or(0);
+                            let code = if code < 0 { code + 0x10000 } else { code };
+                            out.push(char::from_u32(code as u32).unwrap_or('\u{FFFD}'));
+                            fallback = uc;
+                        }
+                        "par" | "line" | "sect" | "page" | "row" => out.push('\n'),
+                        "tab" | "cell" => out.push('\t'),
+                        "emdash" => out.push('—'),
+                        "endash" => out.push('–'),
+                        "bullet" => out.push('•'),
+                        "lquote" => out.push('‘'),
+                        "rquote" => out.push('’'),
+                        "ldblquote" => out.push('“'),
+                        "rdblquote" => out.push('”'),
+                        _ => {}
+                    }
+                } else if next == b'\'' {
+                    let byte = rtf
+                        .get(i + 1..i + 3)
+                        .and_then(|hex| std::str::from_utf8(hex).ok())
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                    i += 3;
+                    if fallback > 0 {
+                        fallback -= 1;
+                    } else if let (Some(byte), false) = (byte, skip) {
+                        out.bytes.push(byte);
+                    }
+                } else {
+                    i += 1;
+                    match next {
+                        b'*' => skip = true,
+                        _ if skip => {}
+                        b'~' => out.push('\u{A0}'),
+                        b'_' => out.push('-'),
+                        b'{' | b'}' | b'\\' => out.push(next as char),
+                        b'\r' | b'\n' => out.push('\n'),
+                        _ => {}
+                    }
+                }
+            }
+            _ if fallback > 0 => fallback -= 1,
+            _ if skip => {}
+            _ => out.bytes.push(c),
+        }
+    }
+    out.flush();
+    out.text
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Text being rendered: 8-bit bytes are buffered and decoded together, so
+/// that multi-byte code pages decode correctly.
+struct RtfText {
+    text: String,
+    bytes: Vec<u8>,
+    encoding: &'static Encoding,
+}
+
+impl RtfText {
+    fn flush(&mut self) {
+        if !self.bytes.is_empty() {
+            self.text.push_str(&self.encoding.decode_without_bom_handling(&self.bytes).0);
+            self.bytes.clear();
+        }
+    }
+
+    fn push(&mut self, c: char) {
+        self.flush();
+        self.text.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(level: u8, id: u32, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![level];
+        out.extend(id.to_le_bytes());
+        out.extend((value.len() as u32).to_le_bytes());
+        out.extend(value);
+        let checksum = value.iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        out.extend(checksum.to_le_bytes());
+        out
+    }
+
+    fn string_prop(ptype: u16, id: u16, value: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(1u32.to_le_bytes());
+        out.extend(ptype.to_le_bytes());
+        out.extend(id.to_le_bytes());
+        out.extend(1u32.to_le_bytes());
+        out.extend((value.len() as u32).to_le_bytes());
+        out.extend(value);
+        out.resize(out.len() + (4 - value.len() % 4) % 4, 0);
+        out
+    }
+
+    /// MS-OXRTFCP §3.1.1, compressed example
+    const COMPRESSED: &[u8] = &[
+        0x2d, 0x00, 0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x4c, 0x5a, 0x46, 0x75, 0xf1, 0xc5, 0xc7, 0xa7, 0x03, 0x00,
+        0x0a, 0x00, 0x72, 0x63, 0x70, 0x67, 0x31, 0x32, 0x35, 0x42, 0x32, 0x0a, 0xf3, 0x20, 0x68, 0x65, 0x6c, 0x09,
+        0x00, 0x20, 0x62, 0x77, 0x05, 0xb0, 0x6c, 0x64, 0x7d, 0x0a, 0x80, 0x0f, 0xa0,
+    ];
+
+    #[test]
+    fn test_decompress_rtf_and_render_text() {
+        assert_eq!(RTF_PREBUF.len(), 207);
+        let rtf = decompress_rtf(COMPRESSED).unwrap();
+        assert_eq!(rtf, b"{\\rtf1\\ansi\\ansicpg1252\\pard hello world}\r\n");
+        assert_eq!(rtf_to_text(&rtf), "hello world");
+
+        // A forged raw size does not drive the allocation
+        let mut forged = COMPRESSED.to_vec();
+        forged[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decompress_rtf(&forged).unwrap(), b"{\\rtf1\\ansi\\ansicpg1252\\pard hello world}\r\n");
+
+        let rtf = b"{\\rtf1\\ansi\\ansicpg1252{\\fonttbl{\\f0 Arial;}}{\\*\\generator Riched20;}\
+                    Bonjour,\\par {\\*\\htmltag84 <br>}Facture n\\'b0 12 \\u8364?\\par\\tab ci-jointe.}";
+        assert_eq!(rtf_to_text(rtf), "Bonjour,\nFacture n° 12 €\n\tci-jointe.");
+    }
+
+    #[test]
+    fn test_parse_attachments_and_rtf_body() {
+        let mut stream = SIGNATURE.to_le_bytes().to_vec();
+        stream.extend(1u16.to_le_bytes());
+        stream.extend(attribute(1, ATT_OEM_CODEPAGE, &[0xE4, 0x04, 0, 0, 0, 0, 0, 0]));
+        stream.extend(attribute(1, ATT_MAPI_PROPS, &string_prop(PT_BINARY, PR_RTF_COMPRESSED, COMPRESSED)));
+        stream.extend(attribute(2, ATT_ATTACH_REND_DATA, &[0; 14]));
+        stream.extend(attribute(2, ATT_ATTACH_TITLE, b"RAPPOR~1.PDF\0"));
+        stream.extend(attribute(2, ATT_ATTACH_DATA, b"%PDF-1.4"));
+        stream.extend(attribute(
+            2,
+            ATT_ATTACHMENT,
+            &string_prop(PT_STRING8, PR_ATTACH_LONG_FILENAME, b"Rapport \xe9t\xe9.pdf\0"),
+        ));
+        stream.extend(attribute(2, ATT_ATTACH_REND_DATA, &[0; 14]));
+        stream.extend(attribute(2, ATT_ATTACH_TITLE, b"vide.txt\0"));
+
+        let tnef = parse(&stream).unwrap();
+        assert_eq!(tnef.body.as_deref(), Some("hello world"));
+        // Exclusive: the attachment without data is dropped
+        assert_eq!(
+            tnef.files,
+            vec![TnefFile {
+                name: Some("Rapport été.pdf".to_string()),
+                data: b"%PDF-1.4".to_vec()
+            }]
+        );
+
+        assert!(parse(b"not a tnef stream").is_err());
+        stream.truncate(stream.len() - 3);
+        assert!(parse(&stream).is_err());
+    }
+}
@@ -798,8 +798,7 @@ fn handle_dest_gui_ipc(
             cfg.destinations.push(DestinationEntry {
                 path,
                 note,
-                rules: Vec::new(),
-                default: false,
+                ..Default::default()
             });
             DestGuiIpcResult::StateChanged
         }
@@ -839,6 +838,7 @@ fn handle_dest_gui_ipc(
                 "from" => DestinationRule::From(value.to_string()),
                 "subject" => DestinationRule::Subject(value.to_string()),
                 "account" => DestinationRule::Account(value.to_string()),
+                "type" => DestinationRule::Type(value.to_lowercase()),
                 _ => return DestGuiIpcResult::Noop,
             };
             crate::destinations::add_rule(cfg, path, rule);
@@ -886,9 +886,7 @@ fn handle_dest_gui_ipc(
                 if cfg.destinations.iter().any(|e| e.path.eq_ignore_ascii_case(path)) { continue }
                 cfg.destinations.push(crate::destinations::DestinationEntry {
                     path: path.to_string(),
-                    note: None,
-                    rules: vec![],
-                    default: false,
+                    ..Default::default()
                 });
             }
             DestGuiIpcResult::StateChanged
@@ -905,7 +903,8 @@ fn handle_dest_gui_ipc(
                 };
             };
             let notes_dir = std::path::PathBuf::from(notes_dir_str);
-            let scan = match crate::dest_cmd::scan_notes(&notes_dir) {
+            let keys = crate::frontmatter::FieldKeys::from_settings(&settings);
+            let scan = match crate::dest_cmd::scan_notes(&notes_dir, &keys) {
                 Ok(r) => r,
                 Err(e) => {
                     eprintln!("dest-gui: scan_notes: {:#}", e);
@@ -1146,6 +1145,7 @@ fn build_route_window(
         .as_deref()
         .map(PathBuf::from)
         .unwrap_or_else(|| PathBuf::from("notes"));
+    let keys = crate::frontmatter::FieldKeys::from_settings(&settings);
 
     // Collect known paths from destinations.yaml for the datalist autocomplete.
     let known_paths: Vec<String> = crate::route::load_destinations()
@@ -1158,15 +1158,15 @@ fn build_route_window(
         .iter()
         .map(|(staging_path, decision)| {
             let file = staging_path.to_string_lossy().into_owned();
-            let subject = read_frontmatter_field(staging_path, "subject")
+            let subject = read_frontmatter_field(staging_path, "subject", &keys)
                 .unwrap_or_else(|| {
                     staging_path
                         .file_stem()
                         .map(|s| s.to_string_lossy().into_owned())
                         .unwrap_or_default()
                 });
-            let from_raw = read_frontmatter_field(staging_path, "from").unwrap_or_default();
-            let date     = read_frontmatter_field(staging_path, "date").unwrap_or_default();
+            let from_raw = read_frontmatter_field(staging_path, "from", &keys).unwrap_or_default();
+            let date     = read_frontmatter_field(staging_path, "date", &keys).unwrap_or_default();
             let (sender_email, sender_domain) = extract_addr_and_domain(&from_raw);
             (file, subject, from_raw, date, decision.rel_path.clone(), decision.is_default, sender_email, sender_domain)
         })
@@ -1188,6 +1188,14 @@ fn build_route_window(
         })
         .collect();
 
+    // Child notes of each row, filed after their parent on Apply.
+    let embedded: HashMap<String, Vec<(PathBuf, RouteDecision)>> = decisions
+        .into_iter()
+        .map(|(staging_path, decision)| {
+            (staging_path.to_string_lossy().into_owned(), decision.embedded)
+        })
+        .collect();
+
     let decisions_json =
         serde_json::to_string(&json_rows).context("failed to serialize decisions")?;
     let known_paths_json =
@@ -1273,10 +1281,24 @@ fn build_route_window(
             }
 
             // 3. Existing apply flow — payload `{ decisions: [...] }` (no `action` field).
-            match apply_route_decisions(&body, &notes_dir, window_id, &proxy_ipc) {
-                Ok(()) => {
+            match apply_route_decisions(&body, &notes_dir, &embedded) {
+                Ok((_, None)) => {
                     let _ = proxy_ipc.send_event(AppCommand::CloseWindow { window_id });
                 }
+                Ok((applied, Some(msg))) => {
+                    // Drop the rows already filed, keep the failed ones for a retry.
+                    if let Ok(js_arr) = serde_json::to_string(&applied) {
+                        let js = format!(
+                            "route_review_deleted({})",
+                            escape_json_for_script(&js_arr)
+                        );
+                        let _ = proxy_ipc.send_event(AppCommand::EvalScript { window_id, js });
+                    }
+                    if let Ok(js_str) = serde_json::to_string(&msg) {
+                        let js = format!("route_review_error({})", js_str);
+                        let _ = proxy_ipc.send_event(AppCommand::EvalScript { window_id, js });
+                    }
+                }
                 Err(e) => {
                     // Surface the error back to the HTML without closing.
                     let msg = format!("{:#}", e);
@@ -1300,52 +1322,97 @@ fn build_route_window(
 /// Validation: each `dest_path` is passed through `join_safe_segments` which
 /// rejects `..`, `\`, and absolute paths.
 /// New paths (not in destinations.txt) are created with `mkdir -p` (D4/D10).
+/// The child notes of each row (`embedded`, keyed by staging path) are filed
+/// by `route::apply_embedded` once their parent is moved.
+///
+/// Returns `(applied, error)` like `delete_staged_emails`: one failing row
+/// never blocks the others. Only an unreadable payload is an `Err`.
 fn apply_route_decisions(
     body: &str,
     notes_dir: &PathBuf,
-    _window_id: WindowId,
-    _proxy: &EventLoopProxy<AppCommand>,
-) -> Result<()> {
+    embedded: &HashMap<String, Vec<(PathBuf, RouteDecision)>>,
+) -> Result<(Vec<String>, Option<String>)> {
     let payload: RouteApplyPayload = serde_json::from_str(body)
         .context("failed to parse route review IPC payload")?;
+    // Per-destination static frontmatter fields (missing/broken file → none).
+    let dest_cfg = crate::destinations::load_yaml(&crate::route::destinations_path())
+        .unwrap_or_default();
+    let settings = Settings::load(&config::settings_path()).unwrap_or_default();
+    let keys = crate::frontmatter::FieldKeys::from_settings(&settings);
 
+    let mut applied = Vec::new();
+    let mut errors = Vec::new();
     for row in &payload.decisions {
-        let staging_md = PathBuf::from(&row.file);
-        // Normalize the destination to carry the email's <Year>/<Month>. The auto
-        // proposal already ends with it; a manually reassigned path (cascade / free
-        // entry / bulk) comes bare from destinations.txt — append it from the email
-        // date so files always land under <dest>/<Year>/<Month> (no double suffix).
-        let dest_path = match read_frontmatter_field(&staging_md, "date")
-            .and_then(|d| chrono::DateTime::parse_from_rfc3339(d.trim()).ok())
-        {
-            Some(dt) => crate::route::ensure_year_month(
-                &row.dest_path,
-                &dt.format("%Y").to_string(),
-                &dt.format("%m").to_string(),
-            ),
-            // Date unreadable/unparseable → keep the path as-is (no guess).
-            None => row.dest_path.clone(),
-        };
-        // Anti-traversal validation — rejects "..", "\", absolute paths.
-        let dest_dir = crate::route::join_safe_segments(notes_dir, &dest_path)
-            .with_context(|| {
-                format!(
-                    "invalid destination path {:?} for file {:?}",
-                    dest_path, row.file
-                )
-            })?;
-        // Create the directory tree (D4: mkdir -p).
-        std::fs::create_dir_all(&dest_dir).with_context(|| {
-            format!("failed to create directory {}", dest_dir.display())
-        })?;
-        // Move .md + its referenced attachment siblings.
-        crate::route::move_email(&staging_md, &dest_dir).with_context(|| {
-            format!(
-                "failed to move {} to {}",
-                staging_md.display(),
-                dest_dir.display()
-            )
-        })?;
+        let children = embedded.get(&row.file).map(Vec::as_slice).unwrap_or_default();
+        match apply_route_row(row, children, notes_dir, &dest_cfg, &keys) {
+            Ok(()) => applied.push(row.file.clone()),
+            Err(e) => errors.push(format!("{}: {:#}", row.file, e)),
+        }
+    }
+    let err = if errors.is_empty() {
+        None
+    } else {
+        Some(format!("Rangement échoué pour {} fichier(s) :\n{}", errors.len(), errors.join("\n")))
+    };
+    Ok((applied, err))
+}
+
+/// Move one reviewed note (and its child notes) to its destination.
+fn apply_route_row(
+    row: &RouteDecisionRow,
+    children: &[(PathBuf, RouteDecision)],
+    notes_dir: &PathBuf,
+    dest_cfg: &crate::destinations::DestinationsConfig,
+    keys: &crate::frontmatter::FieldKeys,
+) -> Result<()> {
+    let staging_md = PathBuf::from(&row.file);
+    // Normalize the destination to carry the email's <Year>/<Month>. The auto
+    // proposal already ends with it; a manually reassigned path (cascade / free
+    // entry / bulk) comes bare from destinations.txt — append it from the email
+    // date so files always land under <dest>/<Year>/<Month> (no double suffix).
+    let dest_path = match std::fs::read_to_string(&staging_md)
+        .ok()
+        .and_then(|c| crate::frontmatter::NoteHeader::read(&c))
+        .and_then(|h| h.date(keys))
+    {
+        Some(dt) => crate::route::ensure_year_month(
+            &row.dest_path,
+            &dt.format("%Y").to_string(),
+            &dt.format("%m").to_string(),
+        ),
+        // Date unreadable/unparseable → keep the path as-is (no guess).
+        None => row.dest_path.clone(),
+    };
+    // Anti-traversal validation — rejects "..", "\", absolute paths.
+    let dest_dir = crate::route::join_safe_segments(notes_dir, &dest_path)
+        .with_context(|| format!("invalid destination path {:?}", dest_path))?;
+    // Create the directory tree (D4: mkdir -p).
+    std::fs::create_dir_all(&dest_dir).with_context(|| {
+        format!("failed to create directory {}", dest_dir.display())
+    })?;
+    // Move .md + its referenced attachment siblings.
+    let final_md = crate::route::move_email(&staging_md, &dest_dir, keys)
+        .with_context(|| format!("failed to move to {}", dest_dir.display()))?;
+    // Pending deletions follow the note to its final path (best effort).
+    if let Err(e) = crate::deletion::relocate_pending(
+        &crate::deletion::pending_path(),
+        &[(staging_md.clone(), final_md.clone())],
+    ) {
+        eprintln!("warning: could not update pending deletions: {:#}", e);
+    }
+    // Child notes of embedded messages, filed on their own
+    let filed = crate::route::apply_embedded(&final_md, children, notes_dir, keys);
+    // Static frontmatter fields of each destination (best effort).
+    for (note_path, rel_path) in std::iter::once((final_md, dest_path)).chain(filed) {
+        if let Some(fields) = crate::destinations::static_fields_for(dest_cfg, &rel_path) {
+            if let Err(e) = crate::frontmatter::append_fields(&note_path, fields) {
+                eprintln!(
+                    "warning: could not add destination fields to {}: {:#}",
+                    note_path.display(),
+                    e
+                );
+            }
+        }
     }
     Ok(())
 }
@@ -1357,11 +1424,13 @@ fn apply_route_decisions(
 /// and `error` is a combined message for any files that failed. Processing
 /// continues past individual failures so one bad file never blocks the rest.
 fn delete_staged_emails(files: &[String]) -> (Vec<String>, Option<String>) {
+    let settings = Settings::load(&config::settings_path()).unwrap_or_default();
+    let keys = crate::frontmatter::FieldKeys::from_settings(&settings);
     let mut deleted = Vec::new();
     let mut errors = Vec::new();
     for file in files {
         let path = PathBuf::from(file);
-        match crate::route::delete_email(&path) {
+        match crate::route::delete_email(&path, &keys) {
             Ok(()) => deleted.push(file.clone()),
             Err(e) => errors.push(format!("{}: {:#}", file, e)),
         }
@@ -1374,24 +1443,16 @@ fn delete_staged_emails(files: &[String]) -> (Vec<String>, Option<String>) {
     (deleted, err)
 }
 
-/// Extract a single field value from the YAML frontmatter of a `.md` file.
+/// Extract a single canonical field value from the frontmatter of a `.md` file,
+/// whatever its dialect or renamed keys.
 /// Returns `None` if the file cannot be read or the field is absent.
-fn read_frontmatter_field(path: &std::path::Path, field: &str) -> Option<String> {
+fn read_frontmatter_field(
+    path: &std::path::Path,
+    field: &str,
+    keys: &crate::frontmatter::FieldKeys,
+) -> Option<String> {
     let content = std::fs::read_to_string(path).ok()?;
-    let rest = content.strip_prefix("---\n")?;
-    let end = rest.find("\n---")?;
-    let frontmatter = &rest[..end];
-    let prefix = format!("{}:", field);
-    for line in frontmatter.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with(&prefix) {
-            let value = trimmed[prefix.len()..].trim().trim_matches('"').trim_matches('\'');
-            if !value.is_empty() {
-                return Some(value.to_string());
-            }
-        }
-    }
-    None
+    crate::frontmatter::NoteHeader::read(&content)?.get_str(field, keys)
 }
 
 /// Parse a config IPC message and act on it synchronously.
@@ -1420,14 +1481,17 @@ fn handle_config_ipc(body: &str) -> (Option<ActionResult>, bool) {
                 let path = config::settings_path();
                 let mut settings = Settings::load(&path).unwrap_or_default();
                 settings.export_base_dir = data.export_base_dir;
+                // Fields the settings window does not edit (folder_name, filename
+                // template/policy, …) are carried over from the file unchanged.
+                let prev = std::mem::take(&mut settings.defaults);
                 settings.defaults = AccountBehavior {
-                    folder_name: settings.defaults.folder_name,
                     quote_depth: data.defaults.quote_depth,
                     skip_existing: data.defaults.skip_existing,
                     collect_contacts: data.defaults.collect_contacts,
                     skip_signature_images: data.defaults.skip_signature_images,
                     delete_after_export: data.defaults.delete_after_export,
                     cleanup_empty_dirs: data.defaults.cleanup_empty_dirs,
+                    ..prev
                 };
                 settings
                     .save(&path)
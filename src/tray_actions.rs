@@ -3,7 +3,8 @@
 //! This module provides the functions that are called when users
 //! interact with the system tray menu.
 
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Sender};
 use std::sync::Arc;
@@ -17,6 +18,7 @@ use crate::progress::ProgressUpdate;
 
 use crate::config::{self, Config, Settings};
 use crate::email_export::{self, ImapExporter};
+use crate::frontmatter::{FieldKeys, NoteHeader};
 use crate::route::{self, EmailMeta, RouteDecision};
 use crate::thunderbird;
 
@@ -63,7 +65,7 @@ pub fn action_export(account_name: String, result_sender: Sender<ActionResult>)
     if let Err(e) = crate::tray::send_command(crate::tray::AppCommand::OpenProgress {
         action_name: "Export".to_string(),
         warning: if delete_warning {
-            Some("Les emails seront supprimés du serveur après export".to_string())
+            Some("Les emails seront supprimés du serveur une fois leurs notes classées et vérifiées".to_string())
         } else {
             None
         },
@@ -149,6 +151,28 @@ fn run_export(
     let mut exporter = ImapExporter::new(account.clone(), false);
     exporter.connect().context("Failed to connect to IMAP server")?;
 
+    // Deletions recorded by earlier exports whose notes have since been routed
+    // by the review window (this export's notes are still in staging). Not
+    // gated on the account setting: a `folders:` override may enable it.
+    let deletion_summary = {
+        let settings = Settings::load(&config::settings_path()).unwrap_or_default();
+        let notes_dir = settings.notes_dir.map(PathBuf::from);
+        match exporter.delete_verified(notes_dir.as_deref()) {
+            Ok(report) if report.deleted + report.rejected > 0 => format!(
+                " — {} supprimés du serveur{}, {} conservés (note absente ou modifiée)",
+                report.deleted,
+                if report.flagged_only > 0 {
+                    format!(" dont {} seulement marqués \\Deleted (pas d'UIDPLUS)", report.flagged_only)
+                } else {
+                    String::new()
+                },
+                report.rejected
+            ),
+            Ok(_) => String::new(),
+            Err(e) => format!(" — suppression impossible : {:#}", e),
+        }
+    };
+
     let (results, decisions) = exporter
         .export_account(on_progress, on_status, Some(cancel_token.as_ref()))
         .context("Export failed")?;
@@ -162,11 +186,19 @@ fn run_export(
     let total_skipped: usize = results.values().map(|s| s.skipped).sum();
     let total_errors: usize = results.values().map(|s| s.errors).sum();
 
+    // Failed messages kept for `retry-failed`, this run's and earlier ones
+    let in_quarantine = crate::quarantine::list(Path::new(&account.export_directory)).len();
+    let quarantine_summary = if in_quarantine > 0 {
+        format!(" — {} en quarantaine (_failed)", in_quarantine)
+    } else {
+        String::new()
+    };
+
     let prefix = if cancelled { "Export annulé" } else { "Export terminé" };
     Ok((
         format!(
-            "{} — {} exportés, {} ignorés, {} erreurs",
-            prefix, total_exported, total_skipped, total_errors
+            "{} — {} exportés, {} ignorés, {} erreurs{}{}",
+            prefix, total_exported, total_skipped, total_errors, deletion_summary, quarantine_summary
         ),
         decisions,
     ))
@@ -228,7 +260,8 @@ fn scan_staged_decisions(account_name: &str) -> Result<Vec<(PathBuf, RouteDecisi
     }
 
     let dests = route::load_destinations();
-    let mut decisions = Vec::new();
+    let keys = &account.field_keys;
+    let mut notes = Vec::new();
 
     let walker = WalkDir::new(&base)
         .into_iter()
@@ -239,56 +272,104 @@ fn scan_staged_decisions(account_name: &str) -> Result<Vec<(PathBuf, RouteDecisi
             continue;
         }
         let path = entry.path();
-        if path.extension().and_then(|s| s.to_str()) != Some("md") {
+        if !crate::output::is_note_file(path) {
             continue;
         }
         let content = match std::fs::read_to_string(path) {
             Ok(c) => c,
             Err(_) => continue,
         };
-        let meta = meta_from_frontmatter(&content, account_name);
-        let decision = route::route_email(&meta, &dests);
-        decisions.push((path.to_path_buf(), decision));
+        // Staged attachments can share a note extension (`.html`, `.org`…).
+        if !crate::output::is_note_content(path, &content) {
+            continue;
+        }
+        notes.push((path.to_path_buf(), content));
     }
 
+    // Child notes of embedded messages are filed with their parent
+    // (`route::apply_embedded`), not listed as rows of their own.
+    let children: HashSet<PathBuf> = notes
+        .iter()
+        .flat_map(|(path, content)| child_notes(path, content, keys))
+        .collect();
+
+    let decisions = notes
+        .iter()
+        .filter(|(path, _)| !children.contains(path))
+        .map(|(path, content)| {
+            let mut seen = HashSet::from([path.clone()]);
+            let decision = staged_decision(path, content, account_name, keys, &dests, &mut seen);
+            (path.clone(), decision)
+        })
+        .collect();
+
     Ok(decisions)
 }
 
-/// Read a single scalar field from a `.md` file's YAML frontmatter block.
-/// Matches the field name exactly (so `subject` never matches `subject_hash`).
-fn frontmatter_field(content: &str, field: &str) -> Option<String> {
-    let rest = content.strip_prefix("---\n")?;
-    let end = rest.find("\n---")?;
-    let frontmatter = &rest[..end];
-    let prefix = format!("{}:", field);
-    for line in frontmatter.lines() {
-        if let Some(value) = line.trim().strip_prefix(&prefix) {
-            let value = value.trim().trim_matches('"').trim_matches('\'');
-            if !value.is_empty() {
-                return Some(value.to_string());
-            }
+/// Child notes of a staged note's embedded messages: the notes listed in its
+/// `attachments` that sit next to it (see `route::is_child_note`).
+fn child_notes(path: &Path, content: &str, keys: &FieldKeys) -> Vec<PathBuf> {
+    let (Some(dir), Some(header)) = (path.parent(), NoteHeader::read(content)) else {
+        return Vec::new();
+    };
+    header
+        .get_list("attachments", keys)
+        .iter()
+        .map(|link| dir.join(link.replace('/', std::path::MAIN_SEPARATOR_STR)))
+        .filter(|child| child.parent() == Some(dir) && route::is_child_note(child))
+        .collect()
+}
+
+/// Route a staged note, with the decisions of its child notes in `embedded`
+/// as the export builds them. `seen` guards against a note listing itself.
+fn staged_decision(
+    path: &Path,
+    content: &str,
+    account_name: &str,
+    keys: &FieldKeys,
+    dests: &[route::Destination],
+    seen: &mut HashSet<PathBuf>,
+) -> RouteDecision {
+    let meta = meta_from_frontmatter(content, account_name, keys);
+    let mut decision = route::route_email(&meta, dests);
+    for child in child_notes(path, content, keys) {
+        if !seen.insert(child.clone()) {
+            continue;
         }
+        let Ok(child_content) = std::fs::read_to_string(&child) else {
+            continue;
+        };
+        let child_decision = staged_decision(&child, &child_content, account_name, keys, dests, seen);
+        decision.embedded.push((child, child_decision));
     }
-    None
+    decision
 }
 
 /// Rebuild the `EmailMeta` used for routing from a staged note's frontmatter.
 /// Mirrors the export-time construction in `email_export::export_to_markdown`.
-fn meta_from_frontmatter(content: &str, account_name: &str) -> EmailMeta {
-    let from_raw = frontmatter_field(content, "from").unwrap_or_default();
-    let subject = frontmatter_field(content, "subject").unwrap_or_default();
-    let date_raw = frontmatter_field(content, "date").unwrap_or_default();
-
-    let addresses = crate::utils::extract_emails(Some(&from_raw));
-    let sender_addr = addresses.first().cloned().unwrap_or_default();
+/// Any configured dialect or renamed key is understood (see `frontmatter`).
+fn meta_from_frontmatter(content: &str, account_name: &str, keys: &FieldKeys) -> EmailMeta {
+    let header = NoteHeader::read(content);
+    let sender_addr = header
+        .as_ref()
+        .map(|h| h.sender_address(keys))
+        .unwrap_or_default();
+    let subject = header
+        .as_ref()
+        .and_then(|h| h.get_str("subject", keys))
+        .unwrap_or_default();
+    let email_type = header
+        .as_ref()
+        .and_then(|h| h.get_str("email_type", keys))
+        .unwrap_or_default();
     let domain = sender_addr
         .rfind('@')
         .map(|i| sender_addr[i + 1..].to_string())
         .unwrap_or_default();
 
-    // Frontmatter dates are written as RFC3339; epoch fallback on parse failure
-    // (same fallback the export path uses for an unparseable Date header).
-    let date = chrono::DateTime::parse_from_rfc3339(date_raw.trim()).unwrap_or_else(|_| {
+    // Epoch fallback on a missing/unparseable date (same fallback the export
+    // path uses for an unparseable Date header).
+    let date = header.and_then(|h| h.date(keys)).unwrap_or_else(|| {
         chrono::DateTime::from_timestamp(0, 0)
             .expect("epoch is valid")
             .fixed_offset()
@@ -299,6 +380,7 @@ fn meta_from_frontmatter(content: &str, account_name: &str) -> EmailMeta {
         domain,
         subject,
         account: account_name.to_string(),
+        email_type,
         date,
     }
 }
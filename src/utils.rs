@@ -260,6 +260,41 @@ pub fn decode_imap_utf7(encoded_str: &str) -> String {
     result
 }
 
+/// Encode a UTF-8 folder name to IMAP modified UTF-7 (RFC 3501 §5.1.3),
+/// the inverse of `decode_imap_utf7`.
+pub fn encode_imap_utf7(name: &str) -> String {
+    use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
+
+    fn flush(pending: &mut Vec<u16>, out: &mut String) {
+        if pending.is_empty() {
+            return;
+        }
+        let bytes: Vec<u8> = pending.iter().flat_map(|u| u.to_be_bytes()).collect();
+        out.push('&');
+        out.push_str(&STANDARD_NO_PAD.encode(bytes).replace('/', ","));
+        out.push('-');
+        pending.clear();
+    }
+
+    let mut out = String::new();
+    let mut pending: Vec<u16> = Vec::new();
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush(&mut pending, &mut out);
+            if c == '&' {
+                out.push_str("&-");
+            } else {
+                out.push(c);
+            }
+        } else {
+            let mut buf = [0u16; 2];
+            pending.extend_from_slice(c.encode_utf16(&mut buf));
+        }
+    }
+    flush(&mut pending, &mut out);
+    out
+}
+
 /// Decode modified base64 (IMAP UTF-7) to string.
 fn decode_modified_base64(encoded: &str) -> Option<String> {
     // IMAP modified base64 uses , instead of /
@@ -651,6 +686,16 @@ mod tests {
         assert_eq!(result, "INBOX.Envoyés");
     }
 
+    #[test]
+    fn test_encode_imap_utf7_round_trip() {
+        assert_eq!(encode_imap_utf7("INBOX.Envoyés"), "INBOX.Envoy&AOk-s");
+        assert_eq!(encode_imap_utf7("Tom & Jerry"), "Tom &- Jerry");
+        assert_eq!(encode_imap_utf7("Archives"), "Archives");
+        for name in ["Éléments envoyés", "日本語/メール", "Boîte 📬 & co"] {
+            assert_eq!(decode_imap_utf7(&encode_imap_utf7(name)), name);
+        }
+    }
+
     #[test]
     fn test_cleanup_empty_dirs_removes_leaf() {
         let temp = tempfile::TempDir::new().unwrap();
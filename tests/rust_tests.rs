@@ -346,6 +346,52 @@ mod settings_tests {
         assert!(config.is_err());
     }
 
+    #[test]
+    fn test_config_merge_signatures_per_account() {
+        use email_to_markdown::signatures::{Aggressiveness, SignatureKeep};
+
+        let temp = TempDir::new().unwrap();
+        let accounts_yaml = "accounts:\n  - name: Pro\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n  - name: Perso\n    server: imap.example.com\n    port: 993\n    username: b@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+
+        let settings_yaml = "export_base_dir: /tmp/emails\ndefaults:\n  signatures:\n    strip: true\naccounts:\n  Perso:\n    signatures:\n      strip: true\n      aggressiveness: high\n      keep: contact\n";
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, settings_yaml).unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let pro = &config.accounts[0].signatures;
+        assert!(pro.strip);
+        assert_eq!((pro.aggressiveness, pro.keep), (Aggressiveness::Medium, SignatureKeep::None));
+        let perso = &config.accounts[1].signatures;
+        assert_eq!((perso.aggressiveness, perso.keep), (Aggressiveness::High, SignatureKeep::Contact));
+    }
+
+    #[test]
+    fn test_config_cleaner_settings_merge_and_validation() {
+        let temp = TempDir::new().unwrap();
+        let accounts_yaml = "accounts:\n  - name: Pro\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n  - name: Perso\n    server: imap.example.com\n    port: 993\n    username: b@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+
+        let settings_yaml = "export_base_dir: /tmp/emails\ndefaults:\n  cleaner:\n    boilerplate:\n      - Sent from my phone\naccounts:\n  Perso:\n    cleaner:\n      stages:\n        unwrap_lines: false\n      rules:\n        - pattern: '\\[EXT\\] '\n";
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, settings_yaml).unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let pro = &config.accounts[0].cleaner;
+        assert_eq!(pro.boilerplate, vec!["Sent from my phone"]);
+        assert!(pro.stages.unwrap_lines && pro.rules.is_empty());
+        let perso = &config.accounts[1].cleaner;
+        assert!(!perso.stages.unwrap_lines && perso.stages.trackers);
+        assert_eq!(perso.rules[0].pattern, "\\[EXT\\] ");
+
+        // An invalid rule pattern is rejected at load time
+        std::fs::write(&settings_path, settings_yaml.replace("'\\[EXT\\] '", "'[EXT'")).unwrap();
+        let err = Config::load_with_settings(&accounts_path, &settings_path).unwrap_err();
+        assert!(err.to_string().contains("Invalid cleaner settings for account 'Perso'"));
+    }
+
     #[test]
     fn test_settings_account_behavior_overrides_round_trip() {
         let temp = TempDir::new().expect("create tempdir");
@@ -465,6 +511,7 @@ mod email_export_tests {
             attachments: vec![],
             email_type: None,
             social_links: Some(links),
+            ..Default::default()
         };
 
         let yaml = serde_yaml::to_string(&fm).expect("serialize");
@@ -485,6 +532,7 @@ mod email_export_tests {
             attachments: vec![],
             email_type: None,
             social_links: None,
+            ..Default::default()
         };
 
         let yaml = serde_yaml::to_string(&fm).expect("serialize");
@@ -503,6 +551,7 @@ mod email_export_tests {
             attachments: vec![],
             email_type: Some("newsletter".to_string()),
             social_links: None,
+            ..Default::default()
         };
 
         let yaml = serde_yaml::to_string(&fm).expect("serialize");
@@ -521,6 +570,7 @@ mod email_export_tests {
             attachments: vec![],
             email_type: None,
             social_links: None,
+            ..Default::default()
         };
 
         let yaml = serde_yaml::to_string(&fm).expect("serialize");
@@ -756,11 +806,27 @@ Content-Transfer-Encoding: quoted-printable\r\n\
     // Phase 5 — End-to-end pipeline integration test on a realistic body.
     const JEVEUX_BODY: &str = "Bonjour stVerif SARL,\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\u{00A0}\n\nMerci pour votre inscription sur JeVeuxTravailler/JeVeuxRecruter.\n\nVotre compte est créé. Il ne vous reste plus qu'une étape pour accéder\nà votre espace recruteur et découvrir les candidats disponibles dans\nvotre secteur en utilisant notre plateforme de recherche avancée.\n\nCliquez sur le bouton ci-dessous pour confirmer votre adresse email.\n\n[Activer mon compte recruteur](https://jeveuxtravailler.com/api/verify-email?token=eyJ1aWQiOiJaZHNBb3FCeE5UT2hXNVBDQTZzZmR3QW9mb2YxIiwidXNlclR5cGUiOiJyZWNydWl0ZXIiLCJleHAiOjE3NzU5MjI3OTF9.7mIbZQR8d3f2XBkzPmIW42toBN6QZbnbUqoXiDvq7aA&utm_source=onboarding)\n\nSi vous n'êtes pas à l'origine de cette demande, vous pouvez ignorer\ncet email.\n\nÀ très vite,\nL'équipe JeVeuxTravailler !\n\n[instagram](https://www.instagram.com/jeveuxtravailler_fr/)\n[tiktok](https://www.tiktok.com/@jeveuxtravailler.com)\n[facebook](https://www.facebook.com/talentissim/?locale=fr_FR)\n[LinkedIn](https://www.linkedin.com/company/jeveuxtravailler-jeveuxrecruter/)\n";
 
+    #[test]
+    fn test_split_reply_german_outlook_and_spanish_gmail() {
+        let german = "Danke, passt.\n\nVon: Alice Martin <alice@example.com>\nGesendet: Montag, 3. Juni 2026 10:00\n\
+                      An: Bob\nBetreff: Angebot\n\nHier ist das Angebot.\n";
+        let split = cleaner::split_reply(german).expect("German Outlook header");
+        assert_eq!(split.own, "Danke, passt.");
+        assert_eq!(split.header, "Von: Alice Martin <alice@example.com>, Gesendet: Montag, 3. Juni 2026 10:00");
+
+        let spanish = "Perfecto.\n\nEl lun, 3 jun 2026 a las 10:00, Alice (<alice@example.com>)\nescribió:\n> Aquí está.\n";
+        let split = cleaner::split_reply(spanish).expect("wrapped Spanish attribution");
+        assert_eq!(split.history, "El lun, 3 jun 2026 a las 10:00, Alice (<alice@example.com>)\nescribió:\n> Aquí está.");
+    }
+
     #[test]
     fn test_clean_e2e_jeveuxtravailler_body() {
         let result = cleaner::clean(JEVEUX_BODY);
         let body = &result.body;
 
+        // Correctly decoded French (NBSP runs, "À très vite") is not mojibake
+        assert!(!result.charset_repaired);
+
         // No QP residue
         assert!(
             !body.contains("=C2=A0"),
@@ -878,6 +944,7 @@ mod route_tests {
         parse_destinations, route_email, upsert_rule,
         Destination, EmailMeta, MatchRule,
     };
+    use email_to_markdown::frontmatter::FieldKeys;
     use chrono::DateTime;
     use std::fs;
     use std::path::PathBuf;
@@ -891,6 +958,7 @@ mod route_tests {
             domain: domain.to_string(),
             subject: subject.to_string(),
             account: account.to_string(),
+            email_type: String::new(),
             date: DateTime::parse_from_rfc3339(date_str).expect("valid date"),
         }
     }
@@ -965,7 +1033,7 @@ mod route_tests {
         fs::write(&md_src, md_content).unwrap();
 
         // Act
-        move_email(&md_src, &dst_dir).unwrap();
+        move_email(&md_src, &dst_dir, &FieldKeys::builtin()).unwrap();
 
         // Inclusive: .md and flat attachment co-located at dest
         let md_dest = dst_dir.join("email.md");
@@ -1024,7 +1092,7 @@ mod route_tests {
         let md_src = src_dir.join("email.md");
         fs::write(&md_src, md_content).unwrap();
 
-        move_email(&md_src, &dst_dir).unwrap();
+        move_email(&md_src, &dst_dir, &FieldKeys::builtin()).unwrap();
 
         // Inclusive: real file (dequoted by serde_yaml) present at dest
         let att_dest = dst_dir.join(att_filename);
@@ -1058,7 +1126,7 @@ mod route_tests {
         let md_src = src_dir.join("plain.md");
         fs::write(&md_src, "---\nsubject: Plain\n---\nNo attachments\n").unwrap();
 
-        move_email(&md_src, &dst_dir).unwrap();
+        move_email(&md_src, &dst_dir, &FieldKeys::builtin()).unwrap();
 
         // Inclusive: .md moved
         assert!(dst_dir.join("plain.md").exists(), "moved .md must exist at dest");
@@ -1077,6 +1145,26 @@ mod route_tests {
         );
     }
 
+    #[test]
+    fn test_move_email_carries_kept_original() {
+        let temp = TempDir::new().unwrap();
+        let src_dir = temp.path().join("staging");
+        let dst_dir = temp.path().join("dest");
+        fs::create_dir_all(&src_dir).unwrap();
+        fs::create_dir_all(&dst_dir).unwrap();
+
+        let md_src = src_dir.join("kept.md");
+        fs::write(&md_src, "---\nsubject: Kept\n---\nBody\n").unwrap();
+        fs::write(src_dir.join("kept.eml"), "Subject: Kept\r\n\r\nBody\r\n").unwrap();
+
+        move_email(&md_src, &dst_dir, &FieldKeys::builtin()).unwrap();
+
+        // Inclusive: the keep_raw original follows its note
+        assert!(dst_dir.join("kept.eml").exists(), "kept original must move with the note");
+        // Exclusive: nothing left in staging
+        assert!(!src_dir.join("kept.eml").exists(), "kept original must not remain at src");
+    }
+
     #[test]
     fn test_move_email_rejects_symlink() {
         let temp = TempDir::new().unwrap();
@@ -1095,7 +1183,7 @@ mod route_tests {
             use std::os::windows::fs::symlink_file;
             match symlink_file(&real_file, &symlink_path) {
                 Ok(()) => {
-                    let result = move_email(&symlink_path, &dst_dir);
+                    let result = move_email(&symlink_path, &dst_dir, &FieldKeys::builtin());
                     assert!(result.is_err(), "move_email must refuse a symlink source");
                     let msg = result.unwrap_err().to_string();
                     assert!(
@@ -1114,7 +1202,7 @@ mod route_tests {
         #[cfg(not(windows))]
         {
             std::os::unix::fs::symlink(&real_file, &symlink_path).unwrap();
-            let result = move_email(&symlink_path, &dst_dir);
+            let result = move_email(&symlink_path, &dst_dir, &FieldKeys::builtin());
             assert!(result.is_err(), "move_email must refuse a symlink source");
             let msg = result.unwrap_err().to_string();
             assert!(
@@ -1152,8 +1240,8 @@ mod route_tests {
         )
         .unwrap();
 
-        move_email(&md_a, &dst_dir).unwrap();
-        move_email(&md_b, &dst_dir).unwrap();
+        move_email(&md_a, &dst_dir, &FieldKeys::builtin()).unwrap();
+        move_email(&md_b, &dst_dir, &FieldKeys::builtin()).unwrap();
 
         // Both attachments survive with distinct content — no overwrite.
         let first = dst_dir.join("2026-06-25_image.png");
@@ -1178,6 +1266,74 @@ mod route_tests {
         assert!(a_content.contains("- 2026-06-25_image.png\n"));
     }
 
+    // Org notes move with their attachments like Markdown notes; a colliding
+    // attachment is renamed in the property drawer and in the body link.
+    #[test]
+    fn test_move_email_org_note_rewrites_colliding_attachment() {
+        let temp = TempDir::new().unwrap();
+        let dst_dir = temp.path().join("dest");
+        fs::create_dir_all(&dst_dir).unwrap();
+        fs::write(dst_dir.join("2026-06-25_devis.pdf"), b"OLD").unwrap();
+
+        let src = temp.path().join("staging");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("2026-06-25_devis.pdf"), b"NEW").unwrap();
+        let note = src.join("email.org");
+        fs::write(
+            &note,
+            ":PROPERTIES:\n:SUBJECT: Devis\n:ATTACHMENTS: [[file:2026-06-25_devis.pdf]]\n:END:\n#+TITLE: Devis\n\n* Pièces jointes\n- [[file:2026-06-25_devis.pdf][2026-06-25_devis.pdf]]\n",
+        )
+        .unwrap();
+
+        let moved = move_email(&note, &dst_dir, &FieldKeys::builtin()).unwrap();
+
+        assert_eq!(moved, dst_dir.join("email.org"));
+        assert_eq!(fs::read(dst_dir.join("2026-06-25_devis_2.pdf")).unwrap(), b"NEW");
+        let content = fs::read_to_string(&moved).unwrap();
+        assert!(content.contains(":ATTACHMENTS: [[file:2026-06-25_devis_2.pdf]]\n"), "{}", content);
+        assert!(content.contains("- [[file:2026-06-25_devis_2.pdf][2026-06-25_devis_2.pdf]]"));
+        // Exclusive: pre-existing destination file untouched
+        assert_eq!(fs::read(dst_dir.join("2026-06-25_devis.pdf")).unwrap(), b"OLD");
+    }
+
+    // Logseq-dialect notes (`key:: value`, comma list) move with their attachments
+    // and get their links rewritten on collision, like YAML notes.
+    #[test]
+    fn test_move_email_logseq_note_rewrites_colliding_attachment() {
+        let temp = TempDir::new().unwrap();
+        let dst_dir = temp.path().join("dest");
+        fs::create_dir_all(&dst_dir).unwrap();
+        fs::write(dst_dir.join("2026-06-25_devis.pdf"), b"OLD").unwrap();
+
+        let src = temp.path().join("staging");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("2026-06-25_devis.pdf"), b"NEW").unwrap();
+        fs::write(src.join("2026-06-25_notes.txt"), b"TXT").unwrap();
+        let md = src.join("email.md");
+        fs::write(
+            &md,
+            "subject:: Devis\nattachments:: 2026-06-25_devis.pdf, 2026-06-25_notes.txt\n\nBody [devis](2026-06-25_devis.pdf)\n",
+        )
+        .unwrap();
+
+        let moved = move_email(&md, &dst_dir, &FieldKeys::builtin()).unwrap();
+
+        // Inclusive: returned path is the final note; both attachments moved
+        assert_eq!(moved, dst_dir.join("email.md"));
+        assert_eq!(fs::read(dst_dir.join("2026-06-25_devis_2.pdf")).unwrap(), b"NEW");
+        assert!(dst_dir.join("2026-06-25_notes.txt").exists());
+        // Inclusive: property line and body link rewritten
+        let content = fs::read_to_string(&moved).unwrap();
+        assert!(
+            content.starts_with("subject:: Devis\nattachments:: 2026-06-25_devis_2.pdf, 2026-06-25_notes.txt\n"),
+            "{}",
+            content
+        );
+        assert!(content.contains("(2026-06-25_devis_2.pdf)"));
+        // Exclusive: pre-existing destination file untouched
+        assert_eq!(fs::read(dst_dir.join("2026-06-25_devis.pdf")).unwrap(), b"OLD");
+    }
+
     // ── delete_email ─────────────────────────────────────────────────────────
 
     // delete_email removes the .md and relocates attachments into _deleted.
@@ -1194,7 +1350,7 @@ mod route_tests {
         let md_content = "---\nsubject: Test\nattachments:\n  - email__file.pdf\n---\nBody text\n";
         fs::write(&md_src, md_content).unwrap();
 
-        delete_email(&md_src).unwrap();
+        delete_email(&md_src, &FieldKeys::builtin()).unwrap();
 
         // Exclusive: the .md is gone.
         assert!(!md_src.exists(), "deleted .md must not remain");
@@ -1216,7 +1372,7 @@ mod route_tests {
         let md_src = src_dir.join("plain.md");
         fs::write(&md_src, "---\nsubject: Plain\n---\nNo attachments\n").unwrap();
 
-        delete_email(&md_src).unwrap();
+        delete_email(&md_src, &FieldKeys::builtin()).unwrap();
 
         assert!(!md_src.exists(), "deleted .md must not remain");
         assert!(
@@ -1236,7 +1392,7 @@ mod route_tests {
         let symlink_path = temp.path().join("link.md");
         std::os::unix::fs::symlink(&real_file, &symlink_path).unwrap();
 
-        let result = delete_email(&symlink_path);
+        let result = delete_email(&symlink_path, &FieldKeys::builtin());
         assert!(result.is_err(), "delete_email must refuse a symlink source");
         // Exclusive: the real target is untouched.
         assert!(real_file.exists(), "symlink target must not be deleted");
@@ -1392,6 +1548,18 @@ Pro/Clients/Acme | from:billing@acme.com, subject:Invoice
         assert!(!decision.is_default);
     }
 
+    #[test]
+    fn test_route_email_matches_type() {
+        let content = "Pro/Agenda | type:invitation\n";
+        let dests = parse_destinations(content).unwrap();
+        let mut meta = make_meta("boss@corp.com", "corp.com", "Revue", "work", "2026-03-10T00:00:00+00:00");
+        // Exclusive: a plain email does not match the type rule
+        assert!(route_email(&meta, &dests).is_default);
+        meta.email_type = "Invitation".to_string();
+        let decision = route_email(&meta, &dests);
+        assert!(decision.rel_path.starts_with("Pro/Agenda/"), "got: {}", decision.rel_path);
+    }
+
     // ── route_email — Perso/Pro polarity ─────────────────────────────────────
 
     #[test]
@@ -1524,7 +1692,7 @@ Pro/Clients/Acme | from:billing@acme.com, subject:Invoice
         fs::write(&md_src, "---\nsubject: Test\n---\nBody\n").unwrap();
 
         let rel_path = "Perso/Finance/Banque/2026/06";
-        apply_decision(&md_src, rel_path, &notes_dir).unwrap();
+        apply_decision(&md_src, rel_path, &notes_dir, &FieldKeys::builtin()).unwrap();
 
         let expected_dir = notes_dir.join("Perso").join("Finance").join("Banque").join("2026").join("06");
         let expected_md = expected_dir.join("email.md");
@@ -1546,7 +1714,7 @@ Pro/Clients/Acme | from:billing@acme.com, subject:Invoice
         fs::write(&md_src, "---\nsubject: Test\n---\nBody\n").unwrap();
 
         // Path traversal via ".."
-        let result = apply_decision(&md_src, "Perso/../../etc/passwd", &notes_dir);
+        let result = apply_decision(&md_src, "Perso/../../etc/passwd", &notes_dir, &FieldKeys::builtin());
         // Inclusive: error returned
         assert!(result.is_err(), "path traversal must be rejected");
         let msg = format!("{:#}", result.unwrap_err());
@@ -1788,7 +1956,7 @@ Pro/Clients/Acme | from:billing@acme.com, subject:Invoice
 
         // Free-typed path not in destinations.txt — apply_decision must still work (D10).
         let free_path = "Perso/NewCategory/FreeSubcat/2026/06";
-        apply_decision(&md_src, free_path, &notes_dir).unwrap();
+        apply_decision(&md_src, free_path, &notes_dir, &FieldKeys::builtin()).unwrap();
 
         let expected_md = notes_dir
             .join("Perso").join("NewCategory").join("FreeSubcat")
@@ -1815,8 +1983,10 @@ Pro/Clients/Acme | from:billing@acme.com, subject:Invoice
 
 mod destinations_tests {
     use email_to_markdown::destinations::{
-        load_yaml, save_yaml, upsert_entry, DestinationEntry, DestinationRule, DestinationsConfig,
+        load_yaml, save_yaml, static_fields_for, upsert_entry, DestinationEntry, DestinationRule,
+        DestinationsConfig,
     };
+    use email_to_markdown::frontmatter::append_fields;
     use std::fs;
     use tempfile::TempDir;
 
@@ -1842,6 +2012,7 @@ mod destinations_tests {
                     DestinationRule::Subject("facture".to_string()),
                 ],
                 default: false,
+                ..Default::default()
             }],
         };
         save_yaml(&path, &cfg).unwrap();
@@ -1875,6 +2046,7 @@ mod destinations_tests {
                 note: None,
                 rules: vec![DestinationRule::Domain("corp.com".to_string())],
                 default: false,
+                ..Default::default()
             }],
         };
         // Same path different case + identical rule → no growth.
@@ -1974,6 +2146,50 @@ mod destinations_tests {
         let err = save_yaml(&link, &DestinationsConfig::default()).unwrap_err();
         assert!(format!("{err:#}").contains("symlink"));
     }
+
+    /// Per-destination static fields are found through the applied `<Year>/<Month>`
+    /// path and added to the note without overriding existing keys.
+    #[test]
+    fn test_static_fields_for_applied_path_and_append() {
+        let cfg: DestinationsConfig = serde_yaml::from_str(
+            "destinations:\n  - path: Pro/Clients/Acme\n    frontmatter:\n      client: Acme\n      subject: ignored\n  - path: Perso\n",
+        )
+        .unwrap();
+
+        // Inclusive: trailing Year/Month ignored, case-insensitive
+        let fields = static_fields_for(&cfg, "pro/clients/acme/2024/03").unwrap();
+        assert_eq!(fields.len(), 2);
+        // Exclusive: entry without fields, unknown path
+        assert!(static_fields_for(&cfg, "Perso/2024/03").is_none());
+        assert!(static_fields_for(&cfg, "Pro/Clients").is_none());
+
+        let temp = TempDir::new().unwrap();
+        let md = temp.path().join("note.md");
+        fs::write(&md, "---\nsubject: Devis\n---\n\nBody\n").unwrap();
+        append_fields(&md, fields).unwrap();
+        let content = fs::read_to_string(&md).unwrap();
+        assert!(content.contains("client: Acme\n"), "{content}");
+        // Exclusive: existing key kept, body untouched
+        assert!(content.contains("subject: Devis\n") && !content.contains("ignored"));
+        assert!(content.ends_with("---\n\nBody\n"), "{content}");
+
+        // JSON notes and Org drawers get the fields too
+        let json = temp.path().join("note.json");
+        fs::write(&json, "{\"subject\":\"Devis\",\"body\":\"Body\"}\n").unwrap();
+        append_fields(&json, fields).unwrap();
+        let record: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(record["client"], "Acme");
+        assert_eq!(record["subject"], "Devis");
+
+        let org = temp.path().join("note.org");
+        fs::write(&org, ":PROPERTIES:\n:SUBJECT: Devis\n:END:\n#+TITLE: Devis\n").unwrap();
+        append_fields(&org, fields).unwrap();
+        assert_eq!(
+            fs::read_to_string(&org).unwrap(),
+            ":PROPERTIES:\n:SUBJECT: Devis\n:CLIENT: Acme\n:END:\n#+TITLE: Devis\n"
+        );
+    }
 }
 
 mod dest_cmd_tests {
@@ -2100,6 +2316,7 @@ mod suggest_tests {
     use email_to_markdown::dest_cmd::{
         extract_domain, scan_domains, strip_trailing_year_month, uncovered_domains,
     };
+    use email_to_markdown::frontmatter::FieldKeys;
     use email_to_markdown::destinations::{DestinationEntry, DestinationRule, DestinationsConfig};
     use std::fs;
     use tempfile::TempDir;
@@ -2122,7 +2339,7 @@ mod suggest_tests {
         write_md(temp.path(), "b.md", "Bob <bob@ubs.ch>");
         write_md(temp.path(), "c.md", "carol@bnp.fr");
 
-        let groups = scan_domains(temp.path()).unwrap();
+        let groups = scan_domains(temp.path(), &FieldKeys::builtin()).unwrap();
         assert_eq!(groups.get("ubs.ch"), Some(&2));
         assert_eq!(groups.get("bnp.fr"), Some(&1));
     }
@@ -2134,7 +2351,7 @@ mod suggest_tests {
         write_md(&temp.path().join(".obsidian"), "x.md", "x@hidden.com");
         write_md(temp.path(), "ok.md", "ok@visible.com");
 
-        let groups = scan_domains(temp.path()).unwrap();
+        let groups = scan_domains(temp.path(), &FieldKeys::builtin()).unwrap();
         assert!(groups.get("hidden.com").is_none(), "dot-dir must be skipped");
         assert_eq!(groups.get("visible.com"), Some(&1));
     }
@@ -2146,7 +2363,7 @@ mod suggest_tests {
         write_md(&temp.path().join("_deleted"), "x.md", "x@gone.com");
         write_md(temp.path(), "ok.md", "ok@here.com");
 
-        let groups = scan_domains(temp.path()).unwrap();
+        let groups = scan_domains(temp.path(), &FieldKeys::builtin()).unwrap();
         assert!(groups.get("gone.com").is_none(), "underscore-dir must be skipped");
         assert_eq!(groups.get("here.com"), Some(&1));
     }
@@ -2163,7 +2380,7 @@ mod suggest_tests {
         write_md(&root, "ok.md", "ok@inside.com");
         std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
 
-        let groups = scan_domains(&root).unwrap();
+        let groups = scan_domains(&root, &FieldKeys::builtin()).unwrap();
         assert!(groups.get("external.com").is_none(), "symlink must not be followed");
         assert_eq!(groups.get("inside.com"), Some(&1));
     }
@@ -2180,7 +2397,7 @@ mod suggest_tests {
         write_md(&root, "ok.md", "ok@inside.com");
         // Symlink creation may require privileges; skip the assertion if it fails.
         if std::os::windows::fs::symlink_dir(&outside, root.join("link")).is_ok() {
-            let groups = scan_domains(&root).unwrap();
+            let groups = scan_domains(&root, &FieldKeys::builtin()).unwrap();
             assert!(groups.get("external.com").is_none(), "symlink must not be followed");
             assert_eq!(groups.get("inside.com"), Some(&1));
         }
@@ -2220,7 +2437,7 @@ mod suggest_tests {
     #[test]
     fn test_suggest_no_md_files() {
         let temp = TempDir::new().unwrap();
-        let groups = scan_domains(temp.path()).unwrap();
+        let groups = scan_domains(temp.path(), &FieldKeys::builtin()).unwrap();
         assert!(groups.is_empty());
     }
 
@@ -2455,3 +2672,290 @@ mod dest_gui_tests {
         assert!(cfg.destinations[1].rules.contains(&DestinationRule::Domain("apple.com".into())));
     }
 }
+
+mod mbox_tests {
+    use email_to_markdown::mbox_cmd::{run, ExportMboxArgs};
+    use mailparse::MailHeaderMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_eml_dir_keeps_message_id_and_date() {
+        let temp = TempDir::new().unwrap();
+        let notes = temp.path().join("Pro/Acme/2024/01");
+        fs::create_dir_all(&notes).unwrap();
+        fs::write(
+            notes.join("devis.md"),
+            "---\nfrom: Alice <alice@example.com>\nto: bob@example.com\ndate: 2024-01-15T10:00:00+01:00\n\
+             subject: Devis\nmessage_id: devis@example.com\nattachments: []\n---\n\nBonjour Bob\n",
+        )
+        .unwrap();
+        let out = temp.path().join("out");
+
+        run(ExportMboxArgs { paths: vec![temp.path().join("Pro")], output: None, eml_dir: Some(out.clone()) })
+            .unwrap();
+
+        let eml = fs::read(out.join("devis.eml")).unwrap();
+        let mail = mailparse::parse_mail(&eml).unwrap();
+        assert_eq!(mail.headers.get_first_value("Message-ID").unwrap(), "<devis@example.com>");
+        assert_eq!(mail.headers.get_first_value("Date").unwrap(), "Mon, 15 Jan 2024 10:00:00 +0100");
+        assert_eq!(mail.get_body().unwrap().trim(), "Bonjour Bob");
+    }
+}
+
+mod quarantine_tests {
+    use chrono::Utc;
+    use email_to_markdown::config::Config;
+    use email_to_markdown::quarantine::{self, FailureStage, QuarantineRecord};
+    use email_to_markdown::retry_cmd::retry_account;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn record(export_directory: std::path::PathBuf, uid: u32) -> QuarantineRecord {
+        QuarantineRecord {
+            account: "Pro".to_string(),
+            folder: "INBOX".to_string(),
+            delimiter: Some("/".to_string()),
+            uid: Some(uid),
+            uid_validity: Some(3),
+            message_id: None,
+            stage: FailureStage::Parse,
+            errors: vec!["Failed to parse email".to_string()],
+            export_directory,
+            tags: vec!["INBOX".to_string()],
+            flags: vec!["\\Seen".to_string()],
+            quarantined_at: Utc::now(),
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_retry_exports_fixed_messages_and_keeps_failing_ones() {
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("Pro");
+        let accounts = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts,
+            "accounts:\n  - name: Pro\n    server: imap.example.com\n    port: 993\n    username: me@example.com\n",
+        )
+        .unwrap();
+        let settings = temp.path().join("settings.yaml");
+        fs::write(&settings, format!("export_base_dir: {}\n", temp.path().display())).unwrap();
+        let account = Config::load_with_settings(&accounts, &settings).unwrap().accounts.remove(0);
+        assert_eq!(account.export_directory, export_dir.to_string_lossy());
+
+        let raw = b"From: Alice <alice@example.com>\r\nTo: me@example.com\r\nSubject: Devis\r\n\
+                    Date: Mon, 15 Jan 2024 10:00:00 +0100\r\nMessage-ID: <devis@example.com>\r\n\r\nBonjour\r\n";
+        let fixed = quarantine::quarantine(&export_dir, &record(export_dir.join("INBOX"), 7), raw).unwrap();
+        // Still failing: its export directory is a regular file
+        let blocker = temp.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        let failing = quarantine::quarantine(&export_dir, &record(blocker.join("INBOX"), 8), raw).unwrap();
+
+        let report = retry_account(&account, &[], false);
+        assert_eq!((report.exported, report.failed), (1, 1));
+        assert!(!fixed.exists(), "exported message leaves the quarantine");
+        let (staging, _) = &report.decisions[0];
+        let note = fs::read_to_string(staging).unwrap();
+        assert!(note.contains("Bonjour"));
+        assert!(note.contains("\\Seen"), "flags come from the report");
+
+        let left = quarantine::list(&export_dir);
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].0, failing);
+        assert_eq!(left[0].1.retries, 1);
+        assert_eq!(left[0].1.stage, FailureStage::Write);
+    }
+}
+
+// ============================================================================
+// Embedded messages — forwarded emails exported as child notes
+// ============================================================================
+
+mod embedded_tests {
+    use email_to_markdown::config::Config;
+    use email_to_markdown::email_export::{export_to_markdown, ExportContext};
+    use email_to_markdown::frontmatter::{FieldKeys, NoteHeader};
+    use email_to_markdown::route::{self, parse_destinations};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_forwarded_invoice_is_filed_by_original_sender() {
+        let temp = TempDir::new().unwrap();
+        let accounts = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts,
+            "accounts:\n  - name: Pro\n    server: imap.example.com\n    port: 993\n    username: me@example.com\n",
+        )
+        .unwrap();
+        let settings = temp.path().join("settings.yaml");
+        fs::write(&settings, format!("export_base_dir: {}\n", temp.path().display())).unwrap();
+        let account = Config::load_with_settings(&accounts, &settings).unwrap().accounts.remove(0);
+        let dests = parse_destinations("Perso/Factures | domain:edf.fr\nPro/Equipe | domain:corp.example\n").unwrap();
+
+        let raw = b"From: Bob <bob@corp.example>\r\nTo: me@example.com\r\nSubject: Fwd: Votre facture\r\n\
+            Date: Tue, 03 Mar 2026 09:00:00 +0100\r\nContent-Type: multipart/mixed; boundary=M\r\n\r\n\
+            --M\r\nContent-Type: text/plain\r\n\r\nPour info, la facture ci-jointe.\r\n\
+            --M\r\nContent-Type: message/rfc822\r\nContent-Disposition: attachment; filename=facture.eml\r\n\r\n\
+            From: EDF <factures@edf.fr>\r\nTo: bob@corp.example\r\nSubject: Votre facture\r\n\
+            Date: Sun, 01 Mar 2026 08:00:00 +0100\r\nMessage-ID: <f1@edf.fr>\r\n\r\nMontant : 42 EUR\r\n\
+            --M--\r\n";
+
+        let export_dir = temp.path().join("Pro").join("INBOX");
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: &temp.path().join("Pro"),
+            account: &account,
+            debug_mode: false,
+            dests: &dests,
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (parent, decision) = export_to_markdown(raw, vec!["INBOX".into()], None, &mut ctx)
+            .unwrap()
+            .expect("exported");
+        assert!(decision.rel_path.starts_with("Pro/Equipe/"));
+        assert_eq!(decision.embedded.len(), 1);
+        let (child, child_decision) = &decision.embedded[0];
+        assert!(child_decision.rel_path.starts_with("Perso/Factures/"), "{}", child_decision.rel_path);
+        assert!(fs::read_to_string(child).unwrap().contains("Montant : 42 EUR"));
+        let child_name = child.file_name().unwrap().to_string_lossy().into_owned();
+        let note = fs::read_to_string(&parent).unwrap();
+        assert!(note.contains(&format!("- {}", child_name)), "listed in attachments:\n{}", note);
+        assert!(note.contains(&format!("[Votre facture]({})", child_name)), "linked from the body:\n{}", note);
+        // Exclusive: not kept as an opaque .eml attachment
+        assert!(!note.contains("facture.eml"));
+
+        // Routing: the parent, then the child filed on its own
+        let notes_dir = temp.path().join("notes");
+        let parent_final = route::apply_decision(&parent, &decision.rel_path, &notes_dir, &FieldKeys::builtin()).unwrap();
+        let filed = route::apply_embedded(&parent_final, &decision.embedded, &notes_dir, &FieldKeys::builtin());
+        assert_eq!(filed.len(), 1);
+        assert!(filed[0].0.starts_with(notes_dir.join("Perso/Factures")));
+        assert!(filed[0].0.exists());
+        let note = fs::read_to_string(&parent_final).unwrap();
+        let link = format!("../../../../Perso/Factures/2026/03/{}", child_name);
+        assert!(note.contains(&format!("[Votre facture]({})", link)), "{}", note);
+    }
+
+    #[test]
+    fn test_embedded_attachment_follows_its_child_note() {
+        let temp = TempDir::new().unwrap();
+        let accounts = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts,
+            "accounts:\n  - name: Pro\n    server: imap.example.com\n    port: 993\n    username: me@example.com\n",
+        )
+        .unwrap();
+        let settings = temp.path().join("settings.yaml");
+        fs::write(&settings, format!("export_base_dir: {}\n", temp.path().display())).unwrap();
+        let account = Config::load_with_settings(&accounts, &settings).unwrap().accounts.remove(0);
+        let dests = parse_destinations("Perso/Factures | domain:edf.fr\nPro/Equipe | domain:corp.example\n").unwrap();
+
+        let raw = b"From: Bob <bob@corp.example>\r\nTo: me@example.com\r\nSubject: Fwd: Votre facture\r\n\
+            Date: Tue, 03 Mar 2026 09:00:00 +0100\r\nContent-Type: multipart/mixed; boundary=M\r\n\r\n\
+            --M\r\nContent-Type: text/plain\r\n\r\nPour info.\r\n\
+            --M\r\nContent-Type: message/rfc822\r\n\r\n\
+            From: EDF <factures@edf.fr>\r\nTo: bob@corp.example\r\nSubject: Votre facture\r\n\
+            Date: Sun, 01 Mar 2026 08:00:00 +0100\r\nMessage-ID: <f1@edf.fr>\r\n\
+            Content-Type: multipart/mixed; boundary=N\r\n\r\n\
+            --N\r\nContent-Type: text/plain\r\n\r\nFacture en PJ.\r\n\
+            --N\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=facture.pdf\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\nJVBERi0xLjQ=\r\n--N--\r\n\
+            --M--\r\n";
+
+        let export_dir = temp.path().join("Pro").join("INBOX");
+        let mut ctx = ExportContext {
+            export_directory: &export_dir,
+            base_export_directory: &temp.path().join("Pro"),
+            account: &account,
+            debug_mode: false,
+            dests: &dests,
+            folder: "INBOX",
+            index: None,
+            flags: &[],
+            signatures: None,
+        };
+        let (parent, decision) = export_to_markdown(raw, vec!["INBOX".into()], None, &mut ctx)
+            .unwrap()
+            .expect("exported");
+        let (child, child_decision) = &decision.embedded[0];
+        let child_name = child.file_name().unwrap().to_string_lossy().into_owned();
+        let content = fs::read_to_string(child).unwrap();
+        let pdf_link = NoteHeader::read(&content).unwrap().get_list("attachments", &FieldKeys::builtin()).remove(0);
+        assert!(export_dir.join(&pdf_link).exists());
+
+        // A note of the same name already sits at the child's destination
+        let notes_dir = temp.path().join("notes");
+        let child_dir = notes_dir.join(&child_decision.rel_path);
+        fs::create_dir_all(&child_dir).unwrap();
+        fs::write(child_dir.join(&child_name), "---\nsubject: other\n---\nOther").unwrap();
+
+        let parent_final = route::apply_decision(&parent, &decision.rel_path, &notes_dir, &FieldKeys::builtin()).unwrap();
+        // Exclusive: the child is not carried along with its parent
+        assert!(!parent_final.with_file_name(&child_name).exists());
+        let filed = route::apply_embedded(&parent_final, &decision.embedded, &notes_dir, &FieldKeys::builtin());
+        assert_eq!(filed.len(), 1);
+        let child_final = &filed[0].0;
+        assert_ne!(child_final.file_name().unwrap().to_string_lossy(), child_name, "renamed on collision");
+        assert_eq!(fs::read_to_string(child_dir.join(&child_name)).unwrap(), "---\nsubject: other\n---\nOther");
+
+        // The child's attachment moved with it and its link still resolves
+        assert!(child_final.with_file_name(&pdf_link).exists());
+        assert!(!export_dir.join(&pdf_link).exists(), "nothing left in staging");
+        // The parent links the renamed child
+        let note = fs::read_to_string(&parent_final).unwrap();
+        let link = format!(
+            "../../../../{}/{}",
+            child_decision.rel_path,
+            child_final.file_name().unwrap().to_string_lossy()
+        );
+        assert!(note.contains(&link), "{}", note);
+        assert!(parent_final.parent().unwrap().join(&link).exists());
+    }
+
+    #[test]
+    fn test_child_link_rewrite_leaves_other_mentions() {
+        let temp = TempDir::new().unwrap();
+        let staging = temp.path().join("staging");
+        let parent_dir = temp.path().join("notes/Pro/2026/03");
+        fs::create_dir_all(&staging).unwrap();
+        fs::create_dir_all(&parent_dir).unwrap();
+        fs::write(staging.join("child.md"), "---\nsubject: Votre facture\n---\nFacture.\n").unwrap();
+        fs::write(parent_dir.join("2026-03-01_child.md.pdf"), "%PDF").unwrap();
+        let parent = parent_dir.join("parent.md");
+        fs::write(
+            &parent,
+            "---\nsubject: Fwd child.md\nattachments:\n- 2026-03-01_child.md.pdf\n- child.md\n---\n\
+             Voir child.md ci-dessous.\n[Votre facture](child.md)\n\n### Pieces jointes :\n\
+             - [2026-03-01_child.md.pdf](2026-03-01_child.md.pdf)\n- [child.md](child.md)\n",
+        )
+        .unwrap();
+
+        let decision = route::RouteDecision {
+            rel_path: "Perso/Mes Factures/2026/03".to_string(),
+            matched_rule: None,
+            is_default: false,
+            embedded: Vec::new(),
+        };
+        let keys = FieldKeys::builtin();
+        route::apply_embedded(&parent, &[(staging.join("child.md"), decision)], &temp.path().join("notes"), &keys);
+
+        let note = fs::read_to_string(&parent).unwrap();
+        let link = "../../../Perso/Mes Factures/2026/03/child.md";
+        // Inclusive: the attachments entry and the link targets
+        assert_eq!(
+            NoteHeader::read(&note).unwrap().get_list("attachments", &keys),
+            vec!["2026-03-01_child.md.pdf".to_string(), link.to_string()]
+        );
+        assert!(note.contains(&format!("[Votre facture](<{}>)", link)), "{}", note);
+        assert!(note.contains(&format!("- [child.md](<{}>)", link)), "{}", note);
+        // Exclusive: the subject, body text and the other attachment keep the bare name
+        assert!(note.contains("subject: Fwd child.md\n"), "{}", note);
+        assert!(note.contains("Voir child.md ci-dessous."), "{}", note);
+        assert!(note.contains("- [2026-03-01_child.md.pdf](2026-03-01_child.md.pdf)"), "{}", note);
+    }
+}